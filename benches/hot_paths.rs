@@ -0,0 +1,147 @@
+//! Benchmarks of the work done for every request.
+//!
+//! Run with `cargo bench`, or `cargo bench -- routing` for one group.
+
+use std::sync::Arc;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use hyper::{Body, Method, Request, StatusCode};
+
+use kaserve::core::config::{RouteConfig, VirtualHostConfig};
+use kaserve::network::http::path::{decode_segments, normalize_path, PathCase};
+use kaserve::network::http::response::ResponseBuilder;
+use kaserve::utils::compression::{negotiate, Encoding};
+use kaserve::{Config, Router};
+
+/// Accept-Encoding header of a current browser
+const BROWSER_ACCEPT_ENCODING: &str = "gzip, deflate, br, zstd";
+
+/// Configuration with a route table and virtual hosts like a small production site
+fn routing_config() -> Config {
+    let route = |path: &str, handler: &str, params: Option<&str>, host: Option<&str>| RouteConfig {
+        path: path.to_string(),
+        handler: handler.to_string(),
+        params: params.map(str::to_string),
+        host: host.map(str::to_string),
+        methods: None,
+        priority: None,
+        timeout: None,
+    };
+    let vhost = |host: &str| VirtualHostConfig {
+        host: host.to_string(),
+        root_dir: "./public".to_string(),
+        directory_listing: None,
+        default_file: None,
+        tls: None,
+        access_log: None,
+        access_log_format: None,
+    };
+    
+    Config {
+        routes: Some(vec![
+            route("/api/*", "proxy", Some("api"), None),
+            route("/api/v2/*", "proxy", Some("api-v2"), None),
+            route("*.php", "fastcgi", Some("*.php"), None),
+            route("/cgi-bin/*", "cgi", Some("/cgi-bin/*"), None),
+            route("/uploads/*", "upload", Some("/uploads/*"), None),
+            route("/assets/*", "static", None, None),
+            route("/blog/*", "proxy", Some("blog"), Some("www.example.com")),
+        ]),
+        virtual_hosts: Some(vec![vhost("www.example.com"), vhost("*.example.org"), vhost("static.example.net")]),
+        ..Config::default()
+    }
+}
+
+fn request(path: &str, host: &str) -> Request<Body> {
+    Request::builder()
+        .method(Method::GET)
+        .uri(path)
+        .header("host", host)
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn routing(c: &mut Criterion) {
+    let router = Router::new(Arc::new(routing_config()));
+    let mut group = c.benchmark_group("routing");
+    
+    for (name, path, host) in [
+        ("static_fallback", "/images/logo.png", "localhost"),
+        ("prefix_route", "/api/v2/users/42", "localhost"),
+        ("suffix_route", "/wiki/index.php", "localhost"),
+        ("vhost_route", "/blog/2024/hello", "www.example.com"),
+        ("vhost_wildcard", "/index.html", "docs.example.org:8080"),
+    ] {
+        let req = request(path, host);
+        group.bench_function(name, |b| b.iter(|| black_box(router.route(black_box(&req)).is_ok())));
+    }
+    
+    group.bench_function("router_new", |b| {
+        let config = Arc::new(routing_config());
+        b.iter(|| black_box(Router::new(Arc::clone(&config))))
+    });
+    
+    group.finish();
+}
+
+fn path_resolution(c: &mut Criterion) {
+    let mut group = c.benchmark_group("path_resolution");
+    
+    group.bench_function("normalize_clean", |b| {
+        b.iter(|| black_box(normalize_path(black_box("/assets/css/site.min.css"), PathCase::Preserve, false)))
+    });
+    group.bench_function("normalize_lowercase", |b| {
+        b.iter(|| black_box(normalize_path(black_box("/Assets/CSS/Site.Min.css."), PathCase::Lower, true)))
+    });
+    group.bench_function("decode_segments", |b| {
+        b.iter(|| black_box(decode_segments(black_box("/docs/r%C3%A9sum%C3%A9/2024%20Q1/report.pdf"))))
+    });
+    
+    group.finish();
+}
+
+fn compression_negotiation(c: &mut Criterion) {
+    let candidates = [Encoding::Zstd, Encoding::Brotli, Encoding::Gzip];
+    let mut group = c.benchmark_group("compression_negotiation");
+    
+    group.bench_function("browser", |b| {
+        b.iter(|| black_box(negotiate(black_box(BROWSER_ACCEPT_ENCODING), &candidates)))
+    });
+    group.bench_function("weighted", |b| {
+        b.iter(|| black_box(negotiate(black_box("br;q=0.8, gzip;q=1.0, *;q=0.1, identity;q=0"), &candidates)))
+    });
+    group.bench_function("none_acceptable", |b| {
+        b.iter(|| black_box(negotiate(black_box("identity"), &candidates)))
+    });
+    
+    group.finish();
+}
+
+fn response_headers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("response_headers");
+    
+    group.bench_function("static_file", |b| {
+        b.iter(|| {
+            ResponseBuilder::with_status(StatusCode::OK)
+                .with_static_file_headers("text/css", Some(std::time::UNIX_EPOCH))
+                .etag("\"5f3a-1f4\"")
+                .cache_control("public, max-age=3600")
+                .header("vary", "Accept-Encoding")
+                .body_bytes(Vec::new())
+                .build()
+        })
+    });
+    group.bench_function("attachment", |b| {
+        b.iter(|| {
+            ResponseBuilder::new()
+                .content_type("application/pdf")
+                .attachment(black_box("Quarterly report – 2024.pdf"))
+                .build()
+        })
+    });
+    
+    group.finish();
+}
+
+criterion_group!(benches, routing, path_resolution, compression_negotiation, response_headers);
+criterion_main!(benches);
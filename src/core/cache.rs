@@ -0,0 +1,262 @@
+use hyper::body::Bytes;
+use memmap2::{Mmap, MmapAsRawDesc};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tracing::debug;
+
+use crate::core::config::StaticFilesConfig;
+use crate::utils::compression::{negotiate, should_compress, Compressor, Encoding, MIN_COMPRESS_SIZE};
+
+/// Default size of the largest file kept in the cache, in bytes
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Default address space for keeping files mapped, in MB
+pub const DEFAULT_MMAP_CACHE_SIZE: u64 = 1024;
+
+/// Contents and metadata of a file held in memory
+#[derive(Debug, Clone)]
+pub struct CachedFile {
+    /// File contents
+    pub content: Bytes,
+    /// MIME type served for the file
+    pub mime: String,
+    /// Modification time when the file was read
+    pub modified: Option<SystemTime>,
+    /// Entity tag of the contents
+    pub etag: Option<String>,
+    /// Compressed variants by content coding, or `None` to compress on demand
+    pub encodings: Option<Vec<(Encoding, Bytes)>>,
+    /// File the contents are mapped from, if they are mapped
+    pub source: Option<Arc<File>>,
+}
+
+impl CachedFile {
+    /// Wrap file contents that are compressed on demand
+    pub fn new(content: Bytes, mime: String, modified: Option<SystemTime>, etag: Option<String>) -> Self {
+        CachedFile {
+            content,
+            mime,
+            modified,
+            etag,
+            encodings: None,
+            source: None,
+        }
+    }
+    
+    /// Wrap the contents of a mapped file, which are never compressed in memory
+    pub fn mapped(content: Bytes, source: File, mime: String, modified: Option<SystemTime>, etag: Option<String>) -> Self {
+        CachedFile {
+            encodings: Some(Vec::new()),
+            source: Some(Arc::new(source)),
+            ..CachedFile::new(content, mime, modified, etag)
+        }
+    }
+    
+    /// Check that a mapped file still has the length it was mapped with.
+    ///
+    /// Reading a mapped page past the end of a truncated file kills the process
+    /// with SIGBUS, so mapped contents are only read after this check passes.
+    pub fn check_mapping(&self) -> io::Result<()> {
+        let Some(source) = &self.source else {
+            return Ok(());
+        };
+        let len = source.metadata()?.len();
+        if len == self.content.len() as u64 {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("mapped file changed size from {} to {} bytes", self.content.len(), len)))
+        }
+    }
+    
+    /// Compress the contents ahead of time if the MIME type benefits from it
+    pub fn precompressed(mut self, compressor: &Compressor) -> Self {
+        let variants = if should_compress(&self.mime) && self.content.len() >= MIN_COMPRESS_SIZE {
+            compressor.precompress(&self.content)
+                .into_iter()
+                .map(|(encoding, data)| (encoding, Bytes::from(data)))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        
+        self.encodings = Some(variants);
+        self
+    }
+    
+    /// Find the precompressed variant the client prefers
+    pub fn encoding_for(&self, accept_encoding: &str) -> Option<(Encoding, Bytes)> {
+        let variants = self.encodings.as_ref()?;
+        let available: Vec<Encoding> = variants.iter().map(|(encoding, _)| *encoding).collect();
+        
+        let encoding = negotiate(accept_encoding, &available)?;
+        variants.iter().find(|(variant, _)| *variant == encoding).cloned()
+    }
+    
+    /// Memory held by the contents and their variants
+    fn size(&self) -> usize {
+        let variants: usize = self.encodings.iter().flatten().map(|(_, data)| data.len()).sum();
+        self.content.len() + variants
+    }
+}
+
+/// Cache entry with the file length it was read at
+struct Entry {
+    /// Cached file
+    file: Arc<CachedFile>,
+    /// Length of the file on disk when it was read
+    len: u64,
+    /// Memory held by the entry
+    size: usize,
+    /// Tick of the last lookup, ordering entries for eviction
+    last_used: u64,
+}
+
+/// Entries and recency order, guarded together
+#[derive(Default)]
+struct CacheState {
+    /// Entries by file path
+    entries: HashMap<PathBuf, Entry>,
+    /// File paths by tick of last use, least recently used first
+    recency: BTreeMap<u64, PathBuf>,
+    /// Monotonic use counter
+    clock: u64,
+    /// Memory held by all entries
+    size: usize,
+}
+
+impl CacheState {
+    /// Drop an entry
+    fn remove(&mut self, path: &Path) {
+        if let Some(entry) = self.entries.remove(path) {
+            self.recency.remove(&entry.last_used);
+            self.size -= entry.size;
+        }
+    }
+    
+    /// Advance the clock and return the new tick
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+}
+
+/// In-memory cache of static files with least-recently-used eviction.
+///
+/// Entries are validated against the file's modification time and length on
+/// every lookup, so changed files are re-read from disk.
+pub struct FileCache {
+    /// Maximum memory held by cached files
+    max_size: usize,
+    /// Largest file that is cached
+    max_file_size: u64,
+    /// Entries and recency order
+    state: Mutex<CacheState>,
+}
+
+impl FileCache {
+    /// Create a cache holding up to `max_size` bytes of files of at most `max_file_size` bytes
+    pub fn new(max_size: usize, max_file_size: u64) -> Self {
+        FileCache {
+            max_size,
+            max_file_size,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+    
+    /// Build the cache configured in `static_files`, or `None` if caching is disabled
+    pub fn from_config(config: &StaticFilesConfig) -> Option<Self> {
+        let megabytes = config.cache_size.filter(|&size| size > 0)?;
+        let max_file_size = config.cache_max_file_size.unwrap_or(DEFAULT_MAX_FILE_SIZE);
+        
+        debug!("Caching static files up to {} bytes in {} MB", max_file_size, megabytes);
+        Some(FileCache::new(megabytes as usize * 1024 * 1024, max_file_size))
+    }
+    
+    /// Build the cache of memory-mapped files configured in `static_files`, or `None` if mapping is disabled.
+    ///
+    /// Mappings take address space rather than memory, so they are bounded separately from `cache_size`.
+    pub fn mappings_from_config(config: &StaticFilesConfig) -> Option<Self> {
+        let threshold = config.mmap_threshold?;
+        let megabytes = config.mmap_cache_size.unwrap_or(DEFAULT_MMAP_CACHE_SIZE);
+        
+        debug!("Mapping static files from {} bytes, keeping {} MB mapped", threshold, megabytes);
+        Some(FileCache::new(megabytes as usize * 1024 * 1024, u64::MAX))
+    }
+    
+    /// Check whether a file of the given length may be cached
+    pub fn cacheable(&self, len: u64) -> bool {
+        len <= self.max_file_size && len <= self.max_size as u64
+    }
+    
+    /// Look up a file, dropping the entry if the file changed on disk
+    pub fn get(&self, path: &Path, metadata: &Metadata) -> Option<Arc<CachedFile>> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = state.entries.get(path)?;
+        
+        if entry.len != metadata.len() || entry.file.modified != metadata.modified().ok() {
+            debug!("Cached {} is stale", path.display());
+            state.remove(path);
+            return None;
+        }
+        
+        let file = Arc::clone(&entry.file);
+        let last_used = entry.last_used;
+        let tick = state.tick();
+        state.recency.remove(&last_used);
+        state.recency.insert(tick, path.to_path_buf());
+        if let Some(entry) = state.entries.get_mut(path) {
+            entry.last_used = tick;
+        }
+        
+        Some(file)
+    }
+    
+    /// Cache a file read with the given length, evicting the least recently used files to make room
+    pub fn insert(&self, path: PathBuf, len: u64, file: CachedFile) -> Arc<CachedFile> {
+        let file = Arc::new(file);
+        let size = file.size();
+        
+        // Without a modification time a changed file could never be detected
+        if file.modified.is_none() || size > self.max_size {
+            return file;
+        }
+        
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.remove(&path);
+        
+        while state.size + size > self.max_size {
+            let Some((_, evicted)) = state.recency.pop_first() else {
+                break;
+            };
+            debug!("Evicting {} from the file cache", evicted.display());
+            if let Some(entry) = state.entries.remove(&evicted) {
+                state.size -= entry.size;
+            }
+        }
+        
+        let tick = state.tick();
+        state.recency.insert(tick, path.clone());
+        state.entries.insert(path, Entry {
+            file: Arc::clone(&file),
+            len,
+            size,
+            last_used: tick,
+        });
+        state.size += size;
+        
+        file
+    }
+}
+
+/// Map an open file into memory as read-only contents
+pub fn map_file(file: impl MmapAsRawDesc) -> io::Result<Bytes> {
+    // SAFETY: the mapping is only read. A file truncated while it is mapped
+    // faults on access, so senders check the file still has the mapped length
+    // (`CachedFile::check_mapping`) before reading each slice.
+    let mmap = unsafe { Mmap::map(file)? };
+    Ok(Bytes::from_owner(mmap))
+}
@@ -1,8 +1,25 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
+use std::net::{IpAddr, SocketAddr};
 use std::path::Path;
 use thiserror::Error;
 
+use crate::core::cache::{DEFAULT_MAX_FILE_SIZE, DEFAULT_MMAP_CACHE_SIZE};
+use crate::core::middleware::Stage;
+use crate::handlers::balancer::BalanceStrategy;
+use crate::handlers::static_files::DEFAULT_SEND_BUFFER_SIZE;
+use crate::network::http::path::PathCase;
+use crate::routing::router::UnmatchedRoutes;
+use crate::security::acme::AcmeChallenge;
+use crate::security::tls::{CipherPolicy, TlsVersion};
+use crate::utils::compression::{DEFAULT_BROTLI_LEVEL, DEFAULT_GZIP_LEVEL, DEFAULT_ZSTD_LEVEL};
+use crate::utils::etag::{EtagStrategy, DEFAULT_CACHE_SIZE as DEFAULT_ETAG_CACHE_SIZE};
+use crate::utils::markdown::DEFAULT_THEME;
+use crate::utils::logging::{AccessLogFormat, LogTarget};
+use crate::utils::rotation::RotationInterval;
+use crate::utils::mime::MimeSniffing;
+
 #[derive(Error, Debug)]
 pub enum ConfigError {
     #[error("Failed to read config file: {0}")]
@@ -10,6 +27,15 @@ pub enum ConfigError {
     
     #[error("Failed to parse TOML: {0}")]
     TomlError(#[from] toml::de::Error),
+    
+    #[error("Failed to serialize TOML: {0}")]
+    TomlSerializeError(#[from] toml::ser::Error),
+    
+    #[error("Invalid configuration override: {0}")]
+    InvalidOverride(String),
+    
+    #[error("Invalid configuration: {}", .0.join("; "))]
+    Invalid(Vec<String>),
 }
 
 /// Server configuration for the Kaserve web server
@@ -24,11 +50,118 @@ pub struct ServerConfig {
     /// Number of worker threads to use
     pub workers: Option<usize>,
     
-    /// Maximum number of connections
+    /// Maximum threads for blocking file and process work (Tokio's default of 512 if unset)
+    pub blocking_threads: Option<usize>,
+    
+    /// Pin each worker thread to its own CPU (Linux only, default false)
+    pub pin_workers: Option<bool>,
+    
+    /// Give every worker its own listening socket with `SO_REUSEPORT`, so the kernel spreads accepts across them (default false)
+    pub reuse_port: Option<bool>,
+    
+    /// Maximum number of open connections; further connections wait or are closed
     pub max_connections: Option<usize>,
     
+    /// Maximum number of open connections from one client address, as seen before PROXY protocol headers
+    pub max_connections_per_ip: Option<usize>,
+    
+    /// Milliseconds a connection over max_connections waits for a free slot (closed immediately if unset)
+    pub connection_queue_timeout: Option<u64>,
+    
     /// Connection timeout in seconds
     pub connection_timeout: Option<u64>,
+    
+    /// Default handler timeout per request in seconds (routes may override)
+    pub request_timeout: Option<u64>,
+    
+    /// Total time in seconds to produce a complete response, including streamed bodies
+    pub response_timeout: Option<u64>,
+    
+    /// Maximum in-flight buffered bytes across all requests, in MB (unlimited if unset)
+    pub memory_budget: Option<usize>,
+    
+    /// Whether to check handler reachability at startup
+    pub self_test: Option<bool>,
+    
+    /// Whether a failed startup self-test aborts startup instead of logging warnings
+    pub self_test_strict: Option<bool>,
+    
+    /// How letter case in request paths is handled (preserve, lower or reject)
+    pub path_case: Option<PathCase>,
+    
+    /// Whether trailing dots are stripped from request path segments
+    pub strip_trailing_dots: Option<bool>,
+    
+    /// Maximum number of request headers (the HTTP/1 parser caps this at 100)
+    pub max_headers: Option<usize>,
+    
+    /// Maximum size in bytes of an HTTP/1 request line and headers, at least 8192 (default about 400 KB)
+    pub max_header_size: Option<usize>,
+    
+    /// Maximum request body size in bytes; larger bodies are refused with 413 (unlimited if unset)
+    pub max_body_size: Option<u64>,
+    
+    /// Seconds a client has to send the complete headers of an HTTP/1 request
+    pub header_read_timeout: Option<u64>,
+    
+    /// Seconds an idle connection is kept open waiting for the next request
+    pub keepalive_timeout: Option<u64>,
+    
+    /// Requests served on one HTTP/1 connection before it is closed (unlimited if unset)
+    pub keepalive_requests: Option<usize>,
+    
+    /// Behavior for requests matching no route ("static" or "not-found")
+    pub unmatched_routes: Option<UnmatchedRoutes>,
+    
+    /// Whether to serve HTTP/2 (ALPN over TLS, prior knowledge in cleartext)
+    pub http2: Option<bool>,
+    
+    /// Maximum concurrent HTTP/2 streams per connection
+    pub http2_max_concurrent_streams: Option<u32>,
+    
+    /// Maximum HTTP/2 frame size in bytes (16384 to 16777215)
+    pub http2_max_frame_size: Option<u32>,
+    
+    /// Seconds to keep serving after a shutdown signal while readiness reports draining
+    pub drain_timeout: Option<u64>,
+    
+    /// Networks (CIDR) of proxies whose Forwarded and X-Forwarded-For headers name the client
+    pub trusted_proxies: Option<Vec<String>>,
+    
+    /// Whether every connection starts with a PROXY protocol (v1 or v2) header naming the client
+    pub proxy_protocol: Option<bool>,
+    
+    /// Unix domain sockets served in addition to the TCP port
+    pub unix_sockets: Option<Vec<UnixSocketConfig>>,
+}
+
+/// Unix domain socket listener configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct UnixSocketConfig {
+    /// Filesystem path of the socket; a stale socket left at the path is replaced
+    pub path: String,
+    
+    /// Permission bits of the socket file (e.g. 0o660)
+    pub mode: Option<u32>,
+    
+    /// Whether peers are proxies trusted to name the client in forwarding headers
+    pub trust_forwarded: Option<bool>,
+}
+
+/// Additional TCP listener configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ListenerConfig {
+    /// Address to bind (defaults to server.host)
+    pub address: Option<String>,
+    
+    /// Port to bind
+    pub port: u16,
+    
+    /// Whether connections terminate TLS using the [tls] certificates (defaults to false)
+    pub tls: Option<bool>,
+    
+    /// Virtual host serving every request on this listener, whatever its Host header
+    pub vhost: Option<String>,
 }
 
 /// Configuration for static file serving
@@ -43,8 +176,133 @@ pub struct StaticFilesConfig {
     /// Default file to serve for directory requests
     pub default_file: Option<String>,
     
-    /// Cache control settings
+    /// Cache-Control value for files no cache rule matches
     pub cache_control: Option<String>,
+    
+    /// Cache-Control rules by path pattern and/or extension; the first matching rule wins
+    pub cache_rules: Option<Vec<CacheRuleConfig>>,
+    
+    /// Whether to resolve clean URLs (e.g. `/about` to `/about.html`)
+    pub clean_urls: Option<bool>,
+    
+    /// Extensions to try when resolving clean URLs
+    pub clean_url_extensions: Option<Vec<String>>,
+    
+    /// Maximum directory depth below the root at which listings are generated
+    pub max_listing_depth: Option<usize>,
+    
+    /// Files larger than this many bytes are streamed instead of buffered
+    pub stream_threshold: Option<u64>,
+    
+    /// MIME type prefixes that are always streamed (e.g. "video/")
+    pub stream_types: Option<Vec<String>>,
+    
+    /// Read size for streaming uncompressed files over plaintext HTTP/1 (bytes, default 262144, 0 to disable)
+    pub send_buffer_size: Option<usize>,
+    
+    /// ETag generation strategy ("mtime" or "content-hash")
+    pub etag: Option<EtagStrategy>,
+    
+    /// Maximum number of cached content-hash ETags
+    pub etag_cache_size: Option<usize>,
+    
+    /// Path patterns whose files are always served as downloads (e.g. "/downloads/*")
+    pub attachment_paths: Option<Vec<String>>,
+    
+    /// MIME type prefixes that are always served as downloads
+    pub attachment_types: Option<Vec<String>>,
+    
+    /// Content-based MIME detection ("off", "fallback" or "always")
+    pub mime_sniffing: Option<MimeSniffing>,
+    
+    /// Serve `name.min.ext` image variants to clients sending `Save-Data: on`
+    pub save_data_variants: Option<bool>,
+    
+    /// Serve `name.br`/`name.gz` sidecar files to clients accepting their encoding (default true)
+    pub precompressed: Option<bool>,
+    
+    /// Serve files whose symlinks lead outside the root directory (default false)
+    pub follow_symlinks: Option<bool>,
+    
+    /// Serve the root default file for unresolved paths without a file extension, for client-side routing (default false)
+    pub spa: Option<bool>,
+    
+    /// Redirect requests for a directory without a trailing slash to the path with one (default true)
+    pub trailing_slash_redirect: Option<bool>,
+    
+    /// Redirect requests naming the default file, such as `/docs/index.html`, to the directory URL (default false)
+    pub canonical_index: Option<bool>,
+    
+    /// Serve `page.html.<language>` variants of `page.html` by `Accept-Language` (default false)
+    pub negotiate_language: Option<bool>,
+    
+    /// Language variant served when none is acceptable and the file itself is missing
+    pub default_language: Option<String>,
+    
+    /// Hide files and directories whose names start with a dot, except `.well-known` (default false)
+    pub exclude_hidden: Option<bool>,
+    
+    /// Glob patterns of paths that are neither served nor listed (e.g. "*.bak", ".git/", "private/*.key")
+    pub exclude: Option<Vec<String>>,
+    
+    /// Memory for caching file contents in MB (caching disabled if unset)
+    pub cache_size: Option<u64>,
+    
+    /// Largest file that is cached, in bytes
+    pub cache_max_file_size: Option<u64>,
+    
+    /// Files of at least this many bytes that are not compressed on the fly are memory-mapped (mapping disabled if unset)
+    pub mmap_threshold: Option<u64>,
+    
+    /// Address space for keeping files mapped in MB (default 1024)
+    pub mmap_cache_size: Option<u64>,
+    
+    /// Render `.md` files as HTML pages instead of serving them raw; `?raw` still gets the source (default false)
+    pub render_markdown: Option<bool>,
+    
+    /// HTML template for rendered Markdown, with `{{title}}` and `{{content}}` placeholders (built-in if unset)
+    pub markdown_template: Option<String>,
+    
+    /// Highlighting theme for code blocks in rendered Markdown (default "InspiredGitHub")
+    pub markdown_theme: Option<String>,
+}
+
+/// Cache-Control rule for static files
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CacheRuleConfig {
+    /// Path pattern using route wildcard syntax (any path if unset)
+    pub path: Option<String>,
+    
+    /// File extensions the rule applies to (any file if unset)
+    pub extensions: Option<Vec<String>>,
+    
+    /// Seconds clients may use the file without revalidating
+    pub max_age: Option<u64>,
+    
+    /// Mark the file as never changing at its URL
+    pub immutable: Option<bool>,
+    
+    /// Require revalidation before every use
+    pub no_cache: Option<bool>,
+    
+    /// Forbid storing the file at all
+    pub no_store: Option<bool>,
+    
+    /// Allow only the client, not shared caches, to store the file
+    pub private: Option<bool>,
+}
+
+/// Response compression configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CompressionConfig {
+    /// Gzip and deflate level (0-9, default 6)
+    pub gzip_level: Option<u32>,
+    
+    /// Brotli quality (0-11, default 5)
+    pub brotli_level: Option<u32>,
+    
+    /// Zstandard level (1-22, default 3)
+    pub zstd_level: Option<i32>,
 }
 
 /// TLS/SSL configuration
@@ -58,6 +316,55 @@ pub struct TlsConfig {
     
     /// Path to key file
     pub key_file: Option<String>,
+    
+    /// Minimum accepted protocol version ("1.2" or "1.3")
+    pub min_version: Option<TlsVersion>,
+    
+    /// Cipher suite policy (safe-default, modern or custom)
+    pub cipher_policy: Option<CipherPolicy>,
+    
+    /// Cipher suite names used by the custom policy
+    pub cipher_suites: Option<Vec<String>>,
+    
+    /// Port of a plain-HTTP listener on server.host redirecting every request to HTTPS (global section only)
+    pub redirect_port: Option<u16>,
+    
+    /// Strict-Transport-Security max-age in seconds, sent on responses over TLS (global section only)
+    pub hsts_max_age: Option<u64>,
+    
+    /// Whether the Strict-Transport-Security policy covers subdomains
+    pub hsts_include_subdomains: Option<bool>,
+    
+    /// Seconds between checks of the certificate and key files for changes, 0 to never reload (global section only)
+    pub reload_interval: Option<u64>,
+    
+    /// Whether to staple OCSP responses fetched from each certificate's responder (global section only)
+    pub ocsp_stapling: Option<bool>,
+}
+
+/// Certificate provisioning from an ACME CA such as Let's Encrypt
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AcmeConfig {
+    /// Domains covered by the certificate; the first names the stored files
+    pub domains: Vec<String>,
+    
+    /// Contact URLs registered with the account (e.g. "mailto:admin@example.com")
+    pub contact: Option<Vec<String>>,
+    
+    /// Whether the CA's terms of service are agreed to, required to register an account
+    pub accept_terms: Option<bool>,
+    
+    /// ACME directory URL (defaults to Let's Encrypt production)
+    pub directory_url: Option<String>,
+    
+    /// Challenge proving control of the domains: http-01 (port 80) or tls-alpn-01 (port 443)
+    pub challenge: Option<AcmeChallenge>,
+    
+    /// Directory storing the account key and certificates (defaults to ./acme)
+    pub storage_dir: Option<String>,
+    
+    /// Days before expiry at which the certificate is renewed (defaults to 30)
+    pub renew_before_days: Option<u64>,
 }
 
 /// Virtual host configuration
@@ -69,8 +376,596 @@ pub struct VirtualHostConfig {
     /// Root directory for this virtual host
     pub root_dir: String,
     
+    /// Whether to enable directory listing (defaults to `static_files.directory_listing`)
+    pub directory_listing: Option<bool>,
+    
+    /// Default file to serve for directory requests (defaults to `static_files.default_file`)
+    pub default_file: Option<String>,
+    
     /// TLS configuration specific to this virtual host
     pub tls: Option<TlsConfig>,
+    
+    /// Access log destination for this virtual host: a file path, "stdout", "stderr" or "off" (defaults to the global access log)
+    pub access_log: Option<String>,
+    
+    /// Access log format for this virtual host ("common" or "combined")
+    pub access_log_format: Option<AccessLogFormat>,
+}
+
+/// Logging configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LoggingConfig {
+    /// Server log level ("error", "warn", "info", "debug" or "trace"; default "info")
+    pub level: Option<String>,
+    
+    /// Server log file, written when the target is "file"
+    pub error_log: Option<String>,
+    
+    /// Where the server log goes: "stdout", "stderr" or "file" (default "file" if error_log is set, otherwise "stdout")
+    pub target: Option<LogTarget>,
+    
+    /// Whether the server log uses ANSI colors (default true, except in files)
+    pub ansi: Option<bool>,
+    
+    /// Log levels of individual modules overriding the level, such as `hyper = "warn"`
+    pub filters: Option<HashMap<String, String>>,
+    
+    /// Requests taking at least this many milliseconds are logged as warnings
+    pub slow_request_threshold: Option<u64>,
+    
+    /// Access log destination: a file path, "stdout", "stderr" or "off" (server log if unset)
+    pub access_log: Option<String>,
+    
+    /// Access log format ("common" or "combined")
+    pub access_log_format: Option<AccessLogFormat>,
+    
+    /// Most access log files kept open at once; others are reopened when written (default 256)
+    pub max_open_logs: Option<usize>,
+    
+    /// Rotation of the access and error log files
+    pub rotation: Option<RotationConfig>,
+}
+
+/// Log file rotation; applies to every log file the server writes
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RotationConfig {
+    /// Rotate a file once it reaches this size in MB
+    pub max_size: Option<u64>,
+    
+    /// Rotate files "hourly" or "daily"
+    pub interval: Option<RotationInterval>,
+    
+    /// Number of rotated files to keep per log (default 7)
+    pub keep: Option<usize>,
+    
+    /// Gzip rotated files
+    pub compress: Option<bool>,
+}
+
+/// Concurrency limit for requests matching a path pattern
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ConcurrencyLimitConfig {
+    /// Path pattern using route wildcard syntax (e.g. "/cgi-bin/*")
+    pub path: String,
+    
+    /// Maximum number of matching requests handled at once
+    pub max_concurrent: usize,
+    
+    /// Milliseconds an excess request may wait for a slot before a 503 (rejected immediately if unset)
+    pub queue_timeout: Option<u64>,
+}
+
+/// Per-client request rate limit for requests matching a path pattern
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RateLimitConfig {
+    /// Path pattern using route wildcard syntax (default "/*", all requests)
+    pub path: Option<String>,
+    
+    /// Maximum number of requests a client may make in a burst
+    pub burst: u32,
+    
+    /// Requests per second a client may sustain
+    pub rate: f64,
+}
+
+/// Action of an access control rule
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AclAction {
+    /// Let the request through
+    Allow,
+    /// Refuse the request with 403
+    Deny,
+}
+
+/// Access control list configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AclConfig {
+    /// Action when no rule matches (default allow)
+    pub default: Option<AclAction>,
+    
+    /// Rules in evaluation order; the first matching rule decides
+    pub rules: Option<Vec<AclRuleConfig>>,
+}
+
+/// Access control rule; all given conditions must match (a rule without conditions matches everything)
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AclRuleConfig {
+    /// Action when the rule matches
+    pub action: AclAction,
+    
+    /// Client address to match
+    pub ip: Option<IpAddr>,
+    
+    /// Client network to match, in CIDR notation (e.g. "10.0.0.0/8")
+    pub network: Option<String>,
+    
+    /// Path pattern to match, using route wildcard syntax
+    pub path: Option<String>,
+    
+    /// Regular expression matched against the User-Agent header
+    pub user_agent: Option<String>,
+    
+    /// Client country to match, as an ISO 3166 code such as "CN" (needs [geoip])
+    pub country: Option<String>,
+    
+    /// Request methods to match (e.g. ["PUT", "DELETE"])
+    pub methods: Option<Vec<String>>,
+}
+
+/// HTTP authentication scheme
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthScheme {
+    /// Basic authentication with users and htpasswd entries
+    Basic,
+    /// Digest authentication (SHA-256 and MD5) with users
+    Digest,
+    /// Bearer authentication with tokens
+    Bearer,
+}
+
+/// Authentication required for protected paths
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AuthConfig {
+    /// Realm shown in authentication prompts (default "Restricted")
+    pub realm: Option<String>,
+    
+    /// Path patterns requiring authentication, using route wildcard syntax
+    pub paths: Vec<String>,
+    
+    /// Users and plain-text passwords accepted with Basic authentication
+    pub users: Option<HashMap<String, String>>,
+    
+    /// htpasswd file of users accepted with Basic authentication (bcrypt, {SHA} or plain entries)
+    pub htpasswd: Option<String>,
+    
+    /// Tokens accepted with Bearer authentication
+    pub tokens: Option<Vec<String>>,
+    
+    /// Schemes offered to clients (default basic and bearer; digest needs plain-text users)
+    pub schemes: Option<Vec<AuthScheme>>,
+    
+    /// Seconds a Digest nonce stays valid before clients must retry with a fresh one (default 300)
+    pub nonce_lifetime: Option<u64>,
+    
+    /// Methods requiring authentication on protected paths, e.g. only writes (default every method)
+    pub methods: Option<Vec<String>>,
+}
+
+/// WebDAV access to static files
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct WebDavConfig {
+    /// Path patterns served over WebDAV, using route wildcard syntax
+    pub paths: Vec<String>,
+    
+    /// Whether only reading methods (GET, HEAD, PROPFIND) are allowed (default false)
+    pub read_only: Option<bool>,
+    
+    /// Maximum size in bytes of a file uploaded with PUT (unlimited if unset)
+    pub max_upload_size: Option<u64>,
+    
+    /// Longest time in seconds a client may hold a lock before refreshing it (default 600)
+    pub max_lock_timeout: Option<u64>,
+    
+    /// Whether clients may write without authentication; otherwise [auth] must protect the paths (default false)
+    pub anonymous_writes: Option<bool>,
+}
+
+/// Cross-origin resource sharing policy
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CorsConfig {
+    /// Origins allowed to make cross-origin requests, such as "https://*.example.com"; "*" allows any origin
+    pub allowed_origins: Vec<String>,
+    
+    /// Path patterns the policy applies to, using route wildcard syntax (default every path)
+    pub paths: Option<Vec<String>>,
+    
+    /// Methods allowed in cross-origin requests (default GET, HEAD and POST); "*" allows any method
+    pub allowed_methods: Option<Vec<String>>,
+    
+    /// Request headers allowed in cross-origin requests (default none beyond the safelisted ones); "*" allows any header
+    pub allowed_headers: Option<Vec<String>>,
+    
+    /// Response headers scripts may read besides the safelisted ones
+    pub exposed_headers: Option<Vec<String>>,
+    
+    /// Whether requests may carry cookies and credentials (default false)
+    pub allow_credentials: Option<bool>,
+    
+    /// Seconds browsers may cache a preflight response
+    pub max_age: Option<u64>,
+}
+
+/// HTTP method override configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct MethodOverrideConfig {
+    /// Whether POST requests may override their method
+    pub enabled: bool,
+    
+    /// Header carrying the overriding method (default "X-HTTP-Method-Override")
+    pub header: Option<String>,
+    
+    /// Methods a POST may be overridden to (default PUT, PATCH and DELETE)
+    pub allowed_methods: Option<Vec<String>>,
+}
+
+/// Reverse proxy configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ProxyConfig {
+    /// Upstream pools, each forwarding one route pattern
+    pub pools: Vec<UpstreamPoolConfig>,
+}
+
+/// A pool of upstream servers behind one route pattern
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct UpstreamPoolConfig {
+    /// Name of the pool, used in logs
+    pub name: String,
+    
+    /// Route pattern forwarded to this pool (e.g. "/api/*")
+    pub path: String,
+    
+    /// Methods forwarded to this pool (any if unset)
+    pub methods: Option<Vec<String>>,
+    
+    /// Route priority; higher wins over more specific patterns (default 0)
+    pub priority: Option<i32>,
+    
+    /// Upstream base URLs (e.g. "http://127.0.0.1:9000")
+    pub servers: Vec<String>,
+    
+    /// Strategy picking the server for each request (default round-robin)
+    pub strategy: Option<BalanceStrategy>,
+    
+    /// Relative weights of the servers under the weighted strategy, one per server (default 1 each)
+    pub weights: Option<Vec<u32>>,
+    
+    /// Consecutive failures after which a server is ejected (default 3)
+    pub max_fails: Option<u32>,
+    
+    /// Seconds an ejected server gets no requests (default 10)
+    pub fail_timeout: Option<u64>,
+    
+    /// Active health checks probing each server in the background
+    pub health_check: Option<HealthCheckConfig>,
+    
+    /// Forward the client's Host header instead of the upstream's authority
+    pub preserve_host: Option<bool>,
+    
+    /// Gzip request bodies as they are forwarded
+    pub compress_requests: Option<bool>,
+    
+    /// Speak only HTTP/2 to the servers, with prior knowledge on cleartext (h2c),
+    /// as gRPC needs to relay trailers (https servers negotiate HTTP/2 anyway)
+    pub http2: Option<bool>,
+    
+    /// Timeout for the upstream response headers in seconds (overrides request_timeout)
+    pub timeout: Option<u64>,
+}
+
+/// Active health check of the servers in an upstream pool
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct HealthCheckConfig {
+    /// Path requested from each server; 2xx and 3xx responses count as passing
+    pub path: String,
+    
+    /// Seconds between probes (default 10)
+    pub interval: Option<u64>,
+    
+    /// Seconds to wait for a probe response (default 2)
+    pub timeout: Option<u64>,
+    
+    /// Consecutive passing probes that mark a server healthy again (default 2)
+    pub healthy_threshold: Option<u32>,
+    
+    /// Consecutive failing probes that mark a server unhealthy (default 3)
+    pub unhealthy_threshold: Option<u32>,
+}
+
+/// FastCGI backend configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct FastCgiConfig {
+    /// Route pattern handled by the backend (e.g. "*.php")
+    pub path: String,
+    
+    /// Methods handled by the backend (any if unset)
+    pub methods: Option<Vec<String>>,
+    
+    /// Route priority; higher wins over more specific patterns (default 0)
+    pub priority: Option<i32>,
+    
+    /// Backend address (e.g. "127.0.0.1:9000")
+    pub address: SocketAddr,
+    
+    /// Document root the backend resolves scripts in (defaults to static_files.root_dir)
+    pub document_root: Option<String>,
+    
+    /// Timeout for the backend response in seconds (overrides request_timeout)
+    pub timeout: Option<u64>,
+}
+
+/// SCGI backend configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ScgiConfig {
+    /// Route pattern handled by the backend (e.g. "/app/*")
+    pub path: String,
+    
+    /// Methods handled by the backend (any if unset)
+    pub methods: Option<Vec<String>>,
+    
+    /// Route priority; higher wins over more specific patterns (default 0)
+    pub priority: Option<i32>,
+    
+    /// Backend address (e.g. "127.0.0.1:4000")
+    pub address: SocketAddr,
+    
+    /// Timeout for the backend response in seconds (overrides request_timeout)
+    pub timeout: Option<u64>,
+}
+
+/// uwsgi backend configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct UwsgiConfig {
+    /// Route pattern handled by the backend (e.g. "/app/*")
+    pub path: String,
+    
+    /// Methods handled by the backend (any if unset)
+    pub methods: Option<Vec<String>>,
+    
+    /// Route priority; higher wins over more specific patterns (default 0)
+    pub priority: Option<i32>,
+    
+    /// Backend address (e.g. "127.0.0.1:3031")
+    pub address: SocketAddr,
+    
+    /// Packet modifier selecting the application type (default 0, WSGI)
+    pub modifier1: Option<u8>,
+    
+    /// Timeout for the backend response in seconds (overrides request_timeout)
+    pub timeout: Option<u64>,
+}
+
+/// CGI script configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CgiConfig {
+    /// Route pattern of the scripts (e.g. "/cgi-bin/*")
+    pub path: String,
+    
+    /// Methods the scripts accept (any if unset)
+    pub methods: Option<Vec<String>>,
+    
+    /// Route priority; higher wins over more specific patterns (default 0)
+    pub priority: Option<i32>,
+    
+    /// Directory scripts are resolved in (defaults to static_files.root_dir)
+    pub document_root: Option<String>,
+    /// Maximum script execution time in seconds (default 30, overrides request_timeout)
+    /// Maximum script execution time in seconds (default 30)
+    pub timeout: Option<u64>,
+    
+    /// Largest output a script may write in bytes, headers included (default 64 MiB)
+    pub max_output_size: Option<u64>,
+}
+
+/// Upload endpoint storing request bodies as files
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct UploadConfig {
+    /// Route pattern of the endpoint (e.g. "/upload/*"); the rest of the path names the file
+    pub path: String,
+    
+    /// Directory uploaded files are stored in
+    pub directory: String,
+    
+    /// Methods accepted for uploads (default PUT and POST)
+    pub methods: Option<Vec<String>>,
+    
+    /// Route priority; higher wins over more specific patterns (default 0)
+    pub priority: Option<i32>,
+    
+    /// Maximum size of an uploaded file in bytes (unlimited if unset)
+    pub max_size: Option<u64>,
+    
+    /// MIME types or prefixes (e.g. "image/") files may have (any if unset)
+    pub allowed_types: Option<Vec<String>>,
+    
+    /// Whether existing files may be replaced (default true)
+    pub overwrite: Option<bool>,
+}
+
+/// Route declared in the route table
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RouteConfig {
+    /// Route pattern (e.g. "/api/*")
+    pub path: String,
+    
+    /// Handler serving the route: "static", "proxy", "fastcgi", "scgi", "uwsgi", "cgi", "upload" or "service"
+    pub handler: String,
+    
+    /// Proxy pool name, the path of the FastCGI, SCGI or uwsgi backend, CGI or upload entry,
+    /// or the pattern a service was mounted at, serving the route
+    pub params: Option<String>,
+    
+    /// Host pattern of the virtual host the route belongs to (all hosts if unset)
+    pub host: Option<String>,
+    
+    /// Methods the route accepts (any if unset)
+    pub methods: Option<Vec<String>>,
+    
+    /// Route priority; higher wins over more specific patterns (default 0)
+    pub priority: Option<i32>,
+    
+    /// Handler timeout in seconds (overrides request_timeout)
+    pub timeout: Option<u64>,
+}
+
+/// Flag modifying a URL rewrite rule
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RewriteFlag {
+    /// Stop processing further rules when this one matches
+    Last,
+    /// Answer with a 302 redirect to the rewritten URL
+    Redirect,
+    /// Answer with a 301 redirect to the rewritten URL
+    Permanent,
+}
+
+/// URL rewrite rule, applied to the request path before routing
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RewriteConfig {
+    /// Regular expression matched against the request path
+    pub pattern: String,
+    
+    /// Replacement path or URL; `$1` or `${name}` insert captured groups
+    pub replacement: String,
+    
+    /// Rule flags: "last", "redirect" (302) or "permanent" (301)
+    pub flags: Option<Vec<RewriteFlag>>,
+}
+
+/// Plugin pipeline configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PluginsConfig {
+    /// Plugins whose hook failures fail the request with 500 instead of being skipped
+    pub required: Option<Vec<String>>,
+    
+    /// Sandboxed WebAssembly plugins, run after plugins registered in code
+    pub wasm: Option<Vec<WasmPluginConfig>>,
+    
+    /// Settings of individual plugins from `[plugins.<name>]` tables, by plugin name
+    #[serde(flatten)]
+    pub settings: HashMap<String, PluginSettings>,
+}
+
+/// Settings of one plugin from its `[plugins.<name>]` table
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PluginSettings {
+    /// Whether the plugin runs (default true)
+    pub enabled: Option<bool>,
+    
+    /// Remaining keys, passed to the plugin's `init` as its configuration
+    #[serde(flatten)]
+    pub config: serde_json::Map<String, serde_json::Value>,
+}
+
+/// WebAssembly plugin configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct WasmPluginConfig {
+    /// Plugin name, used in logs and `plugins.required`
+    pub name: String,
+    
+    /// Path of the module (binary .wasm or text .wat)
+    pub path: String,
+    
+    /// Fuel for each hook call, roughly the number of instructions it may execute (default 10000000)
+    pub fuel: Option<u64>,
+    
+    /// Limit of the plugin's linear memory in megabytes (default 16)
+    pub memory_limit: Option<u64>,
+}
+
+/// GeoIP lookups of client addresses
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct GeoIpConfig {
+    /// MaxMind DB files (.mmdb) to look clients up in, in order, such as a country and an ASN database
+    pub databases: Vec<String>,
+}
+
+/// Placement of a middleware in the request chain
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct MiddlewareConfig {
+    /// Name of the middleware, as provided by a plugin
+    pub name: String,
+    
+    /// Stage to run at: "request", "access" or "handler" (default the one the plugin chose)
+    pub stage: Option<Stage>,
+    
+    /// Path patterns the middleware runs for, using route wildcard syntax (default every path)
+    pub paths: Option<Vec<String>>,
+    
+    /// Virtual host patterns the middleware runs for, as declared in [[virtual_hosts]] (default every host)
+    pub hosts: Option<Vec<String>>,
+    
+    /// Whether the middleware runs (default true)
+    pub enabled: Option<bool>,
+}
+
+/// Error page configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ErrorPagesConfig {
+    /// HTML template with {{status}}, {{reason}} and {{message}} placeholders
+    pub template: Option<String>,
+}
+
+/// Admin endpoint configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AdminConfig {
+    /// Whether to enable the admin endpoints
+    pub enabled: bool,
+    
+    /// Path of the status endpoint
+    pub status_path: Option<String>,
+    
+    /// Path of the build information endpoint
+    pub version_path: Option<String>,
+    
+    /// Path of the effective configuration endpoint
+    pub config_path: Option<String>,
+    
+    /// Path of the route table endpoint
+    pub routes_path: Option<String>,
+    
+    /// Path of the loaded plugins endpoint
+    pub plugins_path: Option<String>,
+    
+    /// Separate address to serve admin endpoints on; they are then not served on the main listener
+    pub listen: Option<SocketAddr>,
+    
+    /// Username required to access admin endpoints
+    pub username: Option<String>,
+    
+    /// Password required to access admin endpoints
+    pub password: Option<String>,
+    
+    /// Bearer token accepted for admin endpoints, alongside the username and password
+    pub token: Option<String>,
+    
+    /// Client addresses allowed to access admin endpoints (any if unset)
+    pub allowed_ips: Option<Vec<IpAddr>>,
+}
+
+/// Health check endpoint configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct HealthConfig {
+    /// Whether to serve the health check endpoints
+    pub enabled: bool,
+    
+    /// Path of the liveness endpoint
+    pub liveness_path: Option<String>,
+    
+    /// Path of the readiness endpoint
+    pub readiness_path: Option<String>,
 }
 
 /// Main configuration structure
@@ -82,47 +977,261 @@ pub struct Config {
     /// Static files configuration
     pub static_files: StaticFilesConfig,
     
+    /// Response compression configuration
+    pub compression: Option<CompressionConfig>,
+    
     /// Global TLS configuration
     pub tls: Option<TlsConfig>,
     
+    /// TCP listeners served in addition to server.host and server.port
+    pub listeners: Option<Vec<ListenerConfig>>,
+    
+    /// Certificates obtained and renewed automatically over ACME
+    pub acme: Option<AcmeConfig>,
+    
     /// Virtual hosts configuration
     pub virtual_hosts: Option<Vec<VirtualHostConfig>>,
+    
+    /// Routes declared in addition to those of proxy pools, gateway backends and CGI entries
+    pub routes: Option<Vec<RouteConfig>>,
+    
+    /// URL rewrite rules in evaluation order
+    pub rewrite: Option<Vec<RewriteConfig>>,
+    
+    /// Logging configuration
+    pub logging: Option<LoggingConfig>,
+    
+    /// Admin endpoint configuration
+    pub admin: Option<AdminConfig>,
+    
+    /// Health check endpoint configuration
+    pub health: Option<HealthConfig>,
+    
+    /// Error page configuration
+    pub error_pages: Option<ErrorPagesConfig>,
+    
+    /// Per-route concurrency limits
+    pub concurrency_limits: Option<Vec<ConcurrencyLimitConfig>>,
+    
+    /// Per-client rate limits
+    pub rate_limits: Option<Vec<RateLimitConfig>>,
+    
+    /// Access control list applied to every request
+    pub acl: Option<AclConfig>,
+    
+    /// GeoIP databases for client countries and networks
+    pub geoip: Option<GeoIpConfig>,
+    
+    /// Authentication for protected paths
+    pub auth: Option<AuthConfig>,
+    
+    /// Cross-origin resource sharing policy
+    pub cors: Option<CorsConfig>,
+    
+    /// WebDAV access to static files
+    pub webdav: Option<WebDavConfig>,
+    
+    /// HTTP method override configuration
+    pub method_override: Option<MethodOverrideConfig>,
+    
+    /// Reverse proxy configuration
+    pub proxy: Option<ProxyConfig>,
+    
+    /// FastCGI backends
+    pub fastcgi: Option<Vec<FastCgiConfig>>,
+    
+    /// SCGI backends
+    pub scgi: Option<Vec<ScgiConfig>>,
+    
+    /// uwsgi backends
+    pub uwsgi: Option<Vec<UwsgiConfig>>,
+    
+    /// CGI script routes
+    pub cgi: Option<Vec<CgiConfig>>,
+    
+    /// Upload endpoints
+    pub upload: Option<Vec<UploadConfig>>,
+    
+    /// Plugin pipeline configuration
+    pub plugins: Option<PluginsConfig>,
+    
+    /// Placement of middleware in the request chain, in the order it runs within each stage
+    pub middleware: Option<Vec<MiddlewareConfig>>,
 }
 
 impl Config {
     /// Load configuration from a file
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
         let content = fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&content)?;
+        Self::from_toml(&content)
+    }
+    
+    /// Parse configuration from TOML text
+    pub fn from_toml(content: &str) -> Result<Self, ConfigError> {
+        let config: Config = toml::from_str(content)?;
         Ok(config)
     }
     
+    /// Save configuration to a file
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError> {
+        let content = toml::to_string_pretty(self)?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+    
+    /// Get the effective configuration, with the default of every unset setting filled in.
+    ///
+    /// Settings whose absence disables a feature or leaves a limit off, such as
+    /// `request_timeout` or `cache_size`, have no default and stay unset.
+    pub fn resolved(mut self) -> Config {
+        let server = &mut self.server;
+        server.workers.get_or_insert_with(num_cpus::get);
+        server.pin_workers.get_or_insert(false);
+        server.reuse_port.get_or_insert(false);
+        server.connection_timeout.get_or_insert(60);
+        server.self_test.get_or_insert(false);
+        server.self_test_strict.get_or_insert(false);
+        server.path_case.get_or_insert_with(PathCase::default);
+        server.strip_trailing_dots.get_or_insert(false);
+        server.unmatched_routes.get_or_insert_with(UnmatchedRoutes::default);
+        server.http2.get_or_insert(false);
+        server.drain_timeout.get_or_insert(0);
+        server.proxy_protocol.get_or_insert(false);
+        
+        let static_files = &mut self.static_files;
+        static_files.directory_listing.get_or_insert(false);
+        static_files.default_file.get_or_insert_with(|| "index.html".to_string());
+        static_files.clean_urls.get_or_insert(false);
+        static_files.clean_url_extensions.get_or_insert_with(|| vec!["html".to_string()]);
+        static_files.send_buffer_size.get_or_insert(DEFAULT_SEND_BUFFER_SIZE);
+        static_files.etag.get_or_insert(EtagStrategy::Mtime);
+        static_files.etag_cache_size.get_or_insert(DEFAULT_ETAG_CACHE_SIZE);
+        static_files.mime_sniffing.get_or_insert_with(MimeSniffing::default);
+        static_files.save_data_variants.get_or_insert(false);
+        static_files.precompressed.get_or_insert(true);
+        static_files.follow_symlinks.get_or_insert(false);
+        static_files.cache_max_file_size.get_or_insert(DEFAULT_MAX_FILE_SIZE);
+        static_files.mmap_cache_size.get_or_insert(DEFAULT_MMAP_CACHE_SIZE);
+        static_files.spa.get_or_insert(false);
+        static_files.trailing_slash_redirect.get_or_insert(true);
+        static_files.canonical_index.get_or_insert(false);
+        static_files.negotiate_language.get_or_insert(false);
+        static_files.exclude_hidden.get_or_insert(false);
+        static_files.render_markdown.get_or_insert(false);
+        static_files.markdown_theme.get_or_insert_with(|| DEFAULT_THEME.to_string());
+        
+        let compression = self.compression.get_or_insert(CompressionConfig {
+            gzip_level: None,
+            brotli_level: None,
+            zstd_level: None,
+        });
+        compression.gzip_level.get_or_insert(DEFAULT_GZIP_LEVEL);
+        compression.brotli_level.get_or_insert(DEFAULT_BROTLI_LEVEL);
+        compression.zstd_level.get_or_insert(DEFAULT_ZSTD_LEVEL);
+        
+        self
+    }
+}
+
+impl Default for Config {
     /// Create a default configuration
-    pub fn default() -> Self {
+    fn default() -> Self {
         Config {
             server: ServerConfig {
                 host: "127.0.0.1".to_string(),
                 port: 8000,
                 workers: Some(num_cpus::get()),
+                blocking_threads: None,
+                pin_workers: None,
+                reuse_port: None,
                 max_connections: Some(1024),
+                max_connections_per_ip: None,
+                connection_queue_timeout: None,
                 connection_timeout: Some(60),
+                request_timeout: None,
+                response_timeout: None,
+                memory_budget: None,
+                self_test: Some(false),
+                self_test_strict: Some(false),
+                path_case: None,
+                strip_trailing_dots: None,
+                max_headers: None,
+                max_header_size: None,
+                max_body_size: None,
+                header_read_timeout: None,
+                keepalive_timeout: None,
+                keepalive_requests: None,
+                unmatched_routes: None,
+                http2: Some(false),
+                http2_max_concurrent_streams: None,
+                http2_max_frame_size: None,
+                drain_timeout: None,
+                trusted_proxies: None,
+                proxy_protocol: None,
+                unix_sockets: None,
             },
             static_files: StaticFilesConfig {
                 root_dir: "./public".to_string(),
                 directory_listing: Some(false),
                 default_file: Some("index.html".to_string()),
                 cache_control: Some("public, max-age=3600".to_string()),
+                cache_rules: None,
+                clean_urls: Some(false),
+                clean_url_extensions: None,
+                max_listing_depth: None,
+                stream_threshold: None,
+                stream_types: None,
+                send_buffer_size: None,
+                etag: Some(EtagStrategy::Mtime),
+                etag_cache_size: None,
+                attachment_paths: None,
+                attachment_types: None,
+                mime_sniffing: None,
+                save_data_variants: None,
+                precompressed: Some(true),
+                follow_symlinks: Some(false),
+                cache_size: None,
+                cache_max_file_size: None,
+                mmap_threshold: None,
+                mmap_cache_size: None,
+                spa: None,
+                trailing_slash_redirect: Some(true),
+                canonical_index: None,
+                negotiate_language: None,
+                default_language: None,
+                exclude_hidden: None,
+                exclude: None,
+                render_markdown: None,
+                markdown_template: None,
+                markdown_theme: None,
             },
+            compression: None,
             tls: None,
+            listeners: None,
+            acme: None,
             virtual_hosts: None,
+            routes: None,
+            rewrite: None,
+            logging: None,
+            admin: None,
+            health: None,
+            error_pages: None,
+            concurrency_limits: None,
+            rate_limits: None,
+            acl: None,
+            auth: None,
+            cors: None,
+            webdav: None,
+            method_override: None,
+            proxy: None,
+            fastcgi: None,
+            scgi: None,
+            uwsgi: None,
+            cgi: None,
+            upload: None,
+            plugins: None,
+            middleware: None,
+            geoip: None,
         }
     }
-    
-    /// Save configuration to a file
-    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError> {
-        let content = toml::to_string_pretty(self)
-            .map_err(|e| ConfigError::TomlError(e))?;
-        fs::write(path, content)?;
-        Ok(())
-    }
 }
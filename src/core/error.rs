@@ -0,0 +1,288 @@
+use hyper::{Body, Response, StatusCode};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use tracing::{debug, error, info};
+
+use crate::core::config::Config;
+use crate::network::http::response::ResponseBuilder;
+
+/// Built-in error page template
+const DEFAULT_TEMPLATE: &str = "<h1>{{status}} {{reason}}</h1><p>{{message}}</p>";
+
+/// Errors that handlers return to be rendered as HTTP error responses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HttpError {
+    /// The request is malformed
+    BadRequest(String),
+    /// Authentication is required; carries one `WWW-Authenticate` challenge per accepted scheme
+    Unauthorized { challenges: Vec<String> },
+    /// Access to the resource is denied
+    Forbidden(String),
+    /// The resource does not exist
+    NotFound,
+    /// The method is not supported by the resource; `allow` lists the methods that are
+    MethodNotAllowed { allow: String },
+    /// The request conflicts with the current state of the resource, such as a missing parent collection
+    Conflict(String),
+    /// A request precondition (If-Match, If-Unmodified-Since, ...) does not hold
+    PreconditionFailed,
+    /// No requested range overlaps a representation of `total` bytes
+    RangeNotSatisfiable { total: u64 },
+    /// The client exceeded its request rate; it may retry after `retry_after` seconds
+    TooManyRequests { retry_after: u64 },
+    /// The request body exceeds the configured size limit
+    PayloadTooLarge,
+    /// The request body has a media type the resource does not accept
+    UnsupportedMediaType,
+    /// The resource is locked against the request
+    Locked,
+    /// The request carries too many or too large headers
+    HeaderFieldsTooLarge,
+    /// The server failed while handling the request
+    Internal(String),
+    /// The requested functionality is not available
+    NotImplemented,
+    /// An upstream server returned an invalid response
+    BadGateway(String),
+    /// The server is temporarily overloaded
+    ServiceUnavailable,
+    /// The handler or upstream did not respond in time
+    GatewayTimeout,
+}
+
+impl HttpError {
+    /// Get the status code for this error
+    pub fn status(&self) -> StatusCode {
+        match self {
+            HttpError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            HttpError::Unauthorized { .. } => StatusCode::UNAUTHORIZED,
+            HttpError::Forbidden(_) => StatusCode::FORBIDDEN,
+            HttpError::NotFound => StatusCode::NOT_FOUND,
+            HttpError::MethodNotAllowed { .. } => StatusCode::METHOD_NOT_ALLOWED,
+            HttpError::Conflict(_) => StatusCode::CONFLICT,
+            HttpError::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            HttpError::RangeNotSatisfiable { .. } => StatusCode::RANGE_NOT_SATISFIABLE,
+            HttpError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            HttpError::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            HttpError::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            HttpError::Locked => StatusCode::LOCKED,
+            HttpError::HeaderFieldsTooLarge => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            HttpError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            HttpError::NotImplemented => StatusCode::NOT_IMPLEMENTED,
+            HttpError::BadGateway(_) => StatusCode::BAD_GATEWAY,
+            HttpError::ServiceUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            HttpError::GatewayTimeout => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+    
+    /// Get the client-facing message for this error
+    pub fn message(&self) -> &str {
+        match self {
+            HttpError::BadRequest(message) | HttpError::Forbidden(message) | HttpError::Conflict(message) => message,
+            HttpError::Unauthorized { .. } => "Authentication is required to access this resource.",
+            HttpError::NotFound => "The requested resource was not found on this server.",
+            HttpError::MethodNotAllowed { .. } => "The request method is not supported for this resource.",
+            HttpError::PreconditionFailed => "A precondition on the request was not met.",
+            HttpError::RangeNotSatisfiable { .. } => "The requested range is not satisfiable.",
+            HttpError::TooManyRequests { .. } => "Too many requests; please slow down.",
+            HttpError::PayloadTooLarge => "The request body is too large.",
+            HttpError::UnsupportedMediaType => "The request body has an unsupported media type.",
+            HttpError::Locked => "The resource is locked.",
+            HttpError::HeaderFieldsTooLarge => "The request carries too many headers.",
+            HttpError::Internal(_) => "The server encountered an internal error.",
+            HttpError::NotImplemented => "The requested functionality is not implemented.",
+            HttpError::BadGateway(_) => "The upstream server returned an invalid response.",
+            HttpError::ServiceUnavailable => "The server is temporarily unable to handle the request.",
+            HttpError::GatewayTimeout => "The request handler did not respond in time.",
+        }
+    }
+    
+    /// Render this error as a response using the given error pages
+    pub fn to_response(&self, pages: &ErrorPages) -> Response<Body> {
+        let builder = ResponseBuilder::with_status(self.status());
+        
+        let builder = match self {
+            HttpError::Unauthorized { challenges } => challenges
+                .iter()
+                .fold(builder, |builder, challenge| builder.append_header("www-authenticate", challenge)),
+            HttpError::MethodNotAllowed { allow } => builder.header("allow", allow),
+            HttpError::RangeNotSatisfiable { total } => builder.header("content-range", &format!("bytes */{}", total)),
+            HttpError::TooManyRequests { retry_after } => builder.header("retry-after", &retry_after.to_string()),
+            HttpError::PayloadTooLarge | HttpError::HeaderFieldsTooLarge => builder.header("connection", "close"),
+            HttpError::ServiceUnavailable => builder.header("retry-after", "1"),
+            _ => builder,
+        };
+        
+        builder
+            .content_type("text/html")
+            .body_string(pages.render(self))
+            .build()
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::Internal(detail) | HttpError::BadGateway(detail) if !detail.is_empty() => {
+                write!(f, "{}: {}", self.status(), detail)
+            }
+            _ => write!(f, "{}: {}", self.status(), self.message()),
+        }
+    }
+}
+
+impl Error for HttpError {}
+
+/// Renderer for error pages, using a configurable HTML template
+#[derive(Debug, Clone)]
+pub struct ErrorPages {
+    /// Template with `{{status}}`, `{{reason}}` and `{{message}}` placeholders
+    template: String,
+}
+
+impl Default for ErrorPages {
+    fn default() -> Self {
+        ErrorPages {
+            template: DEFAULT_TEMPLATE.to_string(),
+        }
+    }
+}
+
+impl ErrorPages {
+    /// Create error pages from a template string
+    pub fn new(template: String) -> Self {
+        ErrorPages { template }
+    }
+    
+    /// Load error pages from the configured template file, if any
+    pub fn from_config(config: &Config) -> std::io::Result<Self> {
+        match config.error_pages.as_ref().and_then(|pages| pages.template.as_ref()) {
+            Some(path) => {
+                info!("Loading error page template from {}", path);
+                Ok(Self::new(fs::read_to_string(path)?))
+            }
+            None => Ok(Self::default()),
+        }
+    }
+    
+    /// Render the page body for an error
+    pub fn render(&self, error: &HttpError) -> String {
+        let status = error.status();
+        self.template
+            .replace("{{status}}", status.as_str())
+            .replace("{{reason}}", status.canonical_reason().unwrap_or(""))
+            .replace("{{message}}", &escape_html(error.message()))
+    }
+    
+    /// Render an error a handler returned, as itself when it is an `HttpError` and as 500 otherwise
+    pub fn render_error(&self, e: Box<dyn Error + Send + Sync>) -> Response<Body> {
+        // Request body errors, such as an exceeded size limit, reach handlers wrapped in hyper's error
+        match std::iter::successors(Some(&*e as &(dyn Error + 'static)), |e| (*e).source())
+            .find_map(|e| e.downcast_ref::<HttpError>())
+        {
+            Some(http_error) => {
+                // Handlers log the context of their own typed errors
+                debug!("Handler error: {}", http_error);
+                http_error.to_response(self)
+            }
+            None => {
+                error!("Handler error: {}", e);
+                HttpError::Internal(e.to_string()).to_response(self)
+            }
+        }
+    }
+}
+
+/// Escape text for inclusion in HTML
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    async fn body_of(response: Response<Body>) -> String {
+        String::from_utf8(hyper::body::to_bytes(response.into_body()).await.unwrap().to_vec()).unwrap()
+    }
+    
+    #[tokio::test]
+    async fn every_variant_renders_its_status_with_the_template() {
+        let pages = ErrorPages::new("[{{status}} {{reason}}] {{message}}".to_string());
+        let cases = [
+            (HttpError::BadRequest("Bad <input>".to_string()), 400),
+            (HttpError::Unauthorized { challenges: vec!["Basic realm=\"x\"".to_string()] }, 401),
+            (HttpError::Forbidden("No entry.".to_string()), 403),
+            (HttpError::NotFound, 404),
+            (HttpError::MethodNotAllowed { allow: "GET".to_string() }, 405),
+            (HttpError::Conflict("Exists.".to_string()), 409),
+            (HttpError::PreconditionFailed, 412),
+            (HttpError::PayloadTooLarge, 413),
+            (HttpError::UnsupportedMediaType, 415),
+            (HttpError::RangeNotSatisfiable { total: 10 }, 416),
+            (HttpError::Locked, 423),
+            (HttpError::TooManyRequests { retry_after: 7 }, 429),
+            (HttpError::HeaderFieldsTooLarge, 431),
+            (HttpError::Internal("secret detail".to_string()), 500),
+            (HttpError::NotImplemented, 501),
+            (HttpError::BadGateway("upstream detail".to_string()), 502),
+            (HttpError::ServiceUnavailable, 503),
+            (HttpError::GatewayTimeout, 504),
+        ];
+        
+        for (error, status) in cases {
+            let response = error.to_response(&pages);
+            assert_eq!(response.status().as_u16(), status, "{:?}", error);
+            assert_eq!(response.headers()["content-type"], "text/html");
+            
+            let body = body_of(response).await;
+            let reason = error.status().canonical_reason().unwrap();
+            assert_eq!(body, format!("[{} {}] {}", status, reason, escape_html(error.message())));
+            assert!(!body.contains("detail"), "{}", body);
+        }
+    }
+    
+    #[test]
+    fn variants_carry_their_headers() {
+        let pages = ErrorPages::default();
+        
+        let response = HttpError::Unauthorized { challenges: vec!["Basic".to_string(), "Bearer".to_string()] }.to_response(&pages);
+        assert_eq!(response.headers().get_all("www-authenticate").iter().count(), 2);
+        assert_eq!(HttpError::MethodNotAllowed { allow: "GET, HEAD".to_string() }.to_response(&pages).headers()["allow"], "GET, HEAD");
+        assert_eq!(HttpError::RangeNotSatisfiable { total: 10 }.to_response(&pages).headers()["content-range"], "bytes */10");
+        assert_eq!(HttpError::TooManyRequests { retry_after: 7 }.to_response(&pages).headers()["retry-after"], "7");
+        assert_eq!(HttpError::ServiceUnavailable.to_response(&pages).headers()["retry-after"], "1");
+    }
+    
+    /// Error wrapping its cause, as hyper wraps request body errors
+    #[derive(Debug)]
+    struct BodyError(HttpError);
+    
+    impl fmt::Display for BodyError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "error reading a body from connection")
+        }
+    }
+    
+    impl Error for BodyError {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+    
+    #[tokio::test]
+    async fn render_error_finds_wrapped_errors_and_hides_others() {
+        let pages = ErrorPages::new("{{status}}".to_string());
+        
+        let response = pages.render_error(Box::new(BodyError(HttpError::PayloadTooLarge)));
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        
+        let response = pages.render_error("database password is hunter2".into());
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body_of(response).await, "500");
+    }
+}
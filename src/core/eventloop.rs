@@ -1,39 +1,234 @@
+use std::io;
+use std::net::IpAddr;
 use std::sync::Arc;
-use tokio::net::{TcpListener, TcpStream};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+#[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+use tokio::net::TcpSocket;
+#[cfg(unix)]
+use tokio::net::UnixListener;
 use tokio::task::JoinHandle;
-use tracing::{error, info};
+use tracing::{debug, error, info, warn};
 
-use crate::core::config::Config;
-use crate::network::connection::ConnectionHandler;
+use crate::core::config::{Config, ListenerConfig, UnixSocketConfig};
+use crate::core::middleware::{Middleware, MiddlewareChain, Stage};
+use crate::handlers::admin::AdminHandler;
+use crate::handlers::service::ServiceRoutes;
+use crate::network::connection::{ConnectionHandler, RequestHandlers, SharedState};
+use crate::plugins::api::WebSocketHandler;
+use crate::plugins::pipeline::PluginPipeline;
+
+/// Pending connection queue length of listening sockets bound with `SO_REUSEPORT`
+#[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+const LISTEN_BACKLOG: u32 = 1024;
 
 /// The main event loop for the Kaserve web server
 pub struct EventLoop {
     /// Server configuration
     config: Arc<Config>,
-    /// List of TCP listeners
-    listeners: Vec<TcpListener>,
+    /// TCP listeners with their configuration
+    listeners: Vec<(TcpListener, ListenerConfig)>,
+    /// Listener serving only the admin endpoints, if configured
+    admin_listener: Option<TcpListener>,
+    /// Plain-HTTP listener redirecting to HTTPS, if configured
+    redirect_listener: Option<TcpListener>,
+    /// Unix domain socket listeners with their configuration
+    #[cfg(unix)]
+    unix_listeners: Vec<(UnixListener, UnixSocketConfig)>,
     /// List of worker tasks
     worker_tasks: Vec<JoinHandle<()>>,
+    /// State shared by all connections
+    shared: SharedState,
 }
 
 impl EventLoop {
     /// Create a new event loop with the given configuration
     pub async fn new(config: Arc<Config>) -> std::io::Result<Self> {
-        let addr = format!("{}:{}", config.server.host, config.server.port);
-        let listener = TcpListener::bind(&addr).await?;
+        let acceptors = match config.server.reuse_port {
+            Some(true) => config.server.workers.unwrap_or_else(num_cpus::get).max(1),
+            _ => 1,
+        };
+        
+        let main_listener_config = Self::main_listener_config(&config);
+        let mut listeners = Vec::new();
+        for listener in Self::bind_tcp(&config.server.host, config.server.port, acceptors).await? {
+            listeners.push((listener, main_listener_config.clone()));
+        }
+        info!("Server listening on {}:{}", config.server.host, config.server.port);
+        
+        for listener_config in config.listeners.iter().flatten() {
+            let address = listener_config.address.as_deref().unwrap_or(&config.server.host);
+            for listener in Self::bind_tcp(address, listener_config.port, acceptors).await? {
+                listeners.push((listener, listener_config.clone()));
+            }
+            info!(
+                "Server listening on {}:{}{}",
+                address,
+                listener_config.port,
+                if listener_config.tls.unwrap_or(false) { " (TLS)" } else { "" },
+            );
+        }
+        
+        let admin_listener = match config.admin.as_ref().filter(|admin| admin.enabled).and_then(|admin| admin.listen) {
+            Some(admin_addr) => {
+                let admin_listener = TcpListener::bind(admin_addr).await?;
+                info!("Admin endpoints listening on {}", admin_addr);
+                Some(admin_listener)
+            }
+            None => None,
+        };
         
-        info!("Server listening on {}", addr);
+        let redirect_port = config.tls.as_ref().filter(|tls| tls.enabled).and_then(|tls| tls.redirect_port);
+        let redirect_listener = match redirect_port {
+            Some(port) => {
+                let redirect_listener = TcpListener::bind((config.server.host.as_str(), port)).await?;
+                info!("Redirecting HTTP on {}:{} to HTTPS", config.server.host, port);
+                Some(redirect_listener)
+            }
+            None => None,
+        };
+        
+        #[cfg(unix)]
+        let mut unix_listeners = Vec::new();
+        for socket_config in config.server.unix_sockets.iter().flatten() {
+            #[cfg(unix)]
+            {
+                unix_listeners.push((Self::bind_unix(socket_config)?, socket_config.clone()));
+                info!("Server listening on unix:{}", socket_config.path);
+            }
+            #[cfg(not(unix))]
+            warn!("Unix domain sockets are not supported on this platform, not binding {}", socket_config.path);
+        }
+        
+        let shared = SharedState::from_config(&config)?;
         
         Ok(EventLoop {
             config,
-            listeners: vec![listener],
+            listeners,
+            admin_listener,
+            redirect_listener,
+            #[cfg(unix)]
+            unix_listeners,
             worker_tasks: Vec::new(),
+            shared,
         })
     }
     
-    /// Add a new TCP listener to the event loop
+    /// Bind a TCP listener, or one per acceptor sharing the address with `SO_REUSEPORT`.
+    ///
+    /// The kernel then spreads new connections across the acceptors instead of
+    /// waking a single one. Platforms without the option get a single listener.
+    async fn bind_tcp(host: &str, port: u16, acceptors: usize) -> io::Result<Vec<TcpListener>> {
+        if acceptors > 1 {
+            match Self::bind_reuse_port(host, port, acceptors).await {
+                Ok(listeners) => {
+                    debug!("Accepting on {}:{} with {} SO_REUSEPORT sockets", host, port, listeners.len());
+                    return Ok(listeners);
+                }
+                Err(e) => warn!("Cannot share {}:{} between acceptors, using one listener: {}", host, port, e),
+            }
+        }
+        
+        Ok(vec![TcpListener::bind((host, port)).await?])
+    }
+    
+    /// Bind `acceptors` listening sockets to the same address with `SO_REUSEPORT`
+    #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+    async fn bind_reuse_port(host: &str, port: u16, acceptors: usize) -> io::Result<Vec<TcpListener>> {
+        let mut last_error = None;
+        for addr in tokio::net::lookup_host((host, port)).await? {
+            let mut addr = addr;
+            let mut listeners = Vec::with_capacity(acceptors);
+            let bound = (0..acceptors).try_for_each(|_| {
+                let socket = if addr.is_ipv4() { TcpSocket::new_v4()? } else { TcpSocket::new_v6()? };
+                socket.set_reuseaddr(true)?;
+                socket.set_reuseport(true)?;
+                socket.bind(addr)?;
+                let listener = socket.listen(LISTEN_BACKLOG)?;
+                // Port 0 picks a free port once; the other sockets must share it
+                addr = listener.local_addr()?;
+                listeners.push(listener);
+                Ok::<_, io::Error>(())
+            });
+            match bound {
+                Ok(()) => return Ok(listeners),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        
+        Err(last_error.unwrap_or_else(|| io::Error::new(io::ErrorKind::AddrNotAvailable, format!("{} resolved to no address", host))))
+    }
+    
+    /// Bind `acceptors` listening sockets to the same address with `SO_REUSEPORT`
+    #[cfg(not(all(unix, not(any(target_os = "solaris", target_os = "illumos")))))]
+    async fn bind_reuse_port(_host: &str, _port: u16, _acceptors: usize) -> io::Result<Vec<TcpListener>> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "SO_REUSEPORT is not supported on this platform"))
+    }
+    
+    /// Settings of the listener at server.host and server.port
+    fn main_listener_config(config: &Config) -> ListenerConfig {
+        ListenerConfig {
+            address: Some(config.server.host.clone()),
+            port: config.server.port,
+            tls: Some(config.tls.as_ref().is_some_and(|tls| tls.enabled)),
+            vhost: None,
+        }
+    }
+    
+    /// Bind a Unix domain socket, replacing a stale socket file and applying its permissions
+    #[cfg(unix)]
+    fn bind_unix(socket_config: &UnixSocketConfig) -> std::io::Result<UnixListener> {
+        use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+        
+        // Remove a socket left by an earlier run, but never any other kind of file
+        if let Ok(metadata) = std::fs::symlink_metadata(&socket_config.path) {
+            if metadata.file_type().is_socket() {
+                warn!("Removing stale socket {}", socket_config.path);
+                std::fs::remove_file(&socket_config.path)?;
+            }
+        }
+        
+        let listener = UnixListener::bind(&socket_config.path)?;
+        if let Some(mode) = socket_config.mode {
+            std::fs::set_permissions(&socket_config.path, std::fs::Permissions::from_mode(mode))?;
+        }
+        
+        Ok(listener)
+    }
+    
+    /// Use the given handlers for WebSocket upgrades
+    pub fn set_websocket_handlers(&mut self, handlers: Vec<Arc<dyn WebSocketHandler>>) {
+        self.shared.websocket_handlers = Arc::new(handlers);
+    }
+    
+    /// Add middleware to the request chain at the given stages, as placed by `[[middleware]]` entries
+    pub fn add_middleware(&mut self, middleware: Vec<(Stage, Arc<dyn Middleware>)>) {
+        let mut chain = MiddlewareChain::clone(&self.shared.middleware);
+        for (stage, middleware) in middleware {
+            debug!("Adding middleware {} at the {:?} stage", middleware.name(), stage);
+            chain.add(stage, middleware);
+        }
+        for name in chain.unplaced() {
+            warn!("No middleware named {} to place in the request chain", name);
+        }
+        self.shared.middleware = Arc::new(chain);
+    }
+    
+    /// Serve the given services mounted by the embedding application
+    pub fn set_services(&mut self, services: ServiceRoutes) {
+        self.shared.services = Arc::new(services);
+    }
+    
+    /// Run the given plugins around handler dispatch
+    pub fn set_plugins(&mut self, plugins: PluginPipeline) {
+        self.shared.plugins = plugins;
+    }
+    
+    /// Add a new TCP listener to the event loop, served like the main listener
     pub fn add_listener(&mut self, listener: TcpListener) {
-        self.listeners.push(listener);
+        let listener_config = Self::main_listener_config(&self.config);
+        self.listeners.push((listener, listener_config));
     }
     
     /// Run the event loop, processing incoming connections
@@ -41,34 +236,136 @@ impl EventLoop {
         let num_workers = self.config.server.workers.unwrap_or_else(num_cpus::get);
         info!("Starting with {} worker threads", num_workers);
         
-        for listener in &self.listeners {
-            let listener = listener.clone();
+        // Build the router and handlers once, now that services and plugins are set
+        let handlers = Arc::new(RequestHandlers::new(&self.config, &self.shared));
+        self.shared.handlers = Some(Arc::clone(&handlers));
+        
+        for (listener, listener_config) in self.listeners.drain(..) {
             let config = Arc::clone(&self.config);
+            let shared = self.shared.clone();
             
             let handle = tokio::spawn(async move {
-                Self::accept_connections(listener, config).await;
+                Self::accept_connections(listener, listener_config, config, shared).await;
             });
             
             self.worker_tasks.push(handle);
         }
         
-        // Wait for all tasks to complete (which should never happen unless there's an error)
+        #[cfg(unix)]
+        for (listener, socket_config) in self.unix_listeners.drain(..) {
+            let config = Arc::clone(&self.config);
+            let shared = self.shared.clone();
+            
+            let handle = tokio::spawn(async move {
+                Self::accept_unix_connections(listener, socket_config, config, shared).await;
+            });
+            
+            self.worker_tasks.push(handle);
+        }
+        
+        if let Some(listener) = self.redirect_listener.take() {
+            let https_port = self.config.server.port;
+            let shared = self.shared.clone();
+            let handle = tokio::spawn(async move {
+                Self::accept_redirect_connections(listener, https_port, shared).await;
+            });
+            self.worker_tasks.push(handle);
+        }
+        
+        // Probe upstreams in the background so traffic only goes to healthy servers
+        self.worker_tasks.extend(self.shared.proxy_pools.spawn_health_checks());
+        
+        // Obtain and renew certificates while serving, swapping them in as they are issued
+        if let Some(acme) = &self.shared.acme {
+            self.worker_tasks.push(acme.spawn_renewal());
+        }
+        if let Some(certificates) = &self.shared.tls_certificates {
+            self.worker_tasks.extend(certificates.spawn_maintenance());
+        }
+        
+        if let Some(listener) = self.admin_listener.take() {
+            let admin_handler = AdminHandler::from_config(Arc::clone(&self.config), self.shared.metrics.clone())
+                .map(|admin| {
+                    admin
+                        .with_router(handlers.router(None))
+                        .with_plugins(self.shared.plugins.clone())
+                        .with_proxy_pools(Arc::clone(&self.shared.proxy_pools))
+                });
+            if let Some(admin_handler) = admin_handler {
+                let shared = self.shared.clone();
+                let handle = tokio::spawn(async move {
+                    Self::accept_admin_connections(listener, admin_handler, shared).await;
+                });
+                self.worker_tasks.push(handle);
+            }
+        }
+        
+        // Serve until asked to stop, then keep serving while load balancers see the server draining
+        Self::shutdown_signal().await;
+        self.shared.readiness.start_draining();
+        let drain_timeout = self.config.server.drain_timeout.unwrap_or(0);
+        info!("Shutdown requested, draining for {} seconds", drain_timeout);
+        tokio::time::sleep(Duration::from_secs(drain_timeout)).await;
+        
         for task in self.worker_tasks.drain(..) {
-            if let Err(e) = task.await {
-                error!("Worker task failed: {}", e);
+            task.abort();
+        }
+        
+        for socket_config in self.config.server.unix_sockets.iter().flatten() {
+            if let Err(e) = std::fs::remove_file(&socket_config.path) {
+                debug!("Failed to remove socket {}: {}", socket_config.path, e);
             }
         }
         
         Ok(())
     }
     
+    /// Wait for Ctrl-C or, on Unix, SIGTERM
+    async fn shutdown_signal() {
+        let ctrl_c = async {
+            if let Err(e) = tokio::signal::ctrl_c().await {
+                error!("Failed to listen for Ctrl-C: {}", e);
+                std::future::pending::<()>().await;
+            }
+        };
+        
+        #[cfg(unix)]
+        let terminate = async {
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(mut signal) => {
+                    signal.recv().await;
+                }
+                Err(e) => {
+                    error!("Failed to listen for SIGTERM: {}", e);
+                    std::future::pending::<()>().await;
+                }
+            }
+        };
+        #[cfg(not(unix))]
+        let terminate = std::future::pending::<()>();
+        
+        tokio::select! {
+            _ = ctrl_c => {}
+            _ = terminate => {}
+        }
+    }
+    
     /// Accept connections on a TCP listener and spawn tasks to handle them
-    async fn accept_connections(listener: TcpListener, config: Arc<Config>) {
+    async fn accept_connections(
+        listener: TcpListener,
+        listener_config: ListenerConfig,
+        config: Arc<Config>,
+        shared: SharedState,
+    ) {
+        let tls = listener_config.tls.unwrap_or(false);
         loop {
             match listener.accept().await {
                 Ok((socket, peer_addr)) => {
                     info!("Accepted connection from {}", peer_addr);
-                    Self::handle_connection(socket, Arc::clone(&config));
+                    let handler = ConnectionHandler::new(socket, Arc::clone(&config), shared.clone())
+                        .tls(tls)
+                        .vhost(listener_config.vhost.clone());
+                    Self::handle_connection(handler, Some(peer_addr.ip()), &config, &shared);
                 }
                 Err(e) => {
                     error!("Failed to accept connection: {}", e);
@@ -77,13 +374,86 @@ impl EventLoop {
         }
     }
     
-    /// Handle a single client connection
-    fn handle_connection(socket: TcpStream, config: Arc<Config>) {
+    /// Accept connections on a Unix domain socket and spawn tasks to handle them
+    #[cfg(unix)]
+    async fn accept_unix_connections(
+        listener: UnixListener,
+        socket_config: UnixSocketConfig,
+        config: Arc<Config>,
+        shared: SharedState,
+    ) {
+        let trust_forwarded = socket_config.trust_forwarded.unwrap_or(false);
+        loop {
+            match listener.accept().await {
+                Ok((socket, _)) => {
+                    debug!("Accepted connection on unix:{}", socket_config.path);
+                    let handler = ConnectionHandler::with_peer(socket, None, Arc::clone(&config), shared.clone())
+                        .trust_forwarded(trust_forwarded);
+                    Self::handle_connection(handler, None, &config, &shared);
+                }
+                Err(e) => {
+                    error!("Failed to accept connection on unix:{}: {}", socket_config.path, e);
+                }
+            }
+        }
+    }
+    
+    /// Accept connections on the admin listener and serve the admin endpoints on them
+    async fn accept_admin_connections(listener: TcpListener, admin_handler: AdminHandler, shared: SharedState) {
+        loop {
+            match listener.accept().await {
+                Ok((socket, peer_addr)) => {
+                    debug!("Accepted admin connection from {}", peer_addr);
+                    let admin_handler = admin_handler.clone();
+                    let error_pages = Arc::clone(&shared.error_pages);
+                    tokio::spawn(async move {
+                        if let Err(e) = ConnectionHandler::serve_admin(socket, admin_handler, error_pages).await {
+                            error!("Error serving admin connection: {}", e);
+                        }
+                    });
+                }
+                Err(e) => {
+                    error!("Failed to accept admin connection: {}", e);
+                }
+            }
+        }
+    }
+    
+    /// Accept connections on the redirect listener and send their requests to HTTPS
+    async fn accept_redirect_connections(listener: TcpListener, https_port: u16, shared: SharedState) {
+        loop {
+            match listener.accept().await {
+                Ok((socket, peer_addr)) => {
+                    debug!("Accepted redirect connection from {}", peer_addr);
+                    let acme = shared.acme.clone();
+                    let error_pages = Arc::clone(&shared.error_pages);
+                    tokio::spawn(async move {
+                        if let Err(e) = ConnectionHandler::serve_https_redirect(socket, https_port, acme, error_pages).await {
+                            debug!("Error serving redirect connection: {}", e);
+                        }
+                    });
+                }
+                Err(e) => {
+                    error!("Failed to accept redirect connection: {}", e);
+                }
+            }
+        }
+    }
+    
+    /// Handle a single client connection once the connection limits admit it
+    fn handle_connection<S>(handler: ConnectionHandler<S>, client_ip: Option<IpAddr>, config: &Config, shared: &SharedState)
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
         let connection_timeout = config.server.connection_timeout.unwrap_or(60);
+        let limiter = Arc::clone(&shared.connection_limiter);
+        let metrics = shared.metrics.clone();
         
         tokio::spawn(async move {
-            // Create a connection handler and process the request
-            let mut handler = ConnectionHandler::new(socket, config);
+            // Dropping a refused handler closes its connection
+            let Some(_permit) = limiter.acquire(client_ip, &metrics).await else {
+                return;
+            };
             
             // Set a timeout for the connection
             let timeout = tokio::time::Duration::from_secs(connection_timeout);
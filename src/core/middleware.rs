@@ -0,0 +1,351 @@
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use hyper::{Body, Request, Response};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::core::config::{Config, MiddlewareConfig};
+use crate::core::error::ErrorPages;
+use crate::routing::router::wildcard_regex;
+use crate::security::acl::Acl;
+use crate::security::auth::AuthPolicy;
+use crate::security::cors::CorsPolicy;
+use crate::security::rate_limit::RateLimits;
+use crate::utils::metrics::Metrics;
+
+/// Names of the built-in middleware, which keep their fixed place in the chain
+pub const BUILTIN_MIDDLEWARE: [&str; 4] = ["acl", "rate_limit", "cors", "auth"];
+
+/// Point in the request pipeline where added middleware runs.
+///
+/// The chain runs request middleware first, then the built-in ACL and rate
+/// limits, access middleware, the built-in CORS and authentication checks,
+/// and finally handler middleware right before the request reaches its handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    /// After path normalization and rewrites, before any access checks
+    Request,
+    /// After the ACL and rate limits, before CORS and authentication
+    Access,
+    /// After authentication, right before the handler
+    Handler,
+}
+
+/// Cross-cutting request processing that wraps the rest of the chain
+#[async_trait]
+pub trait Middleware: Send + Sync {
+    /// Get the name of the middleware, as used in `[[middleware]]` entries
+    fn name(&self) -> &str;
+    
+    /// Process a request, usually by passing it on with `next.run(req)`; returning early short-circuits the chain
+    async fn handle(&self, req: Request<Body>, next: Next<'_>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>>;
+}
+
+/// Final step of a chain, producing the response once every middleware has passed the request on
+pub type Endpoint<'a> = Box<dyn FnOnce(Request<Body>) -> BoxFuture<'a, Response<Body>> + Send + 'a>;
+
+/// Remainder of a middleware chain, handed to each middleware
+pub struct Next<'a> {
+    /// Middleware still to run
+    middlewares: &'a [Arc<dyn Middleware>],
+    /// Renderer for errors returned by middleware
+    error_pages: &'a ErrorPages,
+    /// Step run after the last middleware
+    endpoint: Endpoint<'a>,
+}
+
+impl<'a> Next<'a> {
+    /// Create a chain running the given middleware in order before the endpoint
+    pub fn new(middlewares: &'a [Arc<dyn Middleware>], error_pages: &'a ErrorPages, endpoint: Endpoint<'a>) -> Self {
+        Next { middlewares, error_pages, endpoint }
+    }
+    
+    /// Run the rest of the chain; errors of later middleware are already rendered as responses
+    pub async fn run(self, req: Request<Body>) -> Response<Body> {
+        let Next { middlewares, error_pages, endpoint } = self;
+        match middlewares.split_first() {
+            Some((middleware, rest)) => {
+                let next = Next { middlewares: rest, error_pages, endpoint };
+                match middleware.handle(req, next).await {
+                    Ok(response) => response,
+                    Err(e) => {
+                        debug!("Middleware {} failed the request", middleware.name());
+                        error_pages.render_error(e)
+                    }
+                }
+            }
+            None => endpoint(req).await,
+        }
+    }
+}
+
+/// Errors building a middleware chain
+#[derive(Debug)]
+pub enum MiddlewareError {
+    /// A `[[middleware]]` entry has an invalid path pattern
+    InvalidPattern(String),
+}
+
+impl fmt::Display for MiddlewareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MiddlewareError::InvalidPattern(msg) => write!(f, "Invalid middleware path pattern: {}", msg),
+        }
+    }
+}
+
+impl Error for MiddlewareError {}
+
+/// Where a `[[middleware]]` entry places the middleware of its name
+#[derive(Debug, Clone)]
+struct Placement {
+    /// Name of the middleware placed
+    name: String,
+    /// Stage overriding the one the middleware was added at
+    stage: Option<Stage>,
+    /// Path patterns the middleware runs for (every path if empty)
+    paths: Vec<Regex>,
+    /// Virtual host patterns the middleware runs for (every host if empty)
+    hosts: Vec<String>,
+    /// Whether the middleware runs at all
+    enabled: bool,
+}
+
+impl Placement {
+    /// Compile a `[[middleware]]` entry
+    fn new(config: &MiddlewareConfig) -> Result<Self, MiddlewareError> {
+        let paths = config
+            .paths
+            .iter()
+            .flatten()
+            .map(|path| wildcard_regex(path))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| MiddlewareError::InvalidPattern(e.to_string()))?;
+        
+        Ok(Placement {
+            name: config.name.clone(),
+            stage: config.stage,
+            paths,
+            hosts: config.hosts.clone().unwrap_or_default(),
+            enabled: config.enabled.unwrap_or(true),
+        })
+    }
+}
+
+/// Middleware added to the chain, with the scope it runs in
+#[derive(Clone)]
+struct ChainEntry {
+    /// Stage the middleware runs at
+    stage: Stage,
+    /// Position of the `[[middleware]]` entry placing it, ahead of unplaced middleware
+    rank: usize,
+    /// Path patterns the middleware runs for (every path if empty)
+    paths: Vec<Regex>,
+    /// Virtual host patterns the middleware runs for (every host if empty)
+    hosts: Vec<String>,
+    /// The middleware itself
+    middleware: Arc<dyn Middleware>,
+}
+
+impl ChainEntry {
+    /// Check if the middleware runs for a request path on a virtual host
+    fn applies(&self, path: &str, vhost: Option<&str>) -> bool {
+        (self.paths.is_empty() || self.paths.iter().any(|pattern| pattern.is_match(path)))
+            && (self.hosts.is_empty() || vhost.is_some_and(|vhost| self.hosts.iter().any(|host| host == vhost)))
+    }
+}
+
+/// Ordered middleware run around every request handler.
+///
+/// Added middleware runs at its stage in the order of the `[[middleware]]`
+/// entries naming it, followed by unnamed middleware in the order it was added.
+#[derive(Clone, Default)]
+pub struct MiddlewareChain {
+    /// Added middleware, sorted by stage and rank
+    entries: Vec<ChainEntry>,
+    /// Built-in ACL and rate limits, run between the request and access stages
+    access_control: Vec<Arc<dyn Middleware>>,
+    /// Built-in CORS and authentication, run between the access and handler stages
+    authorization: Vec<Arc<dyn Middleware>>,
+    /// Placements from `[[middleware]]` entries
+    placements: Vec<Placement>,
+}
+
+impl MiddlewareChain {
+    /// Create an empty chain placing middleware as the `[[middleware]]` entries say
+    pub fn from_config(config: &Config) -> Result<Self, MiddlewareError> {
+        let placements = config
+            .middleware
+            .iter()
+            .flatten()
+            .map(Placement::new)
+            .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(MiddlewareChain { placements, ..Default::default() })
+    }
+    
+    /// Add the built-in access checks in their fixed places
+    pub fn with_builtins(
+        mut self,
+        acl: Option<Arc<Acl>>,
+        rate_limits: Arc<RateLimits>,
+        cors: Option<Arc<CorsPolicy>>,
+        auth: Option<Arc<AuthPolicy>>,
+        metrics: Metrics,
+        error_pages: Arc<ErrorPages>,
+    ) -> Self {
+        if let Some(acl) = acl {
+            self.access_control.push(Arc::new(AclMiddleware { acl, error_pages }));
+        }
+        self.access_control.push(Arc::new(RateLimitMiddleware { rate_limits, metrics }));
+        if let Some(cors) = cors {
+            self.authorization.push(Arc::new(CorsMiddleware { cors }));
+        }
+        if let Some(auth) = auth {
+            self.authorization.push(Arc::new(AuthMiddleware { auth }));
+        }
+        self
+    }
+    
+    /// Add middleware at a stage, unless a `[[middleware]]` entry moves or disables it
+    pub fn add(&mut self, stage: Stage, middleware: Arc<dyn Middleware>) {
+        let Some((rank, placement)) = self.placements.iter().enumerate().find(|(_, placement)| placement.name == middleware.name()) else {
+            self.entries.push(ChainEntry { stage, rank: usize::MAX, paths: Vec::new(), hosts: Vec::new(), middleware });
+            self.entries.sort_by_key(|entry| (entry.stage, entry.rank));
+            return;
+        };
+        
+        if !placement.enabled {
+            debug!("Middleware {} is disabled", placement.name);
+            return;
+        }
+        
+        self.entries.push(ChainEntry {
+            stage: placement.stage.unwrap_or(stage),
+            rank,
+            paths: placement.paths.clone(),
+            hosts: placement.hosts.clone(),
+            middleware,
+        });
+        self.entries.sort_by_key(|entry| (entry.stage, entry.rank));
+    }
+    
+    /// Get the names of `[[middleware]]` entries no added middleware answers to
+    pub fn unplaced(&self) -> impl Iterator<Item = &str> {
+        self.placements
+            .iter()
+            .filter(|placement| placement.enabled && !self.entries.iter().any(|entry| entry.middleware.name() == placement.name))
+            .map(|placement| placement.name.as_str())
+    }
+    
+    /// Get the middleware to run, in order, for a request path on a virtual host
+    pub fn select(&self, path: &str, vhost: Option<&str>) -> Vec<Arc<dyn Middleware>> {
+        let staged = |stage| {
+            self.entries
+                .iter()
+                .filter(move |entry| entry.stage == stage && entry.applies(path, vhost))
+                .map(|entry| Arc::clone(&entry.middleware))
+        };
+        
+        staged(Stage::Request)
+            .chain(self.access_control.iter().cloned())
+            .chain(staged(Stage::Access))
+            .chain(self.authorization.iter().cloned())
+            .chain(staged(Stage::Handler))
+            .collect()
+    }
+}
+
+/// Refuses clients the access control list denies, using the real peer address
+struct AclMiddleware {
+    acl: Arc<Acl>,
+    error_pages: Arc<ErrorPages>,
+}
+
+#[async_trait]
+impl Middleware for AclMiddleware {
+    fn name(&self) -> &str {
+        "acl"
+    }
+    
+    async fn handle(&self, req: Request<Body>, next: Next<'_>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let client_ip = req.extensions().get::<SocketAddr>().map(|addr| addr.ip());
+        if self.acl.check_access(&req, client_ip).is_err() {
+            debug!("ACL denied {} for {:?}", req.uri().path(), client_ip);
+            return Ok(self.acl.denial_response(&self.error_pages));
+        }
+        Ok(next.run(req).await)
+    }
+}
+
+/// Throttles clients before any handler does work for them
+struct RateLimitMiddleware {
+    rate_limits: Arc<RateLimits>,
+    metrics: Metrics,
+}
+
+#[async_trait]
+impl Middleware for RateLimitMiddleware {
+    fn name(&self) -> &str {
+        "rate_limit"
+    }
+    
+    async fn handle(&self, req: Request<Body>, next: Next<'_>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        if let Some(addr) = req.extensions().get::<SocketAddr>() {
+            self.rate_limits.check(addr.ip(), req.uri().path(), &self.metrics)?;
+        }
+        Ok(next.run(req).await)
+    }
+}
+
+/// Answers CORS preflights and adds CORS headers to responses on the paths the policy covers
+struct CorsMiddleware {
+    cors: Arc<CorsPolicy>,
+}
+
+#[async_trait]
+impl Middleware for CorsMiddleware {
+    fn name(&self) -> &str {
+        "cors"
+    }
+    
+    async fn handle(&self, req: Request<Body>, next: Next<'_>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        if !self.cors.covers(req.uri().path()) {
+            return Ok(next.run(req).await);
+        }
+        
+        // Browsers send preflights without credentials, so answer them before authentication
+        if CorsPolicy::is_preflight(&req) {
+            return Ok(self.cors.preflight_response(&req));
+        }
+        let origin = self.cors.allowed_origin(&req);
+        let mut response = next.run(req).await;
+        self.cors.add_response_headers(origin, response.headers_mut());
+        Ok(response)
+    }
+}
+
+/// Requires credentials on protected paths before any handler sees the request
+struct AuthMiddleware {
+    auth: Arc<AuthPolicy>,
+}
+
+#[async_trait]
+impl Middleware for AuthMiddleware {
+    fn name(&self) -> &str {
+        "auth"
+    }
+    
+    async fn handle(&self, req: Request<Body>, next: Next<'_>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        if self.auth.protects_request(&req) {
+            self.auth.check(&req).await?;
+        }
+        Ok(next.run(req).await)
+    }
+}
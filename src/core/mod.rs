@@ -1,3 +1,10 @@
 pub mod server;
 pub mod config;
+pub mod overrides;
+pub mod cache;
 pub mod eventloop;
+pub mod runtime;
+pub mod selftest;
+pub mod validation;
+pub mod error;
+pub mod middleware;
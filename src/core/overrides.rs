@@ -0,0 +1,195 @@
+use std::fmt;
+use std::path::Path;
+use toml::Value;
+
+use crate::core::config::{Config, ConfigError};
+
+/// Prefix of environment variables overriding configuration fields
+pub const ENV_PREFIX: &str = "KASERVE_";
+
+/// Separator between field names in override environment variables
+const ENV_SEPARATOR: &str = "__";
+
+/// Override of one configuration field, such as `server.port=8080`.
+///
+/// The value is read as a TOML value when it parses as one, so numbers,
+/// booleans and arrays keep their types, and as a plain string otherwise or
+/// when the field only takes a string, as in `static_files.default_file=404`.
+#[derive(Debug, Clone)]
+pub struct ConfigOverride {
+    /// Path of the field; numeric segments index arrays of tables
+    path: Vec<String>,
+    /// Value replacing the field
+    value: Value,
+    /// Value as given, used when the field rejects the typed value
+    raw: String,
+}
+
+impl ConfigOverride {
+    /// Parse an override given as `field.path=value`
+    pub fn parse(assignment: &str) -> Result<Self, ConfigError> {
+        let (path, raw) = assignment
+            .split_once('=')
+            .ok_or_else(|| ConfigError::InvalidOverride(format!("expected FIELD=VALUE, got '{}'", assignment)))?;
+        
+        let path: Vec<String> = path.trim().split('.').map(str::to_string).collect();
+        if path.iter().any(String::is_empty) {
+            return Err(ConfigError::InvalidOverride(format!("invalid field path in '{}'", assignment)));
+        }
+        
+        Ok(ConfigOverride { path, value: parse_value(raw), raw: raw.to_string() })
+    }
+    
+    /// Collect overrides from `KASERVE_SECTION__FIELD` environment variables.
+    ///
+    /// Double underscores separate field names, so `KASERVE_STATIC_FILES__ROOT_DIR`
+    /// sets `static_files.root_dir`. Variables without a separator are ignored,
+    /// since every top-level field is a section.
+    pub fn from_env<I>(vars: I) -> Vec<Self>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut overrides: Vec<Self> = vars
+            .into_iter()
+            .filter_map(|(name, raw)| {
+                let name = name.strip_prefix(ENV_PREFIX)?;
+                if !name.contains(ENV_SEPARATOR) {
+                    return None;
+                }
+                let path = name.split(ENV_SEPARATOR).map(str::to_ascii_lowercase).collect();
+                Some(ConfigOverride { path, value: parse_value(&raw), raw })
+            })
+            .collect();
+        
+        // Environment order is arbitrary; apply parents before their fields
+        overrides.sort_by(|a, b| a.path.cmp(&b.path));
+        overrides
+    }
+    
+    /// Set the field in a configuration document, preferring the typed value
+    fn apply(&self, document: &mut Value) -> Result<(), ConfigError> {
+        if self.value.is_str() {
+            return self.apply_value(document, &self.value);
+        }
+        
+        let mut typed = document.clone();
+        self.apply_value(&mut typed, &self.value)?;
+        if typed.clone().try_into::<Config>().is_err() {
+            let mut text = document.clone();
+            self.apply_value(&mut text, &Value::String(self.raw.clone()))?;
+            if text.clone().try_into::<Config>().is_ok() {
+                *document = text;
+                return Ok(());
+            }
+        }
+        
+        *document = typed;
+        Ok(())
+    }
+    
+    /// Set the field to a value, creating missing sections
+    fn apply_value(&self, document: &mut Value, value: &Value) -> Result<(), ConfigError> {
+        let mut current = document;
+        for (depth, segment) in self.path.iter().enumerate() {
+            let last = depth + 1 == self.path.len();
+            current = match current {
+                Value::Table(table) if last => {
+                    table.insert(segment.clone(), value.clone());
+                    return Ok(());
+                }
+                Value::Table(table) => {
+                    // Array elements can be overridden but not created
+                    let next = &self.path[depth + 1];
+                    if !table.contains_key(segment) && next.parse::<usize>().is_ok() {
+                        return Err(ConfigError::InvalidOverride(format!("{}: no element {}", self, next)));
+                    }
+                    table.entry(segment.clone()).or_insert_with(|| Value::Table(Default::default()))
+                }
+                Value::Array(array) => {
+                    let slot = segment.parse::<usize>().ok().and_then(|index| array.get_mut(index)).ok_or_else(|| {
+                        ConfigError::InvalidOverride(format!("{}: no element {}", self, segment))
+                    })?;
+                    if last {
+                        *slot = value.clone();
+                        return Ok(());
+                    }
+                    slot
+                }
+                _ => return Err(ConfigError::InvalidOverride(format!("{}: '{}' is not a section", self, segment))),
+            };
+        }
+        
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigOverride {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.join("."))
+    }
+}
+
+/// Read an override value as TOML, falling back to a plain string
+fn parse_value(raw: &str) -> Value {
+    toml::from_str::<toml::Table>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(raw.to_string()))
+}
+
+impl Config {
+    /// Load configuration from a file with overrides layered on top, later ones winning.
+    ///
+    /// Without a file, the overrides apply to the default configuration.
+    pub fn load(path: Option<&Path>, overrides: &[ConfigOverride]) -> Result<Self, ConfigError> {
+        let mut document = match path {
+            Some(path) => toml::from_str::<Value>(&std::fs::read_to_string(path)?)?,
+            None => Value::try_from(Config::default())?,
+        };
+        
+        for config_override in overrides {
+            config_override.apply(&mut document)?;
+        }
+        
+        Ok(document.try_into()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn typed_values_keep_their_types() {
+        let overrides = [
+            ConfigOverride::parse("server.port=9090").unwrap(),
+            ConfigOverride::parse("static_files.directory_listing=true").unwrap(),
+        ];
+        let config = Config::load(None, &overrides).unwrap();
+        
+        assert_eq!(config.server.port, 9090);
+        assert_eq!(config.static_files.directory_listing, Some(true));
+    }
+    
+    #[test]
+    fn string_fields_take_values_that_look_typed() {
+        let overrides = [
+            ConfigOverride::parse("static_files.default_file=2024").unwrap(),
+            ConfigOverride::parse("server.port=8081").unwrap(),
+        ];
+        let config = Config::load(None, &overrides).unwrap();
+        
+        assert_eq!(config.static_files.default_file.as_deref(), Some("2024"));
+        assert_eq!(config.server.port, 8081);
+        
+        let overrides = ConfigOverride::from_env([("KASERVE_STATIC_FILES__DEFAULT_FILE".to_string(), "404".to_string())]);
+        let config = Config::load(None, &overrides).unwrap();
+        assert_eq!(config.static_files.default_file.as_deref(), Some("404"));
+    }
+    
+    #[test]
+    fn values_no_field_accepts_are_rejected() {
+        let overrides = [ConfigOverride::parse("server.port=eighty").unwrap()];
+        assert!(Config::load(None, &overrides).is_err());
+    }
+}
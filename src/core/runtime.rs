@@ -0,0 +1,85 @@
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::runtime::{Builder, Runtime};
+use tracing::{debug, warn};
+
+use crate::core::config::ServerConfig;
+
+/// Name of the runtime's worker and blocking threads
+const THREAD_NAME: &str = "kaserve-worker";
+
+/// Build the Tokio runtime serving requests.
+///
+/// Runs `server.workers` worker threads (one per CPU if unset) and at most
+/// `server.blocking_threads` threads for blocking file and process work.
+/// With `server.pin_workers`, each worker thread is pinned to its own CPU.
+pub fn build_runtime(config: &ServerConfig) -> io::Result<Runtime> {
+    let workers = config.workers.unwrap_or_else(num_cpus::get).max(1);
+    
+    let mut builder = Builder::new_multi_thread();
+    builder.enable_all().worker_threads(workers).thread_name(THREAD_NAME);
+    if let Some(blocking_threads) = config.blocking_threads {
+        builder.max_blocking_threads(blocking_threads.max(1));
+    }
+    
+    if config.pin_workers.unwrap_or(false) {
+        match allowed_cpus() {
+            Some(cpus) if !cpus.is_empty() => {
+                // Worker threads start with the runtime, before any blocking thread
+                let started = Arc::new(AtomicUsize::new(0));
+                builder.on_thread_start(move || {
+                    let index = started.fetch_add(1, Ordering::Relaxed);
+                    if index < workers {
+                        let cpu = cpus[index % cpus.len()];
+                        match pin_current_thread(cpu) {
+                            Ok(()) => debug!("Pinned worker thread to CPU {}", cpu),
+                            Err(e) => warn!("Failed to pin worker thread to CPU {}: {}", cpu, e),
+                        }
+                    }
+                });
+            }
+            _ => warn!("Cannot pin worker threads to CPUs on this platform, leaving them unpinned"),
+        }
+    }
+    
+    builder.build()
+}
+
+/// CPUs the process may run on, in ascending order
+#[cfg(target_os = "linux")]
+fn allowed_cpus() -> Option<Vec<usize>> {
+    // SAFETY: cpu_set_t is plain data, and sched_getaffinity writes at most its size
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return None;
+        }
+        Some((0..libc::CPU_SETSIZE as usize).filter(|&cpu| libc::CPU_ISSET(cpu, &set)).collect())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn allowed_cpus() -> Option<Vec<usize>> {
+    None
+}
+
+/// Restrict the calling thread to one CPU
+#[cfg(target_os = "linux")]
+fn pin_current_thread(cpu: usize) -> io::Result<()> {
+    // SAFETY: cpu_set_t is plain data, and sched_setaffinity only reads it
+    let result = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    match result {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_current_thread(_cpu: usize) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
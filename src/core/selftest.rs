@@ -0,0 +1,145 @@
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+use tokio::net::{lookup_host, TcpStream};
+use tracing::{info, warn};
+
+use crate::core::config::Config;
+
+/// Timeout for each reachability probe
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Error returned when the self-test fails in strict mode
+#[derive(Debug)]
+pub struct SelfTestError {
+    /// Problems found by the self-test
+    pub failures: Vec<String>,
+}
+
+impl fmt::Display for SelfTestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Startup self-test failed: {}", self.failures.join("; "))
+    }
+}
+
+impl Error for SelfTestError {}
+
+/// Result of a startup self-test
+#[derive(Debug, Default)]
+pub struct SelfTestReport {
+    /// Problems found by the self-test
+    pub failures: Vec<String>,
+}
+
+impl SelfTestReport {
+    /// Create an empty report
+    pub fn new() -> Self {
+        SelfTestReport::default()
+    }
+    
+    /// Check that a directory served by a static handler exists
+    pub fn check_directory(&mut self, label: &str, path: &str) {
+        if !Path::new(path).is_dir() {
+            self.failures.push(format!("{}: directory '{}' does not exist", label, path));
+        }
+    }
+    
+    /// Check that a TCP backend resolves and accepts connections
+    pub async fn check_tcp_endpoint(&mut self, label: &str, addr: &str) {
+        let addrs: Vec<_> = match tokio::time::timeout(PROBE_TIMEOUT, lookup_host(addr)).await {
+            Ok(Ok(addrs)) => addrs.collect(),
+            Ok(Err(e)) => {
+                self.failures.push(format!("{}: cannot resolve '{}': {}", label, addr, e));
+                return;
+            }
+            Err(_) => {
+                self.failures.push(format!("{}: resolving '{}' timed out", label, addr));
+                return;
+            }
+        };
+        
+        for socket_addr in &addrs {
+            if let Ok(Ok(_)) = tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect(socket_addr)).await {
+                return;
+            }
+        }
+        
+        self.failures.push(format!("{}: cannot connect to '{}'", label, addr));
+    }
+    
+    /// Check whether the self-test found no problems
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Run the startup self-test against every configured handler
+pub async fn run(config: &Config) -> SelfTestReport {
+    let mut report = SelfTestReport::new();
+    
+    report.check_directory("static_files.root_dir", &config.static_files.root_dir);
+    
+    if let Some(vhosts) = &config.virtual_hosts {
+        for vhost in vhosts {
+            report.check_directory(&format!("virtual_hosts[{}].root_dir", vhost.host), &vhost.root_dir);
+        }
+    }
+    
+    for backend in config.fastcgi.iter().flatten() {
+        report.check_tcp_endpoint(&format!("fastcgi[{}]", backend.path), &backend.address.to_string()).await;
+    }
+    
+    for backend in config.scgi.iter().flatten() {
+        report.check_tcp_endpoint(&format!("scgi[{}]", backend.path), &backend.address.to_string()).await;
+    }
+    
+    for backend in config.uwsgi.iter().flatten() {
+        report.check_tcp_endpoint(&format!("uwsgi[{}]", backend.path), &backend.address.to_string()).await;
+    }
+    
+    if let Some(proxy) = &config.proxy {
+        for pool in &proxy.pools {
+            for server in &pool.servers {
+                let label = format!("proxy.pools[{}]", pool.name);
+                match upstream_address(server) {
+                    Some(addr) => report.check_tcp_endpoint(&label, &addr).await,
+                    None => report.failures.push(format!("{}: invalid upstream URL '{}'", label, server)),
+                }
+            }
+        }
+    }
+    
+    report
+}
+
+/// Get the host:port to probe for an upstream URL, using the scheme's default port
+fn upstream_address(server: &str) -> Option<String> {
+    let uri: hyper::Uri = server.parse().ok()?;
+    let host = uri.host()?;
+    let port = uri.port_u16().unwrap_or(if uri.scheme_str() == Some("https") { 443 } else { 80 });
+    Some(format!("{}:{}", host, port))
+}
+
+/// Run the self-test if enabled, logging problems and failing in strict mode
+pub async fn run_if_enabled(config: &Config) -> Result<(), SelfTestError> {
+    if !config.server.self_test.unwrap_or(false) {
+        return Ok(());
+    }
+    
+    let report = run(config).await;
+    if report.is_ok() {
+        info!("Startup self-test passed");
+        return Ok(());
+    }
+    
+    for failure in &report.failures {
+        warn!("Self-test: {}", failure);
+    }
+    
+    if config.server.self_test_strict.unwrap_or(false) {
+        return Err(SelfTestError { failures: report.failures });
+    }
+    
+    Ok(())
+}
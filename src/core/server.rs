@@ -1,10 +1,83 @@
+use std::collections::HashSet;
 use std::error::Error;
-use std::sync::Arc;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use lazy_static::lazy_static;
 use tracing::{info, error};
 
-use crate::core::config::Config;
+use crate::core::config::{Config, ConfigError};
 use crate::core::eventloop::EventLoop;
+use crate::core::selftest;
+use crate::handlers::service::{ServiceHandler, ServiceRoutes};
+use crate::plugins::api::Plugin;
 use crate::plugins::manager::PluginManager;
+use crate::plugins::pipeline::PluginPipeline;
+use crate::plugins::wasm::WasmPlugin;
+use crate::routing::router::RouterError;
+
+lazy_static! {
+    /// Addresses currently served by a running server in this process
+    static ref RUNNING_ADDRESSES: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// Error types for server lifecycle misuse
+#[derive(Debug)]
+pub enum ServerError {
+    AlreadyInitialized,
+    AlreadyRunning,
+    AlreadyShutDown,
+    AddressInUse(String),
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::AlreadyInitialized => write!(f, "Server has already been initialized"),
+            ServerError::AlreadyRunning => write!(f, "Server has already been started"),
+            ServerError::AlreadyShutDown => write!(f, "Server has already been shut down"),
+            ServerError::AddressInUse(addr) => write!(f, "Another server is already running on {}", addr),
+        }
+    }
+}
+
+impl Error for ServerError {}
+
+/// Lifecycle state of a server instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerState {
+    /// Constructed but not yet initialized
+    Created,
+    /// Plugins initialized
+    Initialized,
+    /// Event loop running
+    Running,
+    /// Shut down, cannot be restarted
+    ShutDown,
+}
+
+/// Registration of a listen address, released when dropped
+struct AddressGuard {
+    addr: String,
+}
+
+impl AddressGuard {
+    /// Register an address, failing if another server in this process owns it
+    fn acquire(addr: String) -> Result<Self, ServerError> {
+        let mut running = RUNNING_ADDRESSES.lock().unwrap();
+        if !running.insert(addr.clone()) {
+            return Err(ServerError::AddressInUse(addr));
+        }
+        Ok(AddressGuard { addr })
+    }
+}
+
+impl Drop for AddressGuard {
+    fn drop(&mut self) {
+        if let Ok(mut running) = RUNNING_ADDRESSES.lock() {
+            running.remove(&self.addr);
+        }
+    }
+}
 
 /// The main server structure for the Kaserve web server
 pub struct Server {
@@ -12,6 +85,10 @@ pub struct Server {
     config: Arc<Config>,
     /// Plugin manager
     plugin_manager: PluginManager,
+    /// Services mounted by the embedding application
+    services: Mutex<ServiceRoutes>,
+    /// Lifecycle state
+    state: Mutex<ServerState>,
 }
 
 impl Server {
@@ -20,25 +97,90 @@ impl Server {
         Server {
             config: Arc::new(config),
             plugin_manager: PluginManager::new(),
+            services: Mutex::new(ServiceRoutes::default()),
+            state: Mutex::new(ServerState::Created),
         }
     }
     
+    /// Start building a server embedded in another program
+    pub fn builder(config: Config) -> ServerBuilder {
+        ServerBuilder::new(config)
+    }
+    
+    /// Get the current lifecycle state
+    pub fn state(&self) -> ServerState {
+        *self.state.lock().unwrap()
+    }
+    
+    /// Register a plugin; its hooks run on every request in registration order
+    pub fn register_plugin<P: Plugin + 'static>(&self, plugin: P) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.plugin_manager.register_plugin(plugin)
+    }
+    
+    /// Mount a service at a route pattern, such as `/api/*`.
+    ///
+    /// The route is matched like configured ones, and `[[routes]]` entries with
+    /// handler "service" can serve it at other paths or hosts by naming the pattern.
+    pub fn mount_service(&self, pattern: &str, handler: ServiceHandler) -> Result<(), RouterError> {
+        self.services.lock().unwrap().mount(pattern, handler)
+    }
+    
     /// Initialize the server and load plugins
-    pub fn init(&mut self) -> Result<(), Box<dyn Error>> {
+    pub fn init(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.state() != ServerState::Created {
+            return Err(Box::new(ServerError::AlreadyInitialized));
+        }
+        
+        // Report every configuration problem before anything is bound or loaded
+        if let Err(e) = self.config.validate() {
+            if let ConfigError::Invalid(problems) = &e {
+                for problem in problems {
+                    error!("Configuration: {}", problem);
+                }
+            }
+            return Err(Box::new(e));
+        }
+        
+        // Compile WebAssembly plugins up front so broken modules fail startup
+        for plugin in WasmPlugin::load_all(&self.config)? {
+            self.plugin_manager.register_plugin(plugin)?;
+        }
+        
         // Initialize the plugin manager
         self.plugin_manager.init(Arc::clone(&self.config))?;
+        *self.state.lock().unwrap() = ServerState::Initialized;
         
         info!("Server initialized successfully");
         Ok(())
     }
     
     /// Run the server and start accepting connections
-    pub async fn run(mut self) -> Result<(), Box<dyn Error>> {
-        // Initialize the server
-        self.init()?;
+    pub async fn run(mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match self.state() {
+            // Initialize the server unless the caller already did
+            ServerState::Created => self.init()?,
+            ServerState::Initialized => {}
+            ServerState::Running => return Err(Box::new(ServerError::AlreadyRunning)),
+            ServerState::ShutDown => return Err(Box::new(ServerError::AlreadyShutDown)),
+        }
+        
+        // Refuse to run a second server on the same address in this process
+        let addr = format!("{}:{}", self.config.server.host, self.config.server.port);
+        let _address_guard = AddressGuard::acquire(addr)?;
+        
+        // Initialize plugins with their settings before any request reaches them
+        self.plugin_manager.init_plugins().await?;
+        
+        // Check that configured handlers are reachable
+        selftest::run_if_enabled(&self.config).await?;
         
         // Create and run the event loop
         let mut event_loop = EventLoop::new(Arc::clone(&self.config)).await?;
+        event_loop.set_websocket_handlers(self.plugin_manager.websocket_handlers());
+        event_loop.add_middleware(self.plugin_manager.middleware());
+        event_loop.set_plugins(PluginPipeline::new(self.plugin_manager.plugins(), &self.config));
+        event_loop.set_services(std::mem::take(&mut *self.services.lock().unwrap()));
+        *self.state.lock().unwrap() = ServerState::Running;
         
         info!("Server started successfully");
         
@@ -49,7 +191,7 @@ impl Server {
         }
         
         // Shutdown plugins
-        if let Err(e) = self.plugin_manager.shutdown() {
+        if let Err(e) = self.shutdown().await {
             error!("Error shutting down plugins: {}", e);
             return Err(e);
         }
@@ -58,7 +200,15 @@ impl Server {
     }
     
     /// Gracefully shut down the server
-    pub async fn shutdown(&self) -> Result<(), Box<dyn Error>> {
+    pub async fn shutdown(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        {
+            let mut state = self.state.lock().unwrap();
+            if *state == ServerState::ShutDown {
+                return Err(Box::new(ServerError::AlreadyShutDown));
+            }
+            *state = ServerState::ShutDown;
+        }
+        
         info!("Shutting down server...");
         
         // Perform any necessary cleanup or connection draining here
@@ -70,3 +220,47 @@ impl Server {
         Ok(())
     }
 }
+
+/// Builder assembling a server with plugins and services.
+///
+/// Registration errors are kept until `build`, so calls can be chained.
+pub struct ServerBuilder {
+    /// Server being assembled
+    server: Server,
+    /// First registration error
+    error: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl ServerBuilder {
+    /// Start building a server with the given configuration
+    pub fn new(config: Config) -> Self {
+        ServerBuilder {
+            server: Server::new(config),
+            error: None,
+        }
+    }
+    
+    /// Register a plugin; its hooks run on every request in registration order
+    pub fn plugin<P: Plugin + 'static>(mut self, plugin: P) -> Self {
+        if self.error.is_none() {
+            self.error = self.server.register_plugin(plugin).err();
+        }
+        self
+    }
+    
+    /// Mount a service at a route pattern
+    pub fn service(mut self, pattern: &str, handler: ServiceHandler) -> Self {
+        if self.error.is_none() {
+            self.error = self.server.mount_service(pattern, handler).err().map(Into::into);
+        }
+        self
+    }
+    
+    /// Finish the server, failing with the first registration error
+    pub fn build(self) -> Result<Server, Box<dyn Error + Send + Sync>> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.server),
+        }
+    }
+}
@@ -0,0 +1,431 @@
+use hyper::Method;
+use std::collections::HashMap;
+use std::fs::File;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::core::config::{Config, ConfigError, TlsConfig};
+use crate::core::middleware::{MiddlewareChain, BUILTIN_MIDDLEWARE};
+use crate::handlers::upload::DEFAULT_UPLOAD_METHODS;
+use crate::routing::rewrite::RewriteRule;
+use crate::routing::router::{wildcard_regex, Route};
+use crate::routing::vhost::VirtualHost;
+use crate::security::acme::Acme;
+use crate::security::cors::CorsPolicy;
+use crate::security::exclusion::ExclusionRules;
+use crate::security::tls;
+use crate::utils::geoip::GeoIp;
+use crate::utils::language::is_language_tag;
+use crate::utils::logging::{log_filter, log_target};
+use crate::utils::markdown::MarkdownRenderer;
+
+/// Methods that change files on a WebDAV share
+const WEBDAV_WRITE_METHODS: [&str; 7] = ["PUT", "DELETE", "MKCOL", "COPY", "MOVE", "LOCK", "UNLOCK"];
+
+/// Problems found in a configuration, each prefixed with the field it concerns
+#[derive(Debug, Default)]
+struct Problems(Vec<String>);
+
+impl Problems {
+    /// Record a problem with a field
+    fn push(&mut self, field: &str, problem: impl std::fmt::Display) {
+        self.0.push(format!("{}: {}", field, problem));
+    }
+    
+    /// Check that a directory exists
+    fn check_directory(&mut self, field: &str, path: &str) {
+        if !Path::new(path).is_dir() {
+            self.push(field, format_args!("directory '{}' does not exist", path));
+        }
+    }
+    
+    /// Check that a file exists and can be opened for reading
+    fn check_readable(&mut self, field: &str, path: &str) {
+        if let Err(e) = File::open(path) {
+            self.push(field, format_args!("cannot read '{}': {}", path, e));
+        }
+    }
+    
+    /// Check that a route pattern and its methods compile
+    fn check_route(&mut self, field: &str, path: &str, methods: Option<&[String]>) {
+        if let Err(e) = Route::new(path, "static").and_then(|route| route.with_methods(methods)) {
+            self.push(field, format_args!("invalid route '{}': {}", path, e));
+        }
+    }
+    
+    /// Check that every listed request method is a valid method name
+    fn check_methods(&mut self, field: &str, methods: Option<&[String]>) {
+        for method in methods.unwrap_or_default() {
+            if Method::from_bytes(method.to_ascii_uppercase().as_bytes()).is_err() {
+                self.push(field, format_args!("invalid method '{}'", method));
+            }
+        }
+    }
+    
+    /// Check the certificate and key of an enabled TLS configuration
+    fn check_tls(&mut self, field: &str, tls: &TlsConfig) {
+        if tls.enabled {
+            self.check_certificate(field, tls);
+        }
+    }
+    
+    /// Check that a TLS configuration names a readable certificate and key
+    fn check_certificate(&mut self, field: &str, tls: &TlsConfig) {
+        for (name, file) in [("cert_file", &tls.cert_file), ("key_file", &tls.key_file)] {
+            match file {
+                Some(path) => self.check_readable(&format!("{}.{}", field, name), path),
+                None => self.push(&format!("{}.{}", field, name), "required when TLS is enabled"),
+            }
+        }
+    }
+}
+
+impl Config {
+    /// Check the configuration for problems that would break or silently change serving.
+    ///
+    /// Every problem is reported, not just the first, so a broken configuration
+    /// can be fixed in one pass.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Problems::default();
+        
+        if self.server.port == 0 {
+            problems.push("server.port", "must be between 1 and 65535");
+        }
+        
+        if let Some(listen) = self.admin.as_ref().filter(|admin| admin.enabled).and_then(|admin| admin.listen) {
+            if listen.port() == 0 {
+                problems.push("admin.listen", "port must be between 1 and 65535");
+            } else if listen.port() == self.server.port
+                && self.server.host.parse::<IpAddr>().is_ok_and(|ip| {
+                    ip == listen.ip() || ip.is_unspecified() || listen.ip().is_unspecified()
+                })
+            {
+                problems.push("admin.listen", "conflicts with the server address");
+            }
+        }
+        
+        for (field, limit) in [
+            ("server.workers", self.server.workers),
+            ("server.blocking_threads", self.server.blocking_threads),
+            ("server.max_connections", self.server.max_connections),
+            ("server.max_connections_per_ip", self.server.max_connections_per_ip),
+            ("server.keepalive_requests", self.server.keepalive_requests),
+            ("logging.max_open_logs", self.logging.as_ref().and_then(|l| l.max_open_logs)),
+        ] {
+            if limit == Some(0) {
+                problems.push(field, "must be at least 1");
+            }
+        }
+        if self.server.max_header_size.is_some_and(|size| size < 8192) {
+            problems.push("server.max_header_size", "must be at least 8192 bytes");
+        }
+        for (field, timeout) in [
+            ("server.header_read_timeout", self.server.header_read_timeout),
+            ("server.keepalive_timeout", self.server.keepalive_timeout),
+        ] {
+            if timeout == Some(0) {
+                problems.push(field, "must be at least 1 second");
+            }
+        }
+        
+        problems.check_directory("static_files.root_dir", &self.static_files.root_dir);
+        if let Some(language) = &self.static_files.default_language {
+            if !is_language_tag(language) {
+                problems.push("static_files.default_language", format!("'{}' is not a language tag such as \"en\" or \"pt-br\"", language));
+            }
+        }
+        
+        // Listeners terminating TLS use the [tls] certificate even when the main listener does not
+        let listener_tls = self.listeners.iter().flatten().any(|listener| listener.tls.unwrap_or(false));
+        match &self.tls {
+            // ACME provisions the certificate when none is configured
+            Some(tls) if self.acme.is_some() && tls.cert_file.is_none() && tls.key_file.is_none() => {}
+            Some(tls) if listener_tls => problems.check_certificate("tls", tls),
+            Some(tls) => problems.check_tls("tls", tls),
+            None if listener_tls => problems.push("tls", "required by listeners with tls = true"),
+            None => {}
+        }
+        
+        let mut acme = None;
+        if let Some(acme_config) = &self.acme {
+            if !self.tls.as_ref().is_some_and(|tls| tls.enabled) && !listener_tls {
+                problems.push("acme", "requires TLS to be enabled");
+            }
+            if acme_config.domains.is_empty() {
+                problems.push("acme.domains", "must name at least one domain");
+            }
+            for (i, domain) in acme_config.domains.iter().enumerate() {
+                let field = format!("acme.domains[{}]", i);
+                if domain.contains('*') {
+                    problems.push(&field, "wildcard certificates need DNS-01 validation, which is not supported");
+                } else if domain.is_empty() || domain.parse::<IpAddr>().is_ok() {
+                    problems.push(&field, format_args!("'{}' is not a DNS name", domain));
+                }
+            }
+            if !acme_config.accept_terms.unwrap_or(false) {
+                problems.push("acme.accept_terms", "the CA's terms of service must be accepted");
+            }
+            match Acme::from_config(acme_config) {
+                Ok(loaded) => acme = Some(Arc::new(loaded)),
+                Err(e) => problems.push("acme.storage_dir", e),
+            }
+        }
+        
+        let mut addresses = vec![(self.server.host.as_str(), self.server.port, "server".to_string())];
+        if let Some((tls, port)) = self.tls.as_ref().and_then(|tls| Some((tls, tls.redirect_port?))) {
+            if !tls.enabled {
+                problems.push("tls.redirect_port", "requires TLS to be enabled");
+            } else if port == 0 {
+                problems.push("tls.redirect_port", "must be between 1 and 65535");
+            } else if port == self.server.port {
+                problems.push("tls.redirect_port", "conflicts with server.port");
+            } else {
+                addresses.push((self.server.host.as_str(), port, "tls.redirect_port".to_string()));
+            }
+        }
+        for (i, listener) in self.listeners.iter().flatten().enumerate() {
+            let field = format!("listeners[{}]", i);
+            let address = listener.address.as_deref().unwrap_or(&self.server.host);
+            if listener.port == 0 {
+                problems.push(&format!("{}.port", field), "must be between 1 and 65535");
+            } else if let Some((_, _, other)) = addresses.iter().find(|(other, port, _)| {
+                *port == listener.port && addresses_overlap(address, other)
+            }) {
+                problems.push(&field, format_args!("{}:{} is already served by {}", address, listener.port, other));
+            }
+            addresses.push((address, listener.port, field.clone()));
+            
+            if let Some(vhost) = &listener.vhost {
+                if !self.virtual_hosts.iter().flatten().any(|config| &config.host == vhost) {
+                    problems.push(&format!("{}.vhost", field), format_args!("no virtual host '{}'", vhost));
+                }
+            }
+        }
+        
+        // Only the first virtual host with a pattern is ever matched
+        let mut hosts: HashMap<String, usize> = HashMap::new();
+        for (i, vhost) in self.virtual_hosts.iter().flatten().enumerate() {
+            let field = format!("virtual_hosts[{}]", i);
+            if let Err(e) = VirtualHost::new(&vhost.host, &vhost.root_dir) {
+                problems.push(&format!("{}.host", field), format_args!("invalid pattern '{}': {}", vhost.host, e));
+            }
+            let first = *hosts.entry(vhost.host.to_ascii_lowercase()).or_insert(i);
+            if first != i {
+                problems.push(&format!("{}.host", field), format_args!("'{}' is already declared by virtual_hosts[{}]", vhost.host, first));
+            }
+            problems.check_directory(&format!("{}.root_dir", field), &vhost.root_dir);
+            if let Some(tls) = &vhost.tls {
+                problems.check_tls(&format!("{}.tls", field), tls);
+            }
+        }
+        
+        for (i, rule) in self.rewrite.iter().flatten().enumerate() {
+            if let Err(e) = RewriteRule::new(&rule.pattern, &rule.replacement) {
+                problems.push(&format!("rewrite[{}].pattern", i), e);
+            }
+        }
+        
+        let pools = self.proxy.as_ref().map(|proxy| proxy.pools.as_slice()).unwrap_or_default();
+        for (i, pool) in pools.iter().enumerate() {
+            problems.check_route(&format!("proxy.pools[{}].path", i), &pool.path, pool.methods.as_deref());
+        }
+        
+        for (i, backend) in self.fastcgi.iter().flatten().enumerate() {
+            problems.check_route(&format!("fastcgi[{}].path", i), &backend.path, backend.methods.as_deref());
+            if let Some(root) = &backend.document_root {
+                problems.check_directory(&format!("fastcgi[{}].document_root", i), root);
+            }
+        }
+        
+        for (i, backend) in self.scgi.iter().flatten().enumerate() {
+            problems.check_route(&format!("scgi[{}].path", i), &backend.path, backend.methods.as_deref());
+        }
+        
+        for (i, backend) in self.uwsgi.iter().flatten().enumerate() {
+            problems.check_route(&format!("uwsgi[{}].path", i), &backend.path, backend.methods.as_deref());
+        }
+        
+        for (i, cgi) in self.cgi.iter().flatten().enumerate() {
+            problems.check_route(&format!("cgi[{}].path", i), &cgi.path, cgi.methods.as_deref());
+            if let Some(root) = &cgi.document_root {
+                problems.check_directory(&format!("cgi[{}].document_root", i), root);
+            }
+        }
+        
+        if let Err(e) = ExclusionRules::from_config(&self.static_files) {
+            problems.push("static_files.exclude", e);
+        }
+        if let Err(e) = MarkdownRenderer::from_config(&self.static_files) {
+            problems.push("static_files", e);
+        }
+        
+        for (i, upload) in self.upload.iter().flatten().enumerate() {
+            let field = format!("upload[{}]", i);
+            problems.check_route(&format!("{}.path", field), &upload.path, upload.methods.as_deref());
+            problems.check_directory(&format!("{}.directory", field), &upload.directory);
+            // Uploads are for authenticated clients only
+            let protected = self.auth.iter().flat_map(|auth| auth.paths.iter()).any(|path| {
+                wildcard_regex(path).is_ok_and(|pattern| pattern.is_match(&upload.path))
+            });
+            if !protected {
+                problems.push(&format!("{}.path", field), format_args!("'{}' is not protected by [auth] paths", upload.path));
+            }
+            let auth_methods = self.auth.as_ref().and_then(|auth| auth.methods.as_ref());
+            let unprotected = upload
+                .methods
+                .clone()
+                .unwrap_or_else(|| DEFAULT_UPLOAD_METHODS.iter().map(|method| method.to_string()).collect())
+                .into_iter()
+                .filter(|method| auth_methods.is_some_and(|methods| !methods.iter().any(|auth| auth.eq_ignore_ascii_case(method))))
+                .collect::<Vec<_>>();
+            if !unprotected.is_empty() {
+                problems.push("auth.methods", format_args!("must include the upload methods {}", unprotected.join(", ")));
+            }
+        }
+        
+        for (i, route) in self.routes.iter().flatten().enumerate() {
+            let field = format!("routes[{}]", i);
+            if let Err(e) = Route::from_config(route) {
+                problems.push(&field, format_args!("invalid route '{}': {}", route.path, e));
+            }
+            
+            if let Some(host) = &route.host {
+                if !self.virtual_hosts.iter().flatten().any(|vhost| &vhost.host == host) {
+                    problems.push(&format!("{}.host", field), format_args!("no virtual host '{}'", host));
+                }
+            }
+            
+            let Some(params) = &route.params else {
+                continue;
+            };
+            let known = match route.handler.as_str() {
+                "proxy" => pools.iter().any(|pool| &pool.name == params),
+                "fastcgi" => self.fastcgi.iter().flatten().any(|backend| &backend.path == params),
+                "scgi" => self.scgi.iter().flatten().any(|backend| &backend.path == params),
+                "uwsgi" => self.uwsgi.iter().flatten().any(|backend| &backend.path == params),
+                "cgi" => self.cgi.iter().flatten().any(|cgi| &cgi.path == params),
+                "upload" => self.upload.iter().flatten().any(|upload| &upload.path == params),
+                // Services are mounted by the embedding application, after loading
+                _ => true,
+            };
+            if !known {
+                problems.push(&format!("{}.params", field), format_args!("no {} entry '{}'", route.handler, params));
+            }
+        }
+        
+        if let Some(cors) = &self.cors {
+            if cors.allowed_origins.is_empty() {
+                problems.push("cors.allowed_origins", "must name at least one origin");
+            }
+            if let Err(e) = CorsPolicy::new(cors) {
+                problems.push("cors", e);
+            }
+        }
+        
+        for (i, rule) in self.acl.iter().flat_map(|acl| acl.rules.iter().flatten()).enumerate() {
+            problems.check_methods(&format!("acl.rules[{}].methods", i), rule.methods.as_deref());
+            if let Some(country) = &rule.country {
+                let field = format!("acl.rules[{}].country", i);
+                if country.len() != 2 || !country.bytes().all(|b| b.is_ascii_alphabetic()) {
+                    problems.push(&field, format_args!("'{}' is not a two-letter country code", country));
+                } else if self.geoip.is_none() {
+                    problems.push(&field, "needs [geoip] databases");
+                }
+            }
+        }
+        if let Err(e) = GeoIp::from_config(self) {
+            problems.push("geoip.databases", e);
+        }
+        if let Some(auth) = &self.auth {
+            problems.check_methods("auth.methods", auth.methods.as_deref());
+        }
+        
+        if let Some(webdav) = &self.webdav {
+            if webdav.paths.is_empty() {
+                problems.push("webdav.paths", "must name at least one path");
+            }
+            for path in &webdav.paths {
+                if let Err(e) = wildcard_regex(path) {
+                    problems.push("webdav.paths", format_args!("invalid pattern '{}': {}", path, e));
+                }
+            }
+            
+            // A writable share must not be open to anyone by accident
+            if !webdav.read_only.unwrap_or(false) && !webdav.anonymous_writes.unwrap_or(false) {
+                match &self.auth {
+                    Some(auth) => {
+                        let protected = auth
+                            .paths
+                            .iter()
+                            .filter_map(|path| wildcard_regex(path).ok())
+                            .collect::<Vec<_>>();
+                        for path in webdav.paths.iter().filter(|path| !protected.iter().any(|pattern| pattern.is_match(path))) {
+                            problems.push("webdav.paths", format_args!("'{}' is writable but not protected by [auth] paths", path));
+                        }
+                        
+                        let protects = |write: &str| {
+                            auth.methods.as_ref().is_none_or(|methods| methods.iter().any(|method| method.eq_ignore_ascii_case(write)))
+                        };
+                        let unprotected = WEBDAV_WRITE_METHODS.iter().filter(|write| !protects(write)).copied().collect::<Vec<_>>();
+                        if !unprotected.is_empty() {
+                            problems.push("auth.methods", format_args!("must include the WebDAV write methods {}", unprotected.join(", ")));
+                        }
+                    }
+                    None => problems.push("webdav", "a writable share needs [auth], read_only or anonymous_writes"),
+                }
+            }
+        }
+        
+        for (i, plugin) in self.plugins.iter().flat_map(|plugins| plugins.wasm.iter().flatten()).enumerate() {
+            problems.check_readable(&format!("plugins.wasm[{}].path", i), &plugin.path);
+        }
+        
+        if let Err(e) = log_filter(self.logging.as_ref()) {
+            problems.push("logging", e);
+        }
+        if let Err(e) = log_target(self.logging.as_ref()) {
+            problems.push("logging.target", e);
+        }
+        
+        for (i, middleware) in self.middleware.iter().flatten().enumerate() {
+            let field = format!("middleware[{}]", i);
+            if BUILTIN_MIDDLEWARE.contains(&middleware.name.as_str()) {
+                problems.push(&format!("{}.name", field), format_args!("'{}' is built in and cannot be moved", middleware.name));
+            }
+            for host in middleware.hosts.iter().flatten() {
+                if !self.virtual_hosts.iter().flatten().any(|vhost| &vhost.host == host) {
+                    problems.push(&format!("{}.hosts", field), format_args!("no virtual host '{}'", host));
+                }
+            }
+        }
+        if let Err(e) = MiddlewareChain::from_config(self) {
+            problems.push("middleware", e);
+        }
+        
+        // Build the TLS policy last, once the files it loads are known to be readable
+        if let Some(tls) = self.tls.as_ref().filter(|tls| (tls.enabled || listener_tls) && problems.0.is_empty()) {
+            let vhosts = self.virtual_hosts.as_deref().unwrap_or_default();
+            let built = tls::SniResolver::from_config(tls, vhosts, acme)
+                .and_then(|resolver| tls::build_server_config(tls, Arc::new(resolver), self.server.http2.unwrap_or(false)));
+            if let Err(e) = built {
+                problems.push("tls", e);
+            }
+        }
+        
+        if problems.0.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems.0))
+        }
+    }
+}
+
+/// Whether two bind addresses can claim the same port, comparing IP addresses
+/// so that a wildcard overlaps every other address
+fn addresses_overlap(a: &str, b: &str) -> bool {
+    match (a.parse::<IpAddr>(), b.parse::<IpAddr>()) {
+        (Ok(a), Ok(b)) => a == b || a.is_unspecified() || b.is_unspecified(),
+        _ => a.eq_ignore_ascii_case(b),
+    }
+}
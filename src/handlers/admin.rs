@@ -0,0 +1,273 @@
+use async_trait::async_trait;
+use hyper::{Body, Request, Response, StatusCode};
+use serde_json::json;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::core::config::Config;
+use crate::core::error::HttpError;
+use crate::handlers::common::Handler;
+use crate::handlers::proxy::ProxyPools;
+use crate::network::http::response::ResponseBuilder;
+use crate::plugins::pipeline::PluginPipeline;
+use crate::routing::router::{Route, Router};
+use crate::security::acl::{AccessCondition, AccessRule, Acl};
+use crate::security::auth::{Authenticator, BasicAuthenticator, BearerAuthenticator};
+use crate::utils::build_info::build_info;
+use crate::utils::metrics::Metrics;
+
+/// Default path of the admin status endpoint
+const DEFAULT_STATUS_PATH: &str = "/admin/status";
+
+/// Default path of the admin build information endpoint
+const DEFAULT_VERSION_PATH: &str = "/admin/version";
+
+/// Default path of the admin effective configuration endpoint
+const DEFAULT_CONFIG_PATH: &str = "/admin/config";
+
+/// Default path of the admin route table endpoint
+const DEFAULT_ROUTES_PATH: &str = "/admin/routes";
+
+/// Default path of the admin loaded plugins endpoint
+const DEFAULT_PLUGINS_PATH: &str = "/admin/plugins";
+
+/// Configuration keys whose values are hidden from the configuration endpoint
+const SECRET_KEYS: [&str; 4] = ["password", "token", "tokens", "users"];
+
+/// Handler for the guarded admin introspection endpoints
+#[derive(Clone)]
+pub struct AdminHandler {
+    /// Server configuration
+    config: Arc<Config>,
+    /// Shared server metrics
+    metrics: Metrics,
+    /// Route table described by the routes endpoint
+    router: Arc<Router>,
+    /// Plugins described by the plugins endpoint
+    plugins: PluginPipeline,
+    /// Proxy pools whose upstream health the status endpoint reports
+    proxy_pools: Arc<ProxyPools>,
+    /// Path of the status endpoint
+    status_path: String,
+    /// Path of the build information endpoint
+    version_path: String,
+    /// Path of the effective configuration endpoint
+    config_path: String,
+    /// Path of the route table endpoint
+    routes_path: String,
+    /// Path of the loaded plugins endpoint
+    plugins_path: String,
+    /// Authenticators guarding admin endpoints; any of them may admit a request
+    authenticators: Arc<Vec<Box<dyn Authenticator>>>,
+    /// Access control for admin endpoints
+    acl: Arc<Acl>,
+}
+
+impl AdminHandler {
+    /// Create an admin handler if the admin endpoint is enabled in the configuration
+    pub fn from_config(config: Arc<Config>, metrics: Metrics) -> Option<Self> {
+        let admin_config = config.admin.clone().filter(|admin| admin.enabled)?;
+        
+        // Without credentials, Basic authentication with no users keeps the endpoints locked
+        let mut authenticators: Vec<Box<dyn Authenticator>> = Vec::new();
+        let mut basic = BasicAuthenticator::new("Kaserve Admin");
+        if let (Some(username), Some(password)) = (&admin_config.username, &admin_config.password) {
+            basic.add_user(username, password);
+        }
+        if basic.has_users() || admin_config.token.is_none() {
+            authenticators.push(Box::new(basic));
+        }
+        if let Some(token) = &admin_config.token {
+            authenticators.push(Box::new(BearerAuthenticator::new("Kaserve Admin", vec![token.clone()])));
+        }
+        
+        let acl = match &admin_config.allowed_ips {
+            Some(ips) => {
+                let mut acl = Acl::new(false);
+                for ip in ips {
+                    acl.add_rule(AccessRule::Allow(AccessCondition::Ip(*ip)));
+                }
+                acl
+            }
+            None => Acl::new(true),
+        };
+        
+        Some(AdminHandler {
+            router: Arc::new(Router::new(Arc::clone(&config))),
+            config,
+            metrics,
+            plugins: PluginPipeline::default(),
+            proxy_pools: Arc::new(ProxyPools::default()),
+            status_path: admin_config.status_path.unwrap_or_else(|| DEFAULT_STATUS_PATH.to_string()),
+            version_path: admin_config.version_path.unwrap_or_else(|| DEFAULT_VERSION_PATH.to_string()),
+            config_path: admin_config.config_path.unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string()),
+            routes_path: admin_config.routes_path.unwrap_or_else(|| DEFAULT_ROUTES_PATH.to_string()),
+            plugins_path: admin_config.plugins_path.unwrap_or_else(|| DEFAULT_PLUGINS_PATH.to_string()),
+            authenticators: Arc::new(authenticators),
+            acl: Arc::new(acl),
+        })
+    }
+    
+    /// Describe the given route table instead of building one from the configuration
+    pub fn with_router(mut self, router: Arc<Router>) -> Self {
+        self.router = router;
+        self
+    }
+    
+    /// Describe the given plugins
+    pub fn with_plugins(mut self, plugins: PluginPipeline) -> Self {
+        self.plugins = plugins;
+        self
+    }
+    
+    /// Report the upstream health of the given proxy pools
+    pub fn with_proxy_pools(mut self, proxy_pools: Arc<ProxyPools>) -> Self {
+        self.proxy_pools = proxy_pools;
+        self
+    }
+    
+    /// Check if a request path belongs to the admin handler
+    pub fn matches(&self, path: &str) -> bool {
+        [&self.status_path, &self.version_path, &self.config_path, &self.routes_path, &self.plugins_path]
+            .iter()
+            .any(|admin_path| *admin_path == path)
+    }
+    
+    /// Build the status document
+    fn status(&self) -> serde_json::Value {
+        let virtual_hosts: Vec<&str> = self.config.virtual_hosts
+            .iter()
+            .flatten()
+            .map(|vhost| vhost.host.as_str())
+            .collect();
+        
+        json!({
+            "build": build_info(),
+            "metrics": self.metrics.snapshot(),
+            "active_connections": self.metrics.get_active_connections(),
+            "upstreams": self.proxy_pools.status(),
+            "config": {
+                "host": self.config.server.host,
+                "port": self.config.server.port,
+                "workers": self.config.server.workers,
+                "max_connections": self.config.server.max_connections,
+                "static_root": self.config.static_files.root_dir,
+                "tls_enabled": self.config.tls.as_ref().map(|tls| tls.enabled).unwrap_or(false),
+                "virtual_hosts": virtual_hosts,
+            },
+        })
+    }
+    
+    /// Build the effective configuration document, with secrets hidden
+    fn effective_config(&self) -> Result<serde_json::Value, serde_json::Error> {
+        let mut document = serde_json::to_value(&*self.config)?;
+        redact(&mut document);
+        Ok(document)
+    }
+    
+    /// Build the route table document, in matching order
+    fn routes(&self) -> serde_json::Value {
+        let virtual_hosts: Vec<serde_json::Value> = self.router
+            .vhosts()
+            .iter()
+            .map(|vhost| json!({
+                "host": vhost.hostname(),
+                "document_root": vhost.document_root(),
+                "routes": vhost.routes().iter().map(route_json).collect::<Vec<_>>(),
+            }))
+            .collect();
+        
+        json!({
+            "routes": self.router.default_routes().iter().map(route_json).collect::<Vec<_>>(),
+            "virtual_hosts": virtual_hosts,
+        })
+    }
+    
+    /// Build the loaded plugins document, in registration order
+    fn plugins(&self) -> serde_json::Value {
+        let plugins: Vec<serde_json::Value> = self.plugins
+            .plugins()
+            .map(|(plugin, required)| json!({
+                "name": plugin.name(),
+                "version": plugin.version(),
+                "required": required,
+            }))
+            .collect();
+        
+        json!({ "plugins": plugins })
+    }
+}
+
+/// Describe a route
+fn route_json(route: &Route) -> serde_json::Value {
+    json!({
+        "pattern": route.pattern,
+        "handler": route.handler_type,
+        "params": route.handler_params,
+        "methods": route.methods.as_ref().map(|methods| methods.iter().map(|method| method.as_str()).collect::<Vec<_>>()),
+        "priority": route.priority,
+        "timeout": route.timeout.map(|timeout| timeout.as_secs()),
+    })
+}
+
+/// Hide the values of secret keys anywhere in a configuration document
+fn redact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if SECRET_KEYS.contains(&key.as_str()) && !value.is_null() {
+                    *value = json!("[redacted]");
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+#[async_trait]
+impl Handler for AdminHandler {
+    async fn handle(&self, req: Request<Body>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let client_ip = req.extensions().get::<SocketAddr>().map(|addr| addr.ip());
+        if self.acl.check_access(&req, client_ip).is_err() {
+            debug!("Admin access denied for {:?}", client_ip);
+            return Err(HttpError::Forbidden("Access denied.".to_string()).into());
+        }
+        
+        let mut authenticated = false;
+        for authenticator in self.authenticators.iter() {
+            if authenticator.authenticate(&req).await.is_ok() {
+                authenticated = true;
+                break;
+            }
+        }
+        if !authenticated {
+            let challenges = self.authenticators.iter().map(|authenticator| authenticator.challenge()).collect();
+            return Err(HttpError::Unauthorized { challenges }.into());
+        }
+        
+        let path = req.uri().path();
+        let document = if path == self.version_path {
+            serde_json::to_value(build_info())?
+        } else if path == self.config_path {
+            self.effective_config()?
+        } else if path == self.routes_path {
+            self.routes()
+        } else if path == self.plugins_path {
+            self.plugins()
+        } else {
+            self.status()
+        };
+        
+        let body = serde_json::to_string_pretty(&document)?;
+        Ok(ResponseBuilder::with_status(StatusCode::OK)
+            .content_type("application/json")
+            .cache_control("no-store")
+            .body_string(body)
+            .build())
+    }
+}
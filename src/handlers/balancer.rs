@@ -0,0 +1,271 @@
+use hyper::Uri;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Default number of consecutive failures that eject an upstream
+pub const DEFAULT_MAX_FAILS: u32 = 3;
+
+/// Default time in seconds an ejected upstream is skipped
+pub const DEFAULT_FAIL_TIMEOUT: u64 = 10;
+
+/// Strategy used to pick the upstream for a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BalanceStrategy {
+    /// Use upstreams in turn
+    #[default]
+    RoundRobin,
+    /// Use the upstream with the fewest requests in flight
+    LeastConnections,
+    /// Use the same upstream for each client address
+    IpHash,
+    /// Use upstreams in turn, in proportion to their weights
+    Weighted,
+}
+
+/// Passive health and load of one upstream
+struct UpstreamState {
+    /// Upstream base URI
+    uri: Uri,
+    /// Relative share of requests under the weighted strategy
+    weight: u32,
+    /// Requests waiting for response headers
+    active: AtomicUsize,
+    /// Consecutive failed requests
+    failures: AtomicU32,
+    /// End of the current ejection, if ejected
+    ejected_until: Mutex<Option<Instant>>,
+    /// Whether active health checks pass (always true without them)
+    healthy: AtomicBool,
+    /// Consecutive probes with the same outcome, counting towards a health change
+    probe_streak: AtomicU32,
+}
+
+impl UpstreamState {
+    /// Check whether the upstream is ejected at `now`
+    fn is_ejected(&self, now: Instant) -> bool {
+        self.ejected_until.lock().unwrap_or_else(|e| e.into_inner()).is_some_and(|until| until > now)
+    }
+    
+    /// Check whether the upstream may get requests at `now`
+    fn is_available(&self, now: Instant) -> bool {
+        self.healthy.load(Ordering::Relaxed) && !self.is_ejected(now)
+    }
+}
+
+/// Upstream picked for a request; counts as in flight until dropped
+pub struct Selection {
+    /// Balancer the upstream belongs to
+    balancer: Arc<Balancer>,
+    /// Index of the upstream
+    index: usize,
+}
+
+impl Selection {
+    /// Base URI of the picked upstream
+    pub fn uri(&self) -> &Uri {
+        &self.balancer.upstreams[self.index].uri
+    }
+    
+    /// Record that the upstream answered
+    pub fn succeeded(&self) {
+        self.balancer.record(self.index, true);
+    }
+    
+    /// Record that the upstream could not be reached
+    pub fn failed(&self) {
+        self.balancer.record(self.index, false);
+    }
+}
+
+impl Drop for Selection {
+    fn drop(&mut self) {
+        self.balancer.upstreams[self.index].active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Picks upstreams of a pool and ejects those failing repeatedly.
+///
+/// An upstream is ejected for `fail_timeout` after `max_fails` consecutive
+/// failures, then gets requests again; one success resets its failure count.
+/// Upstreams failing active health checks get no requests until they pass
+/// again. When no upstream is available, all of them are candidates again
+/// rather than refusing requests outright.
+pub struct Balancer {
+    /// Name of the pool, used in logs
+    name: String,
+    /// Strategy used to pick upstreams
+    strategy: BalanceStrategy,
+    /// Upstreams in configuration order
+    upstreams: Vec<UpstreamState>,
+    /// Rotation counter of the round-robin and weighted strategies
+    next: AtomicUsize,
+    /// Consecutive failures that eject an upstream
+    max_fails: u32,
+    /// Time an ejected upstream is skipped
+    fail_timeout: Duration,
+}
+
+impl Balancer {
+    /// Create a balancer over upstreams with their weights
+    pub fn new(
+        name: &str,
+        strategy: BalanceStrategy,
+        upstreams: Vec<(Uri, u32)>,
+        max_fails: u32,
+        fail_timeout: Duration,
+    ) -> Self {
+        Balancer {
+            name: name.to_string(),
+            strategy,
+            upstreams: upstreams
+                .into_iter()
+                .map(|(uri, weight)| UpstreamState {
+                    uri,
+                    weight,
+                    active: AtomicUsize::new(0),
+                    failures: AtomicU32::new(0),
+                    ejected_until: Mutex::new(None),
+                    healthy: AtomicBool::new(true),
+                    probe_streak: AtomicU32::new(0),
+                })
+                .collect(),
+            next: AtomicUsize::new(0),
+            max_fails,
+            fail_timeout,
+        }
+    }
+    
+    /// Pick the upstream for a request from `client`
+    pub fn select(self: &Arc<Self>, client: Option<IpAddr>) -> Selection {
+        let now = Instant::now();
+        let mut candidates: Vec<usize> = (0..self.upstreams.len())
+            .filter(|&index| self.upstreams[index].is_available(now))
+            .collect();
+        if candidates.is_empty() {
+            candidates = (0..self.upstreams.len()).collect();
+        }
+        
+        let index = match (self.strategy, client) {
+            (BalanceStrategy::LeastConnections, _) => {
+                // Start at a rotating offset so ties are spread across upstreams
+                let offset = self.next.fetch_add(1, Ordering::Relaxed);
+                (0..candidates.len())
+                    .map(|i| candidates[(offset + i) % candidates.len()])
+                    .min_by_key(|&index| self.upstreams[index].active.load(Ordering::Relaxed))
+                    .unwrap_or(candidates[0])
+            }
+            (BalanceStrategy::IpHash, Some(ip)) => {
+                let mut hasher = DefaultHasher::new();
+                ip.hash(&mut hasher);
+                candidates[hasher.finish() as usize % candidates.len()]
+            }
+            (BalanceStrategy::Weighted, _) => {
+                let total: usize = candidates.iter().map(|&index| self.upstreams[index].weight as usize).sum();
+                let mut ticket = self.next.fetch_add(1, Ordering::Relaxed) % total.max(1);
+                candidates
+                    .iter()
+                    .copied()
+                    .find(|&index| {
+                        let weight = self.upstreams[index].weight as usize;
+                        if ticket < weight {
+                            return true;
+                        }
+                        ticket -= weight;
+                        false
+                    })
+                    .unwrap_or(candidates[0])
+            }
+            // Clients without a known address fall back to rotation
+            (BalanceStrategy::RoundRobin, _) | (BalanceStrategy::IpHash, None) => {
+                candidates[self.next.fetch_add(1, Ordering::Relaxed) % candidates.len()]
+            }
+        };
+        
+        self.upstreams[index].active.fetch_add(1, Ordering::Relaxed);
+        Selection { balancer: Arc::clone(self), index }
+    }
+    
+    /// Update the failure count of an upstream, ejecting or restoring it
+    fn record(&self, index: usize, success: bool) {
+        let upstream = &self.upstreams[index];
+        let mut ejected_until = upstream.ejected_until.lock().unwrap_or_else(|e| e.into_inner());
+        
+        if success {
+            upstream.failures.store(0, Ordering::Relaxed);
+            if ejected_until.take().is_some() {
+                info!("Upstream {} in pool '{}' recovered", upstream.uri, self.name);
+            }
+            return;
+        }
+        
+        let failures = upstream.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures >= self.max_fails {
+            let now = Instant::now();
+            if !ejected_until.is_some_and(|until| until > now) {
+                warn!(
+                    "Ejecting upstream {} in pool '{}' for {:?} after {} consecutive failures",
+                    upstream.uri, self.name, self.fail_timeout, failures
+                );
+            }
+            *ejected_until = Some(now + self.fail_timeout);
+        }
+    }
+    
+    /// Record the outcome of an active health check probe.
+    ///
+    /// A healthy upstream turns unhealthy after `unhealthy_threshold` failing
+    /// probes in a row, and back after `healthy_threshold` passing ones.
+    pub fn record_probe(&self, index: usize, passed: bool, healthy_threshold: u32, unhealthy_threshold: u32) {
+        let upstream = &self.upstreams[index];
+        if upstream.healthy.load(Ordering::Relaxed) == passed {
+            upstream.probe_streak.store(0, Ordering::Relaxed);
+            return;
+        }
+        
+        let streak = upstream.probe_streak.fetch_add(1, Ordering::Relaxed) + 1;
+        let threshold = if passed { healthy_threshold } else { unhealthy_threshold };
+        if streak >= threshold {
+            upstream.healthy.store(passed, Ordering::Relaxed);
+            upstream.probe_streak.store(0, Ordering::Relaxed);
+            if passed {
+                info!("Upstream {} in pool '{}' passes health checks again", upstream.uri, self.name);
+            } else {
+                warn!("Upstream {} in pool '{}' failed {} health checks", upstream.uri, self.name, streak);
+            }
+        }
+    }
+    
+    /// Base URIs of the upstreams in configuration order
+    pub fn upstream_uris(&self) -> impl Iterator<Item = &Uri> {
+        self.upstreams.iter().map(|upstream| &upstream.uri)
+    }
+    
+    /// Check whether no upstream may get requests
+    pub fn none_available(&self) -> bool {
+        let now = Instant::now();
+        self.upstreams.iter().all(|upstream| !upstream.is_available(now))
+    }
+    
+    /// Describe the health and load of each upstream
+    pub fn status(&self) -> Vec<serde_json::Value> {
+        let now = Instant::now();
+        self.upstreams
+            .iter()
+            .map(|upstream| json!({
+                "server": upstream.uri.to_string(),
+                "healthy": upstream.healthy.load(Ordering::Relaxed),
+                "ejected": upstream.is_ejected(now),
+                "active_requests": upstream.active.load(Ordering::Relaxed),
+                "consecutive_failures": upstream.failures.load(Ordering::Relaxed),
+            }))
+            .collect()
+    }
+}
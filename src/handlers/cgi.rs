@@ -0,0 +1,597 @@
+use async_trait::async_trait;
+use hyper::body::HttpBody;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::http::uri::Scheme;
+use hyper::{Body, Request, Response, StatusCode};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+use tracing::{debug, error, warn};
+
+use crate::core::config::{CgiConfig, Config};
+use crate::core::error::HttpError;
+use crate::handlers::common::Handler;
+use crate::network::http::path::{decode_segments, PathError};
+use crate::network::http::response::ResponseBuilder;
+use crate::routing::router::parse_host;
+use crate::utils::build_info::VERSION;
+use crate::utils::memory::{body_holding, buffer_body, read_to_end_reserved, reserved_body, MemoryBudget, MemoryReservation};
+
+/// Execution timeout used when none is configured, in seconds
+const DEFAULT_CGI_TIMEOUT: u64 = 30;
+
+/// Largest script output accepted when no limit is configured, in bytes
+const DEFAULT_MAX_OUTPUT_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Handler executing CGI scripts as child processes
+#[derive(Clone)]
+pub struct CgiHandler {
+    /// Route pattern served by this handler
+    script_pattern: String,
+    /// Directory scripts are resolved in
+    document_root: String,
+    /// Maximum time a script may run
+    timeout: Duration,
+    /// Largest output a script may write, in bytes
+    max_output_size: u64,
+    /// Budget the buffered script output is accounted against
+    memory_budget: MemoryBudget,
+}
+
+impl CgiHandler {
+    /// Create a new CGI handler
+    pub fn new(script_pattern: String, document_root: String, timeout: Duration) -> Self {
+        CgiHandler {
+            script_pattern,
+            document_root,
+            timeout,
+            max_output_size: DEFAULT_MAX_OUTPUT_SIZE,
+            memory_budget: MemoryBudget::unlimited(),
+        }
+    }
+    
+    /// Limit the output a script may write, in bytes
+    pub fn with_max_output_size(mut self, max_output_size: u64) -> Self {
+        self.max_output_size = max_output_size;
+        self
+    }
+    
+    /// Account buffered script output against a shared memory budget
+    pub fn with_memory_budget(mut self, memory_budget: MemoryBudget) -> Self {
+        self.memory_budget = memory_budget;
+        self
+    }
+    
+    /// Create a handler from a CGI configuration
+    pub fn from_config(cgi: &CgiConfig, default_root: &str) -> Self {
+        Self::new(
+            cgi.path.clone(),
+            cgi.document_root.clone().unwrap_or_else(|| default_root.to_string()),
+            Duration::from_secs(cgi.timeout.unwrap_or(DEFAULT_CGI_TIMEOUT)),
+        )
+        .with_max_output_size(cgi.max_output_size.unwrap_or(DEFAULT_MAX_OUTPUT_SIZE))
+    }
+    
+    /// Get the route pattern this handler serves
+    pub fn script_pattern(&self) -> &str {
+        &self.script_pattern
+    }
+    
+    /// Run a script with the request's environment and body, returning its stdout
+    async fn execute(&self, script: &Path, env: Vec<(String, String)>, mut body: Body) -> Result<(Vec<u8>, MemoryReservation), HttpError> {
+        let mut command = Command::new(script);
+        command
+            .env_clear()
+            .envs(env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // A timed-out or abandoned request must not leave the script running
+            .kill_on_drop(true);
+        
+        // Keep PATH so interpreters named by `#!/usr/bin/env` can be found
+        if let Some(path) = std::env::var_os("PATH") {
+            command.env("PATH", path);
+        }
+        if let Some(dir) = script.parent() {
+            command.current_dir(dir);
+        }
+        
+        // Run the script in its own process group so its children can be killed with it
+        #[cfg(unix)]
+        command.process_group(0);
+        
+        let mut child = command.spawn().map_err(|e| {
+            error!("Failed to execute CGI script {}: {}", script.display(), e);
+            match e.kind() {
+                std::io::ErrorKind::PermissionDenied => HttpError::Forbidden("The script is not executable.".to_string()),
+                _ => HttpError::Internal(e.to_string()),
+            }
+        })?;
+        let mut group = ProcessGroupGuard { pgid: child.id() };
+        
+        let mut stdin = child.stdin.take();
+        let mut stdout = child.stdout.take();
+        let mut stderr = child.stderr.take();
+        
+        // Feed stdin while draining stdout and stderr so no pipe fills up and blocks the script
+        let feed_stdin = async {
+            if let Some(stdin) = stdin.as_mut() {
+                while let Some(chunk) = body.data().await {
+                    let Ok(chunk) = chunk else { break };
+                    if stdin.write_all(&chunk).await.is_err() {
+                        // The script stopped reading its input
+                        break;
+                    }
+                }
+            }
+            drop(stdin.take());
+            Ok(())
+        };
+        let read_stdout = async {
+            let Some(stdout) = stdout.as_mut() else {
+                return Ok((Vec::new(), self.memory_budget.reservation()));
+            };
+            let (output, reservation) = read_to_end_reserved(&mut stdout.take(self.max_output_size + 1), &self.memory_budget).await?;
+            if output.len() as u64 > self.max_output_size {
+                return Err(std::io::Error::other(format!("output exceeds {} bytes", self.max_output_size)));
+            }
+            Ok((output, reservation))
+        };
+        let read_stderr = async {
+            let mut output = Vec::new();
+            if let Some(stderr) = stderr.as_mut() {
+                let _ = stderr.read_to_end(&mut output).await;
+            }
+            Ok(output)
+        };
+        
+        // A failure reading stdout, such as running out of budget, abandons the script at once;
+        // it is killed with its group rather than left blocked on a full pipe
+        let (_, (output, reservation), errors) = tokio::try_join!(feed_stdin, read_stdout, read_stderr)
+            .map_err(|e| output_error("CGI script", e))?;
+        let status = child.wait().await.map_err(|e| HttpError::Internal(e.to_string()))?;
+        group.pgid = None;
+        
+        if !errors.is_empty() {
+            warn!("CGI {}: {}", script.display(), String::from_utf8_lossy(&errors).trim_end());
+        }
+        
+        if !status.success() && output.is_empty() {
+            return Err(HttpError::BadGateway(format!("CGI script exited with {}", status)));
+        }
+        
+        Ok((output, reservation))
+    }
+}
+
+/// Kills a script's process group if the script is abandoned before it exits
+struct ProcessGroupGuard {
+    /// Process group ID, cleared once the script has exited
+    pgid: Option<u32>,
+}
+
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(pgid) = self.pgid {
+            debug!("Killing CGI process group {}", pgid);
+            // SAFETY: kill has no memory-safety preconditions; a negative PID targets the group
+            unsafe {
+                libc::kill(-(pgid as libc::pid_t), libc::SIGKILL);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Handler for CgiHandler {
+    async fn handle(&self, req: Request<Body>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        debug!("Handling CGI request for: {}", req.uri().path());
+        
+        // Resolve to an absolute path, as the script runs from its own directory
+        let location = locate_script(&self.document_root, req.uri().path()).await?;
+        let script = tokio::fs::canonicalize(&location.file).await.map_err(|_| HttpError::NotFound)?;
+        
+        // CONTENT_LENGTH is taken from the request as declared; scripts read stdin to EOF otherwise
+        let content_length = req
+            .headers()
+            .get(hyper::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        let mut env = cgi_environment(&req, &self.document_root, content_length)?;
+        location.describe(&mut env, &script, &self.document_root);
+        
+        let (output, reservation) = match tokio::time::timeout(self.timeout, self.execute(&script, env, req.into_body())).await {
+            Ok(output) => output?,
+            Err(_) => {
+                warn!("CGI script {} timed out after {:?}", script.display(), self.timeout);
+                return Err(HttpError::GatewayTimeout.into());
+            }
+        };
+        
+        Ok(parse_cgi_response(&output, reservation)?)
+    }
+}
+
+/// CGI handlers for all configured script routes
+#[derive(Clone, Default)]
+pub struct CgiScripts {
+    /// Handlers by route pattern
+    handlers: HashMap<String, CgiHandler>,
+}
+
+impl CgiScripts {
+    /// Build a handler for every configured script route, sharing one memory budget
+    pub fn from_config(config: &Config, memory_budget: &MemoryBudget) -> Self {
+        let handlers = config
+            .cgi
+            .iter()
+            .flatten()
+            .map(|cgi| {
+                let handler = CgiHandler::from_config(cgi, &config.static_files.root_dir)
+                    .with_memory_budget(memory_budget.clone());
+                (handler.script_pattern().to_string(), handler)
+            })
+            .collect();
+        
+        CgiScripts { handlers }
+    }
+    
+    /// Get the handler for a route pattern
+    pub fn get(&self, pattern: &str) -> Option<&CgiHandler> {
+        self.handlers.get(pattern)
+    }
+}
+
+/// Resolve the script file for a request path below `document_root`.
+///
+/// The path is percent-decoded first. The resolved file is executed, so any
+/// traversal is refused outright.
+pub fn script_path(document_root: &str, path: &str) -> Result<PathBuf, HttpError> {
+    Ok(decoded_segments(path)?.iter().fold(PathBuf::from(document_root), |file, segment| file.join(segment.as_ref())))
+}
+
+/// Decode the segments of a script path, refusing traversal
+fn decoded_segments(path: &str) -> Result<Vec<Cow<'_, str>>, HttpError> {
+    let segments = match decode_segments(path) {
+        Ok(segments) => segments,
+        Err(PathError::Traversal) => return Err(HttpError::Forbidden("Invalid script path.".to_string())),
+        Err(_) => return Err(HttpError::BadRequest("Malformed script path.".to_string())),
+    };
+    if segments.iter().any(|segment| segment == "..") {
+        return Err(HttpError::Forbidden("Invalid script path.".to_string()));
+    }
+    Ok(segments)
+}
+
+/// A script found for a request path, and the extra path following it
+#[derive(Debug, PartialEq)]
+pub struct ScriptLocation {
+    /// Script file to execute
+    pub file: PathBuf,
+    /// Decoded URL path of the script, for `SCRIPT_NAME`
+    pub script_name: String,
+    /// Decoded path after the script, for `PATH_INFO`; empty if there is none
+    pub path_info: String,
+}
+
+impl ScriptLocation {
+    /// Describe the script in a CGI environment built for the whole request path
+    pub fn describe(&self, params: &mut Vec<(String, String)>, file: &Path, document_root: &str) {
+        params.retain(|(name, _)| name != "SCRIPT_NAME" && name != "SCRIPT_FILENAME");
+        params.push(("SCRIPT_NAME".to_string(), self.script_name.clone()));
+        params.push(("SCRIPT_FILENAME".to_string(), file.to_string_lossy().into_owned()));
+        
+        if !self.path_info.is_empty() {
+            let translated = Path::new(document_root).join(self.path_info.trim_start_matches('/'));
+            params.push(("PATH_INFO".to_string(), self.path_info.clone()));
+            params.push(("PATH_TRANSLATED".to_string(), translated.to_string_lossy().into_owned()));
+        }
+    }
+}
+
+/// Find the script a request path runs.
+///
+/// Decoded segments are followed from `document_root` through directories
+/// until one names a file, so `/cgi-bin/x.cgi/extra` runs `x.cgi` with
+/// `/extra` as its `PATH_INFO`.
+pub async fn locate_script(document_root: &str, path: &str) -> Result<ScriptLocation, HttpError> {
+    let segments = decoded_segments(path)?;
+    let mut file = PathBuf::from(document_root);
+    
+    for (index, segment) in segments.iter().enumerate() {
+        file.push(segment.as_ref());
+        match tokio::fs::metadata(&file).await {
+            Ok(metadata) if metadata.is_dir() => continue,
+            Ok(_) => {
+                let mut path_info: String = segments[index + 1..].iter().map(|segment| format!("/{}", segment)).collect();
+                if !path_info.is_empty() && path.ends_with('/') {
+                    path_info.push('/');
+                }
+                return Ok(ScriptLocation {
+                    file,
+                    script_name: segments[..=index].iter().map(|segment| format!("/{}", segment)).collect(),
+                    path_info,
+                });
+            }
+            Err(_) => break,
+        }
+    }
+    
+    Err(HttpError::NotFound)
+}
+
+/// Build the CGI/1.1 environment for a request
+pub fn cgi_environment(
+    req: &Request<Body>,
+    document_root: &str,
+    content_length: Option<u64>,
+) -> Result<Vec<(String, String)>, HttpError> {
+    let path = req.uri().path();
+    let script_filename = script_path(document_root, path)?;
+    let remote_addr = req.extensions().get::<SocketAddr>().copied();
+    let (server_name, server_port) = match req.headers().get(hyper::header::HOST).and_then(|h| h.to_str().ok()) {
+        Some(host) => {
+            let (name, port) = parse_host(host);
+            (name, port.map(|p| p.to_string()).unwrap_or_else(|| "80".to_string()))
+        }
+        None => (String::new(), "80".to_string()),
+    };
+    
+    let mut params = vec![
+        ("GATEWAY_INTERFACE".to_string(), "CGI/1.1".to_string()),
+        ("SERVER_SOFTWARE".to_string(), format!("kaserve/{}", VERSION)),
+        ("SERVER_PROTOCOL".to_string(), format!("{:?}", req.version())),
+        ("SERVER_NAME".to_string(), server_name),
+        ("SERVER_PORT".to_string(), server_port),
+        ("REQUEST_METHOD".to_string(), req.method().to_string()),
+        ("REQUEST_URI".to_string(), req.uri().path_and_query().map(|pq| pq.to_string()).unwrap_or_else(|| path.to_string())),
+        ("QUERY_STRING".to_string(), req.uri().query().unwrap_or("").to_string()),
+        ("SCRIPT_NAME".to_string(), path.to_string()),
+        ("SCRIPT_FILENAME".to_string(), script_filename.to_string_lossy().into_owned()),
+        ("DOCUMENT_ROOT".to_string(), document_root.to_string()),
+        // Required by PHP when cgi.force_redirect is enabled
+        ("REDIRECT_STATUS".to_string(), "200".to_string()),
+    ];
+    
+    if let Some(addr) = remote_addr {
+        params.push(("REMOTE_ADDR".to_string(), addr.ip().to_string()));
+        params.push(("REMOTE_PORT".to_string(), addr.port().to_string()));
+    }
+    
+    if req.extensions().get::<Scheme>() == Some(&Scheme::HTTPS) {
+        params.push(("HTTPS".to_string(), "on".to_string()));
+    }
+    
+    if let Some(length) = content_length {
+        params.push(("CONTENT_LENGTH".to_string(), length.to_string()));
+    }
+    
+    for (name, value) in req.headers() {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        
+        let name = name.as_str().to_ascii_uppercase().replace('-', "_");
+        match name.as_str() {
+            "CONTENT_TYPE" => params.push((name, value.to_string())),
+            "CONTENT_LENGTH" => {}
+            // Never let a client header pose as the proxy environment variable
+            "PROXY" => {}
+            _ => params.push((format!("HTTP_{}", name), value.to_string())),
+        }
+    }
+    
+    Ok(params)
+}
+
+/// Build the environment for an application server, such as a WSGI container
+/// behind SCGI or uwsgi, that serves the whole path as `PATH_INFO`
+pub fn application_environment(
+    req: &Request<Body>,
+    document_root: &str,
+    content_length: u64,
+) -> Result<Vec<(String, String)>, HttpError> {
+    let mut params = cgi_environment(req, document_root, Some(content_length))?;
+    for (name, value) in params.iter_mut() {
+        if name == "SCRIPT_NAME" {
+            value.clear();
+        }
+    }
+    params.push(("PATH_INFO".to_string(), req.uri().path().to_string()));
+    
+    Ok(params)
+}
+
+/// Split off a request body for a backend needing its length up front,
+/// buffering it against the memory budget when the client did not declare one
+pub async fn sized_body(req: Request<Body>, memory_budget: &MemoryBudget) -> Result<(Request<Body>, Body, u64), Box<dyn Error + Send + Sync>> {
+    let declared_length = req
+        .headers()
+        .get(hyper::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    
+    let (parts, body) = req.into_parts();
+    let (body, length) = match declared_length {
+        Some(length) => (body, length),
+        None => {
+            let (data, reservation) = buffer_body(body, memory_budget).await?;
+            let length = data.len() as u64;
+            (reserved_body(data, reservation), length)
+        }
+    };
+    
+    Ok((Request::from_parts(parts, Body::empty()), body, length))
+}
+
+/// Split CGI output into response headers and body and build a response.
+///
+/// A `Status` header sets the status code; a `Location` without one redirects with 302.
+/// A leading `HTTP/1.x` status line, as application servers such as uWSGI send,
+/// sets the status code too. Fields named in a `Trailer` header are sent as
+/// trailers after the body, where the client's protocol carries them. The body
+/// holds the output's memory reservation until it has been sent.
+pub fn parse_cgi_response(output: &[u8], reservation: MemoryReservation) -> Result<Response<Body>, HttpError> {
+    let (head, body) = match find_header_end(output) {
+        Some((head_end, body_start)) => (&output[..head_end], &output[body_start..]),
+        None => return Err(HttpError::BadGateway("Malformed CGI response headers".to_string())),
+    };
+    
+    let head = String::from_utf8_lossy(head);
+    let mut status = None;
+    let mut headers = HeaderMap::new();
+    let mut lines = head.lines().peekable();
+    
+    if let Some(status_line) = lines.next_if(|line| line.starts_with("HTTP/")) {
+        let code = status_line.split_whitespace().nth(1).and_then(|code| code.parse::<u16>().ok());
+        status = code.and_then(|code| StatusCode::from_u16(code).ok());
+    }
+    
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(HttpError::BadGateway(format!("Malformed CGI header line: {}", line)));
+        };
+        let value = value.trim();
+        
+        if name.eq_ignore_ascii_case("status") {
+            let code = value.split_whitespace().next().and_then(|code| code.parse::<u16>().ok());
+            status = code.and_then(|code| StatusCode::from_u16(code).ok());
+            continue;
+        }
+        
+        match (HeaderName::from_bytes(name.trim().as_bytes()), HeaderValue::from_str(value)) {
+            (Ok(name), Ok(value)) => {
+                headers.append(name, value);
+            }
+            _ => debug!("Skipping invalid CGI header: {}", line),
+        }
+    }
+    
+    let status = status.unwrap_or(if headers.contains_key(hyper::header::LOCATION) {
+        StatusCode::FOUND
+    } else {
+        StatusCode::OK
+    });
+    
+    // The builder announces the trailers again, naming only those the script set
+    let mut trailers = HeaderMap::new();
+    let announced: Vec<HeaderName> = headers
+        .get_all(hyper::header::TRAILER)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+        .collect();
+    for name in announced {
+        let values: Vec<HeaderValue> = headers.get_all(&name).iter().cloned().collect();
+        headers.remove(&name);
+        for value in values {
+            trailers.append(name.clone(), value);
+        }
+    }
+    headers.remove(hyper::header::TRAILER);
+    
+    // The reserved body has no size hint, so its length is declared up front
+    let mut response = if trailers.is_empty() {
+        let builder = ResponseBuilder::with_status(status);
+        let builder = match status {
+            StatusCode::NO_CONTENT | StatusCode::NOT_MODIFIED => builder,
+            _ => builder.header("content-length", &body.len().to_string()),
+        };
+        builder.body(reserved_body(body.to_vec(), reservation)).build()
+    } else {
+        ResponseBuilder::with_status(status)
+            .body_bytes(body.to_vec())
+            .trailers(trailers)
+            .build()
+            .map(|body| body_holding(body, reservation))
+    };
+    response.headers_mut().extend(headers);
+    
+    Ok(response)
+}
+
+/// Map a failure reading a backend's output, answering 503 when the memory budget ran out
+pub fn output_error(backend: &str, e: std::io::Error) -> HttpError {
+    match e.kind() {
+        std::io::ErrorKind::OutOfMemory => {
+            warn!("Memory budget exhausted buffering {} output", backend);
+            HttpError::ServiceUnavailable
+        }
+        _ => HttpError::BadGateway(format!("{} read failed: {}", backend, e)),
+    }
+}
+
+/// Find the end of the CGI header block, accepting CRLF or bare LF line endings.
+///
+/// Returns the end of the headers and the start of the body.
+fn find_header_end(output: &[u8]) -> Option<(usize, usize)> {
+    let crlf = output.windows(4).position(|w| w == b"\r\n\r\n").map(|i| (i, i + 4));
+    let lf = output.windows(2).position(|w| w == b"\n\n").map(|i| (i, i + 2));
+    
+    match (crlf, lf) {
+        (Some(a), Some(b)) => Some(if a.0 <= b.0 { a } else { b }),
+        (a, b) => a.or(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body::HttpBody;
+    
+    #[tokio::test]
+    async fn scripts_are_found_before_the_extra_path() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(root.path().join("cgi-bin")).unwrap();
+        std::fs::write(root.path().join("cgi-bin/x.cgi"), "").unwrap();
+        std::fs::write(root.path().join("cgi-bin/my script.cgi"), "").unwrap();
+        let document_root = root.path().to_str().unwrap();
+        
+        let location = locate_script(document_root, "/cgi-bin/x.cgi/extra/more%20path/").await.unwrap();
+        assert_eq!(location.file, root.path().join("cgi-bin/x.cgi"));
+        assert_eq!(location.script_name, "/cgi-bin/x.cgi");
+        assert_eq!(location.path_info, "/extra/more path/");
+        
+        let location = locate_script(document_root, "/cgi-bin/my%20script.cgi").await.unwrap();
+        assert_eq!(location.file, root.path().join("cgi-bin/my script.cgi"));
+        assert_eq!(location.path_info, "");
+        
+        assert!(matches!(locate_script(document_root, "/cgi-bin/").await, Err(HttpError::NotFound)));
+        assert!(matches!(locate_script(document_root, "/cgi-bin/missing.cgi/x").await, Err(HttpError::NotFound)));
+        assert!(matches!(locate_script(document_root, "/cgi-bin/../cgi-bin/x.cgi").await, Err(HttpError::Forbidden(_))));
+    }
+    
+    #[tokio::test]
+    async fn announced_fields_follow_the_body_as_trailers() {
+        let output = b"Content-Type: text/plain\r\nTrailer: X-Checksum, X-Missing\r\nX-Checksum: abc123\r\n\r\nhello";
+        let mut response = parse_cgi_response(output, MemoryBudget::unlimited().reservation()).unwrap();
+        
+        assert_eq!(response.headers()[hyper::header::TRAILER], "x-checksum");
+        assert!(!response.headers().contains_key("x-checksum"));
+        assert_eq!(response.headers()[hyper::header::CONTENT_TYPE], "text/plain");
+        
+        let body = response.body_mut();
+        assert_eq!(body.data().await.unwrap().unwrap(), "hello");
+        assert!(body.data().await.is_none());
+        let trailers = body.trailers().await.unwrap().unwrap();
+        assert_eq!(trailers["x-checksum"], "abc123");
+    }
+    
+    #[tokio::test]
+    async fn responses_without_announced_trailers_have_none() {
+        let mut response = parse_cgi_response(b"Status: 404 Not Found\n\nmissing", MemoryBudget::unlimited().reservation()).unwrap();
+        
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(!response.headers().contains_key(hyper::header::TRAILER));
+        assert_eq!(hyper::body::to_bytes(response.body_mut()).await.unwrap(), "missing");
+    }
+}
@@ -18,12 +18,24 @@ pub enum HandlerType {
     /// FastCGI handler
     FastCGI,
     
+    /// SCGI handler
+    Scgi,
+    
+    /// uwsgi handler
+    Uwsgi,
+    
     /// CGI handler
     CGI,
     
     /// Proxy handler
     Proxy,
     
+    /// Upload endpoint
+    Upload,
+    
+    /// Service mounted by the embedding application
+    Service,
+    
     /// Custom handler
     Custom(String),
 }
@@ -34,19 +46,27 @@ impl HandlerType {
         match self {
             HandlerType::StaticFile => "static",
             HandlerType::FastCGI => "fastcgi",
+            HandlerType::Scgi => "scgi",
+            HandlerType::Uwsgi => "uwsgi",
             HandlerType::CGI => "cgi",
             HandlerType::Proxy => "proxy",
+            HandlerType::Upload => "upload",
+            HandlerType::Service => "service",
             HandlerType::Custom(name) => name,
         }
     }
     
-    /// Create a handler type from a string
-    pub fn from_str(s: &str) -> Option<Self> {
+    /// Parse a handler type from its name
+    pub fn parse(s: &str) -> Option<Self> {
         match s {
             "static" => Some(HandlerType::StaticFile),
             "fastcgi" => Some(HandlerType::FastCGI),
+            "scgi" => Some(HandlerType::Scgi),
+            "uwsgi" => Some(HandlerType::Uwsgi),
             "cgi" => Some(HandlerType::CGI),
             "proxy" => Some(HandlerType::Proxy),
+            "upload" => Some(HandlerType::Upload),
+            "service" => Some(HandlerType::Service),
             _ => Some(HandlerType::Custom(s.to_string())),
         }
     }
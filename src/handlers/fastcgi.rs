@@ -1,12 +1,27 @@
 use async_trait::async_trait;
-use hyper::{Body, Request, Response, StatusCode};
+use hyper::body::HttpBody;
+use hyper::{Body, Request, Response};
+use std::collections::HashMap;
 use std::error::Error;
 use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
-use tracing::{debug, error, info};
+use tracing::{debug, error, warn};
 
+use crate::core::config::{Config, FastCgiConfig};
+use crate::core::error::HttpError;
+use crate::handlers::cgi::{cgi_environment, parse_cgi_response};
 use crate::handlers::common::Handler;
-use crate::network::http::response::ResponseBuilder;
+use crate::utils::memory::{buffer_body, reserved_body, MemoryBudget, MemoryReservation};
+
+/// Length of a FastCGI record header
+const HEADER_LEN: usize = 8;
+
+/// Largest content length a single record can carry
+const MAX_CONTENT_LEN: usize = 0xFFFF;
+
+/// Request ID used on each connection, which carries a single request
+const REQUEST_ID: u16 = 1;
 
 /// Basic record types for FastCGI
 #[derive(Debug, Clone, Copy)]
@@ -43,6 +58,8 @@ pub struct FastCGIHandler {
     script_pattern: String,
     /// Document root
     document_root: String,
+    /// Budget buffered request bodies and responses are accounted against
+    memory_budget: MemoryBudget,
 }
 
 impl FastCGIHandler {
@@ -52,9 +69,30 @@ impl FastCGIHandler {
             server_addr,
             script_pattern,
             document_root,
+            memory_budget: MemoryBudget::unlimited(),
         }
     }
     
+    /// Account buffered request bodies and responses against a shared memory budget
+    pub fn with_memory_budget(mut self, memory_budget: MemoryBudget) -> Self {
+        self.memory_budget = memory_budget;
+        self
+    }
+    
+    /// Create a handler from a backend configuration
+    pub fn from_config(backend: &FastCgiConfig, default_root: &str) -> Self {
+        Self::new(
+            backend.address,
+            backend.path.clone(),
+            backend.document_root.clone().unwrap_or_else(|| default_root.to_string()),
+        )
+    }
+    
+    /// Get the route pattern this handler serves
+    pub fn script_pattern(&self) -> &str {
+        &self.script_pattern
+    }
+    
     /// Create FastCGI begin request record
     fn create_begin_request(&self, request_id: u16) -> Vec<u8> {
         let mut buffer = vec![0u8; 16];
@@ -82,53 +120,57 @@ impl FastCGIHandler {
         buffer
     }
     
-    /// Create FastCGI params record
-    fn create_params(&self, request_id: u16, params: &[(&str, &str)]) -> Vec<u8> {
-        // Calculate total params size
-        let mut params_size = 0;
-        for (name, value) in params {
-            params_size += 8; // For name and value length
-            params_size += name.len() + value.len();
-        }
-        
-        let mut buffer = Vec::with_capacity(8 + params_size);
+    /// Create a record carrying `content`, which must fit a single record
+    fn create_record(&self, record_type: RecordType, request_id: u16, content: &[u8]) -> Vec<u8> {
+        let content_len = content.len();
+        let mut buffer = Vec::with_capacity(HEADER_LEN + content_len);
         
         // Header
         buffer.push(1); // version
-        buffer.push(RecordType::Params as u8);
+        buffer.push(record_type as u8);
         buffer.push((request_id >> 8) as u8); // request ID high byte
         buffer.push((request_id & 0xFF) as u8); // request ID low byte
-        buffer.push((params_size >> 8) as u8); // content length high byte
-        buffer.push((params_size & 0xFF) as u8); // content length low byte
+        buffer.push((content_len >> 8) as u8); // content length high byte
+        buffer.push((content_len & 0xFF) as u8); // content length low byte
         buffer.push(0); // padding length
         buffer.push(0); // reserved
         
-        // Body - add each param
+        // Body
+        buffer.extend_from_slice(content);
+        
+        buffer
+    }
+    
+    /// Create the records for a stream, split to the maximum record size.
+    ///
+    /// An empty `data` yields only the empty record that terminates the stream.
+    fn create_stream(&self, record_type: RecordType, request_id: u16, data: &[u8], terminate: bool) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(data.len() + HEADER_LEN * (data.len() / MAX_CONTENT_LEN + 2));
+        
+        for chunk in data.chunks(MAX_CONTENT_LEN) {
+            buffer.extend(self.create_record(record_type, request_id, chunk));
+        }
+        
+        if terminate {
+            buffer.extend(self.create_record(record_type, request_id, &[]));
+        }
+        
+        buffer
+    }
+    
+    /// Encode name-value pairs for PARAMS records
+    fn encode_params(params: &[(String, String)]) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        
         for (name, value) in params {
-            let name_len = name.len();
-            let value_len = value.len();
-            
-            // Name length
-            if name_len < 128 {
-                buffer.push(name_len as u8);
-            } else {
-                buffer.push(((name_len >> 24) | 0x80) as u8);
-                buffer.push((name_len >> 16) as u8);
-                buffer.push((name_len >> 8) as u8);
-                buffer.push((name_len & 0xFF) as u8);
+            for len in [name.len(), value.len()] {
+                if len < 128 {
+                    buffer.push(len as u8);
+                } else {
+                    buffer.extend_from_slice(&(len as u32 | 0x8000_0000).to_be_bytes());
+                }
             }
             
-            // Value length
-            if value_len < 128 {
-                buffer.push(value_len as u8);
-            } else {
-                buffer.push(((value_len >> 24) | 0x80) as u8);
-                buffer.push((value_len >> 16) as u8);
-                buffer.push((value_len >> 8) as u8);
-                buffer.push((value_len & 0xFF) as u8);
-            }
-            
-            // Name and value
             buffer.extend_from_slice(name.as_bytes());
             buffer.extend_from_slice(value.as_bytes());
         }
@@ -136,59 +178,95 @@ impl FastCGIHandler {
         buffer
     }
     
-    /// Create empty params record to signal end of params
-    fn create_empty_params(&self, request_id: u16) -> Vec<u8> {
-        let mut buffer = vec![0u8; 8];
-        
-        // Header
-        buffer[0] = 1; // version
-        buffer[1] = RecordType::Params as u8;
-        buffer[2] = (request_id >> 8) as u8; // request ID high byte
-        buffer[3] = (request_id & 0xFF) as u8; // request ID low byte
-        buffer[4] = 0; // content length high byte
-        buffer[5] = 0; // content length low byte
-        buffer[6] = 0; // padding length
-        buffer[7] = 0; // reserved
+    /// Send the request body as STDIN records, streaming when its length is known
+    async fn send_stdin(&self, stream: &mut TcpStream, request_id: u16, mut body: Body) -> Result<(), Box<dyn Error + Send + Sync>> {
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk?;
+            stream.write_all(&self.create_stream(RecordType::Stdin, request_id, &chunk, false)).await?;
+        }
         
-        buffer
+        stream.write_all(&self.create_record(RecordType::Stdin, request_id, &[])).await?;
+        Ok(())
     }
     
-    /// Create stdin record
-    fn create_stdin(&self, request_id: u16, data: &[u8]) -> Vec<u8> {
-        let data_len = data.len();
-        let mut buffer = Vec::with_capacity(8 + data_len);
+    /// Read records until END_REQUEST, collecting STDOUT and logging STDERR
+    async fn read_output(&self, stream: &mut TcpStream, request_id: u16) -> Result<(Vec<u8>, MemoryReservation), HttpError> {
+        let protocol_error = |e: std::io::Error| HttpError::BadGateway(format!("FastCGI read failed: {}", e));
+        let mut stdout = Vec::new();
+        let mut reservation = self.memory_budget.reservation();
+        let mut header = [0u8; HEADER_LEN];
         
-        // Header
-        buffer.push(1); // version
-        buffer.push(RecordType::Stdin as u8);
-        buffer.push((request_id >> 8) as u8); // request ID high byte
-        buffer.push((request_id & 0xFF) as u8); // request ID low byte
-        buffer.push((data_len >> 8) as u8); // content length high byte
-        buffer.push((data_len & 0xFF) as u8); // content length low byte
-        buffer.push(0); // padding length
-        buffer.push(0); // reserved
-        
-        // Body
-        buffer.extend_from_slice(data);
+        loop {
+            stream.read_exact(&mut header).await.map_err(protocol_error)?;
+            
+            let record_type = header[1];
+            let record_id = u16::from_be_bytes([header[2], header[3]]);
+            let content_len = u16::from_be_bytes([header[4], header[5]]) as usize;
+            let padding_len = header[6] as usize;
+            
+            let mut content = vec![0u8; content_len + padding_len];
+            stream.read_exact(&mut content).await.map_err(protocol_error)?;
+            content.truncate(content_len);
+            
+            if record_id != request_id {
+                debug!("Ignoring FastCGI record for request {}", record_id);
+                continue;
+            }
+            
+            match record_type {
+                t if t == RecordType::Stdout as u8 => {
+                    if !reservation.try_grow(content.len()) {
+                        warn!("Memory budget exhausted buffering FastCGI output from {}", self.server_addr);
+                        return Err(HttpError::ServiceUnavailable);
+                    }
+                    stdout.extend_from_slice(&content);
+                }
+                t if t == RecordType::Stderr as u8 => {
+                    if !content.is_empty() {
+                        warn!("FastCGI {}: {}", self.server_addr, String::from_utf8_lossy(&content).trim_end());
+                    }
+                }
+                t if t == RecordType::EndRequest as u8 => {
+                    // Byte 4 of the body is the protocol status; 0 means the request completed
+                    let protocol_status = content.get(4).copied().unwrap_or(0);
+                    if protocol_status != 0 {
+                        return Err(HttpError::BadGateway(format!("FastCGI request rejected with status {}", protocol_status)));
+                    }
+                    return Ok((stdout, reservation));
+                }
+                other => debug!("Ignoring FastCGI record type {}", other),
+            }
+        }
+    }
+}
+
+/// FastCGI handlers for all configured backends
+#[derive(Clone, Default)]
+pub struct FastCgiBackends {
+    /// Handlers by route pattern
+    handlers: HashMap<String, FastCGIHandler>,
+}
+
+impl FastCgiBackends {
+    /// Build a handler for every configured backend, sharing one memory budget
+    pub fn from_config(config: &Config, memory_budget: &MemoryBudget) -> Self {
+        let handlers = config
+            .fastcgi
+            .iter()
+            .flatten()
+            .map(|backend| {
+                let handler = FastCGIHandler::from_config(backend, &config.static_files.root_dir)
+                    .with_memory_budget(memory_budget.clone());
+                (handler.script_pattern().to_string(), handler)
+            })
+            .collect();
         
-        buffer
+        FastCgiBackends { handlers }
     }
     
-    /// Create empty stdin record to signal end of stdin
-    fn create_empty_stdin(&self, request_id: u16) -> Vec<u8> {
-        let mut buffer = vec![0u8; 8];
-        
-        // Header
-        buffer[0] = 1; // version
-        buffer[1] = RecordType::Stdin as u8;
-        buffer[2] = (request_id >> 8) as u8; // request ID high byte
-        buffer[3] = (request_id & 0xFF) as u8; // request ID low byte
-        buffer[4] = 0; // content length high byte
-        buffer[5] = 0; // content length low byte
-        buffer[6] = 0; // padding length
-        buffer[7] = 0; // reserved
-        
-        buffer
+    /// Get the handler for a route pattern
+    pub fn get(&self, pattern: &str) -> Option<&FastCGIHandler> {
+        self.handlers.get(pattern)
     }
 }
 
@@ -197,21 +275,40 @@ impl Handler for FastCGIHandler {
     async fn handle(&self, req: Request<Body>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
         debug!("Handling FastCGI request for: {}", req.uri().path());
         
-        // For the initial implementation, let's return a placeholder
-        // In a real implementation, we would connect to the FastCGI server
-        // and process the request according to the FastCGI protocol
-        
-        Ok(ResponseBuilder::with_status(StatusCode::NOT_IMPLEMENTED)
-            .content_type("text/html")
-            .body_string("<h1>501 Not Implemented</h1><p>FastCGI support is not fully implemented yet.</p>".to_string())
-            .build())
-        
-        // The full implementation would:
-        // 1. Connect to the FastCGI server via TCP
-        // 2. Send begin request record
-        // 3. Send params records with environment variables
-        // 4. Send stdin records with request body
-        // 5. Process stdout/stderr records from the FastCGI server
-        // 6. Create HTTP response based on the FastCGI response
+        // Backends need CONTENT_LENGTH up front, so only bodies of unknown length are buffered
+        let declared_length = req
+            .headers()
+            .get(hyper::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        
+        let (parts, body) = req.into_parts();
+        let (body, content_length) = match declared_length {
+            Some(length) => (body, Some(length)),
+            None if parts.method == hyper::Method::GET || parts.method == hyper::Method::HEAD => (body, None),
+            None => {
+                let (data, reservation) = buffer_body(body, &self.memory_budget).await?;
+                let length = data.len() as u64;
+                (reserved_body(data, reservation), Some(length))
+            }
+        };
+        let req = Request::from_parts(parts, Body::empty());
+        
+        let params = cgi_environment(&req, &self.document_root, content_length)?;
+        
+        let mut stream = TcpStream::connect(self.server_addr).await.map_err(|e| {
+            error!("Failed to connect to FastCGI server {}: {}", self.server_addr, e);
+            HttpError::BadGateway(e.to_string())
+        })?;
+        
+        let mut request = self.create_begin_request(REQUEST_ID);
+        request.extend(self.create_stream(RecordType::Params, REQUEST_ID, &Self::encode_params(&params), true));
+        stream.write_all(&request).await?;
+        
+        self.send_stdin(&mut stream, REQUEST_ID, body).await?;
+        
+        let (output, reservation) = self.read_output(&mut stream, REQUEST_ID).await?;
+        
+        Ok(parse_cgi_response(&output, reservation)?)
     }
 }
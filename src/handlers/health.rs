@@ -0,0 +1,106 @@
+use async_trait::async_trait;
+use hyper::{Body, Request, Response, StatusCode};
+use serde_json::json;
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::core::config::Config;
+use crate::handlers::common::Handler;
+use crate::handlers::proxy::ProxyPools;
+use crate::network::http::response::ResponseBuilder;
+
+/// Default path of the liveness endpoint
+const DEFAULT_LIVENESS_PATH: &str = "/healthz";
+
+/// Default path of the readiness endpoint
+const DEFAULT_READINESS_PATH: &str = "/readyz";
+
+/// Server-wide readiness flags shared by every connection
+#[derive(Clone, Default)]
+pub struct Readiness {
+    /// Set once a shutdown signal arrived and connections are being drained
+    draining: Arc<AtomicBool>,
+}
+
+impl Readiness {
+    /// Report the server as draining until it exits
+    pub fn start_draining(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+    }
+    
+    /// Check whether the server is draining
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+}
+
+/// Handler for the liveness and readiness probes.
+///
+/// Liveness succeeds whenever the event loop answers. Readiness fails while the
+/// server drains for shutdown or while every upstream of a proxy pool is failing.
+#[derive(Clone)]
+pub struct HealthHandler {
+    /// Path of the liveness endpoint
+    liveness_path: String,
+    /// Path of the readiness endpoint
+    readiness_path: String,
+    /// Server-wide readiness flags
+    readiness: Readiness,
+    /// Proxy pools whose upstream failures make the server unready
+    proxy_pools: Arc<ProxyPools>,
+}
+
+impl HealthHandler {
+    /// Create the health handler if enabled in the configuration
+    pub fn from_config(config: &Config, readiness: Readiness, proxy_pools: Arc<ProxyPools>) -> Option<Self> {
+        let health_config = config.health.as_ref().filter(|health| health.enabled)?;
+        
+        Some(HealthHandler {
+            liveness_path: health_config.liveness_path.clone().unwrap_or_else(|| DEFAULT_LIVENESS_PATH.to_string()),
+            readiness_path: health_config.readiness_path.clone().unwrap_or_else(|| DEFAULT_READINESS_PATH.to_string()),
+            readiness,
+            proxy_pools,
+        })
+    }
+    
+    /// Check if a request path belongs to the health handler
+    pub fn matches(&self, path: &str) -> bool {
+        path == self.liveness_path || path == self.readiness_path
+    }
+    
+    /// Reasons the server is not ready, empty when it is
+    fn unready_reasons(&self) -> Vec<String> {
+        let mut reasons = Vec::new();
+        if self.readiness.is_draining() {
+            reasons.push("draining".to_string());
+        }
+        for pool in self.proxy_pools.failing_pools() {
+            reasons.push(format!("upstream pool '{}' is failing", pool));
+        }
+        reasons
+    }
+}
+
+#[async_trait]
+impl Handler for HealthHandler {
+    async fn handle(&self, req: Request<Body>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let (status, document) = if req.uri().path() == self.liveness_path {
+            (StatusCode::OK, json!({ "status": "alive" }))
+        } else {
+            let reasons = self.unready_reasons();
+            if reasons.is_empty() {
+                (StatusCode::OK, json!({ "status": "ready" }))
+            } else {
+                (StatusCode::SERVICE_UNAVAILABLE, json!({ "status": "unready", "reasons": reasons }))
+            }
+        };
+        
+        let body = serde_json::to_string(&document)?;
+        Ok(ResponseBuilder::with_status(status)
+            .content_type("application/json")
+            .cache_control("no-store")
+            .body_string(body)
+            .build())
+    }
+}
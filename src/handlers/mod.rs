@@ -1,3 +1,13 @@
 pub mod static_files;
 pub mod fastcgi;
+pub mod scgi;
+pub mod uwsgi;
 pub mod common;
+pub mod admin;
+pub mod health;
+pub mod proxy;
+pub mod balancer;
+pub mod cgi;
+pub mod service;
+pub mod upload;
+pub mod webdav;
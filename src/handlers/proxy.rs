@@ -0,0 +1,446 @@
+use async_trait::async_trait;
+use hyper::client::HttpConnector;
+use hyper::header::{self, HeaderValue};
+use hyper::http::uri::Scheme;
+use hyper::{Body, Client, Request, Response, StatusCode, Uri, Version};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::core::config::{Config, HealthCheckConfig, UpstreamPoolConfig};
+use crate::core::error::HttpError;
+use crate::handlers::balancer::{Balancer, DEFAULT_FAIL_TIMEOUT, DEFAULT_MAX_FAILS};
+use crate::handlers::common::Handler;
+use crate::network::http::forwarded::PeerAddr;
+use crate::network::http::headers::{accepts_trailers, strip_hop_by_hop_headers};
+use crate::network::http::upgrade::{is_websocket_upgrade, restore_upgrade_headers, tunnel};
+use crate::utils::compression::compress_request_body;
+
+/// HTTP client shared by all upstream pools
+pub(crate) type UpstreamClient = Client<HttpsConnector<HttpConnector>>;
+
+/// Error types for building upstream pools
+#[derive(Debug)]
+pub enum ProxyError {
+    /// A pool lists no upstream servers
+    NoServers(String),
+    /// An upstream URL is not an absolute http(s) URL
+    InvalidUpstream(String),
+    /// Two pools share a name
+    DuplicatePool(String),
+    /// A pool lists a different number of weights than servers
+    WeightMismatch(String),
+    /// A health check path is not an absolute path
+    InvalidHealthCheckPath(String),
+}
+
+impl fmt::Display for ProxyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProxyError::NoServers(name) => write!(f, "Proxy pool '{}' has no servers", name),
+            ProxyError::InvalidUpstream(url) => write!(f, "Invalid upstream URL: {}", url),
+            ProxyError::DuplicatePool(name) => write!(f, "Duplicate proxy pool: {}", name),
+            ProxyError::WeightMismatch(name) => write!(f, "Proxy pool '{}' needs one weight per server", name),
+            ProxyError::InvalidHealthCheckPath(path) => write!(f, "Invalid health check path: {}", path),
+        }
+    }
+}
+
+impl Error for ProxyError {}
+
+/// Default seconds between health check probes
+const DEFAULT_CHECK_INTERVAL: u64 = 10;
+
+/// Default seconds to wait for a health check response
+const DEFAULT_CHECK_TIMEOUT: u64 = 2;
+
+/// Default passing probes that mark a server healthy again
+const DEFAULT_HEALTHY_THRESHOLD: u32 = 2;
+
+/// Default failing probes that mark a server unhealthy
+const DEFAULT_UNHEALTHY_THRESHOLD: u32 = 3;
+
+/// Active health check settings of a pool
+#[derive(Clone)]
+struct HealthCheck {
+    /// Path and query requested from each server
+    path: Uri,
+    /// Time between probes
+    interval: Duration,
+    /// Time to wait for a probe response
+    timeout: Duration,
+    /// Consecutive passing probes that mark a server healthy
+    healthy_threshold: u32,
+    /// Consecutive failing probes that mark a server unhealthy
+    unhealthy_threshold: u32,
+}
+
+impl HealthCheck {
+    /// Resolve the health check settings of a pool
+    fn from_config(config: &HealthCheckConfig) -> Result<Self, ProxyError> {
+        let path: Uri = config.path
+            .parse()
+            .ok()
+            .filter(|_| config.path.starts_with('/'))
+            .ok_or_else(|| ProxyError::InvalidHealthCheckPath(config.path.clone()))?;
+        
+        Ok(HealthCheck {
+            path,
+            interval: Duration::from_secs(config.interval.unwrap_or(DEFAULT_CHECK_INTERVAL).max(1)),
+            timeout: Duration::from_secs(config.timeout.unwrap_or(DEFAULT_CHECK_TIMEOUT)),
+            healthy_threshold: config.healthy_threshold.unwrap_or(DEFAULT_HEALTHY_THRESHOLD).max(1),
+            unhealthy_threshold: config.unhealthy_threshold.unwrap_or(DEFAULT_UNHEALTHY_THRESHOLD).max(1),
+        })
+    }
+}
+
+/// Handler forwarding requests to a pool of upstream servers
+#[derive(Clone)]
+pub struct ProxyHandler {
+    /// Name of the pool
+    name: String,
+    /// Upstreams and the strategy picking among them
+    balancer: Arc<Balancer>,
+    /// Active health check settings, if configured
+    health_check: Option<HealthCheck>,
+    /// HTTP client used to reach the upstreams
+    client: UpstreamClient,
+    /// Forward the client's Host header unchanged
+    preserve_host: bool,
+    /// Gzip request bodies before forwarding
+    compress_requests: bool,
+    /// Speak only HTTP/2 to the upstreams
+    http2: bool,
+}
+
+impl ProxyHandler {
+    /// Create a proxy handler for an upstream pool
+    pub fn new(pool: &UpstreamPoolConfig, client: UpstreamClient) -> Result<Self, ProxyError> {
+        if pool.servers.is_empty() {
+            return Err(ProxyError::NoServers(pool.name.clone()));
+        }
+        
+        let upstreams = pool
+            .servers
+            .iter()
+            .map(|server| parse_upstream(server))
+            .collect::<Result<Vec<_>, _>>()?;
+        let weights = match &pool.weights {
+            Some(weights) if weights.len() != upstreams.len() => return Err(ProxyError::WeightMismatch(pool.name.clone())),
+            Some(weights) => weights.clone(),
+            None => vec![1; upstreams.len()],
+        };
+        
+        let balancer = Balancer::new(
+            &pool.name,
+            pool.strategy.unwrap_or_default(),
+            upstreams.into_iter().zip(weights).collect(),
+            pool.max_fails.unwrap_or(DEFAULT_MAX_FAILS).max(1),
+            Duration::from_secs(pool.fail_timeout.unwrap_or(DEFAULT_FAIL_TIMEOUT)),
+        );
+        
+        Ok(ProxyHandler {
+            name: pool.name.clone(),
+            balancer: Arc::new(balancer),
+            health_check: pool.health_check.as_ref().map(HealthCheck::from_config).transpose()?,
+            client,
+            preserve_host: pool.preserve_host.unwrap_or(false),
+            compress_requests: pool.compress_requests.unwrap_or(false),
+            http2: pool.http2.unwrap_or(false),
+        })
+    }
+    
+    /// Check whether no upstream of the pool may get requests
+    pub fn is_failing(&self) -> bool {
+        self.balancer.none_available()
+    }
+    
+    /// Start probing the upstreams in the background, if health checks are configured
+    pub fn spawn_health_check(&self) -> Option<JoinHandle<()>> {
+        let check = self.health_check.clone()?;
+        let handler = self.clone();
+        
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(check.interval);
+            loop {
+                interval.tick().await;
+                let probes = handler.balancer.upstream_uris().map(|upstream| handler.probe(upstream, &check));
+                let outcomes = futures::future::join_all(probes).await;
+                for (index, passed) in outcomes.into_iter().enumerate() {
+                    handler.balancer.record_probe(index, passed, check.healthy_threshold, check.unhealthy_threshold);
+                }
+            }
+        }))
+    }
+    
+    /// Probe an upstream, returning whether it answered with a 2xx or 3xx status in time
+    async fn probe(&self, upstream: &Uri, check: &HealthCheck) -> bool {
+        let Ok(uri) = upstream_uri(upstream, &check.path) else {
+            return false;
+        };
+        let Ok(request) = Request::get(uri).body(Body::empty()) else {
+            return false;
+        };
+        
+        match tokio::time::timeout(check.timeout, self.client.request(request)).await {
+            Ok(Ok(response)) => {
+                let status = response.status();
+                if !status.is_success() && !status.is_redirection() {
+                    debug!("Health check of {} in pool '{}' got {}", upstream, self.name, status);
+                    return false;
+                }
+                true
+            }
+            Ok(Err(e)) => {
+                debug!("Health check of {} in pool '{}' failed: {}", upstream, self.name, e);
+                false
+            }
+            Err(_) => {
+                debug!("Health check of {} in pool '{}' timed out", upstream, self.name);
+                false
+            }
+        }
+    }
+    
+    /// Rewrite the request headers for forwarding to `upstream`
+    fn forward_headers(&self, request: &mut Request<Body>, upstream: &Uri) {
+        // The chain grows by the peer we heard from, which is a proxy when the client came from its headers
+        let client_addr = request.extensions()
+            .get::<PeerAddr>()
+            .map(|peer| peer.0)
+            .or_else(|| request.extensions().get::<SocketAddr>().copied());
+        let proto = match request.extensions().get::<Scheme>() {
+            Some(scheme) if *scheme == Scheme::HTTPS => "https",
+            _ => "http",
+        };
+        let headers = request.headers_mut();
+        let original_host = headers.get(header::HOST).cloned();
+        let accepts_trailers = accepts_trailers(headers);
+        
+        strip_hop_by_hop_headers(headers);
+        
+        // TE is hop-by-hop, but an upstream only sends trailers to clients that accept them
+        if accepts_trailers {
+            headers.insert(header::TE, HeaderValue::from_static("trailers"));
+        }
+        
+        // Append the client to any chain reported by earlier proxies
+        if let Some(addr) = client_addr {
+            let forwarded_for = match headers.get("x-forwarded-for").and_then(|v| v.to_str().ok()) {
+                Some(chain) => format!("{}, {}", chain, addr.ip()),
+                None => addr.ip().to_string(),
+            };
+            if let Ok(value) = HeaderValue::from_str(&forwarded_for) {
+                headers.insert("x-forwarded-for", value);
+            }
+        }
+        
+        headers.insert("x-forwarded-proto", HeaderValue::from_static(proto));
+        
+        match original_host {
+            Some(host) => {
+                headers.insert("x-forwarded-host", host);
+            }
+            None => {
+                headers.remove("x-forwarded-host");
+            }
+        }
+        
+        if !self.preserve_host || !headers.contains_key(header::HOST) {
+            if let Some(authority) = upstream.authority() {
+                if let Ok(value) = HeaderValue::from_str(authority.as_str()) {
+                    headers.insert(header::HOST, value);
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Handler for ProxyHandler {
+    async fn handle(&self, mut request: Request<Body>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let client_ip = request.extensions().get::<SocketAddr>().map(|addr| addr.ip());
+        let selection = self.balancer.select(client_ip);
+        let upstream = selection.uri().clone();
+        let target = upstream_uri(&upstream, request.uri())?;
+        
+        debug!("Proxying {} to pool '{}': {}", request.uri(), self.name, target);
+        
+        // Keep the client's side of a WebSocket upgrade to join with the upstream's
+        let client_upgrade = if is_websocket_upgrade(request.headers()) {
+            Some(hyper::upgrade::on(&mut request))
+        } else {
+            None
+        };
+        
+        self.forward_headers(&mut request, &upstream);
+        *request.uri_mut() = target;
+        *request.version_mut() = if self.http2 { Version::HTTP_2 } else { Version::HTTP_11 };
+        if client_upgrade.is_some() {
+            restore_upgrade_headers(request.headers_mut());
+        }
+        
+        if self.compress_requests {
+            request = compress_request_body(request);
+        }
+        
+        // Bodies are streamed in both directions, trailers included
+        let mut response = match self.client.request(request).await {
+            Ok(response) => response,
+            // The client's body failed, such as by exceeding the size limit; the upstream is not at fault
+            Err(e) if e.is_user() => {
+                debug!("Request body to pool '{}' failed: {}", self.name, e);
+                return Err(Box::new(e));
+            }
+            Err(e) => {
+                warn!("Upstream {} in pool '{}' failed: {}", upstream, self.name, e);
+                selection.failed();
+                return Err(Box::new(HttpError::BadGateway(e.to_string())));
+            }
+        };
+        selection.succeeded();
+        
+        strip_hop_by_hop_headers(response.headers_mut());
+        
+        if let Some(client_upgrade) = client_upgrade {
+            if response.status() == StatusCode::SWITCHING_PROTOCOLS {
+                debug!("Tunnelling WebSocket through pool '{}'", self.name);
+                restore_upgrade_headers(response.headers_mut());
+                tunnel(client_upgrade, hyper::upgrade::on(&mut response));
+            }
+        }
+        
+        Ok(response)
+    }
+}
+
+/// Proxy handlers for all configured upstream pools
+#[derive(Clone, Default)]
+pub struct ProxyPools {
+    /// Handlers by pool name
+    handlers: HashMap<String, ProxyHandler>,
+}
+
+impl ProxyPools {
+    /// Build a proxy handler for every configured pool, sharing one client
+    pub fn from_config(config: &Config) -> Result<Self, ProxyError> {
+        let pools = match &config.proxy {
+            Some(proxy) if !proxy.pools.is_empty() => &proxy.pools,
+            _ => return Ok(Self::default()),
+        };
+        
+        let client = build_client();
+        let http2_client = build_http2_client();
+        let mut handlers = HashMap::new();
+        
+        for pool in pools {
+            let client = if pool.http2.unwrap_or(false) { &http2_client } else { &client };
+            let handler = ProxyHandler::new(pool, client.clone())?;
+            if handlers.insert(pool.name.clone(), handler).is_some() {
+                return Err(ProxyError::DuplicatePool(pool.name.clone()));
+            }
+        }
+        
+        Ok(ProxyPools { handlers })
+    }
+    
+    /// Get the handler for a pool
+    pub fn get(&self, name: &str) -> Option<&ProxyHandler> {
+        self.handlers.get(name)
+    }
+    
+    /// Names of the pools with no upstream that may get requests
+    pub fn failing_pools(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.handlers
+            .iter()
+            .filter(|(_, handler)| handler.is_failing())
+            .map(|(name, _)| name.as_str())
+            .collect();
+        names.sort_unstable();
+        names
+    }
+    
+    /// Start the background health checks of all pools that configure them
+    pub fn spawn_health_checks(&self) -> Vec<JoinHandle<()>> {
+        self.handlers.values().filter_map(ProxyHandler::spawn_health_check).collect()
+    }
+    
+    /// Describe the health and load of every upstream, by pool name
+    pub fn status(&self) -> serde_json::Value {
+        let pools: serde_json::Map<String, serde_json::Value> = self.handlers
+            .iter()
+            .map(|(name, handler)| (name.clone(), serde_json::Value::from(handler.balancer.status())))
+            .collect();
+        serde_json::Value::Object(pools)
+    }
+}
+
+/// Parse an upstream base URL, requiring an http(s) scheme and an authority
+fn parse_upstream(server: &str) -> Result<Uri, ProxyError> {
+    let uri: Uri = server.parse().map_err(|_| ProxyError::InvalidUpstream(server.to_string()))?;
+    
+    match (uri.scheme_str(), uri.authority()) {
+        (Some("http") | Some("https"), Some(_)) => Ok(uri),
+        _ => Err(ProxyError::InvalidUpstream(server.to_string())),
+    }
+}
+
+/// Join an upstream base URI with the request's path and query
+fn upstream_uri(upstream: &Uri, request_uri: &Uri) -> Result<Uri, HttpError> {
+    let base_path = upstream.path().trim_end_matches('/');
+    let path_and_query = request_uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+    
+    Uri::builder()
+        .scheme(upstream.scheme_str().unwrap_or("http"))
+        .authority(upstream.authority().map(|a| a.as_str()).unwrap_or_default())
+        .path_and_query(format!("{}{}", base_path, path_and_query))
+        .build()
+        .map_err(|e| HttpError::BadRequest(format!("Cannot forward request: {}", e)))
+}
+
+/// Build the upstream client, speaking whichever HTTP version the upstream negotiates
+pub(crate) fn build_client() -> UpstreamClient {
+    Client::builder().build(https_connector())
+}
+
+/// Build a client speaking only HTTP/2, with prior knowledge to cleartext upstreams
+fn build_http2_client() -> UpstreamClient {
+    Client::builder().http2_only(true).build(https_connector())
+}
+
+/// Connector for http and https upstreams trusting the platform's root certificates
+fn https_connector() -> HttpsConnector<HttpConnector> {
+    let mut roots = rustls::RootCertStore::empty();
+    match rustls_native_certs::load_native_certs() {
+        Ok(certs) => {
+            for cert in certs {
+                if let Err(e) = roots.add(&rustls::Certificate(cert.0)) {
+                    debug!("Skipping invalid root certificate: {}", e);
+                }
+            }
+        }
+        Err(e) => warn!("Failed to load platform root certificates: {}", e),
+    }
+    
+    if roots.is_empty() {
+        warn!("No root certificates found; https upstreams will fail verification");
+    }
+    
+    let tls = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    
+    HttpsConnectorBuilder::new()
+        .with_tls_config(tls)
+        .https_or_http()
+        .enable_http1()
+        .enable_http2()
+        .build()
+}
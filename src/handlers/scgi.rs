@@ -0,0 +1,143 @@
+use async_trait::async_trait;
+use hyper::body::HttpBody;
+use hyper::{Body, Request, Response};
+use std::collections::HashMap;
+use std::error::Error;
+use std::net::SocketAddr;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tracing::{debug, error};
+
+use crate::core::config::{Config, ScgiConfig};
+use crate::core::error::HttpError;
+use crate::handlers::cgi::{application_environment, output_error, parse_cgi_response, sized_body};
+use crate::handlers::common::Handler;
+use crate::utils::memory::{read_to_end_reserved, MemoryBudget};
+
+/// SCGI protocol handler.
+///
+/// Each request opens a connection carrying the environment as a netstring of
+/// NUL-separated pairs followed by the body; the backend answers with CGI
+/// output and closes the connection.
+#[derive(Clone)]
+pub struct ScgiHandler {
+    /// SCGI server address
+    server_addr: SocketAddr,
+    /// Route pattern served by this handler
+    pattern: String,
+    /// Document root used for `SCRIPT_FILENAME` and `DOCUMENT_ROOT`
+    document_root: String,
+    /// Budget buffered request bodies and responses are accounted against
+    memory_budget: MemoryBudget,
+}
+
+impl ScgiHandler {
+    /// Create a new SCGI handler
+    pub fn new(server_addr: SocketAddr, pattern: String, document_root: String) -> Self {
+        ScgiHandler {
+            server_addr,
+            pattern,
+            document_root,
+            memory_budget: MemoryBudget::unlimited(),
+        }
+    }
+    
+    /// Account buffered request bodies and responses against a shared memory budget
+    pub fn with_memory_budget(mut self, memory_budget: MemoryBudget) -> Self {
+        self.memory_budget = memory_budget;
+        self
+    }
+    
+    /// Create a handler from a backend configuration
+    pub fn from_config(backend: &ScgiConfig, document_root: &str) -> Self {
+        Self::new(backend.address, backend.path.clone(), document_root.to_string())
+    }
+    
+    /// Get the route pattern this handler serves
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+    
+    /// Encode the request headers as a netstring.
+    ///
+    /// The protocol requires `CONTENT_LENGTH` first, followed by `SCGI` set to 1.
+    fn encode_headers(params: &[(String, String)], content_length: u64) -> Vec<u8> {
+        let mut headers = Vec::new();
+        let pairs = [
+            ("CONTENT_LENGTH", content_length.to_string()),
+            ("SCGI", "1".to_string()),
+        ];
+        let rest = params
+            .iter()
+            .filter(|(name, _)| name != "CONTENT_LENGTH")
+            .map(|(name, value)| (name.as_str(), value.clone()));
+        
+        for (name, value) in pairs.into_iter().chain(rest) {
+            headers.extend_from_slice(name.as_bytes());
+            headers.push(0);
+            headers.extend_from_slice(value.as_bytes());
+            headers.push(0);
+        }
+        
+        let mut netstring = format!("{}:", headers.len()).into_bytes();
+        netstring.extend(headers);
+        netstring.push(b',');
+        netstring
+    }
+}
+
+/// SCGI handlers for all configured backends
+#[derive(Clone, Default)]
+pub struct ScgiBackends {
+    /// Handlers by route pattern
+    handlers: HashMap<String, ScgiHandler>,
+}
+
+impl ScgiBackends {
+    /// Build a handler for every configured backend, sharing one memory budget
+    pub fn from_config(config: &Config, memory_budget: &MemoryBudget) -> Self {
+        let handlers = config
+            .scgi
+            .iter()
+            .flatten()
+            .map(|backend| {
+                let handler = ScgiHandler::from_config(backend, &config.static_files.root_dir)
+                    .with_memory_budget(memory_budget.clone());
+                (handler.pattern().to_string(), handler)
+            })
+            .collect();
+        
+        ScgiBackends { handlers }
+    }
+    
+    /// Get the handler for a route pattern
+    pub fn get(&self, pattern: &str) -> Option<&ScgiHandler> {
+        self.handlers.get(pattern)
+    }
+}
+
+#[async_trait]
+impl Handler for ScgiHandler {
+    async fn handle(&self, req: Request<Body>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        debug!("Handling SCGI request for: {}", req.uri().path());
+        
+        let (req, mut body, content_length) = sized_body(req, &self.memory_budget).await?;
+        let params = application_environment(&req, &self.document_root, content_length)?;
+        
+        let mut stream = TcpStream::connect(self.server_addr).await.map_err(|e| {
+            error!("Failed to connect to SCGI server {}: {}", self.server_addr, e);
+            HttpError::BadGateway(e.to_string())
+        })?;
+        
+        stream.write_all(&Self::encode_headers(&params, content_length)).await?;
+        while let Some(chunk) = body.data().await {
+            stream.write_all(&chunk?).await?;
+        }
+        
+        let (output, reservation) = read_to_end_reserved(&mut stream, &self.memory_budget)
+            .await
+            .map_err(|e| output_error("SCGI", e))?;
+        
+        Ok(parse_cgi_response(&output, reservation)?)
+    }
+}
@@ -0,0 +1,99 @@
+use async_trait::async_trait;
+use futures::future::{poll_fn, BoxFuture};
+use futures::FutureExt;
+use hyper::{Body, Request, Response};
+use std::collections::HashMap;
+use std::error::Error;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tower::Service;
+
+use crate::handlers::common::Handler;
+use crate::routing::router::{Route, Router, RouterError};
+
+/// Boxed request future of a mounted service
+type ServiceFuture = BoxFuture<'static, Result<Response<Body>, Box<dyn Error + Send + Sync>>>;
+
+/// Handler calling application code in the server process.
+///
+/// Lets embedders serve programmatic endpoints next to static files, from an
+/// async function or a `tower::Service`. Requests reach it through the same
+/// pipeline as other handlers, so access control, plugins and timeouts apply.
+#[derive(Clone)]
+pub struct ServiceHandler {
+    /// Function starting a request
+    call: Arc<dyn Fn(Request<Body>) -> ServiceFuture + Send + Sync>,
+}
+
+impl ServiceHandler {
+    /// Serve requests with an async function
+    pub fn from_fn<F, Fut, E>(f: F) -> Self
+    where
+        F: Fn(Request<Body>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response<Body>, E>> + Send + 'static,
+        E: Into<Box<dyn Error + Send + Sync>>,
+    {
+        ServiceHandler {
+            call: Arc::new(move |req| f(req).map(|result| result.map_err(Into::into)).boxed()),
+        }
+    }
+    
+    /// Serve requests with a `tower::Service`, cloned for each request
+    pub fn from_service<S>(service: S) -> Self
+    where
+        S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + 'static,
+        S::Future: Send + 'static,
+        S::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        let service = Mutex::new(service);
+        ServiceHandler {
+            call: Arc::new(move |req| {
+                let mut service = service.lock().unwrap_or_else(|e| e.into_inner()).clone();
+                async move {
+                    poll_fn(|cx| service.poll_ready(cx)).await.map_err(Into::into)?;
+                    service.call(req).await.map_err(Into::into)
+                }
+                .boxed()
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl Handler for ServiceHandler {
+    async fn handle(&self, req: Request<Body>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        (self.call)(req).await
+    }
+}
+
+/// Services mounted by the embedding application
+#[derive(Clone, Default)]
+pub struct ServiceRoutes {
+    /// Routes of the services, in mount order
+    routes: Vec<Route>,
+    /// Handlers by route pattern
+    handlers: HashMap<String, ServiceHandler>,
+}
+
+impl ServiceRoutes {
+    /// Mount a service at a route pattern, replacing any service mounted there
+    pub fn mount(&mut self, pattern: &str, handler: ServiceHandler) -> Result<(), RouterError> {
+        let route = Route::new(pattern, "service")?.with_params(pattern);
+        if self.handlers.insert(pattern.to_string(), handler).is_none() {
+            self.routes.push(route);
+        }
+        Ok(())
+    }
+    
+    /// Add the service routes to a router
+    pub fn add_routes(&self, router: &mut Router) {
+        for route in &self.routes {
+            router.add_route(route.clone());
+        }
+    }
+    
+    /// Get the handler for a route pattern
+    pub fn get(&self, pattern: &str) -> Option<&ServiceHandler> {
+        self.handlers.get(pattern)
+    }
+}
@@ -1,15 +1,52 @@
 use async_trait::async_trait;
-use hyper::{Body, Request, Response, StatusCode};
+use hyper::body::Bytes;
+use hyper::header::HeaderValue;
+use hyper::http::uri::Scheme;
+use hyper::{Body, Method, Request, Response, StatusCode, Version};
 use std::error::Error;
+use std::io::SeekFrom;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
 use tokio::fs::{self, File};
-use tokio::io::AsyncReadExt;
-use tracing::{debug, error, info};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+use tracing::{debug, error, warn};
 use mime_guess::from_path;
+use percent_encoding::percent_decode_str;
+use regex::Regex;
 
+use crate::core::cache::{map_file, CachedFile, FileCache};
+use crate::core::error::{escape_html, HttpError};
 use crate::handlers::common::Handler;
+use crate::handlers::webdav::WebDav;
+use crate::network::http::conditional::{if_range_matches, Precondition};
+use crate::network::http::path::{decode_segments, encode_segment, PathError};
+use crate::network::http::range::{parse_range, ByteRange, MultipartRanges, RangeError, MAX_RANGES};
 use crate::network::http::response::ResponseBuilder;
-use crate::utils::compression::compress_if_needed;
+use crate::security::exclusion::ExclusionRules;
+use crate::utils::cache_policy::CachePolicy;
+use crate::utils::compression::{negotiate, should_compress, Compressor, Encoding, MIN_COMPRESS_SIZE};
+use crate::utils::etag::{mtime_etag, EtagGenerator, EtagStrategy};
+use crate::utils::language::{is_language_tag, negotiate_language};
+use crate::utils::markdown::{is_markdown, MarkdownRenderer};
+use crate::utils::memory::{reserved_body, MemoryBudget};
+use crate::utils::mime::{sniff_file, MimeSniffing};
+
+/// Chunk size used when streaming multipart range responses
+const MULTIPART_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Methods static files can be requested with
+const ALLOWED_METHODS: &str = "GET, HEAD, OPTIONS";
+
+/// Size of the slices mapped files are sent in
+const MAPPED_CHUNK_SIZE: usize = 256 * 1024;
+
+/// Read size for streaming files sent as they are over plaintext HTTP/1
+pub const DEFAULT_SEND_BUFFER_SIZE: usize = 256 * 1024;
+
+/// Content codings of precompressed sidecar files and their extensions, in order of preference
+const SIDECAR_ENCODINGS: [(Encoding, &str); 2] = [(Encoding::Brotli, "br"), (Encoding::Gzip, "gz")];
 
 /// Handler for serving static files
 #[derive(Clone)]
@@ -20,6 +57,60 @@ pub struct StaticFileHandler {
     enable_directory_listing: bool,
     /// Default file to serve for directory requests
     default_file: String,
+    /// Extensions to try for clean URLs (disabled when empty)
+    clean_url_extensions: Vec<String>,
+    /// Budget that buffered file contents are accounted against
+    memory_budget: MemoryBudget,
+    /// Maximum directory depth below the root at which listings are generated
+    max_listing_depth: Option<usize>,
+    /// Files larger than this are streamed instead of buffered
+    stream_threshold: Option<u64>,
+    /// MIME type prefixes that are always streamed
+    stream_types: Vec<String>,
+    /// Read size for streaming files sent as they are over plaintext HTTP/1 (0 to disable)
+    send_buffer_size: usize,
+    /// ETag generator
+    etag_generator: EtagGenerator,
+    /// Path patterns served as downloads
+    attachment_paths: Vec<Regex>,
+    /// MIME type prefixes served as downloads
+    attachment_types: Vec<String>,
+    /// When file content is sniffed to determine the MIME type
+    mime_sniffing: MimeSniffing,
+    /// Whether `.min` image variants are served to clients sending `Save-Data: on`
+    save_data_variants: bool,
+    /// In-memory cache of buffered files
+    cache: Option<Arc<FileCache>>,
+    /// Files kept memory-mapped, if mapping is enabled
+    mappings: Option<Arc<FileCache>>,
+    /// Smallest file that is mapped instead of read
+    mmap_threshold: u64,
+    /// Whether `.br`/`.gz` sidecar files are served to clients accepting them
+    precompressed_sidecars: bool,
+    /// Compression levels for on-the-fly and cached compression
+    compressor: Compressor,
+    /// Cache-Control policy for served files
+    cache_policy: Arc<CachePolicy>,
+    /// Root directory with symlinks resolved, if it existed at startup
+    canonical_root: Option<PathBuf>,
+    /// Whether symlinks may lead outside the root directory
+    follow_symlinks: bool,
+    /// WebDAV access to part of the tree, if enabled
+    webdav: Option<Arc<WebDav>>,
+    /// Renderer serving Markdown files as HTML, if enabled
+    markdown: Option<Arc<MarkdownRenderer>>,
+    /// Paths hidden from serving and listings
+    exclusions: Arc<ExclusionRules>,
+    /// Whether unresolved extensionless paths fall back to the root default file
+    spa_fallback: bool,
+    /// Whether directory requests without a trailing slash are redirected to the path with one
+    trailing_slash_redirect: bool,
+    /// Whether requests naming the default file are redirected to the directory URL
+    canonical_index: bool,
+    /// Whether `name.<language>` variants are negotiated by `Accept-Language`
+    negotiate_language: bool,
+    /// Language served when no variant is acceptable and the file itself is missing
+    default_language: Option<String>,
 }
 
 impl StaticFileHandler {
@@ -29,24 +120,485 @@ impl StaticFileHandler {
             root_dir: PathBuf::from(root_dir.as_ref()),
             enable_directory_listing,
             default_file,
+            clean_url_extensions: Vec::new(),
+            memory_budget: MemoryBudget::unlimited(),
+            max_listing_depth: None,
+            stream_threshold: None,
+            stream_types: Vec::new(),
+            send_buffer_size: 0,
+            etag_generator: EtagGenerator::new(EtagStrategy::Mtime, None),
+            attachment_paths: Vec::new(),
+            attachment_types: Vec::new(),
+            mime_sniffing: MimeSniffing::Off,
+            save_data_variants: false,
+            cache: None,
+            mappings: None,
+            mmap_threshold: u64::MAX,
+            precompressed_sidecars: false,
+            compressor: Compressor::default(),
+            cache_policy: Arc::new(CachePolicy::default()),
+            canonical_root: std::fs::canonicalize(root_dir.as_ref()).ok(),
+            follow_symlinks: false,
+            webdav: None,
+            markdown: None,
+            exclusions: Arc::default(),
+            spa_fallback: false,
+            trailing_slash_redirect: true,
+            canonical_index: false,
+            negotiate_language: false,
+            default_language: None,
+        }
+    }
+    
+    /// Serve files matching the given path patterns or MIME prefixes as downloads
+    pub fn with_attachments(mut self, paths: &[String], types: Vec<String>) -> Self {
+        self.attachment_paths = paths
+            .iter()
+            .filter_map(|pattern| {
+                let regex_pattern = format!("^{}$", regex::escape(pattern).replace("\\*", ".*"));
+                match Regex::new(&regex_pattern) {
+                    Ok(regex) => Some(regex),
+                    Err(e) => {
+                        error!("Invalid attachment path pattern {}: {}", pattern, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+        self.attachment_types = types;
+        self
+    }
+    
+    /// Add a Content-Disposition attachment header if the request path or MIME type matches
+    fn apply_attachment(&self, builder: ResponseBuilder, req_path: &str, mime: &str, file_path: &Path) -> ResponseBuilder {
+        let matches = self.attachment_paths.iter().any(|regex| regex.is_match(req_path))
+            || self.attachment_types.iter().any(|prefix| mime.starts_with(prefix.as_str()));
+        
+        match file_path.file_name() {
+            Some(name) if matches => builder.attachment(&name.to_string_lossy()),
+            _ => builder,
+        }
+    }
+    
+    /// Set when file content is sniffed to determine the MIME type
+    pub fn with_mime_sniffing(mut self, mime_sniffing: MimeSniffing) -> Self {
+        self.mime_sniffing = mime_sniffing;
+        self
+    }
+    
+    /// Set Cache-Control on file responses according to a policy
+    pub fn with_cache_policy(mut self, cache_policy: Arc<CachePolicy>) -> Self {
+        self.cache_policy = cache_policy;
+        self
+    }
+    
+    /// Serve files from another document root, as for a virtual host
+    pub fn with_document_root<P: AsRef<Path>>(mut self, root_dir: P, enable_directory_listing: bool, default_file: String) -> Self {
+        self.root_dir = PathBuf::from(root_dir.as_ref());
+        self.canonical_root = std::fs::canonicalize(root_dir.as_ref()).ok();
+        self.enable_directory_listing = enable_directory_listing;
+        self.default_file = default_file;
+        self
+    }
+    
+    /// Serve files whose symlinks lead outside the root directory
+    pub fn with_follow_symlinks(mut self, enabled: bool) -> Self {
+        self.follow_symlinks = enabled;
+        self
+    }
+    
+    /// Serve the paths covered by `webdav` over WebDAV
+    pub fn with_webdav(mut self, webdav: Option<Arc<WebDav>>) -> Self {
+        self.webdav = webdav;
+        self
+    }
+    
+    /// Render Markdown files as HTML pages with `markdown`, if set
+    pub fn with_markdown(mut self, markdown: Option<Arc<MarkdownRenderer>>) -> Self {
+        self.markdown = markdown;
+        self
+    }
+    
+    /// Serve the root default file for unresolved paths without a file extension,
+    /// so a single page app can route them on the client
+    pub fn with_spa_fallback(mut self, enabled: bool) -> Self {
+        self.spa_fallback = enabled;
+        self
+    }
+    
+    /// Redirect directory requests without a trailing slash to the path with one,
+    /// and with `canonical_index`, requests naming the default file to the directory URL
+    pub fn with_canonical_redirects(mut self, trailing_slash: bool, canonical_index: bool) -> Self {
+        self.trailing_slash_redirect = trailing_slash;
+        self.canonical_index = canonical_index;
+        self
+    }
+    
+    /// Serve `page.html.<language>` variants of `page.html` in the language the client prefers,
+    /// falling back to `default_language` when none is acceptable and `page.html` is missing
+    pub fn with_language_negotiation(mut self, enabled: bool, default_language: Option<String>) -> Self {
+        self.negotiate_language = enabled;
+        self.default_language = default_language;
+        self
+    }
+    
+    /// Find the language variants of a file (`page.html` -> `page.html.en`), sorted by language
+    async fn language_variants(&self, file_path: &Path) -> Vec<(String, PathBuf)> {
+        if !self.negotiate_language {
+            return Vec::new();
+        }
+        let (Some(parent), Some(name)) = (file_path.parent(), file_path.file_name()) else {
+            return Vec::new();
+        };
+        let prefix = format!("{}.", name.to_string_lossy());
+        let Ok(mut entries) = fs::read_dir(parent).await else {
+            return Vec::new();
+        };
+        
+        let mut variants = Vec::new();
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let Some(language) = entry.file_name().to_string_lossy().strip_prefix(&prefix).map(str::to_string) else {
+                continue;
+            };
+            let variant = entry.path();
+            if !is_language_tag(&language) || !fs::metadata(&variant).await.is_ok_and(|metadata| metadata.is_file()) {
+                continue;
+            }
+            if self.check_contained(&variant).is_ok() && self.is_visible(&variant, false) {
+                variants.push((language, variant));
+            }
+        }
+        variants.sort();
+        variants
+    }
+    
+    /// Tag the entity tag of a language variant with its language, as variants
+    /// of the same size and age would otherwise validate each other
+    fn language_etag(&self, etag: Option<String>, file_path: &Path) -> Option<String> {
+        let language = file_path.extension()
+            .and_then(|ext| ext.to_str())
+            .filter(|ext| self.negotiate_language && is_language_tag(ext));
+        match (etag, language) {
+            (Some(etag), Some(language)) => Some(format!("{}-{}\"", etag.trim_end_matches('"'), language)),
+            (etag, _) => etag,
+        }
+    }
+    
+    /// Pick the file to serve and its language, if the file has language variants.
+    ///
+    /// Without an acceptable variant the file itself is served, or else the
+    /// variant in the default language, or else the first one.
+    async fn negotiated_variant(&self, file_path: &Path, req: &Request<Body>) -> Option<(PathBuf, Option<String>)> {
+        let mut variants = self.language_variants(file_path).await;
+        if variants.is_empty() {
+            return None;
+        }
+        
+        let accept_language = req.headers()
+            .get(hyper::header::ACCEPT_LANGUAGE)
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or("");
+        let languages = variants.iter().map(|(language, _)| language.clone()).collect::<Vec<_>>();
+        let chosen = negotiate_language(accept_language, &languages)
+            .or_else(|| {
+                let default = self.default_language.as_deref()?;
+                languages.iter().map(String::as_str).find(|language| language.eq_ignore_ascii_case(default))
+            })
+            .and_then(|language| languages.iter().position(|candidate| candidate == language));
+        
+        let (language, variant) = match chosen {
+            Some(index) => variants.swap_remove(index),
+            None if file_path.is_file() => return Some((file_path.to_path_buf(), None)),
+            None => variants.swap_remove(0),
+        };
+        Some((variant, Some(language)))
+    }
+    
+    /// Hide the paths matched by `exclusions`
+    pub fn with_exclusions(mut self, exclusions: Arc<ExclusionRules>) -> Self {
+        self.exclusions = exclusions;
+        self
+    }
+    
+    /// Check if a path below the root is visible, given whether it is a directory.
+    ///
+    /// Paths outside the root are left to the containment checks.
+    pub(crate) fn is_visible(&self, path: &Path, is_dir: bool) -> bool {
+        match path.strip_prefix(&self.root_dir) {
+            Ok(relative) => !self.exclusions.excludes(&relative.to_string_lossy(), is_dir),
+            Err(_) => true,
+        }
+    }
+    
+    /// Report an excluded path as missing, so its existence does not leak
+    fn check_visible(&self, path: &Path) -> Result<(), HttpError> {
+        if self.is_visible(path, path.is_dir()) {
+            Ok(())
+        } else {
+            debug!("Refusing excluded path {}", path.display());
+            Err(HttpError::NotFound)
+        }
+    }
+    
+    /// Check if a request path is served over WebDAV
+    pub fn serves_webdav(&self, path: &str) -> bool {
+        self.webdav.as_ref().is_some_and(|webdav| webdav.covers(path))
+    }
+    
+    /// Serve `.min` image variants to clients sending `Save-Data: on`
+    pub fn with_save_data_variants(mut self, enabled: bool) -> Self {
+        self.save_data_variants = enabled;
+        self
+    }
+    
+    /// Find the reduced variant of an image (`photo.jpg` -> `photo.min.jpg`), if enabled and present
+    fn save_data_variant(&self, file_path: &Path) -> Option<PathBuf> {
+        if !self.save_data_variants || !from_path(file_path).first_or_octet_stream().essence_str().starts_with("image/") {
+            return None;
+        }
+        
+        let stem = file_path.file_stem()?.to_string_lossy();
+        let name = match file_path.extension() {
+            Some(ext) => format!("{}.min.{}", stem, ext.to_string_lossy()),
+            None => format!("{}.min", stem),
+        };
+        
+        let variant = file_path.with_file_name(name);
+        variant.is_file().then_some(variant)
+    }
+    
+    /// Serve buffered files from an in-memory cache
+    pub fn with_cache(mut self, cache: Option<Arc<FileCache>>) -> Self {
+        self.cache = cache;
+        self
+    }
+    
+    /// Memory-map files of at least `threshold` bytes that are sent as they are, keeping mappings in `mappings`
+    pub fn with_mappings(mut self, mappings: Option<Arc<FileCache>>, threshold: Option<u64>) -> Self {
+        self.mappings = mappings;
+        self.mmap_threshold = threshold.unwrap_or(u64::MAX);
+        self
+    }
+    
+    /// Map a large file that is not compressed on the fly, keeping the mapping for later requests
+    async fn map_file(&self, file: &File, file_path: &Path, metadata: &std::fs::Metadata, mime: &str) -> Option<Arc<CachedFile>> {
+        let mappings = self.mappings.as_ref()?;
+        if metadata.len() < self.mmap_threshold || metadata.len() == 0 || should_compress(mime) {
+            return None;
+        }
+        
+        let content = match map_file(file) {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("Failed to map {}, reading it instead: {}", file_path.display(), e);
+                return None;
+            }
+        };
+        let modified = metadata.modified().ok();
+        let etag = self.etag_generator.etag(file_path, modified, metadata.len(), Some(&content)).await;
+        let etag = self.language_etag(etag, file_path);
+        
+        debug!("Mapped {} ({} bytes)", file_path.display(), metadata.len());
+        let source = match file.try_clone().await {
+            Ok(source) => source.into_std().await,
+            Err(e) => {
+                warn!("Failed to keep {} open for its mapping, reading it instead: {}", file_path.display(), e);
+                return None;
+            }
+        };
+        let file = CachedFile::mapped(content, source, mime.to_string(), modified, etag);
+        Some(mappings.insert(file_path.to_path_buf(), metadata.len(), file))
+    }
+    
+    /// Compress responses with the given levels
+    pub fn with_compressor(mut self, compressor: Compressor) -> Self {
+        self.compressor = compressor;
+        self
+    }
+    
+    /// Serve precompressed `.br`/`.gz` sidecar files to clients accepting their encoding
+    pub fn with_precompressed_sidecars(mut self, enabled: bool) -> Self {
+        self.precompressed_sidecars = enabled;
+        self
+    }
+    
+    /// Find the precompressed sidecars of a file (`app.js` -> `app.js.br`), preferring brotli.
+    ///
+    /// Sidecars older than the file itself are ignored as stale.
+    fn precompressed_sidecars(&self, file_path: &Path) -> Vec<(Encoding, PathBuf)> {
+        if !self.precompressed_sidecars {
+            return Vec::new();
+        }
+        
+        let modified = std::fs::metadata(file_path).and_then(|m| m.modified()).ok();
+        SIDECAR_ENCODINGS
+            .iter()
+            .filter_map(|&(encoding, extension)| {
+                let sidecar = PathBuf::from(format!("{}.{}", file_path.display(), extension));
+                self.check_contained(&sidecar).ok()?;
+                let sidecar_modified = std::fs::metadata(&sidecar).ok().filter(|m| m.is_file())?.modified().ok();
+                match (sidecar_modified, modified) {
+                    (Some(sidecar_modified), Some(modified)) if sidecar_modified < modified => {
+                        debug!("Ignoring stale sidecar {}", sidecar.display());
+                        None
+                    }
+                    _ => Some((encoding, sidecar)),
+                }
+            })
+            .collect()
+    }
+    
+    /// Use the given ETag generator for file responses
+    pub fn with_etag_generator(mut self, etag_generator: EtagGenerator) -> Self {
+        self.etag_generator = etag_generator;
+        self
+    }
+    
+    /// Stream files above a size threshold or matching MIME type prefixes
+    pub fn with_streaming(mut self, threshold: Option<u64>, types: Vec<String>) -> Self {
+        self.stream_threshold = threshold;
+        self.stream_types = types;
+        self
+    }
+    
+    /// Read streamed files sent as they are over plaintext HTTP/1 in chunks of `size` bytes (0 to disable)
+    pub fn with_send_buffer_size(mut self, size: usize) -> Self {
+        self.send_buffer_size = size;
+        self
+    }
+    
+    /// Stream a file body as it is stored.
+    ///
+    /// hyper owns the socket, so `sendfile(2)` is out of reach. Over plaintext
+    /// HTTP/1 the file is instead read in large chunks, which hyper writes out
+    /// without copying them again, taking far fewer system calls than the default
+    /// 4 KiB reads. TLS records and HTTP/2 frames are small, so both gain nothing.
+    fn file_body<R: AsyncRead + Send + 'static>(&self, req: &Request<Body>, reader: R) -> Body {
+        let plaintext = req.extensions().get::<Scheme>() != Some(&Scheme::HTTPS);
+        if self.send_buffer_size > 0 && plaintext && req.version() < Version::HTTP_2 {
+            Body::wrap_stream(ReaderStream::with_capacity(reader, self.send_buffer_size))
+        } else {
+            Body::wrap_stream(ReaderStream::new(reader))
         }
     }
     
-    /// Get the full filesystem path for a request
-    fn get_file_path(&self, path: &str) -> PathBuf {
-        // Normalize the path to prevent directory traversal attacks
-        let path = path.trim_start_matches('/');
-        let path_buf = Path::new(path);
+    /// Decide whether a file should be streamed rather than buffered
+    fn should_stream(&self, mime: &str, size: u64) -> bool {
+        if self.stream_types.iter().any(|prefix| mime.starts_with(prefix.as_str())) {
+            return true;
+        }
+        
+        matches!(self.stream_threshold, Some(threshold) if size > threshold)
+    }
+    
+    /// Limit directory listings to the given depth below the root
+    pub fn with_max_listing_depth(mut self, depth: Option<usize>) -> Self {
+        self.max_listing_depth = depth;
+        self
+    }
+    
+    /// Account buffered file contents against a shared memory budget
+    pub fn with_memory_budget(mut self, memory_budget: MemoryBudget) -> Self {
+        self.memory_budget = memory_budget;
+        self
+    }
+    
+    /// Get the memory budget buffered contents are accounted against
+    pub fn memory_budget(&self) -> &MemoryBudget {
+        &self.memory_budget
+    }
+    
+    /// Enable clean URL resolution with the given extensions
+    pub fn with_clean_urls(mut self, extensions: Vec<String>) -> Self {
+        self.clean_url_extensions = extensions
+            .into_iter()
+            .map(|ext| ext.trim_start_matches('.').to_string())
+            .filter(|ext| !ext.is_empty())
+            .collect();
+        self
+    }
+    
+    /// Get the full filesystem path for a request.
+    ///
+    /// The path is split on both `/` and `\` and percent-decoded segment by segment,
+    /// so encoded (`%2e%2e`) and Windows-style (`..\`) traversal is caught. Encoded
+    /// separators and NUL bytes are malformed; any `..` segment is refused.
+    fn get_file_path(&self, path: &str) -> Result<PathBuf, HttpError> {
+        let segments = match decode_segments(path) {
+            Ok(segments) => segments,
+            Err(PathError::Traversal) => {
+                warn!("Refusing traversal in request path {}", path);
+                return Err(HttpError::Forbidden("Access denied.".to_string()));
+            }
+            Err(_) => {
+                warn!("Refusing malformed request path {}", path);
+                return Err(HttpError::BadRequest("Malformed request path.".to_string()));
+            }
+        };
         
-        // Ensure the path doesn't contain any ".." segments
         let mut normalized_path = PathBuf::new();
-        for component in path_buf.components() {
-            if component.as_os_str() != ".." {
-                normalized_path.push(component);
+        for segment in segments {
+            match segment.as_ref() {
+                "." => {}
+                ".." => {
+                    warn!("Refusing traversal in request path {}", path);
+                    return Err(HttpError::Forbidden("Access denied.".to_string()));
+                }
+                segment => normalized_path.push(segment),
             }
         }
         
-        self.root_dir.join(normalized_path)
+        let file_path = self.root_dir.join(normalized_path);
+        self.check_visible(&file_path)?;
+        Ok(file_path)
+    }
+    
+    /// Check that a path stays inside the root directory once symlinks are resolved.
+    ///
+    /// Paths that do not exist pass, leaving the caller to report them as missing.
+    fn check_contained(&self, path: &Path) -> Result<(), HttpError> {
+        if self.follow_symlinks {
+            return Ok(());
+        }
+        
+        let resolved = match std::fs::canonicalize(path) {
+            Ok(resolved) => resolved,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => {
+                error!("Failed to resolve {}: {}", path.display(), e);
+                return Err(HttpError::Forbidden("Access denied.".to_string()));
+            }
+        };
+        
+        let root = match &self.canonical_root {
+            Some(root) => root.clone(),
+            None => std::fs::canonicalize(&self.root_dir).map_err(|e| HttpError::Internal(e.to_string()))?,
+        };
+        
+        if resolved.starts_with(&root) {
+            Ok(())
+        } else {
+            warn!("Refusing {}, which resolves to {} outside the root", path.display(), resolved.display());
+            Err(HttpError::Forbidden("Access denied.".to_string()))
+        }
+    }
+    
+    /// Resolve a request path to a file that may not exist yet, such as a WebDAV upload target.
+    ///
+    /// Both the file and its parent directory must stay inside the root once
+    /// symlinks are resolved, so a new file cannot be placed through a link.
+    pub(crate) fn resolve_writable(&self, path: &str) -> Result<PathBuf, HttpError> {
+        let file_path = self.get_file_path(path)?;
+        self.check_contained(&file_path)?;
+        if let Some(parent) = file_path.parent() {
+            self.check_contained(parent)?;
+        }
+        Ok(file_path)
+    }
+    
+    /// Check if a resolved path is the root directory itself
+    pub(crate) fn is_root(&self, path: &Path) -> bool {
+        path.components().eq(self.root_dir.components())
     }
     
     /// Check if a path is a directory and has a default file
@@ -60,13 +612,81 @@ impl StaticFileHandler {
         None
     }
     
-    /// Generate a directory listing
-    async fn list_directory(&self, dir_path: &Path, req_path: &str) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+    /// Resolve a clean URL by trying each configured extension, then the directory index
+    async fn resolve_clean_url(&self, path: &Path) -> Option<PathBuf> {
+        match self.clean_url_file(path) {
+            Some(candidate) => Some(candidate),
+            None => self.check_directory(path).await,
+        }
+    }
+    
+    /// Find the file a clean URL names by trying each configured extension
+    fn clean_url_file(&self, path: &Path) -> Option<PathBuf> {
+        self.clean_url_extensions.iter().find_map(|ext| {
+            let mut candidate = path.as_os_str().to_owned();
+            candidate.push(".");
+            candidate.push(ext);
+            
+            let candidate = PathBuf::from(candidate);
+            candidate.is_file().then_some(candidate)
+        })
+    }
+    
+    /// Find the canonical URL of a request, if it differs from the requested one.
+    ///
+    /// A directory is canonically addressed with a trailing slash, so relative
+    /// links in its default file resolve inside it, and the default file itself
+    /// by its directory. The query string is kept.
+    fn canonical_location(&self, req: &Request<Body>, file_path: &Path) -> Option<String> {
+        let path = req.uri().path();
+        let canonical = if self.trailing_slash_redirect && !path.ends_with('/') && file_path.is_dir() {
+            // `/docs` stays a clean URL when `docs.html` exists next to the directory,
+            // or when clean URLs serve the directory's default file in place
+            if self.clean_url_file(file_path).is_some() {
+                return None;
+            }
+            if !self.clean_url_extensions.is_empty() && file_path.join(&self.default_file).exists() {
+                return None;
+            }
+            format!("{}/", path)
+        } else if self.canonical_index && file_path.is_file() {
+            let directory = path.strip_suffix(self.default_file.as_str())?;
+            if !directory.ends_with('/') {
+                return None;
+            }
+            directory.to_string()
+        } else {
+            return None;
+        };
+        
+        // A leading `//` would make the location protocol-relative, pointing at another host
+        let canonical = format!("/{}", canonical.trim_start_matches('/'));
+        match req.uri().query() {
+            Some(query) => Some(format!("{}?{}", canonical, query)),
+            None => Some(canonical),
+        }
+    }
+    
+    /// Generate a directory listing, as JSON for clients that accept it and as HTML otherwise.
+    ///
+    /// Entries are sorted by the `sort` (`name`, `size` or `modified`) and
+    /// `order` (`asc` or `desc`) query parameters, directories first.
+    async fn list_directory(&self, dir_path: &Path, req: &Request<Body>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
         if !self.enable_directory_listing {
-            return Ok(ResponseBuilder::with_status(StatusCode::FORBIDDEN)
-                .content_type("text/html")
-                .body_string("<h1>403 Forbidden</h1><p>Directory listing is disabled.</p>".to_string())
-                .build());
+            return Err(HttpError::Forbidden("Directory listing is disabled.".to_string()).into());
+        }
+        
+        // Refuse listings nested deeper than the configured limit
+        if let Some(max_depth) = self.max_listing_depth {
+            let depth = dir_path
+                .strip_prefix(&self.root_dir)
+                .map(|relative| relative.components().count())
+                .unwrap_or(usize::MAX);
+            
+            if depth > max_depth {
+                debug!("Directory listing depth {} exceeds limit {}", depth, max_depth);
+                return Err(HttpError::Forbidden("Directory listing is not allowed at this depth.".to_string()).into());
+            }
         }
         
         // Read directory entries
@@ -74,60 +694,129 @@ impl StaticFileHandler {
         let mut read_dir = fs::read_dir(dir_path).await?;
         
         while let Some(entry) = read_dir.next_entry().await? {
-            let file_name = entry.file_name().to_string_lossy().to_string();
-            let file_path = entry.path();
-            let is_dir = file_path.is_dir();
-            let file_type = if is_dir { "Directory" } else { "File" };
-            
-            // Calculate the relative URL for the entry
-            let mut entry_url = format!("{}{}", req_path.trim_end_matches('/'), "/");
-            entry_url.push_str(&file_name);
-            if is_dir {
-                entry_url.push('/');
+            let Ok(metadata) = fs::metadata(entry.path()).await else {
+                continue;
+            };
+            if !self.is_visible(&entry.path(), metadata.is_dir()) {
+                continue;
             }
-            
-            entries.push((file_name, entry_url, file_type));
+            entries.push(ListingEntry {
+                name: entry.file_name().to_string_lossy().to_string(),
+                is_dir: metadata.is_dir(),
+                size: metadata.len(),
+                modified: metadata.modified().ok(),
+            });
         }
         
-        // Sort entries (directories first, then files)
+        let (sort, descending) = listing_order(req.uri().query());
         entries.sort_by(|a, b| {
-            if a.2 == "Directory" && b.2 != "Directory" {
-                std::cmp::Ordering::Less
-            } else if a.2 != "Directory" && b.2 == "Directory" {
-                std::cmp::Ordering::Greater
-            } else {
-                a.0.cmp(&b.0)
-            }
+            let order = match sort {
+                ListingSort::Name => a.name.cmp(&b.name),
+                ListingSort::Size => a.size.cmp(&b.size).then_with(|| a.name.cmp(&b.name)),
+                ListingSort::Modified => a.modified.cmp(&b.modified).then_with(|| a.name.cmp(&b.name)),
+            };
+            // Directories stay ahead of files in either order
+            b.is_dir.cmp(&a.is_dir).then(if descending { order.reverse() } else { order })
         });
         
+        let req_path = req.uri().path();
+        let base = format!("{}/", req_path.trim_end_matches('/'));
+        if accepts_json(req) {
+            let entries = entries
+                .iter()
+                .map(|entry| {
+                    serde_json::json!({
+                        "name": entry.name,
+                        "type": if entry.is_dir { "directory" } else { "file" },
+                        "size": if entry.is_dir { None } else { Some(entry.size) },
+                        "mtime": entry.modified.map(|modified| chrono::DateTime::<chrono::Utc>::from(modified).to_rfc3339_opts(chrono::SecondsFormat::Secs, true)),
+                        "url": entry.url(&base),
+                    })
+                })
+                .collect::<Vec<_>>();
+            let listing = serde_json::json!({ "path": req_path, "entries": entries });
+            
+            return Ok(ResponseBuilder::new()
+                .content_type("application/json")
+                .header("vary", "Accept")
+                .body_string(listing.to_string())
+                .build());
+        }
+        
+        let title = escape_html(&percent_decode_str(req_path).decode_utf8_lossy());
+        
         // Generate HTML for directory listing
-        let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n");
-        html.push_str(&format!("<title>Directory listing for {}</title>\n", req_path));
+        let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        html.push_str(&format!("<title>Directory listing for {}</title>\n", title));
         html.push_str("<style>\n");
         html.push_str("body { font-family: Arial, sans-serif; margin: 20px; }\n");
         html.push_str("h1 { border-bottom: 1px solid #ccc; padding-bottom: 10px; }\n");
+        html.push_str("nav { margin-bottom: 10px; }\n");
         html.push_str("table { border-collapse: collapse; width: 100%; }\n");
         html.push_str("th, td { text-align: left; padding: 8px; }\n");
+        html.push_str("td.size, th.size { text-align: right; }\n");
         html.push_str("tr:nth-child(even) { background-color: #f2f2f2; }\n");
         html.push_str("a { text-decoration: none; }\n");
         html.push_str("a:hover { text-decoration: underline; }\n");
         html.push_str("</style>\n");
         html.push_str("</head>\n<body>\n");
         
-        html.push_str(&format!("<h1>Directory listing for {}</h1>\n", req_path));
-        html.push_str("<table>\n");
-        html.push_str("<tr><th>Name</th><th>Type</th></tr>\n");
+        html.push_str(&format!("<h1>Directory listing for {}</h1>\n", title));
+        
+        // Breadcrumb links to every ancestor directory
+        html.push_str("<nav><a href=\"/\">/</a>");
+        let mut crumb_href = String::from("/");
+        for segment in req_path.split('/').filter(|segment| !segment.is_empty()) {
+            crumb_href.push_str(segment);
+            crumb_href.push('/');
+            html.push_str(&format!(
+                " <a href=\"{}\">{}</a> /",
+                escape_html(&crumb_href),
+                escape_html(&percent_decode_str(segment).decode_utf8_lossy())
+            ));
+        }
+        html.push_str("</nav>\n");
+        
+        // Column headings sort by their column, toggling the order of the current one
+        html.push_str("<table>\n<tr>");
+        for (column, label, class) in [
+            (ListingSort::Name, "Name", ""),
+            (ListingSort::Size, "Size", " class=\"size\""),
+            (ListingSort::Modified, "Modified", ""),
+        ] {
+            let current = column == sort;
+            let order = if current && !descending { "desc" } else { "asc" };
+            let marker = match (current, descending) {
+                (false, _) => "",
+                (true, false) => " &#9650;",
+                (true, true) => " &#9660;",
+            };
+            html.push_str(&format!(
+                "<th{}><a href=\"?sort={}&amp;order={}\">{}</a>{}</th>",
+                class, column.as_str(), order, label, marker
+            ));
+        }
+        html.push_str("</tr>\n");
         
         // Add parent directory link if not at root
         if req_path != "/" {
-            html.push_str("<tr><td><a href=\"..\">..</a></td><td>Parent Directory</td></tr>\n");
+            html.push_str("<tr><td><a href=\"..\">..</a></td><td class=\"size\"></td><td>Parent Directory</td></tr>\n");
         }
         
         // Add entries
-        for (name, url, file_type) in entries {
+        for entry in &entries {
+            let name = if entry.is_dir { format!("{}/", entry.name) } else { entry.name.clone() };
+            let size = if entry.is_dir { "-".to_string() } else { format_size(entry.size) };
+            let modified = entry
+                .modified
+                .map(|modified| chrono::DateTime::<chrono::Utc>::from(modified).format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default();
             html.push_str(&format!(
-                "<tr><td><a href=\"{}\">{}</a></td><td>{}</td></tr>\n",
-                url, name, file_type
+                "<tr><td><a href=\"{}\">{}</a></td><td class=\"size\">{}</td><td>{}</td></tr>\n",
+                escape_html(&entry.url(&base)),
+                escape_html(&name),
+                size,
+                modified
             ));
         }
         
@@ -136,6 +825,7 @@ impl StaticFileHandler {
         
         Ok(ResponseBuilder::new()
             .content_type("text/html")
+            .header("vary", "Accept")
             .body_string(html)
             .build())
     }
@@ -144,49 +834,187 @@ impl StaticFileHandler {
 #[async_trait]
 impl Handler for StaticFileHandler {
     async fn handle(&self, req: Request<Body>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        match &self.webdav {
+            Some(webdav) if webdav.covers(req.uri().path()) => webdav.handle(req, self).await,
+            _ => self.handle_files(req).await,
+        }
+    }
+}
+
+impl StaticFileHandler {
+    /// Serve a request for a file or directory with the plain read-only methods
+    pub(crate) async fn handle_files(&self, req: Request<Body>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        match *req.method() {
+            Method::GET => self.handle_get(req).await,
+            Method::HEAD => {
+                // Answer exactly as GET would, headers and Content-Length included, minus the body
+                let (parts, _) = self.handle_get(req).await?.into_parts();
+                Ok(Response::from_parts(parts, Body::empty()))
+            }
+            Method::OPTIONS => Ok(ResponseBuilder::with_status(StatusCode::NO_CONTENT)
+                .header("allow", ALLOWED_METHODS)
+                .build()),
+            _ => Err(HttpError::MethodNotAllowed { allow: ALLOWED_METHODS.to_string() }.into()),
+        }
+    }
+    
+    /// Serve a GET request for a file or directory
+    async fn handle_get(&self, req: Request<Body>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
         let path = req.uri().path();
-        let file_path = self.get_file_path(path);
+        let file_path = self.get_file_path(path)?;
+        self.check_contained(&file_path)?;
         
         debug!("Handling request for static file: {}", path);
         
+        if let Some(location) = self.canonical_location(&req, &file_path) {
+            debug!("Redirecting {} to canonical URL {}", path, location);
+            return Ok(ResponseBuilder::redirect(StatusCode::MOVED_PERMANENTLY, &location));
+        }
+        
+        // Try clean URL candidates when the path is not a file
+        if !self.clean_url_extensions.is_empty() && !path.ends_with('/') && !file_path.is_file() {
+            if let Some(resolved) = self.resolve_clean_url(&file_path).await {
+                debug!("Resolved clean URL {} to {}", path, resolved.display());
+                return self.serve_file(resolved, req).await;
+            }
+        }
+        
         // Check if path exists
         if !file_path.exists() {
+            // A negotiated file may exist only as its language variants
+            if !self.language_variants(&file_path).await.is_empty() {
+                return self.serve_file(file_path, req).await;
+            }
+            
+            // App routes fall back to the app's entry page; a missing asset such as app.js stays missing
+            let is_asset = path.rsplit('/').next().is_some_and(|name| name.contains('.'));
+            let index = self.root_dir.join(&self.default_file);
+            if self.spa_fallback && !is_asset && index.is_file() {
+                debug!("Serving {} for client-side route {}", index.display(), path);
+                return self.serve_file(index, req).await;
+            }
+            
             debug!("File not found: {}", file_path.display());
-            return Ok(ResponseBuilder::not_found());
+            return Err(HttpError::NotFound.into());
         }
         
         // If it's a directory, check for default file or directory listing
         if file_path.is_dir() {
             let default_file_path = file_path.join(&self.default_file);
             
-            if default_file_path.exists() {
+            if default_file_path.exists() || !self.language_variants(&default_file_path).await.is_empty() {
                 debug!("Serving default file: {}", default_file_path.display());
                 return self.serve_file(default_file_path, req).await;
             } else if self.enable_directory_listing {
                 debug!("Generating directory listing for: {}", file_path.display());
-                return self.list_directory(&file_path, path).await;
+                return self.list_directory(&file_path, &req).await;
             } else {
-                return Ok(ResponseBuilder::with_status(StatusCode::FORBIDDEN)
-                    .content_type("text/html")
-                    .body_string("<h1>403 Forbidden</h1><p>Directory listing is disabled.</p>".to_string())
-                    .build());
+                return Err(HttpError::Forbidden("Directory listing is disabled.".to_string()).into());
             }
         }
         
         // Serve the file
         self.serve_file(file_path, req).await
     }
-}
-
-impl StaticFileHandler {
-    /// Serve a file from the filesystem
-    async fn serve_file(&self, file_path: PathBuf, req: Request<Body>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+    
+    /// Serve a file from the filesystem with the Cache-Control of its policy
+    async fn serve_file(&self, file_path: PathBuf, mut req: Request<Body>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let cache_control = self.cache_policy
+            .directive_for(req.uri().path(), &file_path)
+            .and_then(|directive| HeaderValue::from_str(directive).ok());
+        
+        // Default files and clean URLs resolve to paths the request did not name
+        self.check_visible(&file_path)?;
+        
+        let render_markdown = self.markdown.is_some() && is_markdown(&file_path) && !raw_requested(&req);
+        
+        // Swap in the variant in the client's language; its name no longer tells its MIME type
+        let negotiated = self.negotiated_variant(&file_path, &req).await;
+        let has_variants = negotiated.is_some();
+        let (file_path, language) = match negotiated {
+            Some((variant, Some(language))) => {
+                debug!("Serving {} variant {}", language, variant.display());
+                req.extensions_mut().insert(NegotiatedVariant);
+                (variant, Some(language))
+            }
+            _ => (file_path, None),
+        };
+        
+        let mut response = if render_markdown {
+            self.serve_markdown(file_path, req).await?
+        } else {
+            self.serve_file_contents(file_path, req).await?
+        };
+        let cacheable = response.status().is_success() || response.status() == StatusCode::NOT_MODIFIED;
+        if let Some(cache_control) = cache_control.filter(|_| cacheable) {
+            response.headers_mut().insert(hyper::header::CACHE_CONTROL, cache_control);
+        }
+        if has_variants {
+            response.headers_mut().append(hyper::header::VARY, HeaderValue::from_static("Accept-Language"));
+        }
+        if let Some(language) = language.and_then(|language| HeaderValue::from_str(&language).ok()) {
+            response.headers_mut().insert(hyper::header::CONTENT_LANGUAGE, language);
+        }
+        
+        Ok(response)
+    }
+    
+    /// Serve the contents of a file, honouring conditional, range and encoding headers
+    async fn serve_file_contents(&self, file_path: PathBuf, req: Request<Body>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let save_data = save_data_requested(&req);
+        
+        // Swap in a reduced image variant for clients asking to save data
+        let mut vary = Vec::new();
+        let file_path = match self.save_data_variant(&file_path) {
+            Some(variant) => {
+                vary.push("Save-Data");
+                if save_data {
+                    debug!("Serving Save-Data variant {}", variant.display());
+                    variant
+                } else {
+                    file_path
+                }
+            }
+            None => file_path,
+        };
+        self.check_contained(&file_path)?;
+        
+        // Serve a precompressed sidecar the client accepts. Range requests get the
+        // original file so that offsets refer to the uncompressed content.
+        let sidecars = self.precompressed_sidecars(&file_path);
+        if !sidecars.is_empty() {
+            vary.push("Accept-Encoding");
+            let accept_encoding = req.headers()
+                .get(hyper::header::ACCEPT_ENCODING)
+                .and_then(|h| h.to_str().ok())
+                .unwrap_or("");
+            let available: Vec<Encoding> = sidecars.iter().map(|(encoding, _)| *encoding).collect();
+            let encoding = negotiate(accept_encoding, &available).filter(|_| !req.headers().contains_key(hyper::header::RANGE));
+            if let Some((encoding, sidecar)) = sidecars.iter().find(|(sidecar, _)| Some(*sidecar) == encoding) {
+                return self.serve_sidecar(&req, &file_path, sidecar, *encoding, &vary).await;
+            }
+        }
+        
+        // Serve unchanged cached and mapped files without opening them
+        if self.cache.is_some() || self.mappings.is_some() {
+            if let Ok(metadata) = fs::metadata(&file_path).await {
+                if let Some(cached) = self.cache.as_ref().and_then(|cache| cache.get(&file_path, &metadata)) {
+                    debug!("Serving {} from cache", file_path.display());
+                    return self.buffered_response(&req, &file_path, &cached, vary, save_data, Body::from);
+                }
+                if let Some(mapped) = self.mappings.as_ref().and_then(|mappings| mappings.get(&file_path, &metadata)) {
+                    debug!("Serving {} from its mapping", file_path.display());
+                    return self.buffered_response(&req, &file_path, &mapped, vary, save_data, mapped_body(&mapped, &file_path));
+                }
+            }
+        }
+        
         // Open the file
         let mut file = match File::open(&file_path).await {
             Ok(file) => file,
             Err(e) => {
                 error!("Failed to open file {}: {}", file_path.display(), e);
-                return Ok(ResponseBuilder::not_found());
+                return Err(HttpError::NotFound.into());
             }
         };
         
@@ -195,44 +1023,659 @@ impl StaticFileHandler {
             Ok(metadata) => metadata,
             Err(e) => {
                 error!("Failed to get metadata for {}: {}", file_path.display(), e);
-                return Ok(ResponseBuilder::server_error(Some(&e.to_string())));
+                return Err(HttpError::Internal(e.to_string()).into());
             }
         };
         
-        // Determine MIME type
-        let mime = from_path(&file_path).first_or_octet_stream().to_string();
+        // Determine MIME type, consulting the content when configured
+        let mut mime = guess_mime(&file_path, &req);
+        if self.mime_sniffing.applies_to(&mime) {
+            match sniff_file(&mut file).await {
+                Ok(Some(sniffed)) => {
+                    debug!("Sniffed {} as {}", file_path.display(), sniffed);
+                    mime = sniffed.to_string();
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    error!("Failed to sniff {}: {}", file_path.display(), e);
+                    return Err(HttpError::Internal(e.to_string()).into());
+                }
+            }
+        }
         
-        // Read file content
-        let mut buffer = vec![0; metadata.len() as usize];
-        if let Err(e) = file.read_exact(&mut buffer).await {
-            error!("Failed to read file {}: {}", file_path.display(), e);
-            return Ok(ResponseBuilder::server_error(Some(&e.to_string())));
+        // Map large files sent as they are instead of reading them
+        if let Some(mapped) = self.map_file(&file, &file_path, &metadata, &mime).await {
+            return self.buffered_response(&req, &file_path, &mapped, vary, save_data, mapped_body(&mapped, &file_path));
         }
         
         // Get modified time
         let modified = metadata.modified().ok();
         
-        // Build response
+        // Stream large or media files directly from disk, compressing them on the way
+        // when the client accepts it. Range requests get the original content.
+        if self.should_stream(&mime, metadata.len()) {
+            debug!("Streaming file {} ({} bytes)", file_path.display(), metadata.len());
+            let encoding = if should_compress(&mime) {
+                vary.push("Accept-Encoding");
+                let accept_encoding = req.headers()
+                    .get(hyper::header::ACCEPT_ENCODING)
+                    .and_then(|h| h.to_str().ok())
+                    .unwrap_or("");
+                negotiate(accept_encoding, &Encoding::ALL).filter(|_| !req.headers().contains_key(hyper::header::RANGE))
+            } else {
+                None
+            };
+            
+            let mut response_builder = ResponseBuilder::new()
+                .with_static_file_headers(&mime, modified);
+            let etag = self.etag_generator.etag(&file_path, modified, metadata.len(), None).await;
+            let etag = self.language_etag(etag, &file_path);
+            match (&etag, encoding) {
+                (Some(etag), Some(encoding)) => response_builder = response_builder.etag(&encoding_etag(etag, encoding)),
+                (Some(etag), None) => response_builder = response_builder.etag(etag),
+                (None, _) => {}
+            }
+            if !vary.is_empty() {
+                response_builder = response_builder.header("vary", &vary.join(", "));
+            }
+            let mut response_builder = self.apply_attachment(response_builder, req.uri().path(), &mime, &file_path);
+            if encoding.is_none() {
+                response_builder = response_builder.header("accept-ranges", "bytes");
+            }
+            
+            match response_builder.preconditions(req.headers(), req.method()) {
+                Precondition::NotModified => return Ok(response_builder.not_modified()),
+                Precondition::Failed => return Err(HttpError::PreconditionFailed.into()),
+                Precondition::Proceed => {}
+            }
+            
+            // Seek to the requested ranges instead of reading the whole file
+            match requested_ranges(&req, metadata.len(), etag.as_deref(), modified)? {
+                Some(ranges) if ranges.len() == 1 => {
+                    let range = ranges[0];
+                    debug!("Streaming range {}-{} of {}", range.start, range.end, file_path.display());
+                    if let Err(e) = file.seek(SeekFrom::Start(range.start)).await {
+                        error!("Failed to seek in {}: {}", file_path.display(), e);
+                        return Err(HttpError::Internal(e.to_string()).into());
+                    }
+                    return Ok(response_builder
+                        .status(StatusCode::PARTIAL_CONTENT)
+                        .header("content-range", &range.content_range(metadata.len()))
+                        .header("content-length", &range.len().to_string())
+                        .body(self.file_body(&req, file.take(range.len())))
+                        .build());
+                }
+                Some(ranges) => {
+                    debug!("Streaming {} ranges of {}", ranges.len(), file_path.display());
+                    let multipart = MultipartRanges::new(&mime, metadata.len());
+                    return Ok(response_builder
+                        .status(StatusCode::PARTIAL_CONTENT)
+                        .content_type(&multipart.content_type())
+                        .header("content-length", &multipart.body_len(&ranges).to_string())
+                        .body(stream_multipart(file, ranges, multipart))
+                        .build());
+                }
+                None => {}
+            }
+            
+            if let Some(encoding) = encoding {
+                debug!("Compressing stream of {} with {}", file_path.display(), encoding.name());
+                let body = Body::wrap_stream(ReaderStream::new(file.take(metadata.len())));
+                return Ok(response_builder
+                    .header("content-encoding", encoding.name())
+                    .body(self.compressor.compress_stream(body, encoding))
+                    .build());
+            }
+            
+            return Ok(response_builder
+                .header("content-length", &metadata.len().to_string())
+                .body(self.file_body(&req, file.take(metadata.len())))
+                .build());
+        }
+        
+        // Reserve memory for the buffered content
+        let reservation = match self.memory_budget.try_reserve(metadata.len() as usize) {
+            Some(reservation) => reservation,
+            None => {
+                warn!("Memory budget exhausted, rejecting {}", file_path.display());
+                return Err(HttpError::ServiceUnavailable.into());
+            }
+        };
+        
+        let buffer = match read_reported(&mut file, metadata.len()).await {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                error!("Failed to read file {}: {}", file_path.display(), e);
+                return Err(HttpError::Internal(e.to_string()).into());
+            }
+        };
+        if buffer.len() as u64 != metadata.len() {
+            warn!(
+                "File {} changed size while reading ({} of {} bytes)",
+                file_path.display(),
+                buffer.len(),
+                metadata.len()
+            );
+        }
+        
+        // Add the entity tag, hashing the buffered content if needed
+        let etag = self.etag_generator.etag(&file_path, modified, metadata.len(), Some(&buffer)).await;
+        let etag = self.language_etag(etag, &file_path);
+        let complete = buffer.len() as u64 == metadata.len();
+        let file = CachedFile::new(buffer.into(), mime, modified, etag);
+        
+        // Keep complete reads of small files, compressed ahead of time
+        let file = match &self.cache {
+            Some(cache) if complete && cache.cacheable(metadata.len()) => {
+                debug!("Caching {}", file_path.display());
+                cache.insert(file_path.clone(), metadata.len(), file.precompressed(&self.compressor))
+            }
+            _ => Arc::new(file),
+        };
+        
+        self.buffered_response(&req, &file_path, &file, vary, save_data, |data| reserved_body(data, reservation))
+    }
+    
+    /// Serve a Markdown file rendered as an HTML page, or raw if it is not UTF-8
+    async fn serve_markdown(&self, file_path: PathBuf, req: Request<Body>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let Some(markdown) = &self.markdown else {
+            return self.serve_file_contents(file_path, req).await;
+        };
+        let metadata = fs::metadata(&file_path).await?;
+        let modified = metadata.modified().ok();
+        
+        // The page changes with the source, so a weak tag derived from it validates the page
+        let etag = self.language_etag(Some(format!("W/{}", mtime_etag(modified, metadata.len()))), &file_path).unwrap_or_default();
         let response_builder = ResponseBuilder::new()
+            .with_static_file_headers("text/html; charset=utf-8", modified)
+            .etag(&etag);
+        match response_builder.preconditions(req.headers(), req.method()) {
+            Precondition::Proceed => {}
+            Precondition::NotModified => return Ok(response_builder.not_modified()),
+            Precondition::Failed => return Err(HttpError::PreconditionFailed.into()),
+        }
+        
+        let source = match String::from_utf8(fs::read(&file_path).await?) {
+            Ok(source) => source,
+            Err(_) => {
+                debug!("{} is not UTF-8, serving it raw", file_path.display());
+                return self.serve_file_contents(file_path, req).await;
+            }
+        };
+        
+        debug!("Rendering Markdown {}", file_path.display());
+        let fallback_title = file_path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        let page = markdown.render(&source, &fallback_title);
+        Ok(response_builder.body_string(page).build())
+    }
+    
+    /// Stream a precompressed sidecar in place of the file it encodes
+    async fn serve_sidecar(
+        &self,
+        req: &Request<Body>,
+        file_path: &Path,
+        sidecar: &Path,
+        encoding: Encoding,
+        vary: &[&str],
+    ) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let file = match File::open(sidecar).await {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Failed to open sidecar {}: {}", sidecar.display(), e);
+                return Err(HttpError::NotFound.into());
+            }
+        };
+        let metadata = match file.metadata().await {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                error!("Failed to get metadata for {}: {}", sidecar.display(), e);
+                return Err(HttpError::Internal(e.to_string()).into());
+            }
+        };
+        
+        debug!("Serving {} sidecar {}", encoding.name(), sidecar.display());
+        let mime = guess_mime(file_path, req);
+        let modified = metadata.modified().ok();
+        let mut response_builder = ResponseBuilder::new()
             .with_static_file_headers(&mime, modified);
+        let etag = self.etag_generator.etag(sidecar, modified, metadata.len(), None).await;
+        if let Some(etag) = self.language_etag(etag, file_path) {
+            response_builder = response_builder.etag(&encoding_etag(&etag, encoding));
+        }
+        let response_builder = self.apply_attachment(response_builder, req.uri().path(), &mime, file_path)
+            .header("content-encoding", encoding.name())
+            .header("vary", &vary.join(", "));
+        
+        match response_builder.preconditions(req.headers(), req.method()) {
+            Precondition::NotModified => return Ok(response_builder.not_modified()),
+            Precondition::Failed => return Err(HttpError::PreconditionFailed.into()),
+            Precondition::Proceed => {}
+        }
         
-        // Check if we should compress the response
+        Ok(response_builder
+            .header("content-length", &metadata.len().to_string())
+            .body(self.file_body(req, file.take(metadata.len())))
+            .build())
+    }
+    
+    /// Build the response for buffered file contents, wrapping the data sent with `body`
+    fn buffered_response(
+        &self,
+        req: &Request<Body>,
+        file_path: &Path,
+        file: &CachedFile,
+        mut vary: Vec<&'static str>,
+        save_data: bool,
+        body: impl FnOnce(Bytes) -> Body,
+    ) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let content = &file.content;
+        let mime = file.mime.as_str();
         let accept_encoding = req.headers()
             .get(hyper::header::ACCEPT_ENCODING)
             .and_then(|h| h.to_str().ok())
             .unwrap_or("");
         
-        // Compress content if appropriate
-        let (compressed_data, content_encoding) = compress_if_needed(&buffer, &mime, accept_encoding);
-        
-        // Add content encoding header if compressed
-        let response_builder = if let Some(encoding) = content_encoding {
-            response_builder.header("content-encoding", encoding)
+        // Pick the representation before checking preconditions, as an encoded one has
+        // its own entity tag. Prefer a precompressed variant, otherwise compress content
+        // if appropriate, regardless of size when saving data. Range requests get the
+        // original content.
+        let encoded = if req.headers().contains_key(hyper::header::RANGE) {
+            None
         } else {
-            response_builder
+            match file.encoding_for(accept_encoding) {
+                Some((encoding, data)) => Some((encoding, Some(data))),
+                None if file.encodings.is_none() || save_data => {
+                    let min_size = if save_data { 1 } else { MIN_COMPRESS_SIZE };
+                    let compressible = should_compress(mime) && !content.is_empty() && content.len() >= min_size;
+                    negotiate(accept_encoding, &Encoding::ALL).filter(|_| compressible).map(|encoding| (encoding, None))
+                }
+                None => None,
+            }
         };
         
-        // Return the response
-        Ok(response_builder.body_bytes(compressed_data).build())
+        // Build response
+        let mut response_builder = ResponseBuilder::new()
+            .with_static_file_headers(mime, file.modified);
+        match (&file.etag, &encoded) {
+            (Some(etag), Some((encoding, _))) => response_builder = response_builder.etag(&encoding_etag(etag, *encoding)),
+            (Some(etag), None) => response_builder = response_builder.etag(etag),
+            (None, _) => {}
+        }
+        let mut response_builder = self.apply_attachment(response_builder, req.uri().path(), mime, file_path);
+        // Ranges are only served over the original content
+        if encoded.is_none() {
+            response_builder = response_builder.header("accept-ranges", "bytes");
+        }
+        
+        // Small compressible bodies are only compressed when the client asks to save data
+        let small = should_compress(mime) && !content.is_empty() && content.len() < MIN_COMPRESS_SIZE;
+        if small && !vary.contains(&"Save-Data") {
+            vary.push("Save-Data");
+        }
+        if !vary.is_empty() {
+            response_builder = response_builder.header("vary", &vary.join(", "));
+        }
+        
+        match response_builder.preconditions(req.headers(), req.method()) {
+            Precondition::NotModified => return Ok(response_builder.not_modified()),
+            Precondition::Failed => return Err(HttpError::PreconditionFailed.into()),
+            Precondition::Proceed => {}
+        }
+        
+        // Serve byte ranges by slicing the uncompressed content
+        match requested_ranges(req, content.len() as u64, file.etag.as_deref(), file.modified)? {
+            Some(ranges) if ranges.len() == 1 => {
+                let range = ranges[0];
+                debug!("Serving range {}-{} of {}", range.start, range.end, file_path.display());
+                let slice = content.slice(range.start as usize..=range.end as usize);
+                return Ok(response_builder
+                    .status(StatusCode::PARTIAL_CONTENT)
+                    .header("content-range", &range.content_range(content.len() as u64))
+                    .header("content-length", &slice.len().to_string())
+                    .body(body(slice))
+                    .build());
+            }
+            Some(ranges) => {
+                debug!("Serving {} ranges of {}", ranges.len(), file_path.display());
+                // The ranges are copied out of mapped contents right away
+                file.check_mapping().map_err(|e| HttpError::Internal(e.to_string()))?;
+                let multipart = MultipartRanges::new(mime, content.len() as u64);
+                let encoded = multipart.encode(&ranges, content);
+                return Ok(response_builder
+                    .status(StatusCode::PARTIAL_CONTENT)
+                    .content_type(&multipart.content_type())
+                    .header("content-length", &encoded.len().to_string())
+                    .body(body(encoded.into()))
+                    .build());
+            }
+            None => {}
+        }
+        
+        // Add content encoding header if compressed, compressing now that the body is needed
+        let (data, response_builder) = match encoded {
+            Some((encoding, Some(data))) => (data, response_builder.header("content-encoding", encoding.name())),
+            Some((encoding, None)) => {
+                debug!("Compressing response with {} ({})", encoding.name(), mime);
+                let compressed = self.compressor.compress(content, encoding).map_err(|e| {
+                    error!("Failed to compress {} with {}: {}", file_path.display(), encoding.name(), e);
+                    HttpError::Internal(e.to_string())
+                })?;
+                (Bytes::from(compressed), response_builder.header("content-encoding", encoding.name()))
+            }
+            None => (content.clone(), response_builder),
+        };
+        
+        Ok(response_builder
+            .header("content-length", &data.len().to_string())
+            .body(body(data))
+            .build())
+    }
+}
+
+/// Read up to the `len` bytes a file reported, tolerating files truncated since the stat
+/// and never returning more than was reported for files that grew
+async fn read_reported<R: AsyncRead + Unpin>(reader: &mut R, len: u64) -> std::io::Result<Vec<u8>> {
+    let mut buffer = Vec::with_capacity(len as usize);
+    reader.take(len).read_to_end(&mut buffer).await?;
+    Ok(buffer)
+}
+
+/// Tag an entity tag with a content coding, as encoded representations need their own validators
+fn encoding_etag(etag: &str, encoding: Encoding) -> String {
+    format!("{}-{}\"", etag.trim_end_matches('"'), encoding.name())
+}
+
+/// Get the satisfiable ranges requested for a representation of `total` bytes.
+///
+/// Returns `None` when the full content should be served: no or malformed `Range`
+/// header, an empty file, an `If-Range` that no longer matches, or more than
+/// [`MAX_RANGES`] ranges.
+fn requested_ranges(
+    req: &Request<Body>,
+    total: u64,
+    etag: Option<&str>,
+    modified: Option<SystemTime>,
+) -> Result<Option<Vec<ByteRange>>, HttpError> {
+    let range_header = match req.headers().get(hyper::header::RANGE).and_then(|h| h.to_str().ok()) {
+        Some(value) if total > 0 => value,
+        _ => return Ok(None),
+    };
+    
+    if !if_range_matches(req.headers(), etag, modified) {
+        debug!("If-Range does not match, serving full content");
+        return Ok(None);
+    }
+    
+    match parse_range(range_header, total) {
+        Ok(ranges) if ranges.len() > MAX_RANGES => {
+            debug!("Ignoring Range header with {} ranges", ranges.len());
+            Ok(None)
+        }
+        Ok(ranges) => Ok(Some(ranges)),
+        Err(RangeError::Unsatisfiable) => Err(HttpError::RangeNotSatisfiable { total }),
+        Err(RangeError::Invalid) => Ok(None),
+    }
+}
+
+/// Stream the parts of a multipart range response from an open file
+fn stream_multipart(mut file: File, ranges: Vec<ByteRange>, multipart: MultipartRanges) -> Body {
+    let (mut sender, body) = Body::channel();
+    
+    tokio::spawn(async move {
+        let mut chunk = vec![0u8; MULTIPART_CHUNK_SIZE];
+        
+        for range in &ranges {
+            if sender.send_data(Bytes::from(multipart.part_header(range))).await.is_err() {
+                return;
+            }
+            
+            if let Err(e) = file.seek(SeekFrom::Start(range.start)).await {
+                warn!("Failed to seek for range {}-{}: {}", range.start, range.end, e);
+                sender.abort();
+                return;
+            }
+            
+            let mut remaining = range.len();
+            while remaining > 0 {
+                let want = remaining.min(chunk.len() as u64) as usize;
+                let read = match file.read(&mut chunk[..want]).await {
+                    Ok(0) => {
+                        // The file shrank, so the declared length can no longer be met
+                        warn!("File truncated while streaming range {}-{}", range.start, range.end);
+                        sender.abort();
+                        return;
+                    }
+                    Ok(read) => read,
+                    Err(e) => {
+                        warn!("Failed to read range {}-{}: {}", range.start, range.end, e);
+                        sender.abort();
+                        return;
+                    }
+                };
+                
+                if sender.send_data(Bytes::copy_from_slice(&chunk[..read])).await.is_err() {
+                    return;
+                }
+                remaining -= read as u64;
+            }
+        }
+        
+        let _ = sender.send_data(Bytes::from(multipart.trailer())).await;
+    });
+    
+    body
+}
+
+/// Check if the client asked for the source of a rendered file with a `raw` query parameter
+fn raw_requested(req: &Request<Body>) -> bool {
+    req.uri()
+        .query()
+        .is_some_and(|query| query.split('&').any(|param| param == "raw" || param.starts_with("raw=")))
+}
+
+/// Check whether the client sent `Save-Data: on`
+fn save_data_requested(req: &Request<Body>) -> bool {
+    req.headers()
+        .get("save-data")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.trim().eq_ignore_ascii_case("on"))
+}
+
+/// Send mapped file contents in slices, so they are paged in as the client reads them.
+///
+/// Each slice is copied out of the mapping right after checking the file, so a slice
+/// waiting on a slow client never reads pages of a file truncated in the meantime;
+/// once the file changed size the response is cut short.
+fn mapped_body(mapped: &Arc<CachedFile>, file_path: &Path) -> impl FnOnce(Bytes) -> Body {
+    let mapped = Arc::clone(mapped);
+    let file_path = file_path.to_path_buf();
+    move |content| {
+        let chunks = (0..content.len()).step_by(MAPPED_CHUNK_SIZE).map(move |start| {
+            if let Err(e) = mapped.check_mapping() {
+                warn!("Stopping response from {}: {}", file_path.display(), e);
+                return Err(e);
+            }
+            Ok(Bytes::copy_from_slice(&content[start..content.len().min(start + MAPPED_CHUNK_SIZE)]))
+        });
+        Body::wrap_stream(futures::stream::iter(chunks))
+    }
+}
+
+/// Marks a request, in its extensions, as served a language variant
+#[derive(Clone, Copy)]
+struct NegotiatedVariant;
+
+/// Guess the MIME type of a file from its name, without the language of a negotiated variant
+fn guess_mime(file_path: &Path, req: &Request<Body>) -> String {
+    let named = match req.extensions().get::<NegotiatedVariant>() {
+        Some(_) => file_path.with_extension(""),
+        None => file_path.to_path_buf(),
+    };
+    from_path(named).first_or_octet_stream().to_string()
+}
+
+/// File or directory shown in a directory listing
+struct ListingEntry {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+impl ListingEntry {
+    /// Get the link to the entry from its directory's URL path `base`
+    fn url(&self, base: &str) -> String {
+        let mut url = format!("{}{}", base, encode_segment(&self.name));
+        if self.is_dir {
+            url.push('/');
+        }
+        url
+    }
+}
+
+/// Column a directory listing is sorted by
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ListingSort {
+    Name,
+    Size,
+    Modified,
+}
+
+impl ListingSort {
+    /// Get the query parameter value selecting this column
+    fn as_str(&self) -> &'static str {
+        match self {
+            ListingSort::Name => "name",
+            ListingSort::Size => "size",
+            ListingSort::Modified => "modified",
+        }
+    }
+}
+
+/// Read the listing sort column and whether it is descending from a query string
+fn listing_order(query: Option<&str>) -> (ListingSort, bool) {
+    let mut sort = ListingSort::Name;
+    let mut descending = false;
+    
+    for (key, value) in query.unwrap_or_default().split('&').filter_map(|param| param.split_once('=')) {
+        match (key, value) {
+            ("sort", "size") => sort = ListingSort::Size,
+            ("sort", "modified" | "mtime") => sort = ListingSort::Modified,
+            ("sort", _) => sort = ListingSort::Name,
+            ("order", order) => descending = order.eq_ignore_ascii_case("desc"),
+            _ => {}
+        }
+    }
+    
+    (sort, descending)
+}
+
+/// Check whether the client asks for JSON rather than an HTML page
+fn accepts_json(req: &Request<Body>) -> bool {
+    let Some(accept) = req.headers().get(hyper::header::ACCEPT).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let types = accept
+        .split(',')
+        .filter_map(|item| item.split(';').next())
+        .map(str::trim)
+        .collect::<Vec<_>>();
+    
+    // Browsers list HTML, even next to JSON
+    types.iter().any(|media| media.eq_ignore_ascii_case("application/json"))
+        && !types.iter().any(|media| media.eq_ignore_ascii_case("text/html"))
+}
+
+/// Format a file size in bytes with a binary unit
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    
+    if unit == 0 {
+        format!("{} {}", size, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn media_types_and_large_files_are_streamed() {
+        let handler = StaticFileHandler::new(".", false, "index.html".to_string())
+            .with_streaming(Some(1024), vec!["video/".to_string(), "text/event-stream".to_string()]);
+        
+        assert!(handler.should_stream("video/mp4", 10));
+        assert!(handler.should_stream("text/event-stream", 10));
+        assert!(handler.should_stream("text/plain", 2048));
+        assert!(!handler.should_stream("text/plain", 1024));
+        assert!(!handler.should_stream("image/png", 10));
+    }
+    
+    #[tokio::test]
+    async fn reads_tolerate_files_changing_size_after_the_stat() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.txt");
+        std::fs::write(&path, "0123456789").unwrap();
+        
+        // Truncated between stat and read: the remaining bytes are served
+        let mut file = File::open(&path).await.unwrap();
+        let len = file.metadata().await.unwrap().len();
+        std::fs::write(&path, "0123").unwrap();
+        assert_eq!(read_reported(&mut file, len).await.unwrap(), b"0123");
+        
+        // Grown between stat and read: no more than the reported length
+        let mut file = File::open(&path).await.unwrap();
+        let len = file.metadata().await.unwrap().len();
+        std::fs::write(&path, "0123456789").unwrap();
+        assert_eq!(read_reported(&mut file, len).await.unwrap(), b"0123");
+        
+        let empty = dir.path().join("empty.txt");
+        std::fs::write(&empty, "").unwrap();
+        assert!(read_reported(&mut File::open(&empty).await.unwrap(), 0).await.unwrap().is_empty());
+    }
+    
+    #[test]
+    fn everything_is_buffered_by_default() {
+        let handler = StaticFileHandler::new(".", false, "index.html".to_string());
+        
+        assert!(!handler.should_stream("video/mp4", u64::MAX));
+    }
+    
+    #[test]
+    fn encoded_and_backslash_traversal_is_forbidden() {
+        let handler = StaticFileHandler::new(".", false, "index.html".to_string());
+        
+        for path in ["/../etc/passwd", "/%2e%2e/etc/passwd", "/..\\..\\etc\\passwd", "/a/%2E%2E/..", "/..%5c..%5cetc%5cpasswd"] {
+            assert!(matches!(handler.get_file_path(path), Err(HttpError::Forbidden(_))), "{}", path);
+        }
+        assert!(matches!(handler.get_file_path("/a%2fb"), Err(HttpError::BadRequest(_))));
+    }
+    
+    #[test]
+    fn symlinks_out_of_the_root_are_forbidden_unless_followed() {
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret.txt"), "secret").unwrap();
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join("inside.txt"), "inside").unwrap();
+        std::os::unix::fs::symlink(outside.path().join("secret.txt"), root.path().join("escape.txt")).unwrap();
+        std::os::unix::fs::symlink(root.path().join("inside.txt"), root.path().join("alias.txt")).unwrap();
+        
+        let handler = StaticFileHandler::new(root.path(), false, "index.html".to_string());
+        let escape = handler.get_file_path("/escape.txt").unwrap();
+        assert!(matches!(handler.check_contained(&escape), Err(HttpError::Forbidden(_))));
+        assert!(handler.check_contained(&handler.get_file_path("/alias.txt").unwrap()).is_ok());
+        assert!(handler.check_contained(&handler.get_file_path("/missing.txt").unwrap()).is_ok());
+        
+        let handler = handler.with_follow_symlinks(true);
+        assert!(handler.check_contained(&escape).is_ok());
     }
 }
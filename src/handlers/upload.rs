@@ -0,0 +1,202 @@
+use async_trait::async_trait;
+use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE, IF_MATCH, IF_NONE_MATCH, LOCATION};
+use hyper::{Body, Request, Response, StatusCode};
+use mime_guess::from_path;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::{debug, info, warn};
+
+use crate::core::config::{Config, UploadConfig};
+use crate::core::error::HttpError;
+use crate::handlers::common::Handler;
+use crate::network::http::path::{decode_segments, PathError};
+use crate::network::http::response::ResponseBuilder;
+use crate::utils::upload::{stream_to_file, UploadError};
+
+/// Methods accepted by upload endpoints when none are configured
+pub const DEFAULT_UPLOAD_METHODS: [&str; 2] = ["PUT", "POST"];
+
+/// Handler storing request bodies as files below a directory.
+///
+/// The part of the request path after the route pattern's fixed prefix names
+/// the file, so `PUT /upload/docs/a.pdf` on `/upload/*` writes `docs/a.pdf`.
+/// POST behaves like PUT; bodies are stored as sent, without form decoding.
+#[derive(Clone)]
+pub struct UploadHandler {
+    /// Route pattern served by this handler
+    pattern: String,
+    /// Directory files are stored in
+    directory: PathBuf,
+    /// Maximum size of an uploaded file
+    max_size: Option<u64>,
+    /// MIME types or prefixes files may have, or `None` for any type
+    allowed_types: Option<Vec<String>>,
+    /// Whether existing files may be replaced
+    overwrite: bool,
+}
+
+impl UploadHandler {
+    /// Create a handler from an upload configuration
+    pub fn from_config(upload: &UploadConfig) -> Self {
+        UploadHandler {
+            pattern: upload.path.clone(),
+            directory: PathBuf::from(&upload.directory),
+            max_size: upload.max_size,
+            allowed_types: upload.allowed_types.clone(),
+            overwrite: upload.overwrite.unwrap_or(true),
+        }
+    }
+    
+    /// Resolve the file a request path names below the upload directory.
+    ///
+    /// Missing parent directories are created, but the parent must stay inside
+    /// the upload directory once symlinks are resolved.
+    async fn target_path(&self, path: &str) -> Result<PathBuf, HttpError> {
+        let prefix = self.pattern.split('*').next().unwrap_or_default();
+        let relative = path.strip_prefix(prefix).unwrap_or_default();
+        let segments = match decode_segments(relative) {
+            Ok(segments) => segments,
+            Err(PathError::Traversal) => {
+                warn!("Refusing traversal in upload path {}", path);
+                return Err(HttpError::Forbidden("Access denied.".to_string()));
+            }
+            Err(_) => {
+                warn!("Refusing malformed upload path {}", path);
+                return Err(HttpError::BadRequest("Malformed request path.".to_string()));
+            }
+        };
+        if segments.is_empty() {
+            return Err(HttpError::BadRequest("The request path does not name a file.".to_string()));
+        }
+        
+        let mut target = self.directory.clone();
+        for segment in segments {
+            if segment == "." || segment == ".." {
+                warn!("Refusing traversal in upload path {}", path);
+                return Err(HttpError::Forbidden("Access denied.".to_string()));
+            }
+            target.push(segment.as_ref());
+        }
+        
+        // Check the deepest existing directory before creating anything below it
+        let parent = target.parent().unwrap_or(&self.directory);
+        let existing = parent.ancestors().find(|ancestor| ancestor.exists()).unwrap_or(&self.directory);
+        let root = fs::canonicalize(&self.directory).await.map_err(|e| HttpError::Internal(e.to_string()))?;
+        let resolved = fs::canonicalize(existing).await.map_err(|e| HttpError::Internal(e.to_string()))?;
+        if !resolved.starts_with(&root) || !resolved.is_dir() {
+            warn!("Refusing upload to {}, which resolves outside {}", target.display(), root.display());
+            return Err(HttpError::Forbidden("Access denied.".to_string()));
+        }
+        fs::create_dir_all(parent).await.map_err(|e| HttpError::Internal(e.to_string()))?;
+        
+        Ok(target)
+    }
+    
+    /// Check that both the declared content type and the type implied by the file name are allowed
+    fn check_type(&self, req: &Request<Body>, target: &Path) -> Result<(), HttpError> {
+        let Some(allowed_types) = &self.allowed_types else {
+            return Ok(());
+        };
+        let is_allowed = |mime: &str| {
+            let mime = mime.split(';').next().unwrap_or_default().trim();
+            allowed_types.iter().any(|allowed| {
+                if allowed.ends_with('/') {
+                    mime.len() > allowed.len() && mime[..allowed.len()].eq_ignore_ascii_case(allowed)
+                } else {
+                    mime.eq_ignore_ascii_case(allowed)
+                }
+            })
+        };
+        
+        let declared = req.headers().get(CONTENT_TYPE).and_then(|value| value.to_str().ok()).unwrap_or_default();
+        if !is_allowed(declared) {
+            debug!("Refusing upload of type '{}' to {}", declared, target.display());
+            return Err(HttpError::UnsupportedMediaType);
+        }
+        
+        // A name such as page.html would be served as its extension says, whatever was declared
+        if let Some(implied) = from_path(target).first() {
+            if !is_allowed(implied.essence_str()) {
+                debug!("Refusing upload named as {} to {}", implied, target.display());
+                return Err(HttpError::UnsupportedMediaType);
+            }
+        }
+        
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler for UploadHandler {
+    async fn handle(&self, req: Request<Body>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        // Refuse a body declared too large before reading any of it
+        let declared_size = req
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        if matches!((declared_size, self.max_size), (Some(size), Some(max)) if size > max) {
+            return Err(HttpError::PayloadTooLarge.into());
+        }
+        
+        let target = self.target_path(req.uri().path()).await?;
+        self.check_type(&req, &target)?;
+        
+        let existed = fs::symlink_metadata(&target).await.is_ok();
+        if existed && target.is_dir() {
+            return Err(HttpError::Conflict("The target is a directory.".to_string()).into());
+        }
+        
+        // `If-None-Match: *` asks to create only, `If-Match: *` to replace only
+        let wildcard = |name| req.headers().get(name).is_some_and(|value| value.as_bytes().trim_ascii() == b"*");
+        if (existed && wildcard(IF_NONE_MATCH)) || (!existed && wildcard(IF_MATCH)) {
+            return Err(HttpError::PreconditionFailed.into());
+        }
+        if existed && !self.overwrite {
+            return Err(HttpError::Conflict("A file with this name already exists.".to_string()).into());
+        }
+        
+        let location = req.uri().path().to_string();
+        match stream_to_file(req.into_body(), &target, self.max_size).await {
+            Ok(written) => info!("Stored upload {} ({} bytes)", target.display(), written),
+            Err(UploadError::TooLarge) => return Err(HttpError::PayloadTooLarge.into()),
+            Err(UploadError::InvalidTarget) => return Err(HttpError::BadRequest("Invalid upload target.".to_string()).into()),
+            Err(UploadError::Body(e)) => return Err(e.into()),
+            Err(UploadError::Io(e)) => return Err(e.into()),
+        }
+        
+        if existed {
+            Ok(ResponseBuilder::with_status(StatusCode::NO_CONTENT).build())
+        } else {
+            Ok(ResponseBuilder::with_status(StatusCode::CREATED).header(LOCATION.as_str(), &location).build())
+        }
+    }
+}
+
+/// Upload handlers by route pattern
+#[derive(Default)]
+pub struct UploadEndpoints {
+    /// Handlers by route pattern
+    handlers: HashMap<String, UploadHandler>,
+}
+
+impl UploadEndpoints {
+    /// Build a handler for every configured upload endpoint
+    pub fn from_config(config: &Config) -> Self {
+        let handlers = config
+            .upload
+            .iter()
+            .flatten()
+            .map(|upload| (upload.path.clone(), UploadHandler::from_config(upload)))
+            .collect();
+        
+        UploadEndpoints { handlers }
+    }
+    
+    /// Get the handler for a route pattern
+    pub fn get(&self, pattern: &str) -> Option<&UploadHandler> {
+        self.handlers.get(pattern)
+    }
+}
@@ -0,0 +1,160 @@
+use async_trait::async_trait;
+use hyper::body::HttpBody;
+use hyper::{Body, Request, Response};
+use std::collections::HashMap;
+use std::error::Error;
+use std::net::SocketAddr;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tracing::{debug, error};
+
+use crate::core::config::{Config, UwsgiConfig};
+use crate::core::error::HttpError;
+use crate::handlers::cgi::{application_environment, output_error, parse_cgi_response, sized_body};
+use crate::handlers::common::Handler;
+use crate::utils::memory::{read_to_end_reserved, MemoryBudget};
+
+/// Packet modifier of WSGI requests
+const MODIFIER_WSGI: u8 = 0;
+
+/// Largest size of the variable block, which the packet header stores in 16 bits
+const MAX_DATA_SIZE: usize = 0xFFFF;
+
+/// uwsgi protocol handler.
+///
+/// Each request opens a connection carrying a packet of environment variables
+/// followed by the body; the backend answers with an HTTP response head and
+/// body and closes the connection.
+#[derive(Clone)]
+pub struct UwsgiHandler {
+    /// uwsgi server address
+    server_addr: SocketAddr,
+    /// Route pattern served by this handler
+    pattern: String,
+    /// Document root used for `SCRIPT_FILENAME` and `DOCUMENT_ROOT`
+    document_root: String,
+    /// Budget buffered request bodies and responses are accounted against
+    memory_budget: MemoryBudget,
+    /// Packet modifier selecting the application type
+    modifier1: u8,
+}
+
+impl UwsgiHandler {
+    /// Create a new uwsgi handler for WSGI applications
+    pub fn new(server_addr: SocketAddr, pattern: String, document_root: String) -> Self {
+        UwsgiHandler {
+            server_addr,
+            pattern,
+            document_root,
+            memory_budget: MemoryBudget::unlimited(),
+            modifier1: MODIFIER_WSGI,
+        }
+    }
+    
+    /// Set the packet modifier selecting the application type
+    pub fn with_modifier1(mut self, modifier1: u8) -> Self {
+        self.modifier1 = modifier1;
+        self
+    }
+    
+    /// Account buffered request bodies and responses against a shared memory budget
+    pub fn with_memory_budget(mut self, memory_budget: MemoryBudget) -> Self {
+        self.memory_budget = memory_budget;
+        self
+    }
+    
+    /// Create a handler from a backend configuration
+    pub fn from_config(backend: &UwsgiConfig, document_root: &str) -> Self {
+        Self::new(backend.address, backend.path.clone(), document_root.to_string())
+            .with_modifier1(backend.modifier1.unwrap_or(MODIFIER_WSGI))
+    }
+    
+    /// Get the route pattern this handler serves
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+    
+    /// Encode the packet header and variables.
+    ///
+    /// Each variable is a 16-bit little-endian length and bytes for its name,
+    /// then the same for its value.
+    fn encode_packet(&self, params: &[(String, String)]) -> Result<Vec<u8>, HttpError> {
+        let mut vars = Vec::new();
+        for (name, value) in params {
+            for field in [name, value] {
+                let len = u16::try_from(field.len()).map_err(|_| HttpError::HeaderFieldsTooLarge)?;
+                vars.extend_from_slice(&len.to_le_bytes());
+                vars.extend_from_slice(field.as_bytes());
+            }
+        }
+        
+        if vars.len() > MAX_DATA_SIZE {
+            return Err(HttpError::HeaderFieldsTooLarge);
+        }
+        
+        let mut packet = Vec::with_capacity(4 + vars.len());
+        packet.push(self.modifier1);
+        packet.extend_from_slice(&(vars.len() as u16).to_le_bytes());
+        packet.push(0); // modifier2
+        packet.extend(vars);
+        
+        Ok(packet)
+    }
+}
+
+/// uwsgi handlers for all configured backends
+#[derive(Clone, Default)]
+pub struct UwsgiBackends {
+    /// Handlers by route pattern
+    handlers: HashMap<String, UwsgiHandler>,
+}
+
+impl UwsgiBackends {
+    /// Build a handler for every configured backend, sharing one memory budget
+    pub fn from_config(config: &Config, memory_budget: &MemoryBudget) -> Self {
+        let handlers = config
+            .uwsgi
+            .iter()
+            .flatten()
+            .map(|backend| {
+                let handler = UwsgiHandler::from_config(backend, &config.static_files.root_dir)
+                    .with_memory_budget(memory_budget.clone());
+                (handler.pattern().to_string(), handler)
+            })
+            .collect();
+        
+        UwsgiBackends { handlers }
+    }
+    
+    /// Get the handler for a route pattern
+    pub fn get(&self, pattern: &str) -> Option<&UwsgiHandler> {
+        self.handlers.get(pattern)
+    }
+}
+
+#[async_trait]
+impl Handler for UwsgiHandler {
+    async fn handle(&self, req: Request<Body>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        debug!("Handling uwsgi request for: {}", req.uri().path());
+        
+        let (req, mut body, content_length) = sized_body(req, &self.memory_budget).await?;
+        let params = application_environment(&req, &self.document_root, content_length)?;
+        let packet = self.encode_packet(&params)?;
+        
+        let mut stream = TcpStream::connect(self.server_addr).await.map_err(|e| {
+            error!("Failed to connect to uwsgi server {}: {}", self.server_addr, e);
+            HttpError::BadGateway(e.to_string())
+        })?;
+        
+        stream.write_all(&packet).await?;
+        while let Some(chunk) = body.data().await {
+            stream.write_all(&chunk?).await?;
+        }
+        
+        let (output, reservation) = read_to_end_reserved(&mut stream, &self.memory_budget)
+            .await
+            .map_err(|e| output_error("uwsgi", e))?;
+        
+        Ok(parse_cgi_response(&output, reservation)?)
+    }
+}
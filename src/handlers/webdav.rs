@@ -0,0 +1,647 @@
+use hyper::header::{CONTENT_LENGTH, TRANSFER_ENCODING};
+use hyper::{Body, Request, Response, StatusCode};
+use mime_guess::from_path;
+use regex::Regex;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Write;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::fs;
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+use crate::core::config::Config;
+use crate::core::error::HttpError;
+use crate::handlers::static_files::StaticFileHandler;
+use crate::network::http::path::encode_segment;
+use crate::network::http::response::ResponseBuilder;
+use crate::routing::router::wildcard_regex;
+use crate::utils::etag::mtime_etag;
+use crate::utils::memory::buffer_body;
+use crate::utils::upload::{stream_to_file, UploadError};
+
+/// Methods answered on WebDAV paths
+const ALLOWED_METHODS: &str = "GET, HEAD, OPTIONS, PROPFIND, PUT, DELETE, MKCOL, COPY, MOVE, LOCK, UNLOCK";
+
+/// Methods answered on WebDAV paths in read-only mode
+const READ_ONLY_METHODS: &str = "GET, HEAD, OPTIONS, PROPFIND";
+
+/// Lock timeout granted when the client does not ask for one
+const DEFAULT_LOCK_TIMEOUT: u64 = 600;
+
+/// Write lock held on a resource
+struct Lock {
+    /// Token the holder submits to write to the resource
+    token: String,
+    /// Owner description supplied by the client, as text
+    owner: Option<String>,
+    /// Whether the lock covers every member of a collection
+    infinite: bool,
+    /// Time the lock was granted for, from its last refresh
+    timeout: Duration,
+    /// When the lock expires unless refreshed
+    expires: Instant,
+    /// Request path of the locked resource
+    href: String,
+}
+
+/// WebDAV file sharing on top of the static file handler.
+///
+/// Paths covered by the configured patterns accept the WebDAV methods in
+/// addition to GET and HEAD. Locks are exclusive write locks kept in memory;
+/// a write to a locked resource must name the lock token in its `If` header.
+/// Who may write is left to the ACL and authentication settings.
+pub struct WebDav {
+    /// Path patterns served over WebDAV
+    paths: Vec<Regex>,
+    /// Whether only reading methods are allowed
+    read_only: bool,
+    /// Maximum size of a file uploaded with PUT
+    max_upload_size: Option<u64>,
+    /// Longest time a lock is granted for
+    max_lock_timeout: Duration,
+    /// Active locks by locked file path
+    locks: Mutex<HashMap<PathBuf, Lock>>,
+}
+
+impl WebDav {
+    /// Build the WebDAV settings configured in `webdav`, if any
+    pub fn from_config(config: &Config) -> Result<Option<Self>, regex::Error> {
+        let Some(webdav_config) = &config.webdav else {
+            return Ok(None);
+        };
+        
+        let paths = webdav_config
+            .paths
+            .iter()
+            .map(|path| wildcard_regex(path))
+            .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(Some(WebDav {
+            paths,
+            read_only: webdav_config.read_only.unwrap_or(false),
+            max_upload_size: webdav_config.max_upload_size,
+            max_lock_timeout: Duration::from_secs(webdav_config.max_lock_timeout.unwrap_or(DEFAULT_LOCK_TIMEOUT)),
+            locks: Mutex::new(HashMap::new()),
+        }))
+    }
+    
+    /// Check if a request path is served over WebDAV
+    pub fn covers(&self, path: &str) -> bool {
+        self.paths.iter().any(|pattern| pattern.is_match(path))
+    }
+    
+    /// Handle a request for a covered path, reading and writing files through `files`
+    pub async fn handle(&self, req: Request<Body>, files: &StaticFileHandler) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let method = req.method().clone();
+        let allow = if self.read_only { READ_ONLY_METHODS } else { ALLOWED_METHODS };
+        
+        match method.as_str() {
+            "GET" | "HEAD" => files.handle_files(req).await,
+            "OPTIONS" => Ok(ResponseBuilder::new()
+                .header("dav", "1, 2")
+                .header("allow", allow)
+                .header("ms-author-via", "DAV")
+                .build()),
+            "PROPFIND" => self.propfind(req, files).await,
+            "PUT" | "DELETE" | "MKCOL" | "COPY" | "MOVE" | "LOCK" | "UNLOCK" if self.read_only => {
+                Err(HttpError::MethodNotAllowed { allow: allow.to_string() }.into())
+            }
+            "PUT" => self.put(req, files).await,
+            "DELETE" => self.delete(req, files).await,
+            "MKCOL" => self.mkcol(req, files).await,
+            "COPY" | "MOVE" => self.copy_or_move(req, files, method == "MOVE").await,
+            "LOCK" => self.lock(req, files).await,
+            "UNLOCK" => self.unlock(req, files),
+            _ => Err(HttpError::MethodNotAllowed { allow: allow.to_string() }.into()),
+        }
+    }
+    
+    /// List the properties of a resource and, at depth 1, of its members.
+    ///
+    /// Every request is answered as `allprop`; no dead properties are stored.
+    async fn propfind(&self, req: Request<Body>, files: &StaticFileHandler) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let with_members = match header_str(&req, "depth") {
+            Some("0") => false,
+            Some("1") => true,
+            _ => return Err(HttpError::Forbidden("PROPFIND with infinite depth is not supported.".to_string()).into()),
+        };
+        
+        let href = req.uri().path().to_string();
+        let path = files.resolve_writable(&href)?;
+        let metadata = match fs::metadata(&path).await {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(HttpError::NotFound.into()),
+            Err(e) => return Err(e.into()),
+        };
+        
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n");
+        self.write_response(&mut xml, &href, &path, &metadata);
+        
+        if with_members && metadata.is_dir() {
+            let base = href.trim_end_matches('/');
+            let mut read_dir = fs::read_dir(&path).await?;
+            while let Some(entry) = read_dir.next_entry().await? {
+                let name = entry.file_name().to_string_lossy().to_string();
+                // Uploads still being written are not resources yet
+                if name.starts_with('.') && name.ends_with(".upload") {
+                    continue;
+                }
+                let Ok(metadata) = fs::metadata(entry.path()).await else {
+                    continue;
+                };
+                if !files.is_visible(&entry.path(), metadata.is_dir()) {
+                    continue;
+                }
+                let mut member_href = format!("{}/{}", base, encode_segment(&name));
+                if metadata.is_dir() {
+                    member_href.push('/');
+                }
+                self.write_response(&mut xml, &member_href, &entry.path(), &metadata);
+            }
+        }
+        
+        xml.push_str("</D:multistatus>\n");
+        Ok(ResponseBuilder::with_status(StatusCode::MULTI_STATUS)
+            .content_type("application/xml; charset=utf-8")
+            .body_string(xml)
+            .build())
+    }
+    
+    /// Append the multistatus response for one resource
+    fn write_response(&self, xml: &mut String, href: &str, path: &Path, metadata: &Metadata) {
+        let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        let modified = metadata.modified().ok();
+        
+        let _ = write!(xml, "<D:response><D:href>{}</D:href><D:propstat><D:prop>", escape_xml(href));
+        let _ = write!(xml, "<D:displayname>{}</D:displayname>", escape_xml(&name));
+        if metadata.is_dir() {
+            xml.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+        } else {
+            let mime = from_path(path).first_or_octet_stream();
+            xml.push_str("<D:resourcetype/>");
+            let _ = write!(xml, "<D:getcontentlength>{}</D:getcontentlength>", metadata.len());
+            let _ = write!(xml, "<D:getcontenttype>{}</D:getcontenttype>", escape_xml(mime.as_ref()));
+            let _ = write!(xml, "<D:getetag>{}</D:getetag>", escape_xml(&mtime_etag(modified, metadata.len())));
+        }
+        if let Some(modified) = modified {
+            let _ = write!(xml, "<D:getlastmodified>{}</D:getlastmodified>", httpdate::fmt_http_date(modified));
+        }
+        xml.push_str(
+            "<D:supportedlock><D:lockentry><D:lockscope><D:exclusive/></D:lockscope>\
+             <D:locktype><D:write/></D:locktype></D:lockentry></D:supportedlock>",
+        );
+        
+        xml.push_str("<D:lockdiscovery>");
+        let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+        purge_expired(&mut locks);
+        if let Some(lock) = locks.get(path) {
+            write_active_lock(xml, lock);
+        }
+        drop(locks);
+        xml.push_str("</D:lockdiscovery>");
+        
+        xml.push_str("</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n");
+    }
+    
+    /// Store the request body as a file, replacing any previous content
+    async fn put(&self, req: Request<Body>, files: &StaticFileHandler) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let path = files.resolve_writable(req.uri().path())?;
+        if path.is_dir() {
+            return Err(HttpError::MethodNotAllowed { allow: ALLOWED_METHODS.to_string() }.into());
+        }
+        check_parent(&path)?;
+        
+        let existed = path.exists();
+        self.check_unlocked(&req, &path, false, !existed)?;
+        
+        match stream_to_file(req.into_body(), &path, self.max_upload_size).await {
+            Ok(written) => info!("Stored {} ({} bytes) over WebDAV", path.display(), written),
+            Err(UploadError::TooLarge) => return Err(HttpError::PayloadTooLarge.into()),
+            Err(UploadError::InvalidTarget) => return Err(HttpError::Conflict("Invalid upload target.".to_string()).into()),
+            Err(UploadError::Body(e)) => return Err(e.into()),
+            Err(UploadError::Io(e)) => return Err(e.into()),
+        }
+        
+        Ok(created_or_replaced(existed))
+    }
+    
+    /// Delete a file or a collection with everything in it
+    async fn delete(&self, req: Request<Body>, files: &StaticFileHandler) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let path = files.resolve_writable(req.uri().path())?;
+        let metadata = match fs::symlink_metadata(&path).await {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(HttpError::NotFound.into()),
+            Err(e) => return Err(e.into()),
+        };
+        if files.is_root(&path) {
+            return Err(HttpError::Forbidden("The document root cannot be deleted.".to_string()).into());
+        }
+        self.check_unlocked(&req, &path, true, true)?;
+        
+        if metadata.is_dir() {
+            fs::remove_dir_all(&path).await?;
+        } else {
+            fs::remove_file(&path).await?;
+        }
+        self.release_locks(&path);
+        
+        info!("Deleted {} over WebDAV", path.display());
+        Ok(ResponseBuilder::with_status(StatusCode::NO_CONTENT).build())
+    }
+    
+    /// Create a collection
+    async fn mkcol(&self, req: Request<Body>, files: &StaticFileHandler) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        // No request body format for MKCOL is defined
+        let has_body = req.headers().contains_key(TRANSFER_ENCODING)
+            || header_str(&req, CONTENT_LENGTH.as_str()).is_some_and(|length| length.trim() != "0");
+        if has_body {
+            return Err(HttpError::UnsupportedMediaType.into());
+        }
+        
+        let path = files.resolve_writable(req.uri().path())?;
+        if fs::symlink_metadata(&path).await.is_ok() {
+            return Err(HttpError::MethodNotAllowed { allow: ALLOWED_METHODS.to_string() }.into());
+        }
+        check_parent(&path)?;
+        self.check_unlocked(&req, &path, false, true)?;
+        
+        fs::create_dir(&path).await?;
+        info!("Created collection {} over WebDAV", path.display());
+        Ok(ResponseBuilder::with_status(StatusCode::CREATED).build())
+    }
+    
+    /// Copy or move a resource to the path named by the `Destination` header
+    async fn copy_or_move(
+        &self,
+        req: Request<Body>,
+        files: &StaticFileHandler,
+        is_move: bool,
+    ) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let source = files.resolve_writable(req.uri().path())?;
+        let source_metadata = match fs::symlink_metadata(&source).await {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(HttpError::NotFound.into()),
+            Err(e) => return Err(e.into()),
+        };
+        
+        let destination_href = header_str(&req, "destination")
+            .and_then(|destination| destination.parse::<hyper::Uri>().ok())
+            .map(|uri| uri.path().to_string())
+            .ok_or_else(|| HttpError::BadRequest("A valid Destination header is required.".to_string()))?;
+        if !self.covers(&destination_href) {
+            return Err(HttpError::Forbidden("The destination is not served over WebDAV.".to_string()).into());
+        }
+        let destination = files.resolve_writable(&destination_href)?;
+        
+        if destination == source || destination.starts_with(&source) {
+            return Err(HttpError::Forbidden("A resource cannot be copied or moved into itself.".to_string()).into());
+        }
+        if is_move && files.is_root(&source) {
+            return Err(HttpError::Forbidden("The document root cannot be moved.".to_string()).into());
+        }
+        
+        let shallow = match header_str(&req, "depth") {
+            Some("0") if !is_move => true,
+            None | Some("infinity") => false,
+            Some(_) => return Err(HttpError::BadRequest("Invalid Depth header.".to_string()).into()),
+        };
+        let overwrite = !header_str(&req, "overwrite").is_some_and(|value| value.trim().eq_ignore_ascii_case("F"));
+        
+        let existed = fs::symlink_metadata(&destination).await.is_ok();
+        if existed && !overwrite {
+            return Err(HttpError::PreconditionFailed.into());
+        }
+        check_parent(&destination)?;
+        if is_move {
+            self.check_unlocked(&req, &source, true, true)?;
+        }
+        self.check_unlocked(&req, &destination, true, true)?;
+        
+        if existed {
+            if destination.is_dir() {
+                fs::remove_dir_all(&destination).await?;
+            } else {
+                fs::remove_file(&destination).await?;
+            }
+            self.release_locks(&destination);
+        }
+        
+        if is_move {
+            fs::rename(&source, &destination).await?;
+            // Locks stay with the path they were taken on, which is now gone
+            self.release_locks(&source);
+            info!("Moved {} to {} over WebDAV", source.display(), destination.display());
+        } else {
+            let (from, to) = (source.clone(), destination.clone());
+            let is_dir = source_metadata.is_dir();
+            tokio::task::spawn_blocking(move || copy_recursive(&from, &to, is_dir && !shallow)).await??;
+            info!("Copied {} to {} over WebDAV", source.display(), destination.display());
+        }
+        
+        Ok(created_or_replaced(existed))
+    }
+    
+    /// Take or refresh an exclusive write lock
+    async fn lock(&self, req: Request<Body>, files: &StaticFileHandler) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let href = req.uri().path().to_string();
+        let path = files.resolve_writable(&href)?;
+        let timeout = self.requested_timeout(&req);
+        let submitted = submitted_tokens(&req, "if");
+        let infinite = match header_str(&req, "depth") {
+            None | Some("infinity") => true,
+            Some("0") => false,
+            Some(_) => return Err(HttpError::BadRequest("Invalid Depth header.".to_string()).into()),
+        };
+        
+        let (parts, body) = req.into_parts();
+        let (body, _reservation) = buffer_body(body, files.memory_budget()).await?;
+        let body = String::from_utf8_lossy(&body);
+        
+        // An empty body refreshes a lock named in the If header
+        if body.trim().is_empty() {
+            let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+            purge_expired(&mut locks);
+            let lock = locks
+                .iter_mut()
+                .find(|(locked, lock)| covers_path(locked, lock, &path) && submitted.contains(&lock.token))
+                .map(|(_, lock)| lock)
+                .ok_or(HttpError::PreconditionFailed)?;
+            lock.timeout = timeout;
+            lock.expires = Instant::now() + timeout;
+            debug!("Refreshed lock {} on {}", lock.token, lock.href);
+            
+            let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:prop xmlns:D=\"DAV:\"><D:lockdiscovery>");
+            write_active_lock(&mut xml, lock);
+            xml.push_str("</D:lockdiscovery></D:prop>\n");
+            return Ok(ResponseBuilder::new()
+                .content_type("application/xml; charset=utf-8")
+                .body_string(xml)
+                .build());
+        }
+        
+        if element_text(&body, "lockscope").is_some_and(|scope| scope.contains("shared")) {
+            debug!("Refusing shared lock on {}", parts.uri.path());
+            return Err(HttpError::NotImplemented.into());
+        }
+        let owner = element_text(&body, "owner").map(|owner| strip_tags(&owner)).filter(|owner| !owner.is_empty());
+        
+        // Locking an unmapped path reserves it with an empty file
+        let created = !path.exists();
+        if created {
+            check_parent(&path)?;
+        }
+        
+        let token = new_lock_token()?;
+        let lock = Lock {
+            token: token.clone(),
+            owner,
+            infinite,
+            timeout,
+            expires: Instant::now() + timeout,
+            href,
+        };
+        
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:prop xmlns:D=\"DAV:\"><D:lockdiscovery>");
+        write_active_lock(&mut xml, &lock);
+        xml.push_str("</D:lockdiscovery></D:prop>\n");
+        
+        {
+            let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+            purge_expired(&mut locks);
+            // Exclusive locks conflict with any other lock, whatever tokens are submitted
+            if locks.iter().any(|(locked, lock)| conflicts(locked, lock, &path, infinite, false)) {
+                return Err(HttpError::Locked.into());
+            }
+            if created {
+                std::fs::File::create(&path)?;
+            }
+            debug!("Granted lock {} on {} for {:?}", lock.token, lock.href, lock.timeout);
+            locks.insert(path, lock);
+        }
+        
+        let status = if created { StatusCode::CREATED } else { StatusCode::OK };
+        Ok(ResponseBuilder::with_status(status)
+            .header("lock-token", &format!("<{}>", token))
+            .content_type("application/xml; charset=utf-8")
+            .body_string(xml)
+            .build())
+    }
+    
+    /// Release the lock named in the `Lock-Token` header
+    fn unlock(&self, req: Request<Body>, files: &StaticFileHandler) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        let path = files.resolve_writable(req.uri().path())?;
+        let tokens = submitted_tokens(&req, "lock-token");
+        
+        let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+        purge_expired(&mut locks);
+        let locked = locks
+            .iter()
+            .find(|(locked, lock)| covers_path(locked, lock, &path) && tokens.contains(&lock.token))
+            .map(|(locked, _)| locked.clone())
+            .ok_or_else(|| HttpError::Conflict("The lock token does not match the resource.".to_string()))?;
+        if let Some(lock) = locks.remove(&locked) {
+            debug!("Released lock {} on {}", lock.token, lock.href);
+        }
+        
+        Ok(ResponseBuilder::with_status(StatusCode::NO_CONTENT).build())
+    }
+    
+    /// Check that a write to `path` is not blocked by a lock whose token the request does not submit.
+    ///
+    /// `members` also checks locks on resources inside a collection, and
+    /// `membership` locks on the parent collection, whose member list changes.
+    fn check_unlocked(&self, req: &Request<Body>, path: &Path, members: bool, membership: bool) -> Result<(), HttpError> {
+        let submitted = submitted_tokens(req, "if");
+        let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+        purge_expired(&mut locks);
+        
+        let blocking = locks
+            .iter()
+            .filter(|(_, lock)| !submitted.contains(&lock.token))
+            .find(|(locked, lock)| conflicts(locked, lock, path, members, membership));
+        match blocking {
+            Some((_, lock)) => {
+                debug!("{} is locked by {}", path.display(), lock.href);
+                Err(HttpError::Locked)
+            }
+            None => Ok(()),
+        }
+    }
+    
+    /// Drop the locks on a removed resource and everything inside it
+    fn release_locks(&self, path: &Path) {
+        let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+        locks.retain(|locked, _| !locked.starts_with(path));
+    }
+    
+    /// Get the lock timeout a request asks for, capped at the configured maximum
+    fn requested_timeout(&self, req: &Request<Body>) -> Duration {
+        // The header lists preferences such as "Second-3600, Infinite"; the first one understood wins
+        let requested = header_str(req, "timeout").and_then(|value| {
+            value.split(',').map(str::trim).find_map(|preference| {
+                if preference.eq_ignore_ascii_case("Infinite") {
+                    Some(self.max_lock_timeout)
+                } else {
+                    preference
+                        .strip_prefix("Second-")
+                        .and_then(|seconds| seconds.parse().ok())
+                        .map(Duration::from_secs)
+                }
+            })
+        });
+        
+        requested
+            .unwrap_or(Duration::from_secs(DEFAULT_LOCK_TIMEOUT))
+            .min(self.max_lock_timeout)
+    }
+}
+
+/// Check if a lock taken on `locked` applies to `path`
+fn covers_path(locked: &Path, lock: &Lock, path: &Path) -> bool {
+    locked == path || (lock.infinite && path.starts_with(locked))
+}
+
+/// Check if a lock taken on `locked` blocks a write to `path`
+fn conflicts(locked: &Path, lock: &Lock, path: &Path, members: bool, membership: bool) -> bool {
+    covers_path(locked, lock, path)
+        || (members && locked.starts_with(path))
+        || (membership && path.parent() == Some(locked))
+}
+
+/// Remove locks that have not been refreshed in time
+fn purge_expired(locks: &mut HashMap<PathBuf, Lock>) {
+    let now = Instant::now();
+    locks.retain(|_, lock| {
+        let active = lock.expires > now;
+        if !active {
+            debug!("Lock {} on {} expired", lock.token, lock.href);
+        }
+        active
+    });
+}
+
+/// Append the `activelock` element describing a lock
+fn write_active_lock(xml: &mut String, lock: &Lock) {
+    xml.push_str("<D:activelock><D:locktype><D:write/></D:locktype><D:lockscope><D:exclusive/></D:lockscope>");
+    let _ = write!(xml, "<D:depth>{}</D:depth>", if lock.infinite { "infinity" } else { "0" });
+    if let Some(owner) = &lock.owner {
+        let _ = write!(xml, "<D:owner>{}</D:owner>", escape_xml(owner));
+    }
+    let remaining = lock.expires.saturating_duration_since(Instant::now()).as_secs_f64().ceil() as u64;
+    let _ = write!(xml, "<D:timeout>Second-{}</D:timeout>", remaining);
+    let _ = write!(xml, "<D:locktoken><D:href>{}</D:href></D:locktoken>", escape_xml(&lock.token));
+    let _ = write!(xml, "<D:lockroot><D:href>{}</D:href></D:lockroot>", escape_xml(&lock.href));
+    xml.push_str("</D:activelock>");
+}
+
+/// Generate a unique lock token
+fn new_lock_token() -> Result<String, HttpError> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).map_err(|e| HttpError::Internal(format!("cannot generate lock token: {}", e)))?;
+    // Format as a version 4 UUID
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = bytes.iter().fold(String::with_capacity(32), |mut out, byte| {
+        let _ = write!(out, "{:02x}", byte);
+        out
+    });
+    Ok(format!(
+        "opaquelocktoken:{}-{}-{}-{}-{}",
+        &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..]
+    ))
+}
+
+/// Collect the lock tokens in a header, which are written as `<opaquelocktoken:...>`.
+///
+/// Other conditions of an `If` header, such as entity tags, are not evaluated.
+fn submitted_tokens(req: &Request<Body>, name: &str) -> Vec<String> {
+    header_str(req, name)
+        .map(|value| {
+            value
+                .split('<')
+                .filter_map(|part| part.split_once('>'))
+                .map(|(token, _)| token.trim().to_string())
+                .filter(|token| token.starts_with("opaquelocktoken:"))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Get a request header as a string
+fn header_str<'a>(req: &'a Request<Body>, name: &str) -> Option<&'a str> {
+    req.headers().get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Check that the parent collection of a new resource exists
+fn check_parent(path: &Path) -> Result<(), HttpError> {
+    match path.parent() {
+        Some(parent) if parent.is_dir() => Ok(()),
+        _ => Err(HttpError::Conflict("The parent collection does not exist.".to_string())),
+    }
+}
+
+/// Respond to a write with 201 Created for a new resource or 204 No Content for a replaced one
+fn created_or_replaced(existed: bool) -> Response<Body> {
+    let status = if existed { StatusCode::NO_CONTENT } else { StatusCode::CREATED };
+    ResponseBuilder::with_status(status).build()
+}
+
+/// Copy a file, or a collection with its members when `recursive` is set.
+///
+/// Symlinks are skipped, so a copy never pulls in files from outside the share.
+fn copy_recursive(from: &Path, to: &Path, recursive: bool) -> std::io::Result<()> {
+    let metadata = std::fs::symlink_metadata(from)?;
+    if metadata.is_file() {
+        std::fs::copy(from, to)?;
+        return Ok(());
+    }
+    if !metadata.is_dir() {
+        return Ok(());
+    }
+    
+    std::fs::create_dir(to)?;
+    if recursive {
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()), true)?;
+        }
+    }
+    Ok(())
+}
+
+/// Get the content of the first element with the given local name, whatever its namespace prefix
+fn element_text(xml: &str, name: &str) -> Option<String> {
+    let open = Regex::new(&format!(r"<(?:[\w.-]+:)?{}(?:\s[^>]*)?>", regex::escape(name))).ok()?;
+    let close = Regex::new(&format!(r"</(?:[\w.-]+:)?{}\s*>", regex::escape(name))).ok()?;
+    let start = open.find(xml)?.end();
+    let end = close.find_at(xml, start)?.start();
+    Some(xml[start..end].to_string())
+}
+
+/// Remove the markup from an XML fragment, keeping its text
+fn strip_tags(xml: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in xml.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.trim().to_string()
+}
+
+/// Escape text for use in XML content
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,34 @@
+//! Kaserve, a web server inspired by lighttpd.
+//!
+//! Besides running as the `kaserve` binary, the server can be embedded in other
+//! programs, serving static assets, backends and services mounted by the
+//! application from the same process:
+//!
+//! ```no_run
+//! use hyper::{Body, Request, Response};
+//! use kaserve::{Config, Server, ServiceHandler};
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+//! let server = Server::builder(Config::from_file("config.toml")?)
+//!     .service("/api/*", ServiceHandler::from_fn(|_req: Request<Body>| async {
+//!         Ok::<_, hyper::Error>(Response::new(Body::from("hello")))
+//!     }))
+//!     .build()?;
+//! server.run().await
+//! # }
+//! ```
+
+pub mod core;
+pub mod network;
+pub mod handlers;
+pub mod routing;
+pub mod plugins;
+pub mod security;
+pub mod utils;
+
+pub use crate::core::config::{Config, ConfigError};
+pub use crate::core::server::{Server, ServerBuilder, ServerError, ServerState};
+pub use crate::handlers::common::Handler;
+pub use crate::handlers::service::ServiceHandler;
+pub use crate::plugins::api::{Plugin, PluginContext, WebSocketHandler};
+pub use crate::routing::router::{Route, Router, RouterError};
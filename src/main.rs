@@ -1,40 +1,156 @@
-mod core;
-mod network;
-mod handlers;
-mod routing;
-mod plugins;
-mod security;
-mod utils;
-
-use tracing::{info, Level};
-use tracing_subscriber::FmtSubscriber;
+use tracing::info;
 use std::error::Error;
+use std::path::{Path, PathBuf};
 
-use crate::core::config::Config;
-use crate::core::server::Server;
+use kaserve::core::overrides::ConfigOverride;
+use kaserve::core::runtime::build_runtime;
+use kaserve::utils::build_info;
+use kaserve::utils::logging::init_logging;
+use kaserve::{Config, ConfigError, Server};
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    // Initialize logging
-    setup_logging();
+/// Configuration file read when `--config` is not given
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// Usage text printed by `--help`
+const USAGE: &str = "\
+Usage: kaserve [COMMAND] [OPTIONS]
+
+Commands:
+  serve                      Run the server (default)
+  check                      Validate the configuration and exit, non-zero on errors
+  print-config               Print the effective configuration as TOML
+
+Options:
+  -c, --config <PATH>        Configuration file (default: config.toml, skipped if absent)
+  -s, --set <FIELD=VALUE>    Override a configuration field, e.g. server.port=8080
+      --log-level <LEVEL>    Server log level, like --set logging.level=<LEVEL>
+  -V, --version              Print version information
+  -h, --help                 Print this help
+
+Fields can also be overridden with environment variables such as
+KASERVE_SERVER__PORT=8080, where double underscores separate field names.
+Precedence: --set > environment > configuration file > defaults.";
+
+/// Action requested on the command line
+#[derive(Default, PartialEq, Eq)]
+enum Command {
+    /// Run the server
+    #[default]
+    Serve,
+    /// Validate the configuration
+    Check,
+    /// Print the effective configuration
+    PrintConfig,
+}
+
+/// Options given on the command line
+#[derive(Default)]
+struct CommandLine {
+    /// Action to perform
+    command: Command,
+    /// Configuration file given with `--config`
+    config_path: Option<PathBuf>,
+    /// Field overrides given with `--set`, in order
+    overrides: Vec<ConfigOverride>,
+}
+
+impl CommandLine {
+    /// Parse the arguments, or return `None` after printing help or version information
+    fn parse<I: Iterator<Item = String>>(args: I) -> Result<Option<Self>, Box<dyn Error + Send + Sync>> {
+        let mut command_line = CommandLine::default();
+        let mut args = args.peekable();
+        
+        // The command, if any, comes first
+        let command = match args.peek().map(String::as_str) {
+            Some("serve") => Some(Command::Serve),
+            Some("check") => Some(Command::Check),
+            Some("print-config") => Some(Command::PrintConfig),
+            _ => None,
+        };
+        if let Some(command) = command {
+            command_line.command = command;
+            args.next();
+        }
+        
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+                _ => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline_value.clone().or_else(|| args.next()).ok_or_else(|| format!("{} requires a value", flag))
+            };
+            
+            match flag.as_str() {
+                "-V" | "--version" => {
+                    println!("{}", build_info::version_string());
+                    return Ok(None);
+                }
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    return Ok(None);
+                }
+                "-c" | "--config" => command_line.config_path = Some(PathBuf::from(value()?)),
+                "-s" | "--set" => command_line.overrides.push(ConfigOverride::parse(&value()?)?),
+                "--log-level" => command_line.overrides.push(ConfigOverride::parse(&format!("logging.level={}", value()?))?),
+                _ => return Err(format!("Unknown argument: {} (see --help)", arg).into()),
+            }
+        }
+        
+        Ok(Some(command_line))
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    // Print build information or help and exit without touching configuration
+    let Some(command_line) = CommandLine::parse(std::env::args().skip(1))? else {
+        return Ok(());
+    };
     
-    // Load configuration
-    let config = Config::from_file("config.toml")?;
+    // Load configuration, layering environment and command line overrides over the file
+    let config_path = command_line.config_path
+        .or_else(|| Some(PathBuf::from(DEFAULT_CONFIG_PATH)).filter(|path| Path::new(path).exists()));
+    let mut overrides = ConfigOverride::from_env(std::env::vars());
+    overrides.extend(command_line.overrides);
+    let config = Config::load(config_path.as_deref(), &overrides)?;
     
+    match command_line.command {
+        Command::Serve => {}
+        Command::Check => {
+            let source = config_path.as_ref().map(|path| path.display().to_string()).unwrap_or_else(|| "defaults".to_string());
+            match config.validate() {
+                Ok(()) => println!("{}: configuration is valid", source),
+                Err(ConfigError::Invalid(problems)) => {
+                    for problem in &problems {
+                        eprintln!("{}: {}", source, problem);
+                    }
+                    std::process::exit(1);
+                }
+                Err(e) => return Err(e.into()),
+            }
+            return Ok(());
+        }
+        Command::PrintConfig => {
+            print!("{}", toml::to_string_pretty(&config.resolved())?);
+            return Ok(());
+        }
+    }
+    
+    // Initialize logging
+    init_logging(config.logging.as_ref())?;
+    
+    match &config_path {
+        Some(path) => info!("Loaded configuration from {}", path.display()),
+        None => info!("No configuration file found, using defaults"),
+    }
     info!("Starting Kaserve web server on {}:{}", config.server.host, config.server.port);
     
-    // Create and run server
-    let server = Server::new(config);
-    server.run().await?;
+    // Create and run server on a runtime tuned by the server settings
+    let runtime = build_runtime(&config.server)?;
+    runtime.block_on(async {
+        let server = Server::new(config);
+        server.run().await
+    })?;
     
     Ok(())
 }
-
-fn setup_logging() {
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::INFO)
-        .finish();
-    
-    tracing::subscriber::set_global_default(subscriber)
-        .expect("Failed to set global default subscriber");
-}
@@ -1,73 +1,670 @@
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpStream;
-use hyper::{Body, Request, Response, Server, service::{make_service_fn, service_fn}};
+use hyper::{Body, Request, Response, Uri, Version, service::service_fn};
+use hyper::body::HttpBody;
+use hyper::header::{HeaderMap, HeaderValue};
+use futures::FutureExt;
+use hyper::http::uri::Scheme;
+use hyper::StatusCode;
 use hyper::server::conn::Http;
-use tracing::{error, info, debug};
+use tokio_rustls::TlsAcceptor;
+use tracing::{error, debug, warn};
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::convert::Infallible;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
 
+use crate::core::cache::FileCache;
 use crate::core::config::Config;
-use crate::handlers::static_files::StaticFileHandler;
-use crate::routing::router::Router;
+use crate::core::error::{ErrorPages, HttpError};
+use crate::core::middleware::{Endpoint, MiddlewareChain, Next};
+use crate::handlers::admin::AdminHandler;
+use crate::handlers::cgi::CgiScripts;
+use crate::handlers::common::Handler;
+use crate::handlers::fastcgi::FastCgiBackends;
+use crate::handlers::scgi::ScgiBackends;
+use crate::handlers::service::ServiceRoutes;
+use crate::handlers::uwsgi::UwsgiBackends;
+use crate::handlers::health::{HealthHandler, Readiness};
+use crate::handlers::proxy::ProxyPools;
+use crate::handlers::static_files::{StaticFileHandler, DEFAULT_SEND_BUFFER_SIZE};
+use crate::handlers::upload::UploadEndpoints;
+use crate::handlers::webdav::WebDav;
+use crate::network::http::forwarded::{PeerAddr, TrustedProxies};
+use crate::network::http::method::apply_method_override;
+use crate::network::http::path::{normalize_path, PathError};
+use crate::network::http::request::body_with_limit;
+use crate::network::http::response::{body_with_deadline, ResponseBuilder};
+use crate::network::http::upgrade::{accept_websocket, is_websocket_upgrade};
+use crate::network::connection_limit::ConnectionLimiter;
+use crate::network::idle::{body_while_active, ConnectionActivity, IdleStream};
+use crate::network::proxy_protocol;
+use crate::plugins::api::WebSocketHandler;
+use crate::plugins::pipeline::PluginPipeline;
+use crate::routing::limits::ConcurrencyLimits;
+use crate::routing::rewrite::Rewriter;
+use crate::security::acme::{Acme, ACME_TLS_ALPN};
+use crate::security::acl::Acl;
+use crate::security::cors::CorsPolicy;
+use crate::security::exclusion::ExclusionRules;
+use crate::security::auth::AuthPolicy;
+use crate::security::rate_limit::RateLimits;
+use crate::security::tls::{self, SniResolver};
+use crate::routing::router::{parse_host, RouteMatch, Router, RouterError, UnmatchedRoutes};
+use crate::utils::cache_policy::CachePolicy;
+use crate::utils::compression::Compressor;
+use crate::utils::etag::{EtagGenerator, EtagStrategy};
+use crate::utils::geoip::GeoIp;
+use crate::utils::logging::{AccessLogEntry, AccessLogs};
+use crate::utils::markdown::MarkdownRenderer;
+use crate::utils::memory::MemoryBudget;
+use crate::utils::metrics::Metrics;
 
-/// Handler for TCP connections that processes HTTP requests
-pub struct ConnectionHandler {
-    /// The TCP stream for this connection
-    stream: TcpStream,
+/// Shared handlers and state used to process requests on a connection
+#[derive(Clone)]
+struct RequestPipeline {
     /// Server configuration
     config: Arc<Config>,
+    /// Router for matching requests to handlers, bound to the connection's virtual host if any
+    router: Arc<Router>,
+    /// URL rewrite rules, if configured
+    rewriter: Option<Arc<Rewriter>>,
+    /// Static file, admin and health check handlers
+    handlers: Arc<RequestHandlers>,
+    /// Shared server metrics
+    metrics: Metrics,
+    /// Error page renderer
+    error_pages: Arc<ErrorPages>,
+    /// Access loggers
+    access_logs: Arc<AccessLogs>,
+    /// Per-route concurrency limits
+    concurrency_limits: Arc<ConcurrencyLimits>,
+    /// Middleware run around handler dispatch, including the built-in access checks
+    middleware: Arc<MiddlewareChain>,
+    /// GeoIP lookups of clients, if configured
+    geoip: Option<Arc<GeoIp>>,
+    /// Reverse proxy upstream pools
+    proxy_pools: Arc<ProxyPools>,
+    /// Proxies trusted to report the client address
+    trusted_proxies: Arc<TrustedProxies>,
+    /// Whether a peer without an address is a proxy trusted to name the client
+    trust_forwarded: bool,
+    /// FastCGI backends
+    fastcgi_backends: Arc<FastCgiBackends>,
+    /// SCGI backends
+    scgi_backends: Arc<ScgiBackends>,
+    /// uwsgi backends
+    uwsgi_backends: Arc<UwsgiBackends>,
+    /// CGI script routes
+    cgi_scripts: Arc<CgiScripts>,
+    /// Upload endpoints
+    upload_endpoints: Arc<UploadEndpoints>,
+    /// Services mounted by the embedding application
+    services: Arc<ServiceRoutes>,
+    /// Certificate provisioning answering HTTP-01 challenges, if configured
+    acme: Option<Arc<Acme>>,
+    /// Whether requests arrive over TLS
+    secure: bool,
+    /// Strict-Transport-Security header added to responses, on TLS connections with HSTS configured
+    hsts: Option<HeaderValue>,
+    /// Requests in progress on the connection, tracked when idle connections time out
+    activity: Option<ConnectionActivity>,
+    /// Requests the connection may still serve, when limited
+    requests_left: Option<Arc<AtomicUsize>>,
+    /// WebSocket handlers provided by plugins
+    websocket_handlers: Arc<Vec<Arc<dyn WebSocketHandler>>>,
+    /// Plugins run around handler dispatch
+    plugins: PluginPipeline,
 }
 
-impl ConnectionHandler {
-    /// Create a new connection handler
-    pub fn new(stream: TcpStream, config: Arc<Config>) -> Self {
+/// Server-wide state shared by every connection
+#[derive(Clone)]
+pub struct SharedState {
+    /// Limits on open connections
+    pub connection_limiter: Arc<ConnectionLimiter>,
+    /// Global budget for buffered request and response bodies
+    pub memory_budget: MemoryBudget,
+    /// Shared server metrics
+    pub metrics: Metrics,
+    /// In-memory static file cache, if configured
+    pub file_cache: Option<Arc<FileCache>>,
+    /// Memory-mapped static files, if configured
+    pub mapped_files: Option<Arc<FileCache>>,
+    /// Cache-Control policy for static files
+    pub cache_policy: Arc<CachePolicy>,
+    /// Error page renderer
+    pub error_pages: Arc<ErrorPages>,
+    /// Access loggers
+    pub access_logs: Arc<AccessLogs>,
+    /// URL rewrite rules, if configured
+    pub rewriter: Option<Arc<Rewriter>>,
+    /// Per-route concurrency limits
+    pub concurrency_limits: Arc<ConcurrencyLimits>,
+    /// Access control list, if configured
+    pub acl: Option<Arc<Acl>>,
+    /// Per-client rate limits
+    pub rate_limits: Arc<RateLimits>,
+    /// Authentication for protected paths, if configured
+    pub auth: Option<Arc<AuthPolicy>>,
+    /// Cross-origin resource sharing policy, if configured
+    pub cors: Option<Arc<CorsPolicy>>,
+    /// Middleware run around handler dispatch, including the built-in access checks
+    pub middleware: Arc<MiddlewareChain>,
+    /// GeoIP lookups of clients, if configured
+    pub geoip: Option<Arc<GeoIp>>,
+    /// WebDAV file sharing and its locks, if enabled
+    pub webdav: Option<Arc<WebDav>>,
+    /// Markdown renderer for static files, if enabled
+    pub markdown: Option<Arc<MarkdownRenderer>>,
+    /// Paths hidden from static serving and listings
+    pub exclusions: Arc<ExclusionRules>,
+    /// Reverse proxy upstream pools
+    pub proxy_pools: Arc<ProxyPools>,
+    /// Proxies trusted to report the client address
+    pub trusted_proxies: Arc<TrustedProxies>,
+    /// Readiness flags reported by the health check handler
+    pub readiness: Readiness,
+    /// FastCGI backends
+    pub fastcgi_backends: Arc<FastCgiBackends>,
+    /// SCGI backends
+    pub scgi_backends: Arc<ScgiBackends>,
+    /// uwsgi backends
+    pub uwsgi_backends: Arc<UwsgiBackends>,
+    /// CGI script routes
+    pub cgi_scripts: Arc<CgiScripts>,
+    /// Upload endpoints
+    pub upload_endpoints: Arc<UploadEndpoints>,
+    /// Services mounted by the embedding application
+    pub services: Arc<ServiceRoutes>,
+    /// ACME certificate provisioning, if configured
+    pub acme: Option<Arc<Acme>>,
+    /// TLS acceptor, when any listener terminates TLS
+    pub tls_acceptor: Option<TlsAcceptor>,
+    /// Certificates served by the TLS acceptor, reloaded and stapled in the background
+    pub tls_certificates: Option<Arc<SniResolver>>,
+    /// WebSocket handlers provided by plugins
+    pub websocket_handlers: Arc<Vec<Arc<dyn WebSocketHandler>>>,
+    /// Plugins run around handler dispatch
+    pub plugins: PluginPipeline,
+    /// Router and handlers shared by every connection, built when the event loop starts
+    pub handlers: Option<Arc<RequestHandlers>>,
+}
+
+impl SharedState {
+    /// Build the shared state from the server configuration
+    pub fn from_config(config: &Config) -> std::io::Result<Self> {
+        let rewriter = Rewriter::from_config(config)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let concurrency_limits = ConcurrencyLimits::from_config(config)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let acl = Acl::from_config(config)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?
+            .map(Arc::new);
+        let rate_limits = RateLimits::from_config(config)
+            .map(Arc::new)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let auth = AuthPolicy::from_config(config)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?
+            .map(Arc::new);
+        let cors = CorsPolicy::from_config(config)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?
+            .map(Arc::new);
+        let middleware = MiddlewareChain::from_config(config)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let geoip = GeoIp::from_config(config)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let webdav = WebDav::from_config(config)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let markdown = MarkdownRenderer::from_config(&config.static_files)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let exclusions = ExclusionRules::from_config(&config.static_files)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let cache_policy = CachePolicy::from_config(&config.static_files)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let proxy_pools = ProxyPools::from_config(config)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let trusted_proxies = TrustedProxies::from_config(config)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        // Listeners can terminate TLS even when the main listener does not
+        let listener_tls = config.listeners.iter().flatten().any(|listener| listener.tls.unwrap_or(false));
+        let acme = config.acme.as_ref()
+            .map(Acme::from_config)
+            .transpose()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?
+            .map(Arc::new);
+        let (tls_acceptor, tls_certificates) = match config.tls.as_ref().filter(|tls| tls.enabled || listener_tls) {
+            Some(tls) => {
+                let vhosts = config.virtual_hosts.as_deref().unwrap_or_default();
+                let resolver = SniResolver::from_config(tls, vhosts, acme.clone())
+                    .map(Arc::new)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+                let server_config = tls::build_server_config(tls, Arc::clone(&resolver), config.server.http2.unwrap_or(false))
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+                (Some(TlsAcceptor::from(server_config)), Some(resolver))
+            }
+            None => (None, None),
+        };
+        
+        let metrics = Metrics::new();
+        let error_pages = Arc::new(ErrorPages::from_config(config)?);
+        let middleware = middleware.with_builtins(
+            acl.clone(),
+            Arc::clone(&rate_limits),
+            cors.clone(),
+            auth.clone(),
+            metrics.clone(),
+            Arc::clone(&error_pages),
+        );
+        
+        let memory_budget = MemoryBudget::from_megabytes(config.server.memory_budget);
+        
+        Ok(SharedState {
+            connection_limiter: Arc::new(ConnectionLimiter::from_config(config)),
+            memory_budget: memory_budget.clone(),
+            metrics,
+            file_cache: FileCache::from_config(&config.static_files).map(Arc::new),
+            mapped_files: FileCache::mappings_from_config(&config.static_files).map(Arc::new),
+            cache_policy: Arc::new(cache_policy),
+            error_pages,
+            access_logs: Arc::new(AccessLogs::from_config(config)?),
+            rewriter: rewriter.map(Arc::new),
+            concurrency_limits: Arc::new(concurrency_limits),
+            acl,
+            rate_limits,
+            auth,
+            cors,
+            middleware: Arc::new(middleware),
+            geoip: geoip.map(Arc::new),
+            webdav: webdav.map(Arc::new),
+            markdown: markdown.map(Arc::new),
+            exclusions: Arc::new(exclusions),
+            proxy_pools: Arc::new(proxy_pools),
+            trusted_proxies: Arc::new(trusted_proxies),
+            readiness: Readiness::default(),
+            fastcgi_backends: Arc::new(FastCgiBackends::from_config(config, &memory_budget)),
+            scgi_backends: Arc::new(ScgiBackends::from_config(config, &memory_budget)),
+            uwsgi_backends: Arc::new(UwsgiBackends::from_config(config, &memory_budget)),
+            cgi_scripts: Arc::new(CgiScripts::from_config(config, &memory_budget)),
+            upload_endpoints: Arc::new(UploadEndpoints::from_config(config)),
+            services: Arc::new(ServiceRoutes::default()),
+            acme,
+            tls_acceptor,
+            tls_certificates,
+            websocket_handlers: Arc::new(Vec::new()),
+            plugins: PluginPipeline::default(),
+            handlers: None,
+        })
+    }
+}
+
+/// Router and handlers built once from the configuration and shared by every connection
+pub struct RequestHandlers {
+    /// Router of connections not bound to a virtual host
+    router: Arc<Router>,
+    /// Routers of listeners bound to a virtual host, by host pattern
+    bound_routers: HashMap<String, Arc<Router>>,
+    /// Default static file handler
+    static_handler: StaticFileHandler,
+    /// Static file handlers of virtual hosts, by host pattern
+    vhost_static_handlers: HashMap<String, StaticFileHandler>,
+    /// Admin endpoint handler, if enabled
+    admin_handler: Option<AdminHandler>,
+    /// Health check handler, if enabled
+    health_handler: Option<HealthHandler>,
+}
+
+impl RequestHandlers {
+    /// Build the router and handlers, including the services and plugins already set on the shared state
+    pub fn new(config: &Arc<Config>, shared: &SharedState) -> Self {
+        let mut router = Router::new(Arc::clone(config));
+        shared.services.add_routes(&mut router);
+        let router = Arc::new(router);
+        
+        // Listeners bound to a virtual host ignore the Host header
+        let mut bound_routers = HashMap::new();
+        for vhost in config.listeners.iter().flatten().filter_map(|listener| listener.vhost.as_ref()) {
+            bound_routers.entry(vhost.clone())
+                .or_insert_with(|| Arc::new(Router::clone(&router).bind_vhost(vhost)));
+        }
+        
+        // Create a static file handler
+        let mut static_handler = StaticFileHandler::new(
+            &config.static_files.root_dir,
+            config.static_files.directory_listing.unwrap_or(false),
+            config.static_files.default_file.clone().unwrap_or_else(|| "index.html".to_string()),
+        )
+        .with_memory_budget(shared.memory_budget.clone())
+        .with_max_listing_depth(config.static_files.max_listing_depth)
+        .with_streaming(
+            config.static_files.stream_threshold,
+            config.static_files.stream_types.clone().unwrap_or_default(),
+        )
+        .with_send_buffer_size(config.static_files.send_buffer_size.unwrap_or(DEFAULT_SEND_BUFFER_SIZE))
+        .with_etag_generator(EtagGenerator::new(
+            config.static_files.etag.unwrap_or(EtagStrategy::Mtime),
+            config.static_files.etag_cache_size,
+        ))
+        .with_attachments(
+            config.static_files.attachment_paths.as_deref().unwrap_or_default(),
+            config.static_files.attachment_types.clone().unwrap_or_default(),
+        )
+        .with_mime_sniffing(config.static_files.mime_sniffing.unwrap_or_default())
+        .with_save_data_variants(config.static_files.save_data_variants.unwrap_or(false))
+        .with_follow_symlinks(config.static_files.follow_symlinks.unwrap_or(false))
+        .with_spa_fallback(config.static_files.spa.unwrap_or(false))
+        .with_canonical_redirects(
+            config.static_files.trailing_slash_redirect.unwrap_or(true),
+            config.static_files.canonical_index.unwrap_or(false),
+        )
+        .with_language_negotiation(
+            config.static_files.negotiate_language.unwrap_or(false),
+            config.static_files.default_language.clone(),
+        )
+        .with_precompressed_sidecars(config.static_files.precompressed.unwrap_or(true))
+        .with_compressor(Compressor::from_config(config.compression.as_ref()))
+        .with_cache(shared.file_cache.clone())
+        .with_mappings(shared.mapped_files.clone(), config.static_files.mmap_threshold)
+        .with_cache_policy(Arc::clone(&shared.cache_policy))
+        .with_webdav(shared.webdav.clone())
+        .with_markdown(shared.markdown.clone())
+        .with_exclusions(Arc::clone(&shared.exclusions));
+        
+        if config.static_files.clean_urls.unwrap_or(false) {
+            let extensions = config.static_files.clean_url_extensions.clone()
+                .unwrap_or_else(|| vec!["html".to_string()]);
+            static_handler = static_handler.with_clean_urls(extensions);
+        }
+        
+        // Virtual hosts share the static file settings but serve their own document root;
+        // the first host with a pattern wins, as in routing
+        let mut vhost_static_handlers = HashMap::new();
+        for vhost in config.virtual_hosts.iter().flatten() {
+            vhost_static_handlers.entry(vhost.host.clone()).or_insert_with(|| {
+                static_handler.clone().with_document_root(
+                    &vhost.root_dir,
+                    vhost.directory_listing.or(config.static_files.directory_listing).unwrap_or(false),
+                    vhost.default_file.clone()
+                        .or_else(|| config.static_files.default_file.clone())
+                        .unwrap_or_else(|| "index.html".to_string()),
+                )
+            });
+        }
+        
+        // A separate admin listener serves the admin endpoints itself
+        let admin_handler = AdminHandler::from_config(Arc::clone(config), shared.metrics.clone())
+            .filter(|_| config.admin.as_ref().is_none_or(|admin| admin.listen.is_none()))
+            .map(|admin| {
+                admin
+                    .with_router(Arc::clone(&router))
+                    .with_plugins(shared.plugins.clone())
+                    .with_proxy_pools(Arc::clone(&shared.proxy_pools))
+            });
+        
+        RequestHandlers {
+            router,
+            bound_routers,
+            static_handler,
+            vhost_static_handlers,
+            admin_handler,
+            health_handler: HealthHandler::from_config(config, shared.readiness.clone(), Arc::clone(&shared.proxy_pools)),
+        }
+    }
+    
+    /// Get the router for connections bound to the given virtual host, or to none
+    pub fn router(&self, vhost: Option<&str>) -> Arc<Router> {
+        match vhost {
+            Some(vhost) => self.bound_routers.get(vhost)
+                .cloned()
+                .unwrap_or_else(|| Arc::new(Router::clone(&self.router).bind_vhost(vhost))),
+            None => Arc::clone(&self.router),
+        }
+    }
+}
+
+/// Handler for TCP or Unix socket connections that processes HTTP requests
+pub struct ConnectionHandler<S> {
+    /// The stream for this connection
+    stream: S,
+    /// Address of the peer, if the stream has one
+    remote_addr: Option<SocketAddr>,
+    /// Whether a peer without an address is a proxy trusted to name the client
+    trust_forwarded: bool,
+    /// Whether the connection starts with a TLS handshake
+    tls: bool,
+    /// Virtual host serving every request on the connection, whatever its Host header
+    vhost: Option<String>,
+    /// Server configuration
+    config: Arc<Config>,
+    /// Server-wide shared state
+    shared: SharedState,
+}
+
+impl ConnectionHandler<TcpStream> {
+    /// Create a new connection handler for a TCP connection
+    pub fn new(stream: TcpStream, config: Arc<Config>, shared: SharedState) -> Self {
+        let remote_addr = stream.peer_addr().ok();
+        Self::with_peer(stream, remote_addr, config, shared)
+    }
+    
+    /// Serve a connection accepted on the separate admin listener
+    pub async fn serve_admin(
+        stream: TcpStream,
+        admin_handler: AdminHandler,
+        error_pages: Arc<ErrorPages>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let remote_addr = stream.peer_addr().ok();
+        
+        let service = service_fn(move |mut req: Request<Body>| {
+            let admin_handler = admin_handler.clone();
+            let error_pages = Arc::clone(&error_pages);
+            
+            async move {
+                if let Some(addr) = remote_addr {
+                    req.extensions_mut().insert(addr);
+                }
+                
+                let response = if admin_handler.matches(req.uri().path()) {
+                    Self::into_response(admin_handler.handle(req).await, &error_pages)
+                } else {
+                    HttpError::NotFound.to_response(&error_pages)
+                };
+                Ok::<_, Infallible>(response)
+            }
+        });
+        
+        Http::new().http1_only(true).serve_connection(stream, service).await?;
+        Ok(())
+    }
+    
+    /// Serve a connection on the plain-HTTP redirect listener, sending every request to HTTPS.
+    ///
+    /// HTTP-01 challenges are answered here too, as the CA validates over port 80.
+    pub async fn serve_https_redirect(
+        stream: TcpStream,
+        https_port: u16,
+        acme: Option<Arc<Acme>>,
+        error_pages: Arc<ErrorPages>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let service = service_fn(move |req: Request<Body>| {
+            let error_pages = Arc::clone(&error_pages);
+            let key_authorization = acme.as_ref().and_then(|acme| acme.http_challenge(req.uri().path()));
+            
+            async move {
+                if let Some(key_authorization) = key_authorization {
+                    return Ok::<_, Infallible>(Self::acme_challenge_response(key_authorization));
+                }
+                
+                let response = match Self::https_location(&req, https_port) {
+                    Some(location) => {
+                        debug!("Redirecting {} to {}", req.uri(), location);
+                        ResponseBuilder::redirect(StatusCode::MOVED_PERMANENTLY, &location)
+                    }
+                    None => HttpError::BadRequest("Missing or invalid Host header".to_string()).to_response(&error_pages),
+                };
+                Ok::<_, Infallible>(response)
+            }
+        });
+        
+        Http::new().http1_only(true).serve_connection(stream, service).await?;
+        Ok(())
+    }
+    
+    /// HTTPS URL of a request, keeping its host, path and query
+    fn https_location(req: &Request<Body>, https_port: u16) -> Option<String> {
+        let host = match req.uri().authority() {
+            Some(authority) => authority.as_str(),
+            None => req.headers().get(hyper::header::HOST)?.to_str().ok()?,
+        };
+        
+        // Only redirect to a well-formed host, never to whatever the client sent
+        let (hostname, _) = parse_host(host);
+        let hostname = if hostname.contains(':') { format!("[{}]", hostname) } else { hostname };
+        let authority = match https_port {
+            443 => hostname,
+            port => format!("{}:{}", hostname, port),
+        };
+        authority.parse::<hyper::http::uri::Authority>().ok()?;
+        
+        let target = req.uri().path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+        Some(format!("https://{}{}", authority, target))
+    }
+}
+
+impl<S> ConnectionHandler<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    /// Create a new connection handler for a stream with the given peer address
+    pub fn with_peer(stream: S, remote_addr: Option<SocketAddr>, config: Arc<Config>, shared: SharedState) -> Self {
         ConnectionHandler {
             stream,
+            remote_addr,
+            trust_forwarded: false,
+            tls: config.tls.as_ref().is_some_and(|tls| tls.enabled),
+            vhost: None,
             config,
+            shared,
         }
     }
     
+    /// Treat a peer without an address as a trusted proxy, taking the client from its forwarding headers
+    pub fn trust_forwarded(mut self, trust_forwarded: bool) -> Self {
+        self.trust_forwarded = trust_forwarded;
+        self
+    }
+    
+    /// Set whether the connection starts with a TLS handshake
+    pub fn tls(mut self, tls: bool) -> Self {
+        self.tls = tls;
+        self
+    }
+    
+    /// Serve every request with the given virtual host, whatever its Host header
+    pub fn vhost(mut self, vhost: Option<String>) -> Self {
+        self.vhost = vhost;
+        self
+    }
+    
     /// Process the connection
-    pub async fn process(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    pub async fn process(mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         // Create a hyper HTTP connection
-        let http = Http::new();
+        let http = Self::http_builder(&self.config);
+        let mut remote_addr = self.remote_addr;
+        
+        // A load balancer speaking the PROXY protocol names the client before any other bytes
+        if self.config.server.proxy_protocol.unwrap_or(false) {
+            match proxy_protocol::read_header(&mut self.stream).await {
+                Ok(Some(source)) => {
+                    debug!("PROXY protocol header from {:?} names client {}", remote_addr, source);
+                    remote_addr = Some(source);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    debug!("Dropping connection from {:?}: {}", remote_addr, e);
+                    return Ok(());
+                }
+            }
+        }
         
-        // Create a router for request handling
-        let router = Router::new(Arc::clone(&self.config));
+        // Close connections left idle between requests
+        let keepalive_timeout = self.config.server.keepalive_timeout.map(Duration::from_secs);
+        let activity = keepalive_timeout.map(|_| ConnectionActivity::default());
+        let stream = IdleStream::new(self.stream, keepalive_timeout, activity.clone().unwrap_or_default());
         
-        // Create a static file handler
-        let static_handler = StaticFileHandler::new(
-            &self.config.static_files.root_dir,
-            self.config.static_files.directory_listing.unwrap_or(false),
-            self.config.static_files.default_file.clone().unwrap_or_else(|| "index.html".to_string()),
-        );
+        // Connections served outside the event loop build their own handlers
+        let handlers = match &self.shared.handlers {
+            Some(handlers) => Arc::clone(handlers),
+            None => Arc::new(RequestHandlers::new(&self.config, &self.shared)),
+        };
+        
+        let pipeline = RequestPipeline {
+            config: Arc::clone(&self.config),
+            router: handlers.router(self.vhost.as_deref()),
+            rewriter: self.shared.rewriter.clone(),
+            handlers,
+            metrics: self.shared.metrics.clone(),
+            error_pages: Arc::clone(&self.shared.error_pages),
+            access_logs: Arc::clone(&self.shared.access_logs),
+            concurrency_limits: Arc::clone(&self.shared.concurrency_limits),
+            middleware: Arc::clone(&self.shared.middleware),
+            geoip: self.shared.geoip.clone(),
+            proxy_pools: Arc::clone(&self.shared.proxy_pools),
+            trusted_proxies: Arc::clone(&self.shared.trusted_proxies),
+            trust_forwarded: self.trust_forwarded,
+            fastcgi_backends: Arc::clone(&self.shared.fastcgi_backends),
+            scgi_backends: Arc::clone(&self.shared.scgi_backends),
+            uwsgi_backends: Arc::clone(&self.shared.uwsgi_backends),
+            cgi_scripts: Arc::clone(&self.shared.cgi_scripts),
+            upload_endpoints: Arc::clone(&self.shared.upload_endpoints),
+            services: Arc::clone(&self.shared.services),
+            acme: self.shared.acme.clone(),
+            secure: self.tls && self.shared.tls_acceptor.is_some(),
+            hsts: self.config.tls.as_ref()
+                .filter(|_| self.tls && self.shared.tls_acceptor.is_some())
+                .and_then(tls::hsts_header),
+            activity: activity.clone(),
+            requests_left: self.config.server.keepalive_requests.map(|limit| Arc::new(AtomicUsize::new(limit))),
+            websocket_handlers: Arc::clone(&self.shared.websocket_handlers),
+            plugins: self.shared.plugins.clone(),
+        };
         
         // Create service for handling requests
-        let service = make_service_fn(move |_conn| {
-            let router_clone = router.clone();
-            let static_handler_clone = static_handler.clone();
-            let config_clone = Arc::clone(&self.config);
+        let service = service_fn(move |req: Request<Body>| {
+            let pipeline = pipeline.clone();
             
             async move {
-                Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
-                    let router = router_clone.clone();
-                    let handler = static_handler_clone.clone();
-                    let config = Arc::clone(&config_clone);
-                    
-                    async move {
-                        Self::handle_request(req, router, handler, config).await
-                    }
-                }))
+                Self::handle_request(req, pipeline, remote_addr).await
             }
         });
         
-        // Create server with the service
-        let server = Server::builder(hyper::server::accept::from_stream(futures::stream::once(
-            futures::future::ok::<_, hyper::Error>(self.stream.clone())
-        )))
-        .serve(service);
+        // Serve HTTP requests on this connection
+        self.shared.metrics.connection_opened();
+        let result = match self.shared.tls_acceptor.as_ref().filter(|_| self.tls) {
+            Some(acceptor) => match acceptor.accept(stream).await {
+                Ok(stream) if stream.get_ref().1.alpn_protocol() == Some(ACME_TLS_ALPN) => {
+                    // The CA has seen the validation certificate; there is nothing to serve
+                    debug!("Completed TLS-ALPN-01 validation handshake with {:?}", remote_addr);
+                    self.shared.metrics.connection_closed();
+                    return Ok(());
+                }
+                Ok(stream) => http.serve_connection(stream, service).with_upgrades().await,
+                Err(e) => {
+                    // Failed handshakes are routine (scanners, plain HTTP on the TLS port)
+                    debug!("TLS handshake with {:?} failed: {}", remote_addr, e);
+                    self.shared.metrics.connection_closed();
+                    return Ok(());
+                }
+            },
+            None => http.serve_connection(stream, service).with_upgrades().await,
+        };
+        self.shared.metrics.connection_closed();
         
-        // Run the server to process the connection
-        if let Err(e) = server.await {
+        if let Err(e) = result {
             error!("Error serving connection: {}", e);
             return Err(Box::new(e));
         }
@@ -75,42 +672,528 @@ impl ConnectionHandler {
         Ok(())
     }
     
+    /// Response carrying the key authorization of an HTTP-01 challenge
+    fn acme_challenge_response(key_authorization: String) -> Response<Body> {
+        ResponseBuilder::with_status(StatusCode::OK)
+            .content_type("application/octet-stream")
+            .body_string(key_authorization)
+            .build()
+    }
+    
+    /// Configure the protocols served on a connection.
+    ///
+    /// With HTTP/2 enabled, hyper detects the h2 connection preface itself, so
+    /// both ALPN-negotiated TLS connections and prior-knowledge h2c are served.
+    fn http_builder(config: &Config) -> Http {
+        let mut http = Http::new();
+        
+        // hyper refuses buffers below 8 KB, which could not hold common request heads anyway
+        if let Some(max_header_size) = config.server.max_header_size {
+            http.max_buf_size(max_header_size.max(8192));
+        }
+        if let Some(timeout) = config.server.header_read_timeout {
+            http.http1_header_read_timeout(Duration::from_secs(timeout));
+        }
+        
+        if !config.server.http2.unwrap_or(false) {
+            http.http1_only(true);
+            return http;
+        }
+        
+        if let Some(streams) = config.server.http2_max_concurrent_streams {
+            http.http2_max_concurrent_streams(streams);
+        }
+        if let Some(frame_size) = config.server.http2_max_frame_size {
+            http.http2_max_frame_size(frame_size);
+        }
+        
+        http
+    }
+    
     /// Handle an individual HTTP request
     async fn handle_request(
-        req: Request<Body>,
-        router: Router,
-        static_handler: StaticFileHandler,
-        config: Arc<Config>,
+        mut req: Request<Body>,
+        pipeline: RequestPipeline,
+        peer_addr: Option<SocketAddr>,
     ) -> Result<Response<Body>, Infallible> {
+        // Behind trusted proxies the client is the nearest hop they report, for ACLs, limits and logs alike
+        let remote_addr = match peer_addr {
+            Some(peer) => Some(pipeline.trusted_proxies.client_addr(peer, req.headers())),
+            None if pipeline.trust_forwarded => pipeline.trusted_proxies.forwarded_client(req.headers()),
+            None => None,
+        };
+        if let Some(peer) = peer_addr.filter(|&peer| Some(peer) != remote_addr) {
+            req.extensions_mut().insert(PeerAddr(peer));
+        }
+        
+        // Locate the client once, for plugins, ACL rules and the access log
+        let geo = pipeline.geoip.as_ref().zip(remote_addr).map(|(geoip, addr)| geoip.lookup(addr.ip()));
+        if let Some(geo) = &geo {
+            req.extensions_mut().insert(geo.clone());
+        }
+        
+        let active_request = pipeline.activity.as_ref().map(ConnectionActivity::begin);
         let method = req.method().clone();
         let uri = req.uri().clone();
+        let version = req.version();
+        
+        debug!("{} {}", method, uri);
+        
+        let start = Instant::now();
+        pipeline.metrics.record_request(Self::content_length(req.headers(), req.body()));
         
-        info!("{} {}", method, uri);
+        // Pick the access logger for the request's virtual host
+        let access_log = match pipeline.router.bound_vhost() {
+            Some(vhost) => pipeline.access_logs.select_vhost(vhost.hostname()),
+            None => pipeline.access_logs.select(req.headers().get(hyper::header::HOST).and_then(|h| h.to_str().ok())),
+        };
+        let access_log = access_log.map(|logger| {
+            let header = |name| req.headers().get(name).and_then(|h: &HeaderValue| h.to_str().ok()).map(str::to_string);
+            (logger, header(hyper::header::USER_AGENT), header(hyper::header::REFERER))
+        });
+        
+        // Overall deadline for the response, including any streamed body
+        let deadline = pipeline.config.server.response_timeout
+            .map(|secs| tokio::time::Instant::now() + Duration::from_secs(secs));
+        
+        // Contain handler panics to this request so the connection keeps serving
+        let dispatched = AssertUnwindSafe(Self::dispatch_with_plugins(req, &pipeline, remote_addr, deadline))
+            .catch_unwind()
+            .await;
+        let mut response = match dispatched {
+            Ok(response) => response,
+            Err(panic) => {
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown panic".to_string());
+                error!("Handler panicked on {} {}: {}", method, uri.path(), message);
+                HttpError::Internal(message).to_response(&pipeline.error_pages)
+            }
+        };
+        if let Some(hsts) = &pipeline.hsts {
+            response.headers_mut().entry(hyper::header::STRICT_TRANSPORT_SECURITY).or_insert_with(|| hsts.clone());
+        }
+        
+        // Close an HTTP/1 connection with the last response it may serve
+        if let Some(requests_left) = pipeline.requests_left.as_ref().filter(|_| version <= Version::HTTP_11) {
+            let last = matches!(requests_left.fetch_update(Ordering::AcqRel, Ordering::Acquire, |left| left.checked_sub(1)), Ok(1) | Err(_));
+            if last && response.status() != StatusCode::SWITCHING_PROTOCOLS {
+                debug!("Closing connection after its last allowed request");
+                response.headers_mut().insert(hyper::header::CONNECTION, HeaderValue::from_static("close"));
+            }
+        }
+        
+        // Keep the connection from timing out as idle until the response has been sent
+        if let (Some(activity), Some(active_request)) = (&pipeline.activity, active_request) {
+            if response.status() == StatusCode::SWITCHING_PROTOCOLS {
+                activity.upgrade();
+            } else if !response.body().is_end_stream() {
+                let body = std::mem::take(response.body_mut());
+                *response.body_mut() = body_while_active(body, active_request);
+            }
+        }
+        let status = response.status().as_u16();
+        let bytes = Self::content_length(response.headers(), response.body());
+        
+        // Headers are committed from here on, so a late body can only be cut short
+        if let Some(deadline) = deadline {
+            let body = std::mem::take(response.body_mut());
+            *response.body_mut() = body_with_deadline(body, deadline);
+        }
+        
+        pipeline.metrics.record_response(status, bytes);
+        
+        // Report requests slower than the configured threshold
+        let elapsed = start.elapsed();
+        let slow_threshold = pipeline.config.logging.as_ref().and_then(|l| l.slow_request_threshold);
+        if let Some(threshold) = slow_threshold {
+            if elapsed >= Duration::from_millis(threshold) {
+                warn!(
+                    "Slow request: {} {} -> {} in {} ms",
+                    method,
+                    uri.path(),
+                    status,
+                    elapsed.as_millis()
+                );
+            }
+        }
+        
+        if let Some((logger, user_agent, referer)) = access_log {
+            let client_ip = remote_addr.map(|addr| addr.ip().to_string()).unwrap_or_else(|| "-".to_string());
+            let target = uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+            logger.log_access(&AccessLogEntry {
+                client_ip: &client_ip,
+                method: method.as_str(),
+                target,
+                version: &format!("{:?}", version),
+                status,
+                bytes,
+                user_agent: user_agent.as_deref(),
+                referer: referer.as_deref(),
+                geo: geo.as_ref(),
+            });
+        }
+        
+        Ok(response)
+    }
+    
+    /// Dispatch a request between the plugins' `pre_request` and `post_response` hooks
+    async fn dispatch_with_plugins(
+        req: Request<Body>,
+        pipeline: &RequestPipeline,
+        remote_addr: Option<SocketAddr>,
+        deadline: Option<tokio::time::Instant>,
+    ) -> Response<Body> {
+        if pipeline.plugins.is_empty() {
+            return Self::dispatch(req, pipeline, remote_addr, deadline).await;
+        }
+        
+        let response = match pipeline.plugins.pre_request(req).await {
+            Ok(req) => Self::dispatch(req, pipeline, remote_addr, deadline).await,
+            Err(e) => return e.to_response(&pipeline.error_pages),
+        };
+        
+        match pipeline.plugins.post_response(response).await {
+            Ok(response) => response,
+            Err(e) => e.to_response(&pipeline.error_pages),
+        }
+    }
+    
+    /// Run a request through validation, routing and the matched handler
+    async fn dispatch(
+        mut req: Request<Body>,
+        pipeline: &RequestPipeline,
+        remote_addr: Option<SocketAddr>,
+        deadline: Option<tokio::time::Instant>,
+    ) -> Response<Body> {
+        let error_pages = &pipeline.error_pages;
+        
+        // Refuse header floods before any further processing
+        if let Some(max_headers) = pipeline.config.server.max_headers {
+            let count = req.headers().len();
+            if count > max_headers {
+                debug!("Rejecting request with {} headers (limit {})", count, max_headers);
+                return HttpError::HeaderFieldsTooLarge.to_response(error_pages);
+            }
+        }
+        
+        // Refuse bodies declared too large outright, and cut off chunked ones once they grow too large
+        if let Some(max_body_size) = pipeline.config.server.max_body_size {
+            let size_hint = req.body().size_hint();
+            if size_hint.lower() > max_body_size {
+                debug!("Rejecting request body of {} bytes (limit {})", size_hint.lower(), max_body_size);
+                return HttpError::PayloadTooLarge.to_response(error_pages);
+            }
+            if size_hint.exact().is_none() {
+                let body = std::mem::take(req.body_mut());
+                *req.body_mut() = body_with_limit(body, max_body_size);
+            }
+        }
+        
+        // Decode and canonicalize the path once, so access checks, routing and handlers agree on it
+        if let Err(e) = Self::canonicalize_request_path(&mut req, &pipeline.config) {
+            return e.to_response(error_pages);
+        }
+        
+        // The CA fetches HTTP-01 tokens before any access control could know it
+        if let Some(key_authorization) = pipeline.acme.as_ref().and_then(|acme| acme.http_challenge(req.uri().path())) {
+            return Self::acme_challenge_response(key_authorization);
+        }
+        
+        // Make the peer address and connection scheme available to handlers
+        if let Some(addr) = remote_addr {
+            req.extensions_mut().insert(addr);
+        }
+        req.extensions_mut().insert(if pipeline.secure { Scheme::HTTPS } else { Scheme::HTTP });
+        
+        // Let constrained clients tunnel other methods through POST
+        if let Some(method_override) = &pipeline.config.method_override {
+            apply_method_override(&mut req, method_override);
+        }
+        
+        // Rewrite the URL so access checks and routing see the target resource
+        if let Some(rewriter) = &pipeline.rewriter {
+            if let Some(rewrite) = rewriter.process(&req) {
+                // Keep the original query unless the replacement brings its own
+                let target = match req.uri().query() {
+                    Some(query) if !rewrite.new_path.contains('?') => format!("{}?{}", rewrite.new_path, query),
+                    _ => rewrite.new_path,
+                };
+                
+                if rewrite.is_redirect {
+                    let status = StatusCode::from_u16(rewrite.redirect_status.unwrap_or(302)).unwrap_or(StatusCode::FOUND);
+                    debug!("Redirecting {} to {} ({})", req.uri().path(), target, status);
+                    return ResponseBuilder::redirect(status, &target);
+                }
+                
+                debug!("Rewrote {} to {}", req.uri().path(), target);
+                if !target.starts_with('/') || !Self::set_request_target(&mut req, &target) {
+                    error!("Rewrite of {} produced an invalid target: {}", req.uri().path(), target);
+                    return HttpError::Internal(format!("Invalid rewrite target: {}", target)).to_response(error_pages);
+                }
+                if let Err(e) = Self::canonicalize_request_path(&mut req, &pipeline.config) {
+                    return e.to_response(error_pages);
+                }
+            }
+        }
+        
+        // Answer probes before access checks so load balancers need no credentials
+        if let Some(health_handler) = pipeline.handlers.health_handler.as_ref().filter(|health| health.matches(req.uri().path())) {
+            return Self::into_response(health_handler.handle(req).await, error_pages);
+        }
+        
+        // Run access checks and other middleware for the path and virtual host, then the handler
+        let vhost = pipeline.router.request_vhost(&req).map(|vhost| vhost.hostname());
+        let middleware = pipeline.middleware.select(req.uri().path(), vhost);
+        let endpoint: Endpoint<'_> = Box::new(move |req| Self::route_request(req, pipeline, deadline).boxed());
+        Next::new(&middleware, error_pages, endpoint).run(req).await
+    }
+    
+    /// Run a request that passed the middleware through the matched handler
+    async fn route_request(
+        req: Request<Body>,
+        pipeline: &RequestPipeline,
+        deadline: Option<tokio::time::Instant>,
+    ) -> Response<Body> {
+        let error_pages = &pipeline.error_pages;
         
         // Route the request to the appropriate handler
-        let route_result = router.route(&req);
+        let route_result = pipeline.router.route(&req);
+        let global_timeout = pipeline.config.server.request_timeout.map(Duration::from_secs);
+        let until_deadline = deadline.map(|deadline| deadline.saturating_duration_since(tokio::time::Instant::now()));
+        let static_handler = match &route_result {
+            Ok(RouteMatch { vhost: Some(vhost), .. }) => pipeline.handlers.vhost_static_handlers
+                .get(vhost.hostname())
+                .unwrap_or(&pipeline.handlers.static_handler),
+            _ => &pipeline.handlers.static_handler,
+        };
+        
+        if let Some(admin_handler) = pipeline.handlers.admin_handler.as_ref().filter(|admin| admin.matches(req.uri().path())) {
+            return Self::into_response(admin_handler.handle(req).await, error_pages);
+        }
+        
+        // WebSocket upgrades go to a proxy route or a plugin handler; nothing else can complete them
+        if is_websocket_upgrade(req.headers()) && !matches!(&route_result, Ok(matched) if matched.route.handler_type == "proxy") {
+            let path = req.uri().path();
+            return match pipeline.websocket_handlers.iter().find(|handler| handler.matches(path)) {
+                Some(handler) => {
+                    debug!("Accepting WebSocket upgrade for {}", path);
+                    match accept_websocket(req, Arc::clone(handler)) {
+                        Ok(response) => response,
+                        Err(e) => e.to_response(error_pages),
+                    }
+                }
+                None => {
+                    debug!("No WebSocket handler for {}", path);
+                    HttpError::BadRequest("WebSocket upgrades are not supported for this resource.".to_string())
+                        .to_response(error_pages)
+                }
+            };
+        }
+        
+        // Hold a route concurrency slot until the handler has produced its response
+        let _permit = match pipeline.concurrency_limits.acquire(req.uri().path(), &pipeline.metrics).await {
+            Ok(permit) => permit,
+            Err(e) => return e.to_response(error_pages),
+        };
+        
+        // WebDAV paths are file shares whatever the routes say
+        if static_handler.serves_webdav(req.uri().path()) {
+            let timeout = Self::shortest(global_timeout, until_deadline);
+            let result = Self::with_timeout(timeout, static_handler.handle(req)).await;
+            return Self::into_response(result, error_pages);
+        }
         
         match route_result {
-            Ok(route) => {
-                debug!("Route matched: {:?}", route);
+            Ok(RouteMatch { route, vhost }) => {
+                debug!("Route matched: {:?} (virtual host {:?})", route, vhost.map(|vhost| vhost.hostname()));
+                
+                // A per-route timeout takes precedence over the global one
+                let timeout = Self::shortest(route.timeout.or(global_timeout), until_deadline);
                 
                 // Handle the request based on the route type
-                match route.handler_type.as_str() {
-                    "static" => static_handler.handle(req).await,
-                    // Add other handler types as needed
-                    _ => {
-                        error!("Unknown handler type: {}", route.handler_type);
-                        Ok(Response::builder()
-                            .status(500)
-                            .body(Body::from("Internal Server Error: Unknown handler type"))
-                            .unwrap())
+                let result = Self::with_timeout(timeout, async {
+                    match route.handler_type.as_str() {
+                        "static" => static_handler.handle(req).await,
+                        "proxy" => {
+                            let pool = route.handler_params.as_deref().unwrap_or_default();
+                            match pipeline.proxy_pools.get(pool) {
+                                Some(proxy_handler) => proxy_handler.handle(req).await,
+                                None => Err(Box::new(HttpError::Internal(format!("Unknown proxy pool: {}", pool))).into()),
+                            }
+                        }
+                        "fastcgi" => {
+                            let pattern = route.handler_params.as_deref().unwrap_or_default();
+                            match pipeline.fastcgi_backends.get(pattern) {
+                                Some(fastcgi_handler) => fastcgi_handler.handle(req).await,
+                                None => Err(Box::new(HttpError::Internal(format!("Unknown FastCGI route: {}", pattern))).into()),
+                            }
+                        }
+                        "scgi" => {
+                            let pattern = route.handler_params.as_deref().unwrap_or_default();
+                            match pipeline.scgi_backends.get(pattern) {
+                                Some(scgi_handler) => scgi_handler.handle(req).await,
+                                None => Err(Box::new(HttpError::Internal(format!("Unknown SCGI route: {}", pattern))).into()),
+                            }
+                        }
+                        "uwsgi" => {
+                            let pattern = route.handler_params.as_deref().unwrap_or_default();
+                            match pipeline.uwsgi_backends.get(pattern) {
+                                Some(uwsgi_handler) => uwsgi_handler.handle(req).await,
+                                None => Err(Box::new(HttpError::Internal(format!("Unknown uwsgi route: {}", pattern))).into()),
+                            }
+                        }
+                        "cgi" => {
+                            let pattern = route.handler_params.as_deref().unwrap_or_default();
+                            match pipeline.cgi_scripts.get(pattern) {
+                                Some(cgi_handler) => cgi_handler.handle(req).await,
+                                None => Err(Box::new(HttpError::Internal(format!("Unknown CGI route: {}", pattern))).into()),
+                            }
+                        }
+                        "upload" => {
+                            let pattern = route.handler_params.as_deref().unwrap_or_default();
+                            match pipeline.upload_endpoints.get(pattern) {
+                                Some(upload_handler) => upload_handler.handle(req).await,
+                                None => Err(Box::new(HttpError::Internal(format!("Unknown upload route: {}", pattern))).into()),
+                            }
+                        }
+                        "service" => {
+                            let pattern = route.handler_params.as_deref().unwrap_or_default();
+                            match pipeline.services.get(pattern) {
+                                Some(service_handler) => service_handler.handle(req).await,
+                                None => Err(Box::new(HttpError::Internal(format!("Unknown service route: {}", pattern))).into()),
+                            }
+                        }
+                        // Add other handler types as needed
+                        _ => {
+                            Err(Box::new(HttpError::Internal(format!("Unknown handler type: {}", route.handler_type))).into())
+                        }
                     }
+                }).await;
+                
+                Self::into_response(result, error_pages)
+            }
+            Err(RouterError::MethodNotAllowed { allow }) => HttpError::MethodNotAllowed { allow }.to_response(error_pages),
+            Err(_) => match pipeline.config.server.unmatched_routes.unwrap_or_default() {
+                UnmatchedRoutes::Static => {
+                    // If no route matches, default to static file handler
+                    let timeout = Self::shortest(global_timeout, until_deadline);
+                    let result = Self::with_timeout(timeout, static_handler.handle(req)).await;
+                    Self::into_response(result, error_pages)
+                }
+                UnmatchedRoutes::NotFound => {
+                    debug!("No route matched {}", req.uri().path());
+                    HttpError::NotFound.to_response(error_pages)
                 }
+            },
+        }
+    }
+    
+    /// Replace the request path with its canonical form, applying the configured case and trailing-dot handling.
+    ///
+    /// Malformed paths are refused with 400, paths climbing out of the root with 403
+    /// and refused case variants with 404. Targets other than a path, such as
+    /// `OPTIONS *`, are left alone.
+    fn canonicalize_request_path(req: &mut Request<Body>, config: &Config) -> Result<(), HttpError> {
+        let path = req.uri().path();
+        if !path.starts_with('/') {
+            return Ok(());
+        }
+        
+        let case = config.server.path_case.unwrap_or_default();
+        let strip_trailing_dots = config.server.strip_trailing_dots.unwrap_or(false);
+        let canonical = match normalize_path(path, case, strip_trailing_dots) {
+            Ok(Cow::Borrowed(_)) => return Ok(()),
+            Ok(Cow::Owned(canonical)) => canonical,
+            Err(PathError::Malformed) => {
+                warn!("Refusing malformed request path {}", path);
+                return Err(HttpError::BadRequest("Malformed request path.".to_string()));
+            }
+            Err(PathError::Traversal) => {
+                warn!("Refusing traversal in request path {}", path);
+                return Err(HttpError::Forbidden("Access denied.".to_string()));
             }
-            Err(_) => {
-                // If no route matches, default to static file handler
-                static_handler.handle(req).await
+            Err(PathError::CaseVariant) => {
+                debug!("Refusing case-variant path: {}", path);
+                return Err(HttpError::NotFound);
+            }
+        };
+        
+        let path_and_query = match req.uri().query() {
+            Some(query) => format!("{}?{}", canonical, query),
+            None => canonical,
+        };
+        if !Self::set_request_target(req, &path_and_query) {
+            return Err(HttpError::BadRequest("Malformed request path.".to_string()));
+        }
+        
+        debug!("Canonicalized request path to {}", req.uri().path());
+        Ok(())
+    }
+    
+    /// Replace the path and query of the request URI.
+    ///
+    /// Returns `false`, leaving the request unchanged, when `path_and_query` is not a valid target.
+    fn set_request_target(req: &mut Request<Body>, path_and_query: &str) -> bool {
+        let mut parts = req.uri().clone().into_parts();
+        match path_and_query.parse() {
+            Ok(path_and_query) => parts.path_and_query = Some(path_and_query),
+            Err(_) => return false,
+        }
+        
+        match Uri::from_parts(parts) {
+            Ok(uri) => {
+                *req.uri_mut() = uri;
+                true
             }
+            Err(_) => false,
+        }
+    }
+    
+    /// Get a message's body size from Content-Length, falling back to the body's size hint
+    fn content_length(headers: &HeaderMap, body: &Body) -> u64 {
+        headers
+            .get(hyper::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_else(|| body.size_hint().lower())
+    }
+    
+    /// Pick the shorter of two optional timeouts
+    fn shortest(a: Option<Duration>, b: Option<Duration>) -> Option<Duration> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+    
+    /// Run a handler future, cancelling it and failing with a gateway timeout if the timeout elapses
+    async fn with_timeout<F>(timeout: Option<Duration>, future: F) -> Result<Response<Body>, Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: Future<Output = Result<Response<Body>, Box<dyn std::error::Error + Send + Sync>>>,
+    {
+        match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, future).await {
+                Ok(result) => result,
+                Err(_) => {
+                    warn!("Handler timed out after {:?}", timeout);
+                    Err(Box::new(HttpError::GatewayTimeout))
+                }
+            },
+            None => future.await,
+        }
+    }
+    
+    /// Convert a handler result into a response, rendering typed errors and mapping others to 500
+    fn into_response(
+        result: Result<Response<Body>, Box<dyn std::error::Error + Send + Sync>>,
+        error_pages: &ErrorPages,
+    ) -> Response<Body> {
+        match result {
+            Ok(response) => response,
+            Err(e) => error_pages.render_error(e),
         }
     }
 }
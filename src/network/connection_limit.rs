@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::debug;
+
+use crate::core::config::Config;
+use crate::utils::metrics::Metrics;
+
+/// Limits on concurrently open connections, server-wide and per client address
+#[derive(Debug, Default)]
+pub struct ConnectionLimiter {
+    /// Slots for open connections, if the total is limited
+    global: Option<Arc<Semaphore>>,
+    /// How long a connection over the global limit may wait for a slot (closed immediately if unset)
+    queue_timeout: Option<Duration>,
+    /// Maximum open connections per client address, if limited
+    per_ip: Option<usize>,
+    /// Open connections by client address
+    open_by_ip: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+/// Slot held by an open connection, released when dropped
+pub struct ConnectionPermit {
+    _global: Option<OwnedSemaphorePermit>,
+    _client: Option<ClientSlot>,
+}
+
+/// Connection counted against a client address, released when dropped
+struct ClientSlot {
+    ip: IpAddr,
+    open_by_ip: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl ConnectionLimiter {
+    /// Build the limits configured in `server`
+    pub fn from_config(config: &Config) -> Self {
+        ConnectionLimiter {
+            global: config.server.max_connections.map(|max| Arc::new(Semaphore::new(max.max(1)))),
+            queue_timeout: config.server.connection_queue_timeout.map(Duration::from_millis),
+            per_ip: config.server.max_connections_per_ip.map(|max| max.max(1)),
+            open_by_ip: Arc::default(),
+        }
+    }
+    
+    /// Acquire a slot for a new connection from `ip`, if any address is known.
+    ///
+    /// Returns `None` when the connection must be closed. A client over its own
+    /// limit is turned away at once; otherwise a connection over the global limit
+    /// waits for a slot as long as the queue timeout allows.
+    pub async fn acquire(&self, ip: Option<IpAddr>, metrics: &Metrics) -> Option<ConnectionPermit> {
+        let client = match (ip, self.per_ip) {
+            (Some(ip), Some(per_ip)) => {
+                let mut open_by_ip = self.open_by_ip.lock().unwrap_or_else(|e| e.into_inner());
+                let open = open_by_ip.entry(ip).or_insert(0);
+                if *open >= per_ip {
+                    debug!("Connection limit of {} per client reached, closing connection from {}", per_ip, ip);
+                    metrics.record_connection_rejected();
+                    return None;
+                }
+                *open += 1;
+                Some(ClientSlot { ip, open_by_ip: Arc::clone(&self.open_by_ip) })
+            }
+            _ => None,
+        };
+        
+        let Some(global) = &self.global else {
+            return Some(ConnectionPermit { _global: None, _client: client });
+        };
+        
+        if let Ok(permit) = Arc::clone(global).try_acquire_owned() {
+            return Some(ConnectionPermit { _global: Some(permit), _client: client });
+        }
+        
+        // Queue briefly if configured, otherwise close right away
+        if let Some(queue_timeout) = self.queue_timeout {
+            metrics.record_connection_queued();
+            if let Ok(Ok(permit)) = tokio::time::timeout(queue_timeout, Arc::clone(global).acquire_owned()).await {
+                return Some(ConnectionPermit { _global: Some(permit), _client: client });
+            }
+        }
+        
+        debug!("Connection limit reached, closing connection from {:?}", ip);
+        metrics.record_connection_rejected();
+        None
+    }
+}
+
+impl Drop for ClientSlot {
+    fn drop(&mut self) {
+        let mut open_by_ip = self.open_by_ip.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(open) = open_by_ip.get_mut(&self.ip) {
+            *open -= 1;
+            if *open == 0 {
+                open_by_ip.remove(&self.ip);
+            }
+        }
+    }
+}
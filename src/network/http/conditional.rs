@@ -0,0 +1,109 @@
+use hyper::header::{self, HeaderMap};
+use hyper::Method;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Outcome of evaluating request preconditions (RFC 7232)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precondition {
+    /// No precondition prevents serving the representation
+    Proceed,
+    /// The client's cached representation is current; answer 304
+    NotModified,
+    /// A precondition failed; answer 412
+    Failed,
+}
+
+/// Evaluate the conditional request headers against a representation's validators.
+///
+/// Follows the precedence of RFC 7232 section 6: `If-Match`, then
+/// `If-Unmodified-Since`, then `If-None-Match`, then `If-Modified-Since`.
+pub fn evaluate(
+    headers: &HeaderMap,
+    method: &Method,
+    etag: Option<&str>,
+    last_modified: Option<SystemTime>,
+) -> Precondition {
+    let safe = method == Method::GET || method == Method::HEAD;
+    
+    if let Some(if_match) = header_str(headers, header::IF_MATCH) {
+        if !etag_list_matches(if_match, etag, true) {
+            return Precondition::Failed;
+        }
+    } else if let Some(since) = header_date(headers, header::IF_UNMODIFIED_SINCE) {
+        if matches!(last_modified, Some(modified) if seconds(modified) > seconds(since)) {
+            return Precondition::Failed;
+        }
+    }
+    
+    if let Some(if_none_match) = header_str(headers, header::IF_NONE_MATCH) {
+        if etag_list_matches(if_none_match, etag, false) {
+            return if safe { Precondition::NotModified } else { Precondition::Failed };
+        }
+    } else if safe {
+        if let Some(since) = header_date(headers, header::IF_MODIFIED_SINCE) {
+            if matches!(last_modified, Some(modified) if seconds(modified) <= seconds(since)) {
+                return Precondition::NotModified;
+            }
+        }
+    }
+    
+    Precondition::Proceed
+}
+
+/// Check whether an `If-Range` header (if any) still matches the representation.
+///
+/// A range request whose `If-Range` does not match is answered with the full content.
+pub fn if_range_matches(headers: &HeaderMap, etag: Option<&str>, last_modified: Option<SystemTime>) -> bool {
+    let value = match header_str(headers, header::IF_RANGE) {
+        Some(value) => value.trim(),
+        None => return true,
+    };
+    
+    if value.starts_with('"') || value.starts_with("W/") {
+        return etag_list_matches(value, etag, true);
+    }
+    
+    // A date only validates when it is exactly the last modification time
+    match (httpdate::parse_http_date(value).ok(), last_modified) {
+        (Some(date), Some(modified)) => seconds(date) == seconds(modified),
+        _ => false,
+    }
+}
+
+/// Check whether a comma-separated entity-tag list (or `*`) matches `etag`.
+///
+/// Strong comparison requires both tags to be strong and identical; weak
+/// comparison ignores the `W/` prefix.
+fn etag_list_matches(list: &str, etag: Option<&str>, strong: bool) -> bool {
+    let etag = match etag {
+        Some(etag) => etag,
+        None => return false,
+    };
+    
+    if list.trim() == "*" {
+        return true;
+    }
+    
+    list.split(',').map(str::trim).any(|candidate| {
+        if strong {
+            !candidate.starts_with("W/") && !etag.starts_with("W/") && candidate == etag
+        } else {
+            candidate.trim_start_matches("W/") == etag.trim_start_matches("W/")
+        }
+    })
+}
+
+/// Get a header value as a string
+fn header_str(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+/// Parse a header value as an HTTP date, ignoring invalid dates
+fn header_date(headers: &HeaderMap, name: header::HeaderName) -> Option<SystemTime> {
+    header_str(headers, name).and_then(|v| httpdate::parse_http_date(v.trim()).ok())
+}
+
+/// Whole seconds since the epoch, the resolution of HTTP dates
+fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
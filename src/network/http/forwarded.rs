@@ -0,0 +1,114 @@
+use hyper::header::{HeaderMap, FORWARDED};
+use std::net::{IpAddr, SocketAddr};
+use tracing::debug;
+
+use crate::core::config::Config;
+use crate::security::acl::{AccessCondition, AclError};
+
+/// Address of the connection's immediate peer, kept in request extensions
+/// when the client address was taken from forwarding headers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerAddr(pub SocketAddr);
+
+/// Proxies trusted to report the client address in `Forwarded` or `X-Forwarded-For`.
+///
+/// Starting from the peer, hops are walked from the nearest to the farthest,
+/// and the first one that is not a trusted proxy is the client. Headers sent
+/// by untrusted peers are ignored, so clients cannot spoof their address.
+#[derive(Debug, Default)]
+pub struct TrustedProxies {
+    /// Trusted proxy networks
+    networks: Vec<AccessCondition>,
+}
+
+impl TrustedProxies {
+    /// Build the trusted proxy list from `server.trusted_proxies`
+    pub fn from_config(config: &Config) -> Result<Self, AclError> {
+        let networks = config.server.trusted_proxies
+            .iter()
+            .flatten()
+            .map(|network| AccessCondition::network(network))
+            .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(TrustedProxies { networks })
+    }
+    
+    /// Check whether an address belongs to a trusted proxy
+    pub fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.networks.iter().any(|network| network.contains_ip(ip))
+    }
+    
+    /// Resolve the client address of a request received from `peer`
+    pub fn client_addr(&self, peer: SocketAddr, headers: &HeaderMap) -> SocketAddr {
+        if !self.is_trusted(peer.ip()) {
+            return peer;
+        }
+        
+        let client = self.nearest_untrusted(headers).unwrap_or(peer);
+        if client != peer {
+            debug!("Client {} reported by trusted proxy {}", client, peer);
+        }
+        client
+    }
+    
+    /// Resolve the client address of a request from a trusted peer without an address,
+    /// such as a proxy on a Unix socket
+    pub fn forwarded_client(&self, headers: &HeaderMap) -> Option<SocketAddr> {
+        self.nearest_untrusted(headers)
+    }
+    
+    /// Walk the reported hops from the nearest, stopping at the first untrusted one
+    fn nearest_untrusted(&self, headers: &HeaderMap) -> Option<SocketAddr> {
+        let mut client = None;
+        for hop in forwarded_hops(headers).into_iter().rev() {
+            // Obfuscated or unknown hops end the chain at the last known proxy
+            let Some(hop) = hop else {
+                break;
+            };
+            client = Some(hop);
+            if !self.is_trusted(hop.ip()) {
+                break;
+            }
+        }
+        client
+    }
+}
+
+/// Hops listed in `Forwarded`, or else `X-Forwarded-For`, from the farthest to the nearest
+fn forwarded_hops(headers: &HeaderMap) -> Vec<Option<SocketAddr>> {
+    let values = |name: &str| {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .collect::<Vec<_>>()
+    };
+    
+    let forwarded = values(FORWARDED.as_str());
+    if !forwarded.is_empty() {
+        return forwarded
+            .into_iter()
+            .map(|element| {
+                element
+                    .split(';')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+                    .and_then(|(_, node)| parse_node(node))
+            })
+            .collect();
+    }
+    
+    values("x-forwarded-for").into_iter().map(parse_node).collect()
+}
+
+/// Parse a node such as `192.0.2.1`, `192.0.2.1:8080`, `"[2001:db8::1]:8080"` or `2001:db8::1`
+fn parse_node(node: &str) -> Option<SocketAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Ok(addr) = node.parse::<SocketAddr>() {
+        return Some(addr);
+    }
+    
+    let ip = node.strip_prefix('[').and_then(|node| node.strip_suffix(']')).unwrap_or(node);
+    ip.parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, 0))
+}
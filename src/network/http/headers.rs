@@ -0,0 +1,87 @@
+use hyper::header::{self, HeaderMap, HeaderName};
+use tracing::debug;
+
+/// Hop-by-hop headers that apply to a single connection and must not be forwarded.
+///
+/// `Trailer` is deliberately absent so that relayed trailers stay announced.
+const HOP_BY_HOP_HEADERS: [&str; 9] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailers",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Check whether a header is a fixed hop-by-hop header
+pub fn is_hop_by_hop(name: &HeaderName) -> bool {
+    HOP_BY_HOP_HEADERS.contains(&name.as_str())
+}
+
+/// Check whether the `TE` header accepts trailers
+pub fn accepts_trailers(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::TE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|coding| coding.split(';').next().is_some_and(|name| name.trim().eq_ignore_ascii_case("trailers")))
+}
+
+/// Remove hop-by-hop headers, including any listed in the `Connection` header
+pub fn strip_hop_by_hop_headers(headers: &mut HeaderMap) {
+    // Collect headers nominated by the Connection header before removing it
+    let nominated: Vec<HeaderName> = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+        .collect();
+    
+    for name in nominated {
+        debug!("Stripping connection-nominated header: {}", name);
+        headers.remove(&name);
+    }
+    
+    for name in HOP_BY_HOP_HEADERS {
+        headers.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+    
+    #[test]
+    fn strips_fixed_and_nominated_headers() {
+        let mut headers = HeaderMap::new();
+        for name in HOP_BY_HOP_HEADERS {
+            headers.insert(name, HeaderValue::from_static("x"));
+        }
+        headers.insert("connection", HeaderValue::from_static("X-Private, keep-alive"));
+        headers.insert("x-private", HeaderValue::from_static("secret"));
+        headers.insert("trailer", HeaderValue::from_static("grpc-status"));
+        headers.insert("content-type", HeaderValue::from_static("text/plain"));
+        
+        strip_hop_by_hop_headers(&mut headers);
+        
+        let mut remaining: Vec<&str> = headers.keys().map(|name| name.as_str()).collect();
+        remaining.sort();
+        assert_eq!(remaining, ["content-type", "trailer"]);
+    }
+    
+    #[test]
+    fn te_trailers_is_detected_among_other_codings() {
+        let mut headers = HeaderMap::new();
+        headers.insert("te", HeaderValue::from_static("gzip, Trailers;q=1"));
+        assert!(accepts_trailers(&headers));
+        
+        headers.insert("te", HeaderValue::from_static("gzip"));
+        assert!(!accepts_trailers(&headers));
+    }
+}
@@ -0,0 +1,89 @@
+use hyper::{Body, Method, Request};
+use tracing::debug;
+
+use crate::core::config::MethodOverrideConfig;
+
+/// Default header carrying the overriding method
+const DEFAULT_OVERRIDE_HEADER: &str = "x-http-method-override";
+
+/// Methods a POST may be overridden to by default
+const DEFAULT_ALLOWED_METHODS: [&str; 3] = ["PUT", "PATCH", "DELETE"];
+
+/// Rewrite the method of a POST request carrying an allowed override header.
+///
+/// The override header is removed once applied so handlers see a plain request.
+pub fn apply_method_override(req: &mut Request<Body>, config: &MethodOverrideConfig) {
+    if !config.enabled || req.method() != Method::POST {
+        return;
+    }
+    
+    let header = config.header.as_deref().unwrap_or(DEFAULT_OVERRIDE_HEADER);
+    let Some(value) = req.headers().get(header).and_then(|v| v.to_str().ok()) else {
+        return;
+    };
+    
+    let requested = value.trim().to_ascii_uppercase();
+    let allowed = match &config.allowed_methods {
+        Some(methods) => methods.iter().any(|m| m.eq_ignore_ascii_case(&requested)),
+        None => DEFAULT_ALLOWED_METHODS.contains(&requested.as_str()),
+    };
+    
+    if !allowed {
+        debug!("Ignoring disallowed method override: {}", requested);
+        return;
+    }
+    
+    if let Ok(method) = Method::from_bytes(requested.as_bytes()) {
+        debug!("Overriding POST with {}", method);
+        *req.method_mut() = method;
+        req.headers_mut().remove(header);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn config(enabled: bool, header: Option<&str>, allowed: Option<&[&str]>) -> MethodOverrideConfig {
+        MethodOverrideConfig {
+            enabled,
+            header: header.map(str::to_string),
+            allowed_methods: allowed.map(|methods| methods.iter().map(|m| m.to_string()).collect()),
+        }
+    }
+    
+    fn request(method: Method, header: &str, value: &str) -> Request<Body> {
+        Request::builder().method(method).uri("/").header(header, value).body(Body::empty()).unwrap()
+    }
+    
+    #[test]
+    fn post_is_overridden_and_the_header_removed() {
+        let mut req = request(Method::POST, "X-HTTP-Method-Override", "delete");
+        apply_method_override(&mut req, &config(true, None, None));
+        assert_eq!(req.method(), Method::DELETE);
+        assert!(!req.headers().contains_key("x-http-method-override"));
+        
+        let mut req = request(Method::POST, "X-Method", "PUT");
+        apply_method_override(&mut req, &config(true, Some("X-Method"), None));
+        assert_eq!(req.method(), Method::PUT);
+    }
+    
+    #[test]
+    fn overrides_are_ignored_unless_enabled_allowed_and_on_post() {
+        let mut req = request(Method::POST, "X-HTTP-Method-Override", "PUT");
+        apply_method_override(&mut req, &config(false, None, None));
+        assert_eq!(req.method(), Method::POST);
+        
+        let mut req = request(Method::POST, "X-HTTP-Method-Override", "CONNECT");
+        apply_method_override(&mut req, &config(true, None, None));
+        assert_eq!(req.method(), Method::POST);
+        
+        let mut req = request(Method::POST, "X-HTTP-Method-Override", "DELETE");
+        apply_method_override(&mut req, &config(true, None, Some(&["PUT"])));
+        assert_eq!(req.method(), Method::POST);
+        
+        let mut req = request(Method::GET, "X-HTTP-Method-Override", "DELETE");
+        apply_method_override(&mut req, &config(true, None, None));
+        assert_eq!(req.method(), Method::GET);
+    }
+}
@@ -1,2 +1,9 @@
 pub mod request;
 pub mod response;
+pub mod headers;
+pub mod range;
+pub mod path;
+pub mod method;
+pub mod conditional;
+pub mod upgrade;
+pub mod forwarded;
@@ -0,0 +1,164 @@
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// Characters percent-encoded in a path segment
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+/// Characters percent-encoded in a canonical request path: all but RFC 3986 `pchar`
+const CANONICAL_SEGMENT: &AsciiSet = &SEGMENT
+    .remove(b'!').remove(b'$').remove(b'&').remove(b'\'').remove(b'(').remove(b')')
+    .remove(b'*').remove(b'+').remove(b',').remove(b';').remove(b'=').remove(b':').remove(b'@');
+
+/// Why a request path has no canonical form
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathError {
+    /// A segment is not valid UTF-8 once decoded, or decodes to a separator or NUL byte
+    Malformed,
+    /// A segment climbs to the parent directory
+    Traversal,
+    /// The path has uppercase letters and case variants are refused
+    CaseVariant,
+}
+
+/// How letter case in request paths is treated before matching and resolution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PathCase {
+    /// Use the path exactly as requested
+    #[default]
+    Preserve,
+    /// Lowercase the path so case variants resolve to a single resource
+    Lower,
+    /// Refuse paths containing uppercase letters
+    Reject,
+}
+
+/// Split a request path into percent-decoded segments.
+///
+/// Both `/` and `\` separate segments. A segment that is not valid UTF-8 once
+/// decoded, or decodes to a separator or NUL byte (`%2f`, `%5c`, `%00`), would
+/// otherwise smuggle extra segments past routing and is malformed; when the
+/// smuggled segments climb to the parent directory (`..%5c..`) it is traversal.
+pub fn decode_segments(path: &str) -> Result<Vec<Cow<'_, str>>, PathError> {
+    path.split(['/', '\\'])
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| {
+            let decoded = percent_decode_str(segment).decode_utf8().map_err(|_| PathError::Malformed)?;
+            if !decoded.contains(['/', '\\', '\0']) {
+                Ok(decoded)
+            } else if decoded.split(['/', '\\']).any(|part| part == "..") {
+                Err(PathError::Traversal)
+            } else {
+                Err(PathError::Malformed)
+            }
+        })
+        .collect()
+}
+
+/// Get the canonical form of a request path, the one access checks, routing and handlers all see.
+///
+/// Segments are percent-decoded, `.` and empty segments dropped, and the result
+/// re-encoded with only the characters a path segment cannot hold escaped, so
+/// `/%73ecret/./a.txt` and `//secret/a.txt` both become `/secret/a.txt`.
+/// A trailing slash is kept, as it names a directory.
+pub fn canonical_path(path: &str) -> Result<Cow<'_, str>, PathError> {
+    normalize_path(path, PathCase::Preserve, false)
+}
+
+/// Get the canonical form of a request path for a case-insensitive filesystem.
+///
+/// As [`canonical_path`], with each decoded segment also lowercased or refused
+/// under `case`, and stripped of the trailing dots some filesystems ignore when
+/// `strip_trailing_dots` is set (`secret.txt%2E` names `secret.txt`). A segment
+/// of dots alone is then refused, as such filesystems may read it as `.` or `..`.
+pub fn normalize_path(path: &str, case: PathCase, strip_trailing_dots: bool) -> Result<Cow<'_, str>, PathError> {
+    let segments = decode_segments(path)?;
+    
+    let mut canonical = String::with_capacity(path.len());
+    for segment in segments {
+        let mut segment = segment.as_ref();
+        match segment {
+            "." => continue,
+            ".." => return Err(PathError::Traversal),
+            _ if strip_trailing_dots && segment.bytes().all(|b| b == b'.') => return Err(PathError::Traversal),
+            _ if strip_trailing_dots => segment = segment.trim_end_matches('.'),
+            _ => {}
+        }
+        
+        let lowercase;
+        if segment.chars().any(char::is_uppercase) {
+            match case {
+                PathCase::Preserve => {}
+                PathCase::Lower => {
+                    lowercase = segment.to_lowercase();
+                    segment = &lowercase;
+                }
+                PathCase::Reject => return Err(PathError::CaseVariant),
+            }
+        }
+        
+        canonical.push('/');
+        canonical.extend(utf8_percent_encode(segment, CANONICAL_SEGMENT));
+    }
+    if canonical.is_empty() || path.ends_with(['/', '\\']) {
+        canonical.push('/');
+    }
+    
+    Ok(if canonical == path { Cow::Borrowed(path) } else { Cow::Owned(canonical) })
+}
+
+/// Percent-encode a file name for use as one segment of a URL path
+pub fn encode_segment(name: &str) -> String {
+    utf8_percent_encode(name, SEGMENT).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn canonical_path_decodes_and_drops_dot_and_empty_segments() {
+        assert_eq!(canonical_path("/%73ecret/./a.txt").unwrap(), "/secret/a.txt");
+        assert_eq!(canonical_path("//secret//a.txt").unwrap(), "/secret/a.txt");
+        assert_eq!(canonical_path("/docs/").unwrap(), "/docs/");
+        assert_eq!(canonical_path("").unwrap(), "/");
+        assert_eq!(canonical_path("/a%20b/caf%c3%a9").unwrap(), "/a%20b/caf%C3%A9");
+        assert_eq!(canonical_path("/a+b/c@d:e").unwrap(), "/a+b/c@d:e");
+        assert!(matches!(canonical_path("/index.html").unwrap(), Cow::Borrowed(_)));
+    }
+    
+    #[test]
+    fn canonical_path_refuses_traversal_and_malformed_segments() {
+        assert_eq!(canonical_path("/a/../b"), Err(PathError::Traversal));
+        assert_eq!(canonical_path("/%2e%2e/etc/passwd"), Err(PathError::Traversal));
+        assert_eq!(canonical_path("/..\\etc\\passwd"), Err(PathError::Traversal));
+        assert_eq!(canonical_path("/a%2fb"), Err(PathError::Malformed));
+        assert_eq!(canonical_path("/a%5cb"), Err(PathError::Malformed));
+        assert_eq!(canonical_path("/a%00"), Err(PathError::Malformed));
+        assert_eq!(canonical_path("/%ff"), Err(PathError::Malformed));
+        assert_eq!(canonical_path("/..%5c..%5cetc%5cpasswd"), Err(PathError::Traversal));
+        assert_eq!(canonical_path("/a/%2e%2e%2fsecret"), Err(PathError::Traversal));
+    }
+    
+    #[test]
+    fn case_is_judged_on_decoded_segments() {
+        assert_eq!(normalize_path("/caf%C3%A9.txt", PathCase::Reject, false).unwrap(), "/caf%C3%A9.txt");
+        assert_eq!(normalize_path("/Index.html", PathCase::Reject, false), Err(PathError::CaseVariant));
+        assert_eq!(normalize_path("/%49ndex.html", PathCase::Reject, false), Err(PathError::CaseVariant));
+        assert_eq!(normalize_path("/Docs/Index.HTML", PathCase::Lower, false).unwrap(), "/docs/index.html");
+        assert_eq!(normalize_path("/%C3%89T%C3%89.txt", PathCase::Lower, false).unwrap(), "/%C3%A9t%C3%A9.txt");
+        assert_eq!(normalize_path("/Index.html", PathCase::Preserve, false).unwrap(), "/Index.html");
+    }
+    
+    #[test]
+    fn trailing_dots_are_stripped_after_decoding() {
+        assert_eq!(normalize_path("/secret.txt.", PathCase::Preserve, true).unwrap(), "/secret.txt");
+        assert_eq!(normalize_path("/secret.txt%2E", PathCase::Preserve, true).unwrap(), "/secret.txt");
+        assert_eq!(normalize_path("/dir.%2e/secret.txt", PathCase::Preserve, true).unwrap(), "/dir/secret.txt");
+        assert_eq!(normalize_path("/a/...", PathCase::Preserve, true), Err(PathError::Traversal));
+        assert_eq!(normalize_path("/a/%2e%2e%2e/b", PathCase::Preserve, true), Err(PathError::Traversal));
+        assert_eq!(normalize_path("/secret.txt.", PathCase::Preserve, false).unwrap(), "/secret.txt.");
+        assert_eq!(normalize_path("/a/...", PathCase::Preserve, false).unwrap(), "/a/...");
+    }
+}
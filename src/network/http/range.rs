@@ -0,0 +1,174 @@
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Most ranges served in one multipart response; larger sets are answered in full
+pub const MAX_RANGES: usize = 16;
+
+/// Counter making multipart boundaries unique within the process
+static BOUNDARY_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Error types for Range header parsing
+#[derive(Debug, PartialEq, Eq)]
+pub enum RangeError {
+    /// The header is malformed and should be ignored
+    Invalid,
+    /// No requested range overlaps the representation
+    Unsatisfiable,
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RangeError::Invalid => write!(f, "Invalid range header"),
+            RangeError::Unsatisfiable => write!(f, "Range not satisfiable"),
+        }
+    }
+}
+
+impl Error for RangeError {}
+
+/// An inclusive byte range within a representation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    /// First byte position
+    pub start: u64,
+    /// Last byte position (inclusive)
+    pub end: u64,
+}
+
+impl ByteRange {
+    /// Number of bytes covered by this range
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+    
+    /// Check whether the range is empty (never true for parsed ranges)
+    pub fn is_empty(&self) -> bool {
+        self.end < self.start
+    }
+    
+    /// Format the Content-Range header value for this range
+    pub fn content_range(&self, total: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, total)
+    }
+}
+
+/// Parse a `Range` header value against a representation of `total` bytes
+pub fn parse_range(value: &str, total: u64) -> Result<Vec<ByteRange>, RangeError> {
+    let specs = value
+        .trim()
+        .strip_prefix("bytes=")
+        .ok_or(RangeError::Invalid)?;
+    
+    let mut ranges = Vec::new();
+    for spec in specs.split(',') {
+        let (first, last) = spec.trim().split_once('-').ok_or(RangeError::Invalid)?;
+        
+        let range = if first.is_empty() {
+            // Suffix range: the last N bytes
+            let suffix: u64 = last.parse().map_err(|_| RangeError::Invalid)?;
+            if suffix == 0 || total == 0 {
+                continue;
+            }
+            ByteRange {
+                start: total.saturating_sub(suffix),
+                end: total - 1,
+            }
+        } else {
+            let start: u64 = first.parse().map_err(|_| RangeError::Invalid)?;
+            let end = if last.is_empty() {
+                total.saturating_sub(1)
+            } else {
+                let end: u64 = last.parse().map_err(|_| RangeError::Invalid)?;
+                if end < start {
+                    return Err(RangeError::Invalid);
+                }
+                end.min(total.saturating_sub(1))
+            };
+            
+            // Ranges starting past the end are unsatisfiable and skipped
+            if start >= total {
+                continue;
+            }
+            ByteRange { start, end }
+        };
+        
+        ranges.push(range);
+    }
+    
+    if ranges.is_empty() {
+        return Err(RangeError::Unsatisfiable);
+    }
+    
+    Ok(ranges)
+}
+
+/// Layout of a `multipart/byteranges` body for a set of ranges
+#[derive(Debug, Clone)]
+pub struct MultipartRanges {
+    /// Boundary separating the parts
+    boundary: String,
+    /// Content type of each part
+    content_type: String,
+    /// Size of the whole representation
+    total: u64,
+}
+
+impl MultipartRanges {
+    /// Create a layout with a fresh boundary
+    pub fn new(content_type: &str, total: u64) -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        let counter = BOUNDARY_COUNTER.fetch_add(1, Ordering::Relaxed);
+        
+        MultipartRanges {
+            boundary: format!("kaserve-{:08x}{:08x}", nanos, counter),
+            content_type: content_type.to_string(),
+            total,
+        }
+    }
+    
+    /// Content-Type header value for the multipart response
+    pub fn content_type(&self) -> String {
+        format!("multipart/byteranges; boundary={}", self.boundary)
+    }
+    
+    /// Delimiter and headers preceding the data of a part
+    pub fn part_header(&self, range: &ByteRange) -> String {
+        format!(
+            "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+            self.boundary,
+            self.content_type,
+            range.content_range(self.total)
+        )
+    }
+    
+    /// Closing delimiter after the last part
+    pub fn trailer(&self) -> String {
+        format!("\r\n--{}--\r\n", self.boundary)
+    }
+    
+    /// Total length of the multipart body for the given ranges
+    pub fn body_len(&self, ranges: &[ByteRange]) -> u64 {
+        let parts: u64 = ranges
+            .iter()
+            .map(|range| self.part_header(range).len() as u64 + range.len())
+            .sum();
+        parts + self.trailer().len() as u64
+    }
+    
+    /// Assemble the multipart body from an in-memory representation
+    pub fn encode(&self, ranges: &[ByteRange], data: &[u8]) -> Vec<u8> {
+        let mut body = Vec::with_capacity(self.body_len(ranges) as usize);
+        for range in ranges {
+            body.extend_from_slice(self.part_header(range).as_bytes());
+            body.extend_from_slice(&data[range.start as usize..=range.end as usize]);
+        }
+        body.extend_from_slice(self.trailer().as_bytes());
+        body
+    }
+}
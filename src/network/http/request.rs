@@ -1,7 +1,12 @@
+use hyper::body::HttpBody;
 use hyper::{Body, Request};
 use std::collections::HashMap;
+use std::error::Error;
 use std::net::SocketAddr;
 
+use crate::core::error::HttpError;
+use crate::utils::geoip::GeoInfo;
+
 /// Extended request information with additional context
 pub struct RequestContext {
     /// The original HTTP request
@@ -13,12 +18,13 @@ pub struct RequestContext {
 }
 
 impl RequestContext {
-    /// Create a new request context
+    /// Create a new request context, with the client's GeoIP details as `geo.*` attributes
     pub fn new(request: Request<Body>) -> Self {
+        let attributes = request.extensions().get::<GeoInfo>().map(GeoInfo::attributes).unwrap_or_default();
         RequestContext {
             request,
             remote_addr: None,
-            attributes: HashMap::new(),
+            attributes,
         }
     }
     
@@ -68,3 +74,24 @@ impl RequestContext {
         self.attributes.get(name)
     }
 }
+
+/// Wrap a request body so that reading fails once more than `limit` bytes arrive.
+///
+/// The error carries `HttpError::PayloadTooLarge` as its source, so a handler
+/// passing it on is answered with 413.
+pub fn body_with_limit(body: Body, limit: u64) -> Body {
+    let stream = futures::stream::unfold(Some((body, 0u64)), move |state| async move {
+        let (mut body, received) = state?;
+        let chunk = match body.data().await? {
+            Ok(chunk) => chunk,
+            Err(e) => return Some((Err(Box::new(e) as Box<dyn Error + Send + Sync>), None)),
+        };
+        let received = received + chunk.len() as u64;
+        if received > limit {
+            return Some((Err(Box::new(HttpError::PayloadTooLarge) as Box<dyn Error + Send + Sync>), None));
+        }
+        Some((Ok(chunk), Some((body, received))))
+    });
+    
+    Body::wrap_stream(stream)
+}
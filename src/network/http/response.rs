@@ -1,6 +1,27 @@
-use hyper::{Body, Response, StatusCode};
-use hyper::header;
+use hyper::{Body, Method, Response, StatusCode};
+use hyper::body::HttpBody;
+use hyper::header::{self, HeaderMap};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
 use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+use crate::network::http::conditional::{self, Precondition};
+
+/// Characters that must be percent-encoded in an RFC 5987 `attr-char` value
+const RFC5987_ATTR_CHAR: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'!')
+    .remove(b'#')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'+')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'^')
+    .remove(b'_')
+    .remove(b'`')
+    .remove(b'|')
+    .remove(b'~');
 
 /// Helper for building HTTP responses
 pub struct ResponseBuilder {
@@ -10,6 +31,8 @@ pub struct ResponseBuilder {
     headers: hyper::header::HeaderMap,
     /// Response body
     body: Option<Body>,
+    /// Trailing header fields sent after the body
+    trailers: Option<hyper::header::HeaderMap>,
 }
 
 impl ResponseBuilder {
@@ -19,6 +42,7 @@ impl ResponseBuilder {
             status: StatusCode::OK,
             headers: hyper::header::HeaderMap::new(),
             body: None,
+            trailers: None,
         }
     }
     
@@ -46,6 +70,17 @@ impl ResponseBuilder {
         self
     }
     
+    /// Add a header to the response, keeping any earlier values of the same header
+    pub fn append_header(mut self, name: &str, value: &str) -> Self {
+        if let (Ok(name), Ok(value)) = (
+            hyper::header::HeaderName::from_bytes(name.as_bytes()),
+            hyper::header::HeaderValue::from_str(value),
+        ) {
+            self.headers.append(name, value);
+        }
+        self
+    }
+    
     /// Set the content type header
     pub fn content_type(self, content_type: &str) -> Self {
         self.header("content-type", content_type)
@@ -56,6 +91,60 @@ impl ResponseBuilder {
         self.header("cache-control", directive)
     }
     
+    /// Mark the response as a download with the given filename (RFC 6266/5987)
+    pub fn attachment(self, filename: &str) -> Self {
+        // Plain ASCII fallback for clients that ignore filename*
+        let fallback: String = filename
+            .chars()
+            .map(|c| if (c.is_ascii_graphic() && c != '"' && c != '\\') || c == ' ' { c } else { '_' })
+            .collect();
+        
+        let value = if filename.is_ascii() && fallback == filename {
+            format!("attachment; filename=\"{}\"", fallback)
+        } else {
+            let encoded = utf8_percent_encode(filename, RFC5987_ATTR_CHAR);
+            format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", fallback, encoded)
+        };
+        
+        self.header("content-disposition", &value)
+    }
+    
+    /// Set the entity tag
+    pub fn etag(self, etag: &str) -> Self {
+        self.header("etag", etag)
+    }
+    
+    /// Evaluate a request's preconditions against this response's `ETag` and `Last-Modified`
+    pub fn preconditions(&self, request_headers: &HeaderMap, method: &Method) -> Precondition {
+        let etag = self.headers.get(header::ETAG).and_then(|v| v.to_str().ok());
+        let last_modified = self.headers
+            .get(header::LAST_MODIFIED)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| httpdate::parse_http_date(v).ok());
+        
+        conditional::evaluate(request_headers, method, etag, last_modified)
+    }
+    
+    /// Build a 304 Not Modified response carrying only the headers allowed on it
+    pub fn not_modified(self) -> Response<Body> {
+        let mut builder = Self::with_status(StatusCode::NOT_MODIFIED);
+        
+        for name in [
+            header::ETAG,
+            header::LAST_MODIFIED,
+            header::CACHE_CONTROL,
+            header::CONTENT_LOCATION,
+            header::EXPIRES,
+            header::VARY,
+        ] {
+            if let Some(value) = self.headers.get(&name) {
+                builder.headers.insert(name, value.clone());
+            }
+        }
+        
+        builder.build()
+    }
+    
     /// Add common headers for static file responses
     pub fn with_static_file_headers(self, mime_type: &str, modified: Option<SystemTime>) -> Self {
         let with_content_type = self.content_type(mime_type);
@@ -85,12 +174,24 @@ impl ResponseBuilder {
         self
     }
     
+    /// Set body from a hyper body
+    pub fn body(mut self, body: Body) -> Self {
+        self.body = Some(body);
+        self
+    }
+    
     /// Set an empty body
     pub fn empty_body(mut self) -> Self {
         self.body = Some(Body::empty());
         self
     }
     
+    /// Set trailing header fields to send after the body
+    pub fn trailers(mut self, trailers: hyper::header::HeaderMap) -> Self {
+        self.trailers = Some(trailers);
+        self
+    }
+    
     /// Build the final response
     pub fn build(self) -> Response<Body> {
         let mut response = Response::builder()
@@ -101,8 +202,17 @@ impl ResponseBuilder {
             response = response.header(name, value);
         }
         
+        let mut body = self.body.unwrap_or_else(Body::empty);
+        
+        // Announce and attach trailers if any were provided
+        if let Some(trailers) = self.trailers.filter(|t| !t.is_empty()) {
+            let names: Vec<&str> = trailers.keys().map(|name| name.as_str()).collect();
+            response = response.header(header::TRAILER, names.join(", "));
+            body = body_with_trailers(body, trailers);
+        }
+        
         // Set the body or use empty body if none provided
-        response.body(self.body.unwrap_or_else(Body::empty))
+        response.body(body)
             .unwrap_or_else(|_| {
                 Response::builder()
                     .status(StatusCode::INTERNAL_SERVER_ERROR)
@@ -111,6 +221,14 @@ impl ResponseBuilder {
             })
     }
     
+    /// Create a redirect response pointing to `location`
+    pub fn redirect(status: StatusCode, location: &str) -> Response<Body> {
+        Self::with_status(status)
+            .header("location", location)
+            .empty_body()
+            .build()
+    }
+    
     /// Create a simple 404 Not Found response
     pub fn not_found() -> Response<Body> {
         Self::with_status(StatusCode::NOT_FOUND)
@@ -128,3 +246,67 @@ impl ResponseBuilder {
             .build()
     }
 }
+
+impl Default for ResponseBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Wrap a body so that it ends once `deadline` passes.
+///
+/// Data produced before the deadline is still delivered. A chunked body is then
+/// terminated normally; a body with a fixed Content-Length comes up short, which
+/// makes the connection close instead of sending an error after the headers.
+pub fn body_with_deadline(body: Body, deadline: Instant) -> Body {
+    let stream = futures::stream::unfold(Some(body), move |body| async move {
+        let mut body = body?;
+        tokio::select! {
+            chunk = body.data() => chunk.map(|chunk| (chunk, Some(body))),
+            _ = tokio::time::sleep_until(deadline) => {
+                warn!("Response deadline reached, truncating body");
+                None
+            }
+        }
+    });
+    
+    Body::wrap_stream(stream)
+}
+
+/// Wrap a body so that the given trailers are sent once its data is exhausted.
+///
+/// Trailers are transmitted on HTTP/2 and chunked HTTP/1.1 responses where the
+/// protocol implementation supports them; otherwise they are dropped.
+pub fn body_with_trailers(mut body: Body, trailers: hyper::header::HeaderMap) -> Body {
+    let (mut sender, wrapped) = Body::channel();
+    
+    tokio::spawn(async move {
+        while let Some(chunk) = body.data().await {
+            match chunk {
+                Ok(data) => {
+                    if sender.send_data(data).await.is_err() {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    debug!("Error reading body before trailers: {}", e);
+                    sender.abort();
+                    return;
+                }
+            }
+        }
+        
+        // Merge trailers from the inner body with the explicit ones
+        let mut all_trailers = match body.trailers().await {
+            Ok(Some(inner)) => inner,
+            _ => hyper::header::HeaderMap::new(),
+        };
+        all_trailers.extend(trailers);
+        
+        if let Err(e) = sender.send_trailers(all_trailers).await {
+            debug!("Failed to send trailers: {}", e);
+        }
+    });
+    
+    wrapped
+}
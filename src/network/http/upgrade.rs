@@ -0,0 +1,97 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::upgrade::OnUpgrade;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+use crate::core::error::HttpError;
+use crate::network::http::response::ResponseBuilder;
+use crate::plugins::api::WebSocketHandler;
+
+/// GUID appended to the client key when computing `Sec-WebSocket-Accept` (RFC 6455)
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Check whether a header list contains a token, ignoring case
+fn has_token(headers: &HeaderMap, name: header::HeaderName, token: &str) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case(token))
+}
+
+/// Check whether a request asks to upgrade the connection to WebSocket
+pub fn is_websocket_upgrade(headers: &HeaderMap) -> bool {
+    has_token(headers, header::CONNECTION, "upgrade") && has_token(headers, header::UPGRADE, "websocket")
+}
+
+/// Restore the upgrade headers that hop-by-hop stripping removed
+pub fn restore_upgrade_headers(headers: &mut HeaderMap) {
+    headers.insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+    headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+}
+
+/// Compute the `Sec-WebSocket-Accept` value for a client key
+pub fn websocket_accept(key: &str) -> String {
+    let digest = sha1_smol::Sha1::from(format!("{}{}", key.trim(), WEBSOCKET_GUID)).digest();
+    STANDARD.encode(digest.bytes())
+}
+
+/// Relay bytes between two upgraded connections once both upgrades complete
+pub fn tunnel(client: OnUpgrade, upstream: OnUpgrade) {
+    tokio::spawn(async move {
+        let (mut client, mut upstream) = match tokio::try_join!(client, upstream) {
+            Ok(connections) => connections,
+            Err(e) => {
+                warn!("Connection upgrade failed: {}", e);
+                return;
+            }
+        };
+        
+        match tokio::io::copy_bidirectional(&mut client, &mut upstream).await {
+            Ok((sent, received)) => debug!("Upgraded connection closed ({} bytes sent, {} received)", sent, received),
+            Err(e) => debug!("Upgraded connection ended: {}", e),
+        }
+    });
+}
+
+/// Complete a WebSocket handshake and hand the connection to a plugin handler
+pub fn accept_websocket(
+    mut req: Request<Body>,
+    handler: Arc<dyn WebSocketHandler>,
+) -> Result<Response<Body>, HttpError> {
+    let version_ok = req
+        .headers()
+        .get(header::SEC_WEBSOCKET_VERSION)
+        .is_some_and(|v| v.as_bytes() == b"13");
+    let key = req
+        .headers()
+        .get(header::SEC_WEBSOCKET_KEY)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    
+    let key = match key {
+        Some(key) if version_ok && req.method() == Method::GET => key,
+        _ => return Err(HttpError::BadRequest("Invalid WebSocket handshake.".to_string())),
+    };
+    
+    let on_upgrade = hyper::upgrade::on(&mut req);
+    let (parts, _) = req.into_parts();
+    let head = Request::from_parts(parts, ());
+    
+    tokio::spawn(async move {
+        match on_upgrade.await {
+            Ok(upgraded) => handler.handle(upgraded, head).await,
+            Err(e) => warn!("WebSocket upgrade failed: {}", e),
+        }
+    });
+    
+    Ok(ResponseBuilder::with_status(StatusCode::SWITCHING_PROTOCOLS)
+        .header("connection", "upgrade")
+        .header("upgrade", "websocket")
+        .header("sec-websocket-accept", &websocket_accept(&key))
+        .build())
+}
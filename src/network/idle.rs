@@ -0,0 +1,159 @@
+use futures::Future;
+use hyper::body::HttpBody;
+use hyper::Body;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{Instant, Sleep};
+use tracing::debug;
+
+/// Requests in progress on a connection, which keep it from timing out as idle
+#[derive(Clone, Default)]
+pub struct ConnectionActivity {
+    state: Arc<ActivityState>,
+}
+
+#[derive(Default)]
+struct ActivityState {
+    /// Requests whose response has not been sent completely
+    active: AtomicUsize,
+    /// Whether the connection was handed to another protocol, such as WebSocket
+    upgraded: AtomicBool,
+}
+
+/// A request in progress, counted until dropped
+pub struct ActiveRequest {
+    state: Arc<ActivityState>,
+}
+
+impl ConnectionActivity {
+    /// Count a request as in progress until the returned guard is dropped
+    pub fn begin(&self) -> ActiveRequest {
+        self.state.active.fetch_add(1, Ordering::AcqRel);
+        ActiveRequest { state: Arc::clone(&self.state) }
+    }
+    
+    /// Stop timing the connection out, as an upgraded protocol manages its own lifetime
+    pub fn upgrade(&self) {
+        self.state.upgraded.store(true, Ordering::Release);
+    }
+    
+    /// Check if the connection is waiting for a request
+    fn is_idle(&self) -> bool {
+        self.state.active.load(Ordering::Acquire) == 0 && !self.state.upgraded.load(Ordering::Acquire)
+    }
+}
+
+impl Drop for ActiveRequest {
+    fn drop(&mut self) {
+        self.state.active.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Connection stream that ends once it has been idle for too long.
+///
+/// The timer restarts whenever bytes are read or written, and only runs out
+/// while no request is in progress, so slow handlers and long downloads are
+/// unaffected. Timing out reads as the client closing the connection, which
+/// lets the HTTP implementation shut it down cleanly.
+pub struct IdleStream<S> {
+    inner: S,
+    /// Idle timeout and its timer, if connections time out
+    timer: Option<(Duration, Pin<Box<Sleep>>)>,
+    activity: ConnectionActivity,
+}
+
+impl<S> IdleStream<S> {
+    /// Wrap a stream, closing it after `timeout` without activity; without a timeout it never closes
+    pub fn new(inner: S, timeout: Option<Duration>, activity: ConnectionActivity) -> Self {
+        IdleStream {
+            inner,
+            timer: timeout.map(|timeout| (timeout, Box::pin(tokio::time::sleep(timeout)))),
+            activity,
+        }
+    }
+    
+    /// Restart the idle timer
+    fn touch(&mut self) {
+        if let Some((timeout, sleep)) = &mut self.timer {
+            sleep.as_mut().reset(Instant::now() + *timeout);
+        }
+    }
+    
+    /// Check if the idle timer has run out, registering for a wakeup if not
+    fn poll_expired(&mut self, cx: &mut Context<'_>) -> bool {
+        let Some((timeout, sleep)) = self.timer.as_mut().filter(|_| self.activity.is_idle()) else {
+            return false;
+        };
+        if sleep.as_mut().poll(cx).is_pending() {
+            return false;
+        }
+        debug!("Closing connection idle for {:?}", timeout);
+        true
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for IdleStream<S> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        match Pin::new(&mut self.inner).poll_read(cx, buf) {
+            Poll::Ready(result) => {
+                if buf.filled().len() > filled {
+                    self.touch();
+                }
+                Poll::Ready(result)
+            }
+            Poll::Pending if self.poll_expired(cx) => Poll::Ready(Ok(())),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for IdleStream<S> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if matches!(result, Poll::Ready(Ok(written)) if written > 0) {
+            self.touch();
+        }
+        result
+    }
+    
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+        if matches!(result, Poll::Ready(Ok(written)) if written > 0) {
+            self.touch();
+        }
+        result
+    }
+    
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+    
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+    
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Wrap a response body so that its request stays in progress until the body has been sent
+pub fn body_while_active(body: Body, request: ActiveRequest) -> Body {
+    let stream = futures::stream::unfold(Some((body, request)), |state| async move {
+        let (mut body, request) = state?;
+        let chunk = body.data().await?;
+        Some((chunk, Some((body, request))))
+    });
+    
+    Body::wrap_stream(stream)
+}
@@ -1,2 +1,5 @@
 pub mod connection;
+pub mod connection_limit;
 pub mod http;
+pub mod idle;
+pub mod proxy_protocol;
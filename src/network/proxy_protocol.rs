@@ -0,0 +1,124 @@
+use std::error::Error;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Signature opening a PROXY protocol v2 header
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// Maximum length of a PROXY protocol v1 header, including CRLF
+const V1_MAX_LEN: usize = 107;
+
+/// Length of the shortest v1 header (`PROXY UNKNOWN\r\n`)
+const V1_MIN_LEN: usize = 15;
+
+/// Error types for PROXY protocol headers
+#[derive(Debug)]
+pub enum ProxyProtocolError {
+    /// The connection failed or closed before the header was complete
+    Io(std::io::Error),
+    /// The connection does not start with a valid header
+    Invalid(String),
+}
+
+impl fmt::Display for ProxyProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProxyProtocolError::Io(e) => write!(f, "Failed to read PROXY protocol header: {}", e),
+            ProxyProtocolError::Invalid(reason) => write!(f, "Invalid PROXY protocol header: {}", reason),
+        }
+    }
+}
+
+impl Error for ProxyProtocolError {}
+
+impl From<std::io::Error> for ProxyProtocolError {
+    fn from(e: std::io::Error) -> Self {
+        ProxyProtocolError::Io(e)
+    }
+}
+
+/// Read the PROXY protocol (v1 or v2) header opening a connection.
+///
+/// Returns the source address it reports, or `None` for `UNKNOWN` and `LOCAL`
+/// headers, which leave the peer address as is. Reads exactly the header, so
+/// the stream continues with the proxied bytes.
+pub async fn read_header<S: AsyncRead + Unpin>(stream: &mut S) -> Result<Option<SocketAddr>, ProxyProtocolError> {
+    let mut header = vec![0; V1_MIN_LEN];
+    stream.read_exact(&mut header).await?;
+    
+    if header.starts_with(&V2_SIGNATURE) {
+        header.push(stream.read_u8().await?);
+        let len = u16::from_be_bytes([header[14], header[15]]) as usize;
+        let mut payload = vec![0; len];
+        stream.read_exact(&mut payload).await?;
+        return parse_v2(header[12], header[13], &payload);
+    }
+    
+    if !header.starts_with(b"PROXY ") {
+        return Err(ProxyProtocolError::Invalid("missing signature".to_string()));
+    }
+    
+    // The line is short, so read it a byte at a time to avoid consuming proxied data
+    while !header.ends_with(b"\r\n") {
+        if header.len() >= V1_MAX_LEN {
+            return Err(ProxyProtocolError::Invalid("v1 header too long".to_string()));
+        }
+        header.push(stream.read_u8().await?);
+    }
+    
+    let line = std::str::from_utf8(&header[..header.len() - 2])
+        .map_err(|_| ProxyProtocolError::Invalid("v1 header is not ASCII".to_string()))?;
+    parse_v1(line)
+}
+
+/// Parse a v1 line such as `PROXY TCP4 192.0.2.1 198.51.100.1 56324 443`
+fn parse_v1(line: &str) -> Result<Option<SocketAddr>, ProxyProtocolError> {
+    let invalid = || ProxyProtocolError::Invalid(format!("malformed v1 header: {}", line));
+    let fields: Vec<&str> = line.split(' ').collect();
+    
+    match fields.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        ["PROXY", "TCP4" | "TCP6", source, _destination, source_port, _destination_port] => {
+            let ip: IpAddr = source.parse().map_err(|_| invalid())?;
+            let port: u16 = source_port.parse().map_err(|_| invalid())?;
+            Ok(Some(SocketAddr::new(ip, port)))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Parse the binary v2 header fields following the signature
+fn parse_v2(version_command: u8, family: u8, payload: &[u8]) -> Result<Option<SocketAddr>, ProxyProtocolError> {
+    if version_command >> 4 != 2 {
+        return Err(ProxyProtocolError::Invalid(format!("unsupported version {}", version_command >> 4)));
+    }
+    
+    match version_command & 0x0f {
+        // LOCAL: health checks from the proxy itself
+        0 => return Ok(None),
+        1 => {}
+        command => return Err(ProxyProtocolError::Invalid(format!("unsupported command {}", command))),
+    }
+    
+    let truncated = || ProxyProtocolError::Invalid("truncated v2 addresses".to_string());
+    match family >> 4 {
+        // AF_INET: source, destination, source port, destination port
+        1 => {
+            let addresses = payload.get(..12).ok_or_else(truncated)?;
+            let ip = Ipv4Addr::new(addresses[0], addresses[1], addresses[2], addresses[3]);
+            let port = u16::from_be_bytes([addresses[8], addresses[9]]);
+            Ok(Some(SocketAddr::new(IpAddr::V4(ip), port)))
+        }
+        // AF_INET6
+        2 => {
+            let addresses = payload.get(..36).ok_or_else(truncated)?;
+            let mut octets = [0; 16];
+            octets.copy_from_slice(&addresses[..16]);
+            let port = u16::from_be_bytes([addresses[32], addresses[33]]);
+            Ok(Some(SocketAddr::new(IpAddr::V6(Ipv6Addr::from(octets)), port)))
+        }
+        // AF_UNSPEC and AF_UNIX carry no usable client address
+        _ => Ok(None),
+    }
+}
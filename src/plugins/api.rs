@@ -1,10 +1,11 @@
 use async_trait::async_trait;
+use hyper::upgrade::Upgraded;
 use hyper::{Body, Request, Response};
 use std::error::Error;
 use std::sync::Arc;
 
 use crate::core::config::Config;
-use crate::core::server::Server;
+use crate::core::middleware::{Middleware, Stage};
 
 /// Plugin trait that must be implemented by all plugins
 #[async_trait]
@@ -15,8 +16,8 @@ pub trait Plugin: Send + Sync {
     /// Get the version of the plugin
     fn version(&self) -> &str;
     
-    /// Initialize the plugin
-    async fn init(&mut self, config: Arc<Config>) -> Result<(), Box<dyn Error + Send + Sync>>;
+    /// Initialize the plugin with the server configuration and its `[plugins.<name>]` settings
+    async fn init(&mut self, context: PluginContext) -> Result<(), Box<dyn Error + Send + Sync>>;
     
     /// Shutdown the plugin
     async fn shutdown(&mut self) -> Result<(), Box<dyn Error + Send + Sync>>;
@@ -32,6 +33,26 @@ pub trait Plugin: Send + Sync {
         // Default implementation: pass through response
         Ok(res)
     }
+    
+    /// Get the WebSocket handler provided by this plugin, if any
+    fn websocket_handler(&self) -> Option<Arc<dyn WebSocketHandler>> {
+        None
+    }
+    
+    /// Get the middleware provided by this plugin, with the stage each runs at unless configured otherwise
+    fn middleware(&self) -> Vec<(Stage, Arc<dyn Middleware>)> {
+        Vec::new()
+    }
+}
+
+/// Handler for WebSocket connections, provided by plugins
+#[async_trait]
+pub trait WebSocketHandler: Send + Sync {
+    /// Check whether this handler accepts WebSocket upgrades for a path
+    fn matches(&self, path: &str) -> bool;
+    
+    /// Take over a connection after the handshake; frames are read and written by the handler
+    async fn handle(&self, connection: Upgraded, request: Request<()>);
 }
 
 /// Plugin lifecycle events
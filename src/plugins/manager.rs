@@ -1,15 +1,18 @@
-use std::collections::HashMap;
 use std::error::Error;
 use std::sync::{Arc, Mutex};
-use tracing::{debug, error, info};
+use tracing::{debug, info};
 
 use crate::core::config::Config;
-use crate::plugins::api::{Plugin, PluginContext, PluginEvent};
+use crate::core::middleware::{Middleware, Stage};
+use crate::plugins::api::{Plugin, PluginContext, PluginEvent, WebSocketHandler};
+
+/// Plugin as held by the manager and the request pipeline
+pub type RegisteredPlugin = Arc<Box<dyn Plugin>>;
 
 /// Manager for server plugins
 pub struct PluginManager {
-    /// Registered plugins
-    plugins: Arc<Mutex<HashMap<String, Box<dyn Plugin>>>>,
+    /// Registered plugins in registration order
+    plugins: Arc<Mutex<Vec<RegisteredPlugin>>>,
     /// Server configuration
     config: Option<Arc<Config>>,
 }
@@ -18,39 +21,87 @@ impl PluginManager {
     /// Create a new plugin manager
     pub fn new() -> Self {
         PluginManager {
-            plugins: Arc::new(Mutex::new(HashMap::new())),
+            plugins: Arc::new(Mutex::new(Vec::new())),
             config: None,
         }
     }
     
     /// Initialize the plugin manager
     pub fn init(&mut self, config: Arc<Config>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.config.is_some() {
+            return Err("Plugin manager has already been initialized".into());
+        }
+        
         self.config = Some(Arc::clone(&config));
         
-        // Initialize plugins
-        let plugins = self.plugins.lock().unwrap();
-        for (name, plugin) in plugins.iter() {
+        Ok(())
+    }
+    
+    /// Initialize registered plugins with their `[plugins.<name>]` settings, dropping disabled plugins
+    pub async fn init_plugins(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let config = self.config.clone().ok_or("Plugin manager has not been initialized")?;
+        let registered = std::mem::take(&mut *self.plugins.lock().unwrap());
+        
+        let mut initialized = Vec::with_capacity(registered.len());
+        for mut plugin in registered {
+            let name = plugin.name().to_string();
+            let settings = config.plugins.as_ref().and_then(|plugins| plugins.settings.get(&name));
+            if settings.and_then(|settings| settings.enabled) == Some(false) {
+                info!("Plugin {} is disabled", name);
+                continue;
+            }
+            
             info!("Initializing plugin: {} v{}", name, plugin.version());
+            let mut context = PluginContext::new(Arc::clone(&config));
+            if let Some(settings) = settings {
+                context = context.with_plugin_config(serde_json::Value::Object(settings.config.clone()));
+            }
+            
+            // Plugins are only shared once the server runs, so each is still uniquely owned here
+            let instance = Arc::get_mut(&mut plugin).ok_or_else(|| format!("Plugin {} is in use and cannot be initialized", name))?;
+            instance.init(context).await.map_err(|e| format!("Failed to initialize plugin {}: {}", name, e))?;
+            initialized.push(plugin);
         }
         
+        *self.plugins.lock().unwrap() = initialized;
         Ok(())
     }
     
-    /// Register a plugin
+    /// Register a plugin, replacing a registered plugin of the same name in place
     pub fn register_plugin<P: Plugin + 'static>(&self, plugin: P) -> Result<(), Box<dyn Error + Send + Sync>> {
-        let name = plugin.name().to_string();
-        info!("Registering plugin: {} v{}", name, plugin.version());
+        info!("Registering plugin: {} v{}", plugin.name(), plugin.version());
         
+        let plugin: RegisteredPlugin = Arc::new(Box::new(plugin));
         let mut plugins = self.plugins.lock().unwrap();
-        plugins.insert(name, Box::new(plugin));
+        match plugins.iter_mut().find(|registered| registered.name() == plugin.name()) {
+            Some(registered) => *registered = plugin,
+            None => plugins.push(plugin),
+        }
         
         Ok(())
     }
     
     /// Get a plugin by name
-    pub fn get_plugin(&self, name: &str) -> Option<Arc<Box<dyn Plugin>>> {
+    pub fn get_plugin(&self, name: &str) -> Option<RegisteredPlugin> {
+        let plugins = self.plugins.lock().unwrap();
+        plugins.iter().find(|plugin| plugin.name() == name).cloned()
+    }
+    
+    /// Get the registered plugins in registration order
+    pub fn plugins(&self) -> Vec<RegisteredPlugin> {
+        self.plugins.lock().unwrap().clone()
+    }
+    
+    /// Collect the WebSocket handlers provided by registered plugins
+    pub fn websocket_handlers(&self) -> Vec<Arc<dyn WebSocketHandler>> {
+        let plugins = self.plugins.lock().unwrap();
+        plugins.iter().filter_map(|plugin| plugin.websocket_handler()).collect()
+    }
+    
+    /// Collect the middleware provided by registered plugins, in registration order
+    pub fn middleware(&self) -> Vec<(Stage, Arc<dyn Middleware>)> {
         let plugins = self.plugins.lock().unwrap();
-        plugins.get(name).map(|p| Arc::new(Box::clone(p)))
+        plugins.iter().flat_map(|plugin| plugin.middleware()).collect()
     }
     
     /// Notify all plugins of an event
@@ -59,8 +110,8 @@ impl PluginManager {
         
         debug!("Notifying plugins of event: {:?}", event);
         
-        for (name, _) in plugins.iter() {
-            debug!("Notifying plugin: {}", name);
+        for plugin in plugins.iter() {
+            debug!("Notifying plugin: {}", plugin.name());
             // In a full implementation, we would call methods on the plugin based on the event
         }
     }
@@ -76,3 +127,9 @@ impl PluginManager {
         Ok(())
     }
 }
+
+impl Default for PluginManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,2 +1,4 @@
 pub mod manager;
 pub mod api;
+pub mod pipeline;
+pub mod wasm;
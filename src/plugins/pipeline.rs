@@ -0,0 +1,184 @@
+use futures::{FutureExt, StreamExt};
+use hyper::body::HttpBody;
+use hyper::header::TRAILER;
+use hyper::{Body, Request, Response};
+use std::any::Any;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, error, warn};
+
+use crate::core::config::Config;
+use crate::core::error::HttpError;
+use crate::plugins::manager::RegisteredPlugin;
+
+/// Registered plugins run as middleware around handler dispatch.
+///
+/// `pre_request` hooks run in registration order and `post_response` hooks in
+/// reverse order. A hook returning an `HttpError` deliberately rejects the
+/// request with that error. A hook failing otherwise, or panicking, is skipped
+/// and the message it was given continues down the chain, unless the plugin is
+/// listed in `plugins.required` or already consumed the body, which fails the request.
+#[derive(Clone, Default)]
+pub struct PluginPipeline {
+    /// Plugins in registration order, with whether their failures fail the request
+    plugins: Arc<Vec<(RegisteredPlugin, bool)>>,
+}
+
+impl PluginPipeline {
+    /// Build the pipeline from plugins in registration order
+    pub fn new(plugins: Vec<RegisteredPlugin>, config: &Config) -> Self {
+        let required = config.plugins.as_ref().and_then(|plugins| plugins.required.as_deref()).unwrap_or_default();
+        let plugins = plugins
+            .into_iter()
+            .map(|plugin| {
+                let is_required = required.iter().any(|name| name == plugin.name());
+                (plugin, is_required)
+            })
+            .collect();
+        
+        PluginPipeline { plugins: Arc::new(plugins) }
+    }
+    
+    /// Check whether any plugins are registered
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+    
+    /// Get the plugins in registration order, with whether their failures fail the request
+    pub fn plugins(&self) -> impl Iterator<Item = (&RegisteredPlugin, bool)> {
+        self.plugins.iter().map(|(plugin, required)| (plugin, *required))
+    }
+    
+    /// Run the `pre_request` hooks
+    pub async fn pre_request(&self, mut req: Request<Body>) -> Result<Request<Body>, HttpError> {
+        for (plugin, required) in self.plugins.iter() {
+            let (parts, body) = req.into_parts();
+            let mut head = Request::new(());
+            *head.method_mut() = parts.method.clone();
+            *head.uri_mut() = parts.uri.clone();
+            *head.version_mut() = parts.version;
+            *head.headers_mut() = parts.headers.clone();
+            
+            let (body, slot) = detach(body, false);
+            let outcome = run_hook(plugin.pre_request(Request::from_parts(parts, body))).await;
+            req = match outcome {
+                Ok(next) => next,
+                Err(HookFailure::Rejected(e)) => {
+                    debug!("Plugin {} rejected the request: {}", plugin.name(), e);
+                    return Err(e);
+                }
+                Err(HookFailure::Failed(reason)) => {
+                    let body = Self::recover(plugin.name(), *required, "request", &reason, &slot)?;
+                    let (head, ()) = head.into_parts();
+                    Request::from_parts(head, body)
+                }
+            };
+        }
+        
+        Ok(req)
+    }
+    
+    /// Run the `post_response` hooks
+    pub async fn post_response(&self, mut res: Response<Body>) -> Result<Response<Body>, HttpError> {
+        for (plugin, required) in self.plugins.iter().rev() {
+            let (parts, body) = res.into_parts();
+            let mut head = Response::new(());
+            *head.status_mut() = parts.status;
+            *head.version_mut() = parts.version;
+            *head.headers_mut() = parts.headers.clone();
+            
+            let (body, slot) = detach(body, parts.headers.contains_key(TRAILER));
+            let outcome = run_hook(plugin.post_response(Response::from_parts(parts, body))).await;
+            res = match outcome {
+                Ok(next) => next,
+                Err(HookFailure::Rejected(e)) => {
+                    debug!("Plugin {} replaced the response: {}", plugin.name(), e);
+                    return Err(e);
+                }
+                Err(HookFailure::Failed(reason)) => {
+                    let body = Self::recover(plugin.name(), *required, "response", &reason, &slot)?;
+                    let (head, ()) = head.into_parts();
+                    Response::from_parts(head, body)
+                }
+            };
+        }
+        
+        Ok(res)
+    }
+    
+    /// Get back the body a failed plugin was given, or the error failing the request
+    fn recover(name: &str, required: bool, message: &str, reason: &str, slot: &BodySlot) -> Result<Body, HttpError> {
+        if required {
+            error!("Required plugin {} failed on {}: {}", name, message, reason);
+            return Err(HttpError::Internal(format!("Plugin {} failed", name)));
+        }
+        
+        match slot.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            Some(body) => {
+                warn!("Plugin {} failed on {}, skipping it: {}", name, message, reason);
+                Ok(body)
+            }
+            None => {
+                error!("Plugin {} failed on {} after reading its body: {}", name, message, reason);
+                Err(HttpError::Internal(format!("Plugin {} failed", name)))
+            }
+        }
+    }
+}
+
+/// Body held back until a plugin first reads it
+type BodySlot = Arc<Mutex<Option<Body>>>;
+
+/// Hand a body to a plugin so it can be recovered if the plugin fails without reading it.
+///
+/// Empty bodies are trivially recoverable. Bodies with trailers are handed over
+/// as is, since a wrapping stream would drop the trailers.
+fn detach(body: Body, has_trailers: bool) -> (Body, BodySlot) {
+    if body.is_end_stream() {
+        return (Body::empty(), Arc::new(Mutex::new(Some(Body::empty()))));
+    }
+    if has_trailers {
+        return (body, Arc::new(Mutex::new(None)));
+    }
+    
+    let slot = Arc::new(Mutex::new(Some(body)));
+    let taken = Arc::clone(&slot);
+    let stream = futures::stream::once(async move { taken.lock().unwrap_or_else(|e| e.into_inner()).take() })
+        .filter_map(futures::future::ready)
+        .flatten();
+    
+    (Body::wrap_stream(stream), slot)
+}
+
+/// Way a plugin hook did not pass its message on
+enum HookFailure {
+    /// The hook rejected the request with an HTTP error
+    Rejected(HttpError),
+    /// The hook failed or panicked
+    Failed(String),
+}
+
+/// Run a plugin hook, catching errors and panics
+async fn run_hook<T, F>(hook: F) -> Result<T, HookFailure>
+where
+    F: Future<Output = Result<T, Box<dyn std::error::Error + Send + Sync>>>,
+{
+    match AssertUnwindSafe(hook).catch_unwind().await {
+        Ok(Ok(message)) => Ok(message),
+        Ok(Err(e)) => match e.downcast::<HttpError>() {
+            Ok(http_error) => Err(HookFailure::Rejected(*http_error)),
+            Err(e) => Err(HookFailure::Failed(e.to_string())),
+        },
+        Err(panic) => Err(HookFailure::Failed(panic_message(panic))),
+    }
+}
+
+/// Describe a panic payload
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
@@ -0,0 +1,336 @@
+use async_trait::async_trait;
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::{Body, Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+use wasmtime::{Caller, Engine, Extern, InstancePre, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use crate::core::config::{Config, WasmPluginConfig};
+use crate::core::error::HttpError;
+use crate::plugins::api::{Plugin, PluginContext};
+
+/// Default fuel for each hook call, roughly the number of instructions it may execute
+pub const DEFAULT_FUEL: u64 = 10_000_000;
+
+/// Default limit of a plugin's linear memory in megabytes
+pub const DEFAULT_MEMORY_LIMIT: u64 = 16;
+
+/// Export called with the request head
+const REQUEST_HOOK: &str = "on_request";
+
+/// Export called with the response head
+const RESPONSE_HOOK: &str = "on_response";
+
+/// Export the host calls to reserve guest memory for the hook input
+const ALLOC_EXPORT: &str = "kaserve_alloc";
+
+/// Error types for WebAssembly plugins
+#[derive(Debug)]
+pub enum WasmError {
+    /// The module could not be read, compiled or linked
+    Load(String, wasmtime::Error),
+    /// The module traps, runs out of fuel or memory, or lacks a required export
+    Call(wasmtime::Error),
+    /// The module exchanged malformed data with the host
+    Protocol(String),
+}
+
+impl fmt::Display for WasmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WasmError::Load(path, e) => write!(f, "Failed to load WebAssembly plugin {}: {:#}", path, e),
+            WasmError::Call(e) => write!(f, "WebAssembly plugin failed: {:#}", e),
+            WasmError::Protocol(reason) => write!(f, "WebAssembly plugin protocol error: {}", reason),
+        }
+    }
+}
+
+impl Error for WasmError {}
+
+impl From<wasmtime::Error> for WasmError {
+    fn from(e: wasmtime::Error) -> Self {
+        WasmError::Call(e)
+    }
+}
+
+/// Request head passed to `on_request` as JSON
+#[derive(Serialize)]
+struct RequestHead {
+    method: String,
+    uri: String,
+    headers: Vec<(String, String)>,
+}
+
+/// Response head passed to `on_response` as JSON
+#[derive(Serialize)]
+struct ResponseHead {
+    status: u16,
+    headers: Vec<(String, String)>,
+}
+
+/// Changes a hook asks for, returned as JSON
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+struct Action {
+    /// Header fields to set, replacing existing values
+    set_headers: Vec<(String, String)>,
+    /// Header fields to remove
+    remove_headers: Vec<String>,
+    /// New request target (requests only)
+    uri: Option<String>,
+    /// New status code (responses only)
+    status: Option<u16>,
+    /// Status to reject the request with (requests only)
+    reject: Option<u16>,
+}
+
+/// State of a plugin instance for one hook call
+struct HostState {
+    /// Plugin name, for guest log messages
+    name: Arc<str>,
+    /// Memory limits of the instance
+    limits: StoreLimits,
+}
+
+/// Compiled module ready to be instantiated for each hook call
+struct WasmModule {
+    /// Plugin name
+    name: Arc<str>,
+    /// Module linked against the host functions
+    instance_pre: InstancePre<HostState>,
+    /// Fuel for each hook call
+    fuel: u64,
+    /// Linear memory limit in bytes
+    memory_limit: usize,
+    /// Whether the module exports `on_request`
+    request_hook: bool,
+    /// Whether the module exports `on_response`
+    response_hook: bool,
+}
+
+impl WasmModule {
+    /// Run a hook in a fresh instance, returning the action it asks for, if any.
+    ///
+    /// The hook receives a pointer and length of its JSON input, written to memory
+    /// reserved through `kaserve_alloc`, and returns `(ptr << 32) | len` of its JSON
+    /// action, or 0 to leave the message unchanged.
+    fn call(&self, hook: &str, input: &[u8]) -> Result<Option<Action>, WasmError> {
+        let mut store = Store::new(self.instance_pre.module().engine(), HostState {
+            name: Arc::clone(&self.name),
+            limits: StoreLimitsBuilder::new().memory_size(self.memory_limit).instances(1).build(),
+        });
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(self.fuel)?;
+        
+        let instance = self.instance_pre.instantiate(&mut store)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| WasmError::Protocol("module does not export its memory".to_string()))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&mut store, ALLOC_EXPORT)?;
+        let hook = instance.get_typed_func::<(i32, i32), i64>(&mut store, hook)?;
+        
+        let len = i32::try_from(input.len()).map_err(|_| WasmError::Protocol("hook input too large".to_string()))?;
+        let ptr = alloc.call(&mut store, len)?;
+        memory
+            .write(&mut store, ptr as u32 as usize, input)
+            .map_err(|_| WasmError::Protocol("kaserve_alloc returned memory out of bounds".to_string()))?;
+        
+        let packed = hook.call(&mut store, (ptr, len))?;
+        if packed == 0 {
+            return Ok(None);
+        }
+        
+        let (out_ptr, out_len) = ((packed >> 32) as u32 as usize, packed as u32 as usize);
+        let output = memory
+            .data(&store)
+            .get(out_ptr..out_ptr + out_len)
+            .ok_or_else(|| WasmError::Protocol("hook output out of bounds".to_string()))?;
+        serde_json::from_slice(output)
+            .map(Some)
+            .map_err(|e| WasmError::Protocol(format!("invalid hook output: {}", e)))
+    }
+}
+
+/// Plugin running a sandboxed WebAssembly module.
+///
+/// The module sees message heads only; bodies pass through untouched. It has no
+/// access to the host beyond the `kaserve.log(level, ptr, len)` import, and each
+/// hook call gets a fresh instance limited in fuel and memory.
+pub struct WasmPlugin {
+    /// Plugin name
+    name: String,
+    /// Compiled module
+    module: Arc<WasmModule>,
+}
+
+impl WasmPlugin {
+    /// Compile and link the module described by `config`
+    pub fn load(engine: &Engine, config: &WasmPluginConfig) -> Result<Self, WasmError> {
+        let load_error = |e| WasmError::Load(config.path.clone(), e);
+        let module = Module::from_file(engine, &config.path).map_err(load_error)?;
+        
+        let mut linker = Linker::new(engine);
+        linker.func_wrap("kaserve", "log", guest_log).map_err(load_error)?;
+        let instance_pre = linker.instantiate_pre(&module).map_err(load_error)?;
+        
+        let request_hook = module.get_export(REQUEST_HOOK).is_some();
+        let response_hook = module.get_export(RESPONSE_HOOK).is_some();
+        let fuel = config.fuel.unwrap_or(DEFAULT_FUEL);
+        let memory_limit = config.memory_limit.unwrap_or(DEFAULT_MEMORY_LIMIT) as usize * 1024 * 1024;
+        
+        info!(
+            "Loaded WebAssembly plugin {} from {} (request hook: {}, response hook: {})",
+            config.name, config.path, request_hook, response_hook
+        );
+        Ok(WasmPlugin {
+            name: config.name.clone(),
+            module: Arc::new(WasmModule {
+                name: Arc::from(config.name.as_str()),
+                instance_pre,
+                fuel,
+                memory_limit,
+                request_hook,
+                response_hook,
+            }),
+        })
+    }
+    
+    /// Load the plugins configured in `plugins.wasm`, sharing one engine
+    pub fn load_all(config: &Config) -> Result<Vec<Self>, WasmError> {
+        let plugin_configs = config.plugins.as_ref().and_then(|plugins| plugins.wasm.as_deref()).unwrap_or_default();
+        if plugin_configs.is_empty() {
+            return Ok(Vec::new());
+        }
+        
+        let mut engine_config = wasmtime::Config::new();
+        engine_config.consume_fuel(true);
+        let engine = Engine::new(&engine_config).map_err(|e| WasmError::Load("engine".to_string(), e))?;
+        
+        plugin_configs.iter().map(|plugin_config| Self::load(&engine, plugin_config)).collect()
+    }
+    
+    /// Run a hook off the async runtime, since guest code is CPU-bound
+    async fn run(&self, hook: &'static str, input: Vec<u8>) -> Result<Option<Action>, Box<dyn Error + Send + Sync>> {
+        let module = Arc::clone(&self.module);
+        let action = tokio::task::spawn_blocking(move || module.call(hook, &input)).await??;
+        debug!("WebAssembly plugin {} {}: {:?}", self.name, hook, action);
+        Ok(action)
+    }
+}
+
+#[async_trait]
+impl Plugin for WasmPlugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    
+    fn version(&self) -> &str {
+        "wasm"
+    }
+    
+    async fn init(&mut self, _context: PluginContext) -> Result<(), Box<dyn Error + Send + Sync>> {
+        Ok(())
+    }
+    
+    async fn shutdown(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        Ok(())
+    }
+    
+    async fn pre_request(&self, mut req: Request<Body>) -> Result<Request<Body>, Box<dyn Error + Send + Sync>> {
+        if !self.module.request_hook {
+            return Ok(req);
+        }
+        
+        let head = RequestHead {
+            method: req.method().to_string(),
+            uri: req.uri().to_string(),
+            headers: header_pairs(req.headers()),
+        };
+        let Some(action) = self.run(REQUEST_HOOK, serde_json::to_vec(&head)?).await? else {
+            return Ok(req);
+        };
+        
+        if let Some(status) = action.reject {
+            return Err(Box::new(rejection(status)));
+        }
+        apply_headers(req.headers_mut(), &action)?;
+        if let Some(uri) = action.uri {
+            *req.uri_mut() = uri.parse()?;
+        }
+        
+        Ok(req)
+    }
+    
+    async fn post_response(&self, mut res: Response<Body>) -> Result<Response<Body>, Box<dyn Error + Send + Sync>> {
+        if !self.module.response_hook {
+            return Ok(res);
+        }
+        
+        let head = ResponseHead {
+            status: res.status().as_u16(),
+            headers: header_pairs(res.headers()),
+        };
+        let Some(action) = self.run(RESPONSE_HOOK, serde_json::to_vec(&head)?).await? else {
+            return Ok(res);
+        };
+        
+        apply_headers(res.headers_mut(), &action)?;
+        if let Some(status) = action.status {
+            *res.status_mut() = StatusCode::from_u16(status)?;
+        }
+        
+        Ok(res)
+    }
+}
+
+/// Host function letting guests write to the server log
+fn guest_log(mut caller: Caller<'_, HostState>, level: i32, ptr: i32, len: i32) {
+    let Some(Extern::Memory(memory)) = caller.get_export("memory") else {
+        return;
+    };
+    let (start, end) = (ptr as u32 as usize, ptr as u32 as usize + len as u32 as usize);
+    let Some(bytes) = memory.data(&caller).get(start..end) else {
+        return;
+    };
+    
+    let message = String::from_utf8_lossy(bytes);
+    let name = &caller.data().name;
+    match level {
+        0 => debug!("[{}] {}", name, message),
+        1 => info!("[{}] {}", name, message),
+        _ => warn!("[{}] {}", name, message),
+    }
+}
+
+/// List header fields as name/value pairs, replacing invalid UTF-8
+fn header_pairs(headers: &hyper::HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+        .collect()
+}
+
+/// Apply the header changes of an action
+fn apply_headers(headers: &mut hyper::HeaderMap, action: &Action) -> Result<(), Box<dyn Error + Send + Sync>> {
+    for name in &action.remove_headers {
+        headers.remove(HeaderName::from_bytes(name.as_bytes())?);
+    }
+    for (name, value) in &action.set_headers {
+        headers.insert(HeaderName::from_bytes(name.as_bytes())?, HeaderValue::from_str(value)?);
+    }
+    Ok(())
+}
+
+/// Error a request is rejected with for the status a plugin asked for
+fn rejection(status: u16) -> HttpError {
+    match status {
+        400 => HttpError::BadRequest("The request was rejected.".to_string()),
+        404 => HttpError::NotFound,
+        429 => HttpError::TooManyRequests { retry_after: 1 },
+        503 => HttpError::ServiceUnavailable,
+        _ => HttpError::Forbidden("The request was rejected.".to_string()),
+    }
+}
@@ -0,0 +1,77 @@
+use regex::Regex;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{debug, warn};
+
+use crate::core::config::Config;
+use crate::core::error::HttpError;
+use crate::routing::router::wildcard_regex;
+use crate::utils::metrics::Metrics;
+
+/// Concurrency limit applied to request paths matching a pattern
+#[derive(Debug)]
+struct ConcurrencyLimit {
+    /// Path pattern as configured
+    pattern: String,
+    /// Compiled pattern
+    regex: Regex,
+    /// Slots for requests in flight
+    semaphore: Arc<Semaphore>,
+    /// How long an excess request may wait for a slot (rejected immediately if unset)
+    queue_timeout: Option<Duration>,
+}
+
+/// Per-route concurrency limits shared by all connections
+#[derive(Debug, Default)]
+pub struct ConcurrencyLimits {
+    /// Limits in configuration order; the first matching pattern applies
+    limits: Vec<ConcurrencyLimit>,
+}
+
+impl ConcurrencyLimits {
+    /// Build the limits configured in `concurrency_limits`
+    pub fn from_config(config: &Config) -> Result<Self, regex::Error> {
+        let mut limits = Vec::new();
+        
+        for limit_config in config.concurrency_limits.iter().flatten() {
+            let regex = wildcard_regex(&limit_config.path)?;
+            let max_concurrent = limit_config.max_concurrent.max(1);
+            
+            debug!("Limiting {} to {} concurrent requests", limit_config.path, max_concurrent);
+            limits.push(ConcurrencyLimit {
+                pattern: limit_config.path.clone(),
+                regex,
+                semaphore: Arc::new(Semaphore::new(max_concurrent)),
+                queue_timeout: limit_config.queue_timeout.map(Duration::from_millis),
+            });
+        }
+        
+        Ok(ConcurrencyLimits { limits })
+    }
+    
+    /// Acquire a slot for a request path.
+    ///
+    /// Returns `None` when no limit applies. The slot is released when the permit is dropped.
+    pub async fn acquire(&self, path: &str, metrics: &Metrics) -> Result<Option<OwnedSemaphorePermit>, HttpError> {
+        let Some(limit) = self.limits.iter().find(|limit| limit.regex.is_match(path)) else {
+            return Ok(None);
+        };
+        
+        if let Ok(permit) = Arc::clone(&limit.semaphore).try_acquire_owned() {
+            return Ok(Some(permit));
+        }
+        
+        // Queue briefly if configured, otherwise reject right away
+        if let Some(queue_timeout) = limit.queue_timeout {
+            metrics.record_concurrency_queued();
+            if let Ok(Ok(permit)) = tokio::time::timeout(queue_timeout, Arc::clone(&limit.semaphore).acquire_owned()).await {
+                return Ok(Some(permit));
+            }
+        }
+        
+        warn!("Concurrency limit for {} reached, rejecting {}", limit.pattern, path);
+        metrics.record_concurrency_rejected();
+        Err(HttpError::ServiceUnavailable)
+    }
+}
@@ -1,3 +1,4 @@
 pub mod router;
 pub mod vhost;
 pub mod rewrite;
+pub mod limits;
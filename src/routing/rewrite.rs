@@ -1,20 +1,22 @@
 use hyper::Request;
-use regex::{Regex, Captures};
+use regex::Regex;
 use std::error::Error;
 use std::fmt;
-use tracing::{debug, error};
+use tracing::debug;
+
+use crate::core::config::{Config, RewriteFlag};
 
 /// Error types for URL rewriting
 #[derive(Debug)]
 pub enum RewriteError {
-    InvalidPattern,
+    InvalidPattern(String),
     InvalidReplacement,
 }
 
 impl fmt::Display for RewriteError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            RewriteError::InvalidPattern => write!(f, "Invalid rewrite pattern"),
+            RewriteError::InvalidPattern(pattern) => write!(f, "Invalid rewrite pattern: {}", pattern),
             RewriteError::InvalidReplacement => write!(f, "Invalid rewrite replacement"),
         }
     }
@@ -42,7 +44,7 @@ impl RewriteRule {
     pub fn new(pattern: &str, replacement: &str) -> Result<Self, RewriteError> {
         let regex = match Regex::new(pattern) {
             Ok(r) => r,
-            Err(_) => return Err(RewriteError::InvalidPattern),
+            Err(_) => return Err(RewriteError::InvalidPattern(pattern.to_string())),
         };
         
         Ok(RewriteRule {
@@ -114,12 +116,40 @@ impl Rewriter {
         }
     }
     
+    /// Build the rewriter configured in `rewrite`, or `None` if no rules are configured
+    pub fn from_config(config: &Config) -> Result<Option<Self>, RewriteError> {
+        let rule_configs = match config.rewrite.as_deref() {
+            Some(rule_configs) if !rule_configs.is_empty() => rule_configs,
+            _ => return Ok(None),
+        };
+        
+        let mut rewriter = Rewriter::new();
+        for rule_config in rule_configs {
+            let flags = rule_config.flags.as_deref().unwrap_or_default();
+            let mut rule = RewriteRule::new(&rule_config.pattern, &rule_config.replacement)?
+                .last(flags.contains(&RewriteFlag::Last));
+            if flags.contains(&RewriteFlag::Permanent) {
+                rule = rule.redirect(true, 301);
+            } else if flags.contains(&RewriteFlag::Redirect) {
+                rule = rule.redirect(true, 302);
+            }
+            
+            debug!("Rewrite rule {} -> {} {:?}", rule_config.pattern, rule_config.replacement, flags);
+            rewriter.add_rule(rule);
+        }
+        
+        Ok(Some(rewriter))
+    }
+    
     /// Add a rewrite rule
     pub fn add_rule(&mut self, rule: RewriteRule) {
         self.rules.push(rule);
     }
     
-    /// Process a request through the rewrite rules
+    /// Process a request through the rewrite rules.
+    ///
+    /// Rules apply in order to the path produced by the previous match; a redirect
+    /// or a rule flagged `last` ends processing.
     pub fn process<T>(&self, req: &Request<T>) -> Option<RewriteResult> {
         let path = req.uri().path();
         
@@ -133,7 +163,7 @@ impl Rewriter {
                 current_path = rewrite_result.new_path.clone();
                 result = Some(rewrite_result.clone());
                 
-                if rewrite_result.is_last {
+                if rewrite_result.is_last || rewrite_result.is_redirect {
                     break;
                 }
             }
@@ -142,3 +172,9 @@ impl Rewriter {
         result
     }
 }
+
+impl Default for Rewriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,12 +1,16 @@
+use std::cmp::Reverse;
 use std::sync::Arc;
-use hyper::{Body, Request};
+use hyper::{Body, Method, Request};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt;
+use std::time::Duration;
 use tracing::{debug, error};
 
-use crate::core::config::Config;
+use crate::core::config::{Config, RouteConfig};
 use crate::handlers::common::HandlerType;
+use crate::handlers::upload::DEFAULT_UPLOAD_METHODS;
 use crate::routing::vhost::VirtualHost;
 
 /// Error types for the router
@@ -14,6 +18,10 @@ use crate::routing::vhost::VirtualHost;
 pub enum RouterError {
     NoMatchingRoute,
     InvalidRoutePattern,
+    InvalidMethod(String),
+    InvalidHandler(String),
+    /// A route matches the path but not the method; `allow` lists the methods that are
+    MethodNotAllowed { allow: String },
 }
 
 impl fmt::Display for RouterError {
@@ -21,12 +29,26 @@ impl fmt::Display for RouterError {
         match self {
             RouterError::NoMatchingRoute => write!(f, "No matching route found"),
             RouterError::InvalidRoutePattern => write!(f, "Invalid route pattern"),
+            RouterError::InvalidMethod(method) => write!(f, "Invalid route method: {}", method),
+            RouterError::InvalidHandler(reason) => write!(f, "Invalid route handler: {}", reason),
+            RouterError::MethodNotAllowed { allow } => write!(f, "Method not allowed (allowed: {})", allow),
         }
     }
 }
 
 impl Error for RouterError {}
 
+/// Behavior when no route matches a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnmatchedRoutes {
+    /// Fall through to the default static file handler
+    #[default]
+    Static,
+    /// Respond with 404 Not Found
+    NotFound,
+}
+
 /// A route represents a mapping from a URL pattern to a handler
 #[derive(Debug, Clone)]
 pub struct Route {
@@ -38,16 +60,56 @@ pub struct Route {
     pub handler_type: String,
     /// Additional handler parameters
     pub handler_params: Option<String>,
+    /// Handler timeout overriding the global request timeout
+    pub timeout: Option<Duration>,
+    /// Methods the route accepts (any if unset)
+    pub methods: Option<Vec<Method>>,
+    /// Explicit priority, ranking above pattern specificity
+    pub priority: i32,
+    /// Matcher precompiled from the pattern
+    matcher: Matcher,
+}
+
+/// How a route pattern is checked against a path.
+///
+/// Patterns whose literal parts contain no regex metacharacters are matched with
+/// plain string comparisons, which gives the same result as their regex.
+#[derive(Debug, Clone)]
+enum Matcher {
+    /// Pattern without a wildcard
+    Exact(String),
+    /// Pattern ending in its only wildcard, such as `/api/*`
+    Prefix(String),
+    /// Pattern starting with its only wildcard, such as `*.html`
+    Suffix(String),
+    /// Any other pattern, matched with the route regex
+    Regex,
+}
+
+impl Matcher {
+    /// Pick the cheapest matcher for a pattern
+    fn compile(pattern: &str) -> Self {
+        let literal = |part: &str| regex::escape(part) == part;
+        let wildcards = pattern.matches('*').count();
+        
+        if wildcards == 0 && literal(pattern) {
+            Matcher::Exact(pattern.to_string())
+        } else if wildcards > 1 {
+            Matcher::Regex
+        } else if let Some(prefix) = pattern.strip_suffix('*').filter(|prefix| literal(prefix)) {
+            Matcher::Prefix(prefix.to_string())
+        } else if let Some(suffix) = pattern.strip_prefix('*').filter(|suffix| literal(suffix)) {
+            Matcher::Suffix(suffix.to_string())
+        } else {
+            Matcher::Regex
+        }
+    }
 }
 
 impl Route {
     /// Create a new route
     pub fn new(pattern: &str, handler_type: &str) -> Result<Self, RouterError> {
-        // Convert pattern to regex
-        // Simple implementation: replace * with .* and add start/end anchors
-        let regex_pattern = format!("^{}$", pattern.replace("*", ".*"));
-        
-        let regex = match Regex::new(&regex_pattern) {
+        let regex = match wildcard_regex(pattern) {
             Ok(r) => r,
             Err(_) => return Err(RouterError::InvalidRoutePattern),
         };
@@ -57,12 +119,64 @@ impl Route {
             regex,
             handler_type: handler_type.to_string(),
             handler_params: None,
+            timeout: None,
+            methods: None,
+            priority: 0,
+            matcher: Matcher::compile(pattern),
         })
     }
     
+    /// Create a route declared in the route table
+    pub fn from_config(config: &RouteConfig) -> Result<Self, RouterError> {
+        let handler_type = match HandlerType::parse(&config.handler) {
+            Some(HandlerType::Custom(_)) | None => return Err(RouterError::InvalidHandler(config.handler.clone())),
+            Some(handler_type) => handler_type,
+        };
+        
+        let mut route = Route::new(&config.path, handler_type.as_str())?
+            .with_methods(config.methods.as_deref())?
+            .with_priority(config.priority);
+        
+        // Other handlers look up the pool, backend or script entry named by the parameter
+        match (&handler_type, &config.params) {
+            (HandlerType::StaticFile, _) => {}
+            (_, Some(params)) => route = route.with_params(params),
+            (_, None) => return Err(RouterError::InvalidHandler(format!("{} requires params", config.handler))),
+        }
+        
+        if let Some(secs) = config.timeout {
+            route = route.with_timeout(Duration::from_secs(secs));
+        }
+        
+        Ok(route)
+    }
+    
     /// Check if this route matches a path
     pub fn matches(&self, path: &str) -> bool {
-        self.regex.is_match(path)
+        match &self.matcher {
+            Matcher::Exact(pattern) => path == pattern,
+            Matcher::Prefix(prefix) => path.starts_with(prefix.as_str()),
+            Matcher::Suffix(suffix) => path.ends_with(suffix.as_str()),
+            Matcher::Regex => self.regex.is_match(path),
+        }
+    }
+    
+    /// Specificity of the pattern: the length of its literal prefix, then of all its literals.
+    ///
+    /// The leading slash every path shares does not count, so `*.php` ranks above `/*`.
+    pub fn specificity(&self) -> (usize, usize) {
+        let pattern = self.pattern.strip_prefix('/').unwrap_or(&self.pattern);
+        let prefix = pattern.find('*').unwrap_or(pattern.len());
+        let literals = pattern.chars().filter(|&c| c != '*').count();
+        (prefix, literals)
+    }
+    
+    /// Check if this route accepts a method; HEAD is accepted wherever GET is
+    pub fn allows(&self, method: &Method) -> bool {
+        match &self.methods {
+            Some(methods) => methods.contains(method) || (*method == Method::HEAD && methods.contains(&Method::GET)),
+            None => true,
+        }
     }
     
     /// Set handler parameters
@@ -70,6 +184,115 @@ impl Route {
         self.handler_params = Some(params.to_string());
         self
     }
+    
+    /// Set a handler timeout for this route
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+    
+    /// Set an explicit priority for this route, if configured
+    pub fn with_priority(mut self, priority: Option<i32>) -> Self {
+        self.priority = priority.unwrap_or(0);
+        self
+    }
+    
+    /// Restrict the route to the given methods, if any are configured
+    pub fn with_methods(mut self, methods: Option<&[String]>) -> Result<Self, RouterError> {
+        if let Some(methods) = methods {
+            let methods = methods
+                .iter()
+                .map(|method| {
+                    Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                        .map_err(|_| RouterError::InvalidMethod(method.clone()))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            self.methods = Some(methods);
+        }
+        Ok(self)
+    }
+}
+
+/// Compile a wildcard path pattern, the syntax routes and path-scoped settings share.
+///
+/// `*` matches any run of characters, `/` included; everything else matches
+/// literally, so `/v1.0/*` does not match `/v1X0/a` nor `/c++/*` match `/cc/a`.
+pub fn wildcard_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    let literals = pattern.split('*').map(regex::escape).collect::<Vec<_>>();
+    Regex::new(&format!("^{}$", literals.join(".*")))
+}
+
+/// Order routes for matching: by priority, then by specificity, then as declared
+pub fn sort_routes(routes: &mut [Route]) {
+    routes.sort_by_key(|route| (Reverse(route.priority), Reverse(route.specificity())));
+}
+
+/// Find the route for a path and method.
+///
+/// The first route matching the path identifies the resource; routes declared
+/// with the same pattern share its methods, and the first of them accepting the
+/// method is chosen. If none does, the error lists the methods they accept.
+pub fn find_route<'r>(routes: &'r [Route], path: &str, method: &Method) -> Result<&'r Route, RouterError> {
+    let first = routes.iter().find(|route| route.matches(path)).ok_or(RouterError::NoMatchingRoute)?;
+    let resource_routes = || routes.iter().filter(|route| route.pattern == first.pattern);
+    
+    if let Some(route) = resource_routes().find(|route| route.allows(method)) {
+        return Ok(route);
+    }
+    
+    let mut allow: Vec<&str> = Vec::new();
+    for methods in resource_routes().filter_map(|route| route.methods.as_ref()) {
+        for method in methods {
+            if !allow.contains(&method.as_str()) {
+                allow.push(method.as_str());
+            }
+            if *method == Method::GET && !allow.contains(&"HEAD") {
+                allow.push("HEAD");
+            }
+        }
+    }
+    
+    debug!("{} {} matches {} but not its methods", method, path, first.pattern);
+    Err(RouterError::MethodNotAllowed { allow: allow.join(", ") })
+}
+
+/// Split a Host header value into hostname and optional port.
+///
+/// Handles `name`, `name:port`, `[v6]` and `[v6]:port` forms. Bracketed IPv6
+/// literals are returned without brackets, and the hostname is lowercased with
+/// any trailing dot removed so equivalent hosts compare equal.
+pub fn parse_host(host: &str) -> (String, Option<u16>) {
+    let host = host.trim();
+    
+    let (name, port) = if let Some(rest) = host.strip_prefix('[') {
+        // Bracketed IPv6 literal, optionally followed by a port
+        match rest.find(']') {
+            Some(end) => {
+                let port = rest[end + 1..].strip_prefix(':').and_then(|p| p.parse().ok());
+                (&rest[..end], port)
+            }
+            None => (rest, None),
+        }
+    } else if host.matches(':').count() > 1 {
+        // Unbracketed IPv6 literal, which cannot carry a port
+        (host, None)
+    } else {
+        match host.rsplit_once(':') {
+            Some((name, port)) => (name, port.parse().ok()),
+            None => (host, None),
+        }
+    };
+    
+    (name.trim_end_matches('.').to_ascii_lowercase(), port)
+}
+
+/// Route matched for a request, with the virtual host it was found in
+#[derive(Debug)]
+pub struct RouteMatch<'a> {
+    /// Matched route
+    pub route: &'a Route,
+    /// Virtual host the request was addressed to, if any
+    pub vhost: Option<&'a VirtualHost>,
 }
 
 /// Router for matching requests to handlers
@@ -81,6 +304,8 @@ pub struct Router {
     vhosts: Vec<VirtualHost>,
     /// Default routes
     default_routes: Vec<Route>,
+    /// Index of the virtual host serving every request, whatever its Host header
+    bound_vhost: Option<usize>,
 }
 
 impl Router {
@@ -90,11 +315,92 @@ impl Router {
             config,
             vhosts: Vec::new(),
             default_routes: Vec::new(),
+            bound_vhost: None,
         };
         
-        // Add default static file route
-        if let Ok(route) = Route::new("/*", "static") {
-            router.default_routes.push(route);
+        // Add proxy routes ahead of the catch-all static route
+        if let Some(proxy) = &router.config.proxy {
+            for pool in &proxy.pools {
+                match Route::new(&pool.path, "proxy").and_then(|route| route.with_methods(pool.methods.as_deref())) {
+                    Ok(route) => {
+                        let route = route.with_params(&pool.name).with_priority(pool.priority);
+                        let route = match pool.timeout {
+                            Some(secs) => route.with_timeout(Duration::from_secs(secs)),
+                            None => route,
+                        };
+                        router.default_routes.push(route);
+                    }
+                    Err(e) => error!("Invalid path for proxy pool {}: {}", pool.name, e),
+                }
+            }
+        }
+        
+        // FastCGI routes are keyed by their pattern
+        if let Some(backends) = &router.config.fastcgi {
+            for backend in backends {
+                match Route::new(&backend.path, "fastcgi").and_then(|route| route.with_methods(backend.methods.as_deref())) {
+                    Ok(route) => {
+                        let route = route.with_params(&backend.path).with_priority(backend.priority);
+                        let route = match backend.timeout {
+                            Some(secs) => route.with_timeout(Duration::from_secs(secs)),
+                            None => route,
+                        };
+                        router.default_routes.push(route);
+                    }
+                    Err(e) => error!("Invalid path for FastCGI backend {}: {}", backend.address, e),
+                }
+            }
+        }
+        
+        // SCGI and uwsgi routes are keyed by their pattern like FastCGI ones
+        let app_servers = router.config.scgi.iter().flatten()
+            .map(|backend| ("scgi", &backend.path, &backend.methods, backend.priority, backend.timeout, backend.address))
+            .chain(router.config.uwsgi.iter().flatten()
+                .map(|backend| ("uwsgi", &backend.path, &backend.methods, backend.priority, backend.timeout, backend.address)));
+        for (handler, path, methods, priority, timeout, address) in app_servers {
+            match Route::new(path, handler).and_then(|route| route.with_methods(methods.as_deref())) {
+                Ok(route) => {
+                    let route = route.with_params(path).with_priority(priority);
+                    let route = match timeout {
+                        Some(secs) => route.with_timeout(Duration::from_secs(secs)),
+                        None => route,
+                    };
+                    router.default_routes.push(route);
+                }
+                Err(e) => error!("Invalid path for {} backend {}: {}", handler, address, e),
+            }
+        }
+        
+        // CGI routes are keyed by their pattern; a configured execution timeout also overrides the global one
+        for cgi in router.config.cgi.iter().flatten() {
+            match Route::new(&cgi.path, "cgi").and_then(|route| route.with_methods(cgi.methods.as_deref())) {
+                Ok(route) => {
+                    let route = route.with_params(&cgi.path).with_priority(cgi.priority);
+                    let route = match cgi.timeout {
+                        Some(secs) => route.with_timeout(Duration::from_secs(secs)),
+                        None => route,
+                    };
+                    router.default_routes.push(route);
+                }
+                Err(e) => error!("Invalid path for CGI route {}: {}", cgi.path, e),
+            }
+        }
+        
+        // Upload routes are keyed by their pattern and accept PUT and POST unless configured otherwise
+        for upload in router.config.upload.iter().flatten() {
+            let methods = upload.methods.clone()
+                .unwrap_or_else(|| DEFAULT_UPLOAD_METHODS.iter().map(|method| method.to_string()).collect());
+            match Route::new(&upload.path, "upload").and_then(|route| route.with_methods(Some(&methods))) {
+                Ok(route) => router.default_routes.push(route.with_params(&upload.path).with_priority(upload.priority)),
+                Err(e) => error!("Invalid path for upload route {}: {}", upload.path, e),
+            }
+        }
+        
+        // Add default static file route, unless unmatched requests are answered with 404
+        if router.config.server.unmatched_routes.unwrap_or_default() == UnmatchedRoutes::Static {
+            if let Ok(route) = Route::new("/*", "static") {
+                router.default_routes.push(route);
+            }
         }
         
         // Initialize virtual hosts if configured
@@ -111,48 +417,134 @@ impl Router {
             }
         }
         
+        // Routes from the route table join their virtual host or the default routes
+        for route_config in router.config.routes.iter().flatten() {
+            let route = match Route::from_config(route_config) {
+                Ok(route) => route,
+                Err(e) => {
+                    error!("Invalid route {}: {}", route_config.path, e);
+                    continue;
+                }
+            };
+            
+            match &route_config.host {
+                Some(host) => match router.vhosts.iter_mut().find(|vhost| vhost.hostname() == host) {
+                    Some(vhost) => vhost.add_route(route),
+                    None => error!("Route {} names unknown virtual host {}", route_config.path, host),
+                },
+                None => router.default_routes.push(route),
+            }
+        }
+        
+        // Declaration order only breaks ties between equally ranked routes
+        sort_routes(&mut router.default_routes);
+        
         router
     }
     
+    /// Get the default routes in matching order
+    pub fn default_routes(&self) -> &[Route] {
+        &self.default_routes
+    }
+    
+    /// Get the virtual hosts in matching order
+    pub fn vhosts(&self) -> &[VirtualHost] {
+        &self.vhosts
+    }
+    
+    /// Serve every request with the virtual host declared with the given pattern.
+    ///
+    /// Requests are routed as if addressed to that host, falling back to the
+    /// default routes; an unknown pattern leaves Host-based matching in place.
+    pub fn bind_vhost(mut self, pattern: &str) -> Self {
+        self.bound_vhost = self.vhosts.iter().position(|vhost| vhost.hostname() == pattern);
+        if self.bound_vhost.is_none() {
+            error!("Cannot bind unknown virtual host: {}", pattern);
+        }
+        self
+    }
+    
+    /// Get the virtual host bound to this router, if any
+    pub fn bound_vhost(&self) -> Option<&VirtualHost> {
+        self.bound_vhost.map(|index| &self.vhosts[index])
+    }
+    
+    /// Get the virtual host serving a request: the bound one, or the first matching its Host header
+    pub fn request_vhost(&self, req: &Request<Body>) -> Option<&VirtualHost> {
+        if let Some(vhost) = self.bound_vhost() {
+            return Some(vhost);
+        }
+        let host = req.headers().get("host").and_then(|h| h.to_str().ok())?;
+        let hostname = parse_host(host).0;
+        self.vhosts.iter().find(|vhost| vhost.matches(&hostname))
+    }
+    
     /// Add a route to the router
     pub fn add_route(&mut self, route: Route) {
         self.default_routes.push(route);
+        sort_routes(&mut self.default_routes);
     }
     
-    /// Route a request to a handler
-    pub fn route(&self, req: &Request<Body>) -> Result<Route, RouterError> {
+    /// Route a request to a handler.
+    ///
+    /// Fails with `MethodNotAllowed` when a route matches the path but not the request method.
+    pub fn route(&self, req: &Request<Body>) -> Result<RouteMatch<'_>, RouterError> {
         let path = req.uri().path();
+        let method = req.method();
         debug!("Routing request for path: {}", path);
         
-        // Check for virtual host matching
-        if let Some(host) = req.headers().get("host").and_then(|h| h.to_str().ok()) {
-            debug!("Request has host header: {}", host);
-            
-            // Extract hostname without port
-            let hostname = host.split(':').next().unwrap_or(host);
-            
-            // Check if we have a matching virtual host
-            for vhost in &self.vhosts {
-                if vhost.matches(hostname) {
-                    debug!("Found matching virtual host: {}", vhost.hostname());
-                    
-                    // Try to match a route in this virtual host
-                    if let Some(route) = vhost.match_route(path) {
-                        return Ok(route);
-                    }
-                }
+        // Routes scoped to the virtual host come first; the global routes, including
+        // the static catch-all, serve the rest with the virtual host's document root
+        let vhost = self.request_vhost(req);
+        if let Some(vhost) = vhost {
+            debug!("Found matching virtual host: {}", vhost.hostname());
+            match vhost.match_route(path, method) {
+                Ok(route) => return Ok(RouteMatch { route, vhost: Some(vhost) }),
+                Err(RouterError::NoMatchingRoute) => {}
+                Err(e) => return Err(e),
             }
         }
         
-        // If no virtual host matches, try default routes
-        for route in &self.default_routes {
-            if route.matches(path) {
-                debug!("Matched default route: {}", route.pattern);
-                return Ok(route.clone());
-            }
-        }
+        let route = find_route(&self.default_routes, path, method)?;
+        debug!("Matched default route: {}", route.pattern);
+        Ok(RouteMatch { route, vhost })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn wildcard_patterns_match_other_characters_literally() {
+        let pattern = wildcard_regex("/v1.0/*").unwrap();
+        assert!(pattern.is_match("/v1.0/users"));
+        assert!(!pattern.is_match("/v1X0/users"));
         
-        // If we get here, no route matched
-        Err(RouterError::NoMatchingRoute)
+        let pattern = wildcard_regex("/c++/*.txt").unwrap();
+        assert!(pattern.is_match("/c++/notes/s.txt"));
+        assert!(!pattern.is_match("/c/s.txt"));
+        assert!(!pattern.is_match("/c++/s.txt.bak"));
+        
+        assert!(wildcard_regex("/(a|b)[x]/*").unwrap().is_match("/(a|b)[x]/y"));
+    }
+    
+    #[test]
+    fn parse_host_keeps_ipv6_literals_whole() {
+        assert_eq!(parse_host("[::1]:8080"), ("::1".to_string(), Some(8080)));
+        assert_eq!(parse_host("[2001:db8::1]"), ("2001:db8::1".to_string(), None));
+        assert_eq!(parse_host("2001:db8::1"), ("2001:db8::1".to_string(), None));
+    }
+    
+    #[test]
+    fn parse_host_splits_off_the_port() {
+        assert_eq!(parse_host("Example.COM:8443"), ("example.com".to_string(), Some(8443)));
+        assert_eq!(parse_host("127.0.0.1:80"), ("127.0.0.1".to_string(), Some(80)));
+    }
+    
+    #[test]
+    fn parse_host_accepts_bare_names() {
+        assert_eq!(parse_host("example.com"), ("example.com".to_string(), None));
+        assert_eq!(parse_host(" example.com. "), ("example.com".to_string(), None));
     }
 }
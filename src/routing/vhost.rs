@@ -1,10 +1,11 @@
+use hyper::Method;
 use regex::Regex;
 use std::path::PathBuf;
 
-use crate::routing::router::Route;
+use crate::routing::router::{find_route, sort_routes, Route, RouterError};
 
 /// Virtual host configuration for serving multiple websites
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct VirtualHost {
     /// Hostname pattern for matching
     hostname_pattern: String,
@@ -12,7 +13,7 @@ pub struct VirtualHost {
     hostname_regex: Regex,
     /// Document root for this virtual host
     document_root: PathBuf,
-    /// Routes specific to this virtual host
+    /// Routes scoped to this virtual host, matched before the global routes
     routes: Vec<Route>,
 }
 
@@ -29,23 +30,19 @@ impl VirtualHost {
         let regex_pattern = format!("^{}$", pattern);
         let regex = Regex::new(&regex_pattern)?;
         
-        // Create default routes for this virtual host
-        let mut routes = Vec::new();
-        if let Ok(route) = Route::new("/*", "static") {
-            routes.push(route);
-        }
-        
+        // Requests no route of this host matches fall back to the global routes
         Ok(VirtualHost {
             hostname_pattern: hostname_pattern.to_string(),
             hostname_regex: regex,
             document_root: PathBuf::from(document_root),
-            routes,
+            routes: Vec::new(),
         })
     }
     
     /// Add a route to this virtual host
     pub fn add_route(&mut self, route: Route) {
         self.routes.push(route);
+        sort_routes(&mut self.routes);
     }
     
     /// Check if this virtual host matches a hostname
@@ -63,14 +60,13 @@ impl VirtualHost {
         &self.document_root
     }
     
+    /// Get the routes of this virtual host in matching order
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+    
     /// Match a route for this virtual host
-    pub fn match_route(&self, path: &str) -> Option<Route> {
-        for route in &self.routes {
-            if route.matches(path) {
-                return Some(route.clone());
-            }
-        }
-        
-        None
+    pub fn match_route(&self, path: &str, method: &Method) -> Result<&Route, RouterError> {
+        find_route(&self.routes, path, method)
     }
 }
@@ -1,22 +1,28 @@
-use hyper::{Body, Request, Response, StatusCode};
+use hyper::{Body, Method, Request, Response};
 use regex::Regex;
 use std::error::Error;
 use std::fmt;
 use std::net::IpAddr;
 use tracing::{debug, error, info};
 
+use crate::core::config::{AclAction, AclRuleConfig, Config};
+use crate::core::error::{ErrorPages, HttpError};
+use crate::network::http::path::canonical_path;
+use crate::routing::router::wildcard_regex;
+use crate::utils::geoip::GeoInfo;
+
 /// Error types for ACL
 #[derive(Debug)]
 pub enum AclError {
     AccessDenied,
-    ConfigurationError,
+    ConfigurationError(String),
 }
 
 impl fmt::Display for AclError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             AclError::AccessDenied => write!(f, "Access denied"),
-            AclError::ConfigurationError => write!(f, "ACL configuration error"),
+            AclError::ConfigurationError(detail) => write!(f, "ACL configuration error: {}", detail),
         }
     }
 }
@@ -37,14 +43,20 @@ pub enum AccessRule {
 pub enum AccessCondition {
     /// Match by IP address
     Ip(IpAddr),
-    /// Match by IP network (CIDR)
-    Network(String),
+    /// Match by IP network (CIDR): network address and prefix length
+    Network(IpAddr, u8),
     /// Match by path pattern
     Path(Regex),
     /// Match by user agent
     UserAgent(Regex),
+    /// Match by request method
+    Method(Vec<Method>),
+    /// Match by client country code, as looked up in the GeoIP databases
+    Country(String),
     /// Match any request
     All,
+    /// Match when every condition matches
+    AllOf(Vec<AccessCondition>),
 }
 
 impl AccessRule {
@@ -74,12 +86,14 @@ impl AccessCondition {
                     false
                 }
             }
-            AccessCondition::Network(cidr) => {
-                // In a real implementation, we would use a CIDR library to check IP ranges
-                false
+            AccessCondition::Network(network, prefix) => {
+                match client_ip {
+                    Some(client) => network_contains(*network, *prefix, client),
+                    None => false,
+                }
             }
             AccessCondition::Path(pattern) => {
-                pattern.is_match(req.uri().path())
+                canonical_path(req.uri().path()).is_ok_and(|path| pattern.is_match(&path))
             }
             AccessCondition::UserAgent(pattern) => {
                 if let Some(ua) = req.headers().get("user-agent") {
@@ -89,11 +103,67 @@ impl AccessCondition {
                 }
                 false
             }
+            AccessCondition::Method(methods) => methods.contains(req.method()),
+            AccessCondition::Country(code) => req
+                .extensions()
+                .get::<GeoInfo>()
+                .and_then(|geo| geo.country.as_deref())
+                .is_some_and(|country| country.eq_ignore_ascii_case(code)),
             AccessCondition::All => true,
+            AccessCondition::AllOf(conditions) => {
+                conditions.iter().all(|condition| condition.matches(req, client_ip))
+            }
+        }
+    }
+    
+    /// Check whether an address is matched by an address or network condition
+    pub fn contains_ip(&self, ip: IpAddr) -> bool {
+        match self {
+            AccessCondition::Ip(address) => ip.to_canonical() == *address,
+            AccessCondition::Network(network, prefix) => network_contains(*network, *prefix, ip),
+            _ => false,
+        }
+    }
+    
+    /// Parse a network in CIDR notation (e.g. "10.0.0.0/8"); a bare address matches only itself
+    pub fn network(cidr: &str) -> Result<Self, AclError> {
+        let invalid = || AclError::ConfigurationError(format!("invalid network: {}", cidr));
+        let (address, prefix) = match cidr.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (cidr, None),
+        };
+        
+        let network: IpAddr = address.trim().parse().map_err(|_| invalid())?;
+        let max_prefix = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.trim().parse::<u8>().map_err(|_| invalid())?,
+            None => max_prefix,
+        };
+        
+        if prefix > max_prefix {
+            return Err(invalid());
         }
+        
+        Ok(AccessCondition::Network(network, prefix))
     }
 }
 
+/// Check whether an address lies in a network; IPv4-mapped IPv6 clients match IPv4 networks
+fn network_contains(network: IpAddr, prefix: u8, client: IpAddr) -> bool {
+    match (network, client.to_canonical()) {
+        (IpAddr::V4(network), IpAddr::V4(client)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(network) & mask == u32::from(client) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(client)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(network) & mask == u128::from(client) & mask
+        }
+        _ => false,
+    }
+}
+
+
 /// Access Control List
 pub struct Acl {
     /// List of access rules
@@ -111,6 +181,70 @@ impl Acl {
         }
     }
     
+    /// Build the ACL configured in `acl`, if any
+    pub fn from_config(config: &Config) -> Result<Option<Self>, AclError> {
+        let acl_config = match &config.acl {
+            Some(acl_config) => acl_config,
+            None => return Ok(None),
+        };
+        
+        let mut acl = Acl::new(acl_config.default.unwrap_or(AclAction::Allow) == AclAction::Allow);
+        for rule_config in acl_config.rules.iter().flatten() {
+            acl.add_rule(Self::rule_from_config(rule_config)?);
+        }
+        
+        info!("Loaded ACL with {} rules", acl.rules.len());
+        Ok(Some(acl))
+    }
+    
+    /// Build a rule from its configuration; a rule without conditions matches every request
+    fn rule_from_config(rule_config: &AclRuleConfig) -> Result<AccessRule, AclError> {
+        let mut conditions = Vec::new();
+        
+        if let Some(ip) = rule_config.ip {
+            conditions.push(AccessCondition::Ip(ip));
+        }
+        if let Some(network) = &rule_config.network {
+            conditions.push(AccessCondition::network(network)?);
+        }
+        if let Some(path) = &rule_config.path {
+            let pattern = wildcard_regex(path)
+                .map_err(|e| AclError::ConfigurationError(e.to_string()))?;
+            conditions.push(AccessCondition::Path(pattern));
+        }
+        if let Some(user_agent) = &rule_config.user_agent {
+            let pattern = Regex::new(user_agent).map_err(|e| {
+                error!("Invalid ACL user agent pattern {}: {}", user_agent, e);
+                AclError::ConfigurationError(e.to_string())
+            })?;
+            conditions.push(AccessCondition::UserAgent(pattern));
+        }
+        if let Some(country) = &rule_config.country {
+            conditions.push(AccessCondition::Country(country.clone()));
+        }
+        if let Some(methods) = &rule_config.methods {
+            let methods = methods
+                .iter()
+                .map(|method| {
+                    Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                        .map_err(|_| AclError::ConfigurationError(format!("invalid method: {}", method)))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            conditions.push(AccessCondition::Method(methods));
+        }
+        
+        let condition = match conditions.len() {
+            0 => AccessCondition::All,
+            1 => conditions.remove(0),
+            _ => AccessCondition::AllOf(conditions),
+        };
+        
+        Ok(match rule_config.action {
+            AclAction::Allow => AccessRule::Allow(condition),
+            AclAction::Deny => AccessRule::Deny(condition),
+        })
+    }
+    
     /// Add a rule to the ACL
     pub fn add_rule(&mut self, rule: AccessRule) {
         self.rules.push(rule);
@@ -120,6 +254,12 @@ impl Acl {
     pub fn check_access(&self, req: &Request<Body>, client_ip: Option<IpAddr>) -> Result<(), AclError> {
         debug!("Checking ACL for path: {}", req.uri().path());
         
+        // Path rules match the canonical path; one without a canonical form could evade them
+        if canonical_path(req.uri().path()).is_err() {
+            debug!("ACL denies a path without a canonical form");
+            return Err(AclError::AccessDenied);
+        }
+        
         for rule in &self.rules {
             if rule.matches(req, client_ip) {
                 if rule.allows() {
@@ -143,10 +283,53 @@ impl Acl {
     }
     
     /// Create a denial response
-    pub fn denial_response(&self) -> Response<Body> {
-        Response::builder()
-            .status(StatusCode::FORBIDDEN)
-            .body(Body::from("403 Forbidden: Access denied"))
-            .unwrap()
+    pub fn denial_response(&self, pages: &ErrorPages) -> Response<Body> {
+        HttpError::Forbidden("Access denied.".to_string()).to_response(pages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn request(path: &str) -> Request<Body> {
+        Request::builder().uri(path).body(Body::empty()).unwrap()
+    }
+    
+    fn deny_admin() -> Acl {
+        let mut acl = Acl::new(true);
+        acl.add_rule(AccessRule::Deny(AccessCondition::Path(Regex::new("^/admin/.*$").unwrap())));
+        acl
+    }
+    
+    #[test]
+    fn path_rules_match_encoded_and_dotted_variants() {
+        let acl = deny_admin();
+        for path in ["/admin/users", "/%61dmin/users", "/./admin/users", "//admin/users", "/admin//./users"] {
+            assert!(acl.check_access(&request(path), None).is_err(), "{} was allowed", path);
+        }
+        assert!(acl.check_access(&request("/public/admin"), None).is_ok());
+    }
+    
+    #[test]
+    fn paths_without_a_canonical_form_are_denied() {
+        let acl = deny_admin();
+        assert!(acl.check_access(&request("/public/%2e%2e/admin/users"), None).is_err());
+        assert!(acl.check_access(&request("/admin%2fusers"), None).is_err());
+    }
+    
+    #[test]
+    fn path_rules_with_regex_metacharacters_match_literally() {
+        let rule = toml::from_str("action = \"deny\"\npath = \"/c++/*\"").unwrap();
+        let mut acl = Acl::new(true);
+        acl.add_rule(Acl::rule_from_config(&rule).unwrap());
+        assert!(acl.check_access(&request("/c++/s.txt"), None).is_err());
+        assert!(acl.check_access(&request("/c%2B%2B/s.txt"), None).is_err());
+        
+        let rule = toml::from_str("action = \"deny\"\npath = \"/v1.0/*\"").unwrap();
+        let mut acl = Acl::new(true);
+        acl.add_rule(Acl::rule_from_config(&rule).unwrap());
+        assert!(acl.check_access(&request("/v1.0/users"), None).is_err());
+        assert!(acl.check_access(&request("/v1X0/users"), None).is_ok());
     }
 }
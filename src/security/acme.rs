@@ -0,0 +1,561 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hyper::header::{HeaderValue, CONTENT_TYPE, LOCATION};
+use hyper::{Body, Method, Request, Uri};
+use rcgen::{CertificateParams, CustomExtension, DistinguishedName, KeyPair, PKCS_ECDSA_P256_SHA256};
+use ring::rand::SystemRandom;
+use ring::signature::{EcdsaKeyPair, KeyPair as _, ECDSA_P256_SHA256_FIXED_SIGNING};
+use rustls::sign::{self, CertifiedKey};
+use rustls::{Certificate, PrivateKey};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info};
+
+use crate::core::config::AcmeConfig;
+use crate::handlers::proxy::{build_client, UpstreamClient};
+use crate::security::tls;
+
+/// Directory of the Let's Encrypt production CA
+pub const LETS_ENCRYPT_DIRECTORY: &str = "https://acme-v02.api.letsencrypt.org/directory";
+
+/// ALPN protocol of TLS-ALPN-01 validation handshakes
+pub const ACME_TLS_ALPN: &[u8] = b"acme-tls/1";
+
+/// Path prefix of HTTP-01 challenge requests
+const HTTP_CHALLENGE_PREFIX: &str = "/.well-known/acme-challenge/";
+
+/// Interval between checks of the certificate expiry
+const CHECK_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Delay before retrying a failed renewal
+const RETRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Delay between polls of a pending authorization or order
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Number of polls before giving up on an authorization or order
+const POLL_ATTEMPTS: usize = 30;
+
+/// Error types for certificate provisioning
+#[derive(Debug)]
+pub enum AcmeError {
+    /// A stored key or certificate could not be read or written
+    Io(String, std::io::Error),
+    /// The CA could not be reached
+    Http(String),
+    /// The CA rejected a request or answered unexpectedly
+    Server(String),
+    /// A key, signature or certificate request could not be produced
+    Crypto(String),
+    /// A stored or issued certificate is unusable
+    Certificate(String),
+}
+
+impl fmt::Display for AcmeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AcmeError::Io(path, e) => write!(f, "Failed to access {}: {}", path, e),
+            AcmeError::Http(message) => write!(f, "ACME request failed: {}", message),
+            AcmeError::Server(message) => write!(f, "ACME server error: {}", message),
+            AcmeError::Crypto(message) => write!(f, "ACME cryptography error: {}", message),
+            AcmeError::Certificate(message) => write!(f, "Invalid certificate: {}", message),
+        }
+    }
+}
+
+impl Error for AcmeError {}
+
+impl From<rcgen::Error> for AcmeError {
+    fn from(e: rcgen::Error) -> Self {
+        AcmeError::Crypto(e.to_string())
+    }
+}
+
+/// Challenge type used to prove control of the domains
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum AcmeChallenge {
+    /// Serve a token under `/.well-known/acme-challenge/` over plain HTTP on port 80
+    #[default]
+    #[serde(rename = "http-01")]
+    Http01,
+    /// Present a validation certificate to `acme-tls/1` handshakes on port 443
+    #[serde(rename = "tls-alpn-01")]
+    TlsAlpn01,
+}
+
+impl AcmeChallenge {
+    /// Name of the challenge type in ACME authorizations
+    fn name(self) -> &'static str {
+        match self {
+            AcmeChallenge::Http01 => "http-01",
+            AcmeChallenge::TlsAlpn01 => "tls-alpn-01",
+        }
+    }
+}
+
+/// Certificate issued for the configured domains, with its expiry
+struct IssuedCertificate {
+    /// Certificate chain and signing key
+    key: Arc<CertifiedKey>,
+    /// Expiry as seconds since the Unix epoch
+    not_after: i64,
+}
+
+/// Certificates obtained and renewed from an ACME CA such as Let's Encrypt.
+///
+/// The certificate is stored on disk and reloaded at startup; renewals replace
+/// it in place, so new handshakes pick it up without a restart. Pending
+/// challenges are answered from here by the HTTP pipeline and the TLS resolver.
+pub struct Acme {
+    /// ACME configuration
+    config: AcmeConfig,
+    /// Directory holding the account key and certificates
+    storage_dir: PathBuf,
+    /// Current certificate, once one is stored or issued
+    certificate: RwLock<Option<IssuedCertificate>>,
+    /// Key authorizations of pending HTTP-01 challenges, by token
+    http_challenges: RwLock<HashMap<String, String>>,
+    /// Validation certificates of pending TLS-ALPN-01 challenges, by domain
+    tls_challenges: RwLock<HashMap<String, Arc<CertifiedKey>>>,
+}
+
+impl Acme {
+    /// Set up provisioning, loading a previously issued certificate if one is stored
+    pub fn from_config(config: &AcmeConfig) -> Result<Self, AcmeError> {
+        let acme = Acme {
+            config: config.clone(),
+            storage_dir: PathBuf::from(config.storage_dir.as_deref().unwrap_or("./acme")),
+            certificate: RwLock::new(None),
+            http_challenges: RwLock::new(HashMap::new()),
+            tls_challenges: RwLock::new(HashMap::new()),
+        };
+        
+        let (cert_path, key_path) = acme.certificate_paths();
+        if cert_path.exists() && key_path.exists() {
+            let certs = tls::load_certificates(&cert_path.to_string_lossy())
+                .map_err(|e| AcmeError::Certificate(e.to_string()))?;
+            let key = tls::load_private_key(&key_path.to_string_lossy())
+                .map_err(|e| AcmeError::Certificate(e.to_string()))?;
+            acme.install(certs, key)?;
+            info!("Loaded ACME certificate for {}", acme.config.domains.join(", "));
+        }
+        
+        Ok(acme)
+    }
+    
+    /// Get the challenge type used for validation
+    pub fn challenge(&self) -> AcmeChallenge {
+        self.config.challenge.unwrap_or_default()
+    }
+    
+    /// Get the issued certificate if it covers the server name
+    pub fn certificate_for(&self, server_name: &str) -> Option<Arc<CertifiedKey>> {
+        if !self.config.domains.iter().any(|domain| domain.eq_ignore_ascii_case(server_name)) {
+            return None;
+        }
+        
+        let certificate = self.certificate.read().unwrap_or_else(|e| e.into_inner());
+        certificate.as_ref().map(|issued| Arc::clone(&issued.key))
+    }
+    
+    /// Get the validation certificate of a pending TLS-ALPN-01 challenge
+    pub fn challenge_certificate(&self, server_name: &str) -> Option<Arc<CertifiedKey>> {
+        let challenges = self.tls_challenges.read().unwrap_or_else(|e| e.into_inner());
+        challenges.get(&server_name.to_ascii_lowercase()).cloned()
+    }
+    
+    /// Get the key authorization answering an HTTP-01 challenge request path
+    pub fn http_challenge(&self, path: &str) -> Option<String> {
+        let token = path.strip_prefix(HTTP_CHALLENGE_PREFIX)?;
+        let challenges = self.http_challenges.read().unwrap_or_else(|e| e.into_inner());
+        challenges.get(token).cloned()
+    }
+    
+    /// Renew the certificate in the background whenever it nears expiry
+    pub fn spawn_renewal(self: &Arc<Self>) -> JoinHandle<()> {
+        let acme = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                let wait = match acme.renew_if_due().await {
+                    Ok(()) => CHECK_INTERVAL,
+                    Err(e) => {
+                        error!("Certificate provisioning for {} failed: {}", acme.config.domains.join(", "), e);
+                        RETRY_INTERVAL
+                    }
+                };
+                tokio::time::sleep(wait).await;
+            }
+        })
+    }
+    
+    /// Obtain a certificate when none is stored or the current one expires soon
+    async fn renew_if_due(&self) -> Result<(), AcmeError> {
+        let renew_before = self.config.renew_before_days.unwrap_or(30) as i64 * 24 * 60 * 60;
+        let not_after = self.certificate.read().unwrap_or_else(|e| e.into_inner()).as_ref().map(|issued| issued.not_after);
+        if let Some(not_after) = not_after {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or_default();
+            if not_after - now > renew_before {
+                debug!("ACME certificate valid for another {} days", (not_after - now) / 86400);
+                return Ok(());
+            }
+        }
+        
+        info!("Requesting certificate for {}", self.config.domains.join(", "));
+        self.obtain().await
+    }
+    
+    /// Run an ACME order for the configured domains and install the issued certificate
+    async fn obtain(&self) -> Result<(), AcmeError> {
+        let directory_url = self.config.directory_url.as_deref().unwrap_or(LETS_ENCRYPT_DIRECTORY);
+        let mut client = AcmeClient::connect(directory_url, self.account_key()?).await?;
+        client.register(self.config.contact.as_deref().unwrap_or_default()).await?;
+        
+        let identifiers: Vec<Value> = self.config.domains.iter().map(|domain| json!({"type": "dns", "value": domain})).collect();
+        let new_order = client.directory.new_order.clone();
+        let response = client.post(&new_order, Some(&json!({"identifiers": identifiers}))).await?;
+        let order = response.json()?;
+        let order_url = response.location.ok_or_else(|| AcmeError::Server("order without a location".to_string()))?;
+        
+        for authorization in order["authorizations"].as_array().into_iter().flatten() {
+            let url = authorization.as_str().ok_or_else(|| AcmeError::Server("invalid authorization URL".to_string()))?;
+            self.authorize(&mut client, url).await?;
+        }
+        
+        // The certificate key never leaves this server; the CA only sees the request
+        let mut params = CertificateParams::new(self.config.domains.clone());
+        params.distinguished_name = DistinguishedName::new();
+        params.key_pair = Some(KeyPair::generate(&PKCS_ECDSA_P256_SHA256)?);
+        let request = rcgen::Certificate::from_params(params)?;
+        let csr = URL_SAFE_NO_PAD.encode(request.serialize_request_der()?);
+        
+        let finalize = order["finalize"].as_str().ok_or_else(|| AcmeError::Server("order without a finalize URL".to_string()))?;
+        client.post(finalize, Some(&json!({"csr": csr}))).await?;
+        let order = client.poll(&order_url).await?;
+        
+        let certificate_url = order["certificate"].as_str().ok_or_else(|| AcmeError::Server("order without a certificate".to_string()))?;
+        let chain = client.post(certificate_url, None).await?.body;
+        let certs: Vec<Certificate> = rustls_pemfile::certs(&mut chain.as_slice())
+            .map_err(|e| AcmeError::Certificate(e.to_string()))?
+            .into_iter()
+            .map(Certificate)
+            .collect();
+        let key = PrivateKey(request.serialize_private_key_der());
+        let not_after = self.install(certs, key)?;
+        
+        let (cert_path, key_path) = self.certificate_paths();
+        write_file(&key_path, request.serialize_private_key_pem().as_bytes(), true)?;
+        write_file(&cert_path, &chain, false)?;
+        info!(
+            "Installed certificate for {}, valid for {} days",
+            self.config.domains.join(", "),
+            (not_after - SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or_default()) / 86400,
+        );
+        
+        Ok(())
+    }
+    
+    /// Complete the configured challenge of one authorization
+    async fn authorize(&self, client: &mut AcmeClient, url: &str) -> Result<(), AcmeError> {
+        let authorization = client.post(url, None).await?.json()?;
+        if authorization["status"] == "valid" {
+            return Ok(());
+        }
+        
+        let domain = authorization["identifier"]["value"].as_str().unwrap_or_default().to_ascii_lowercase();
+        let challenge = authorization["challenges"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|challenge| challenge["type"] == self.challenge().name())
+            .ok_or_else(|| AcmeError::Server(format!("no {} challenge offered for {}", self.challenge().name(), domain)))?;
+        let token = challenge["token"].as_str().unwrap_or_default().to_string();
+        let challenge_url = challenge["url"].as_str().unwrap_or_default().to_string();
+        let key_authorization = format!("{}.{}", token, client.thumbprint);
+        
+        debug!("Answering {} challenge for {}", self.challenge().name(), domain);
+        match self.challenge() {
+            AcmeChallenge::Http01 => {
+                self.http_challenges.write().unwrap_or_else(|e| e.into_inner()).insert(token.clone(), key_authorization);
+            }
+            AcmeChallenge::TlsAlpn01 => {
+                let key = validation_certificate(&domain, &key_authorization)?;
+                self.tls_challenges.write().unwrap_or_else(|e| e.into_inner()).insert(domain.clone(), Arc::new(key));
+            }
+        }
+        
+        let result = match client.post(&challenge_url, Some(&json!({}))).await {
+            Ok(_) => client.poll(url).await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+        
+        self.http_challenges.write().unwrap_or_else(|e| e.into_inner()).remove(&token);
+        self.tls_challenges.write().unwrap_or_else(|e| e.into_inner()).remove(&domain);
+        result
+    }
+    
+    /// Replace the current certificate, returning its expiry
+    fn install(&self, certs: Vec<Certificate>, key: PrivateKey) -> Result<i64, AcmeError> {
+        let leaf = certs.first().ok_or_else(|| AcmeError::Certificate("empty certificate chain".to_string()))?;
+        let (_, parsed) = x509_parser::parse_x509_certificate(&leaf.0).map_err(|e| AcmeError::Certificate(e.to_string()))?;
+        let not_after = parsed.validity().not_after.timestamp();
+        let signing_key = sign::any_supported_type(&key).map_err(|e| AcmeError::Certificate(e.to_string()))?;
+        
+        let issued = IssuedCertificate {
+            key: Arc::new(CertifiedKey::new(certs, signing_key)),
+            not_after,
+        };
+        *self.certificate.write().unwrap_or_else(|e| e.into_inner()) = Some(issued);
+        Ok(not_after)
+    }
+    
+    /// Paths of the stored certificate chain and key, named after the first domain
+    fn certificate_paths(&self) -> (PathBuf, PathBuf) {
+        let name = self.config.domains.first().map(String::as_str).unwrap_or("certificate");
+        (
+            self.storage_dir.join(format!("{}.crt", name)),
+            self.storage_dir.join(format!("{}.key", name)),
+        )
+    }
+    
+    /// Load the account key, creating one on first use
+    fn account_key(&self) -> Result<Vec<u8>, AcmeError> {
+        let path = self.storage_dir.join("account.key");
+        if path.exists() {
+            let pem = std::fs::read(&path).map_err(|e| AcmeError::Io(path.display().to_string(), e))?;
+            return rustls_pemfile::pkcs8_private_keys(&mut pem.as_slice())
+                .map_err(|e| AcmeError::Io(path.display().to_string(), e))?
+                .into_iter()
+                .next()
+                .ok_or_else(|| AcmeError::Crypto(format!("no PKCS#8 key in {}", path.display())));
+        }
+        
+        info!("Creating ACME account key at {}", path.display());
+        let key = KeyPair::generate(&PKCS_ECDSA_P256_SHA256)?;
+        write_file(&path, key.serialize_pem().as_bytes(), true)?;
+        Ok(key.serialize_der())
+    }
+}
+
+/// Self-signed certificate proving control of a domain to TLS-ALPN-01 validation (RFC 8737)
+fn validation_certificate(domain: &str, key_authorization: &str) -> Result<CertifiedKey, AcmeError> {
+    let mut params = CertificateParams::new(vec![domain.to_string()]);
+    params.custom_extensions = vec![CustomExtension::new_acme_identifier(&Sha256::digest(key_authorization.as_bytes()))];
+    let certificate = rcgen::Certificate::from_params(params)?;
+    let signing_key = sign::any_supported_type(&PrivateKey(certificate.serialize_private_key_der()))
+        .map_err(|e| AcmeError::Crypto(e.to_string()))?;
+    
+    Ok(CertifiedKey::new(vec![Certificate(certificate.serialize_der()?)], signing_key))
+}
+
+/// Write a file in the storage directory, readable only by the server for keys
+fn write_file(path: &Path, contents: &[u8], private: bool) -> Result<(), AcmeError> {
+    let io_error = |e| AcmeError::Io(path.display().to_string(), e);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(io_error)?;
+    }
+    
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+    
+    std::io::Write::write_all(&mut options.open(path).map_err(io_error)?, contents).map_err(io_error)
+}
+
+/// Resource URLs published in the CA's directory
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Directory {
+    /// URL issuing anti-replay nonces
+    new_nonce: String,
+    /// URL registering accounts
+    new_account: String,
+    /// URL creating orders
+    new_order: String,
+}
+
+/// Response to a signed ACME request
+struct AcmeResponse {
+    /// URL of the created resource, if any
+    location: Option<String>,
+    /// Response body
+    body: Vec<u8>,
+}
+
+impl AcmeResponse {
+    /// Parse the body as a JSON object
+    fn json(&self) -> Result<Value, AcmeError> {
+        serde_json::from_slice(&self.body).map_err(|e| AcmeError::Server(format!("invalid JSON response: {}", e)))
+    }
+}
+
+/// ACME (RFC 8555) client signing requests with an account key
+struct AcmeClient {
+    /// HTTP client reaching the CA
+    http: UpstreamClient,
+    /// Account key signing every request
+    key: EcdsaKeyPair,
+    /// Random source for signatures
+    rng: SystemRandom,
+    /// Public account key as a JWK, sent until the account URL is known
+    jwk: Value,
+    /// Base64url SHA-256 thumbprint of the JWK, part of every key authorization
+    thumbprint: String,
+    /// Resource URLs of the CA
+    directory: Directory,
+    /// Nonce for the next request, taken from the previous response
+    nonce: Option<String>,
+    /// Account URL, identifying the account once registered
+    kid: Option<String>,
+}
+
+impl AcmeClient {
+    /// Fetch the CA's directory
+    async fn connect(directory_url: &str, account_key: Vec<u8>) -> Result<Self, AcmeError> {
+        let rng = SystemRandom::new();
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &account_key, &rng)
+            .map_err(|e| AcmeError::Crypto(format!("invalid account key: {}", e)))?;
+        
+        // The public key is an uncompressed point: 0x04, then x and y
+        let point = key.public_key().as_ref();
+        let (x, y) = (URL_SAFE_NO_PAD.encode(&point[1..33]), URL_SAFE_NO_PAD.encode(&point[33..65]));
+        // Thumbprint members in lexicographic order, without whitespace (RFC 7638)
+        let canonical = format!(r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#, x, y);
+        let thumbprint = URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()));
+        let jwk = json!({"crv": "P-256", "kty": "EC", "x": x, "y": y});
+        
+        let http = build_client();
+        let uri: Uri = directory_url.parse().map_err(|e| AcmeError::Http(format!("invalid directory URL: {}", e)))?;
+        let response = http.get(uri).await.map_err(|e| AcmeError::Http(e.to_string()))?;
+        let body = hyper::body::to_bytes(response.into_body()).await.map_err(|e| AcmeError::Http(e.to_string()))?;
+        let directory = serde_json::from_slice(&body).map_err(|e| AcmeError::Server(format!("invalid directory: {}", e)))?;
+        
+        Ok(AcmeClient {
+            http,
+            key,
+            rng,
+            jwk,
+            thumbprint,
+            directory,
+            nonce: None,
+            kid: None,
+        })
+    }
+    
+    /// Find or create the account of the key, agreeing to the CA's terms
+    async fn register(&mut self, contact: &[String]) -> Result<(), AcmeError> {
+        let new_account = self.directory.new_account.clone();
+        let payload = json!({"termsOfServiceAgreed": true, "contact": contact});
+        let response = self.post(&new_account, Some(&payload)).await?;
+        self.kid = Some(response.location.ok_or_else(|| AcmeError::Server("account without a location".to_string()))?);
+        debug!("Using ACME account {}", self.kid.as_deref().unwrap_or_default());
+        Ok(())
+    }
+    
+    /// Poll an authorization or order until it is valid
+    async fn poll(&mut self, url: &str) -> Result<Value, AcmeError> {
+        for _ in 0..POLL_ATTEMPTS {
+            let resource = self.post(url, None).await?.json()?;
+            match resource["status"].as_str() {
+                Some("valid") => return Ok(resource),
+                Some("invalid") => {
+                    let detail = resource["challenges"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .find_map(|challenge| challenge["error"]["detail"].as_str())
+                        .or_else(|| resource["error"]["detail"].as_str())
+                        .unwrap_or("validation failed");
+                    return Err(AcmeError::Server(format!("{} is invalid: {}", url, detail)));
+                }
+                _ => tokio::time::sleep(POLL_INTERVAL).await,
+            }
+        }
+        
+        Err(AcmeError::Server(format!("{} did not become valid in time", url)))
+    }
+    
+    /// Send a signed request; without a payload, this is a POST-as-GET
+    async fn post(&mut self, url: &str, payload: Option<&Value>) -> Result<AcmeResponse, AcmeError> {
+        // A stale nonce is refused with badNonce and a fresh one, so retry once
+        match self.try_post(url, payload).await {
+            Err(AcmeError::Server(message)) if message.contains("badNonce") => self.try_post(url, payload).await,
+            result => result,
+        }
+    }
+    
+    /// Send one signed request
+    async fn try_post(&mut self, url: &str, payload: Option<&Value>) -> Result<AcmeResponse, AcmeError> {
+        let nonce = match self.nonce.take() {
+            Some(nonce) => nonce,
+            None => self.fetch_nonce().await?,
+        };
+        
+        let mut protected = json!({"alg": "ES256", "nonce": nonce, "url": url});
+        match &self.kid {
+            Some(kid) => protected["kid"] = json!(kid),
+            None => protected["jwk"] = self.jwk.clone(),
+        }
+        let protected = URL_SAFE_NO_PAD.encode(protected.to_string());
+        let payload = payload.map(|payload| URL_SAFE_NO_PAD.encode(payload.to_string())).unwrap_or_default();
+        let signature = self
+            .key
+            .sign(&self.rng, format!("{}.{}", protected, payload).as_bytes())
+            .map_err(|e| AcmeError::Crypto(e.to_string()))?;
+        let body = json!({
+            "protected": protected,
+            "payload": payload,
+            "signature": URL_SAFE_NO_PAD.encode(signature.as_ref()),
+        });
+        
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(url)
+            .header(CONTENT_TYPE, "application/jose+json")
+            .body(Body::from(body.to_string()))
+            .map_err(|e| AcmeError::Http(e.to_string()))?;
+        let response = self.http.request(request).await.map_err(|e| AcmeError::Http(e.to_string()))?;
+        
+        self.nonce = header_string(response.headers().get("replay-nonce"));
+        let location = header_string(response.headers().get(LOCATION));
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.map_err(|e| AcmeError::Http(e.to_string()))?.to_vec();
+        
+        if !status.is_success() {
+            return Err(AcmeError::Server(format!("{} answered {}: {}", url, status, String::from_utf8_lossy(&body))));
+        }
+        
+        Ok(AcmeResponse { location, body })
+    }
+    
+    /// Fetch a fresh nonce
+    async fn fetch_nonce(&self) -> Result<String, AcmeError> {
+        let request = Request::builder()
+            .method(Method::HEAD)
+            .uri(&self.directory.new_nonce)
+            .body(Body::empty())
+            .map_err(|e| AcmeError::Http(e.to_string()))?;
+        let response = self.http.request(request).await.map_err(|e| AcmeError::Http(e.to_string()))?;
+        
+        header_string(response.headers().get("replay-nonce"))
+            .ok_or_else(|| AcmeError::Server("no Replay-Nonce in newNonce response".to_string()))
+    }
+}
+
+/// Read a header value as a string
+fn header_string(value: Option<&HeaderValue>) -> Option<String> {
+    value.and_then(|value| value.to_str().ok()).map(str::to_string)
+}
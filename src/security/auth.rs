@@ -1,16 +1,30 @@
 use async_trait::async_trait;
-use hyper::{Body, Request, Response, StatusCode};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use regex::Regex;
 use std::error::Error;
 use std::fmt;
 use std::collections::HashMap;
-use tracing::{debug, error, info};
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+use crate::core::config::{AuthScheme, Config};
+use crate::core::error::HttpError;
+use crate::network::http::path::canonical_path;
+use crate::routing::router::wildcard_regex;
+use crate::security::digest::{DigestAlgorithm, DigestAuthenticator, DEFAULT_NONCE_LIFETIME};
 
 /// Error types for authentication
 #[derive(Debug)]
 pub enum AuthError {
     InvalidCredentials,
     MissingCredentials,
-    ConfigurationError,
+    /// Digest credentials were valid for an expired nonce
+    StaleNonce,
+    ConfigurationError(String),
 }
 
 impl fmt::Display for AuthError {
@@ -18,7 +32,8 @@ impl fmt::Display for AuthError {
         match self {
             AuthError::InvalidCredentials => write!(f, "Invalid credentials"),
             AuthError::MissingCredentials => write!(f, "Missing credentials"),
-            AuthError::ConfigurationError => write!(f, "Authentication configuration error"),
+            AuthError::StaleNonce => write!(f, "Stale nonce"),
+            AuthError::ConfigurationError(detail) => write!(f, "Authentication configuration error: {}", detail),
         }
     }
 }
@@ -45,16 +60,69 @@ pub trait Authenticator: Send + Sync {
     /// Authenticate a request
     async fn authenticate(&self, req: &Request<Body>) -> Result<bool, AuthError>;
     
+    /// Get the `WWW-Authenticate` challenge for this authenticator
+    fn challenge(&self) -> String;
+    
+    /// Get the challenge after a failed attempt, which may depend on why it failed
+    fn challenge_after(&self, _error: &AuthError) -> String {
+        self.challenge()
+    }
+    
     /// Create a challenge response when authentication fails
     fn challenge_response(&self) -> Response<Body>;
 }
 
+/// Stored password of a Basic authentication user
+#[derive(Debug, Clone)]
+enum StoredPassword {
+    /// Plain-text password
+    Plain(String),
+    /// bcrypt hash (`$2y$...`)
+    Bcrypt(String),
+    /// Base64 SHA-1 digest (`{SHA}...`)
+    Sha1(String),
+}
+
+impl StoredPassword {
+    /// Interpret an htpasswd hash, returning `None` for unsupported schemes
+    fn from_htpasswd(hash: &str) -> Option<Self> {
+        if hash.starts_with("$2a$") || hash.starts_with("$2b$") || hash.starts_with("$2y$") {
+            Some(StoredPassword::Bcrypt(hash.to_string()))
+        } else if let Some(digest) = hash.strip_prefix("{SHA}") {
+            Some(StoredPassword::Sha1(digest.to_string()))
+        } else if hash.starts_with('$') {
+            // apr1 MD5 and crypt(3) variants
+            None
+        } else {
+            Some(StoredPassword::Plain(hash.to_string()))
+        }
+    }
+    
+    /// Check a password against the stored one
+    async fn verify(&self, password: &str) -> bool {
+        match self {
+            StoredPassword::Plain(stored) => constant_time_eq(stored.as_bytes(), password.as_bytes()),
+            StoredPassword::Sha1(stored) => {
+                let digest = STANDARD.encode(sha1_smol::Sha1::from(password).digest().bytes());
+                constant_time_eq(stored.as_bytes(), digest.as_bytes())
+            }
+            StoredPassword::Bcrypt(hash) => {
+                // bcrypt is deliberately slow; keep it off the connection's worker
+                let (hash, password) = (hash.clone(), password.to_string());
+                tokio::task::spawn_blocking(move || bcrypt::verify(password, &hash).unwrap_or(false))
+                    .await
+                    .unwrap_or(false)
+            }
+        }
+    }
+}
+
 /// Basic authenticator using username/password
 pub struct BasicAuthenticator {
     /// Realm for basic auth
     realm: String,
     /// Map of username to password
-    credentials: HashMap<String, String>,
+    credentials: HashMap<String, StoredPassword>,
 }
 
 impl BasicAuthenticator {
@@ -68,7 +136,43 @@ impl BasicAuthenticator {
     
     /// Add a user with password
     pub fn add_user(&mut self, username: &str, password: &str) {
-        self.credentials.insert(username.to_string(), password.to_string());
+        self.credentials.insert(username.to_string(), StoredPassword::Plain(password.to_string()));
+    }
+    
+    /// Load users from an htpasswd file, returning the number of users added.
+    ///
+    /// bcrypt, `{SHA}` and plain-text entries are supported; other schemes are skipped.
+    pub fn load_htpasswd(&mut self, path: &str) -> Result<usize, AuthError> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| AuthError::ConfigurationError(format!("cannot read {}: {}", path, e)))?;
+        let mut added = 0;
+        
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            
+            let Some((username, hash)) = line.split_once(':') else {
+                warn!("Skipping malformed line {} in {}", number + 1, path);
+                continue;
+            };
+            
+            match StoredPassword::from_htpasswd(hash) {
+                Some(password) => {
+                    self.credentials.insert(username.to_string(), password);
+                    added += 1;
+                }
+                None => warn!("Skipping user {} in {}: unsupported hash scheme (use bcrypt)", username, path),
+            }
+        }
+        
+        Ok(added)
+    }
+    
+    /// Check whether any users are configured
+    pub fn has_users(&self) -> bool {
+        !self.credentials.is_empty()
     }
     
     /// Parse basic auth header
@@ -78,7 +182,7 @@ impl BasicAuthenticator {
         }
         
         let encoded = auth_header.trim_start_matches("Basic ");
-        let decoded = match base64::decode(encoded) {
+        let decoded = match STANDARD.decode(encoded) {
             Ok(bytes) => String::from_utf8_lossy(&bytes).to_string(),
             Err(_) => return Err(AuthError::InvalidCredentials),
         };
@@ -113,7 +217,7 @@ impl Authenticator for BasicAuthenticator {
         
         // Check against stored credentials
         if let Some(stored_password) = self.credentials.get(&username) {
-            if stored_password == &password {
+            if stored_password.verify(&password).await {
                 debug!("Basic authentication successful for user: {}", username);
                 return Ok(true);
             }
@@ -123,11 +227,243 @@ impl Authenticator for BasicAuthenticator {
         Err(AuthError::InvalidCredentials)
     }
     
+    fn challenge(&self) -> String {
+        format!("Basic realm=\"{}\"", self.realm)
+    }
+    
+    fn challenge_response(&self) -> Response<Body> {
+        Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .header("WWW-Authenticate", self.challenge())
+            .body(Body::from("401 Unauthorized: Authentication required"))
+            .unwrap()
+    }
+}
+
+/// Bearer authenticator accepting a fixed set of tokens
+pub struct BearerAuthenticator {
+    /// Realm for bearer auth
+    realm: String,
+    /// Accepted tokens
+    tokens: Vec<String>,
+}
+
+impl BearerAuthenticator {
+    /// Create a new bearer authenticator
+    pub fn new(realm: &str, tokens: Vec<String>) -> Self {
+        BearerAuthenticator {
+            realm: realm.to_string(),
+            tokens,
+        }
+    }
+}
+
+#[async_trait]
+impl Authenticator for BearerAuthenticator {
+    fn method(&self) -> AuthMethod {
+        AuthMethod::Bearer
+    }
+    
+    async fn authenticate(&self, req: &Request<Body>) -> Result<bool, AuthError> {
+        let auth_header = match req.headers().get("authorization") {
+            Some(value) => value.to_str().map_err(|_| AuthError::InvalidCredentials)?,
+            None => return Err(AuthError::MissingCredentials),
+        };
+        
+        let token = match auth_header.strip_prefix("Bearer ") {
+            Some(token) => token.trim(),
+            None => return Err(AuthError::InvalidCredentials),
+        };
+        
+        // Compare against every token so timing does not reveal which one is closest
+        let matched = self
+            .tokens
+            .iter()
+            .fold(false, |matched, stored| matched | constant_time_eq(stored.as_bytes(), token.as_bytes()));
+        
+        if matched {
+            debug!("Bearer authentication successful");
+            Ok(true)
+        } else {
+            error!("Bearer authentication failed");
+            Err(AuthError::InvalidCredentials)
+        }
+    }
+    
+    fn challenge(&self) -> String {
+        format!("Bearer realm=\"{}\"", self.realm)
+    }
+    
     fn challenge_response(&self) -> Response<Body> {
         Response::builder()
             .status(StatusCode::UNAUTHORIZED)
-            .header("WWW-Authenticate", format!("Basic realm=\"{}\"", self.realm))
+            .header("WWW-Authenticate", self.challenge())
             .body(Body::from("401 Unauthorized: Authentication required"))
             .unwrap()
     }
 }
+
+/// Authentication required for requests to protected paths
+pub struct AuthPolicy {
+    /// Protected path patterns
+    patterns: Vec<Regex>,
+    /// Methods requiring authentication, or `None` for every method
+    methods: Option<Vec<Method>>,
+    /// Authenticators tried in order; any one accepting the request suffices
+    authenticators: Vec<Arc<dyn Authenticator>>,
+}
+
+impl AuthPolicy {
+    /// Build the policy configured in `auth`, if any
+    pub fn from_config(config: &Config) -> Result<Option<Self>, AuthError> {
+        let auth_config = match &config.auth {
+            Some(auth_config) if !auth_config.paths.is_empty() => auth_config,
+            _ => return Ok(None),
+        };
+        
+        let realm = auth_config.realm.as_deref().unwrap_or("Restricted");
+        let schemes = auth_config.schemes.clone().unwrap_or_else(|| vec![AuthScheme::Basic, AuthScheme::Bearer]);
+        let mut authenticators: Vec<Arc<dyn Authenticator>> = Vec::new();
+        
+        // Offer the stronger Digest algorithm first
+        if schemes.contains(&AuthScheme::Digest) {
+            let users = auth_config.users.as_ref().filter(|users| !users.is_empty()).ok_or_else(|| {
+                AuthError::ConfigurationError("digest authentication needs [auth.users] passwords".to_string())
+            })?;
+            let nonce_lifetime = Duration::from_secs(auth_config.nonce_lifetime.unwrap_or(DEFAULT_NONCE_LIFETIME));
+            
+            for algorithm in [DigestAlgorithm::Sha256, DigestAlgorithm::Md5] {
+                let mut digest = DigestAuthenticator::new(realm, algorithm, nonce_lifetime)?;
+                for (username, password) in users {
+                    digest.add_user(username, password);
+                }
+                authenticators.push(Arc::new(digest));
+            }
+        }
+        
+        if schemes.contains(&AuthScheme::Basic) {
+            let mut basic = BasicAuthenticator::new(realm);
+            for (username, password) in auth_config.users.iter().flatten() {
+                basic.add_user(username, password);
+            }
+            if let Some(htpasswd) = &auth_config.htpasswd {
+                let added = basic.load_htpasswd(htpasswd)?;
+                info!("Loaded {} users from {}", added, htpasswd);
+            }
+            if basic.has_users() {
+                authenticators.push(Arc::new(basic));
+            }
+        }
+        
+        if schemes.contains(&AuthScheme::Bearer) {
+            if let Some(tokens) = auth_config.tokens.clone().filter(|tokens| !tokens.is_empty()) {
+                authenticators.push(Arc::new(BearerAuthenticator::new(realm, tokens)));
+            }
+        }
+        
+        if authenticators.is_empty() {
+            return Err(AuthError::ConfigurationError(
+                "protected paths are configured but no users or tokens".to_string(),
+            ));
+        }
+        
+        let patterns = auth_config
+            .paths
+            .iter()
+            .map(|path| wildcard_regex(path))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AuthError::ConfigurationError(e.to_string()))?;
+        
+        let methods = auth_config
+            .methods
+            .as_ref()
+            .map(|methods| {
+                methods
+                    .iter()
+                    .map(|method| {
+                        Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                            .map_err(|_| AuthError::ConfigurationError(format!("invalid method: {}", method)))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+        
+        Ok(Some(AuthPolicy { patterns, methods, authenticators }))
+    }
+    
+    /// Check if a request path requires authentication.
+    ///
+    /// Patterns match the canonical path, so encoded or dotted variants of a protected
+    /// path are protected too. A path without a canonical form is always protected.
+    pub fn protects(&self, path: &str) -> bool {
+        match canonical_path(path) {
+            Ok(path) => self.patterns.iter().any(|pattern| pattern.is_match(&path)),
+            Err(_) => true,
+        }
+    }
+    
+    /// Check if a request requires authentication, by its path and method
+    pub fn protects_request(&self, req: &Request<Body>) -> bool {
+        self.methods.as_ref().is_none_or(|methods| methods.contains(req.method())) && self.protects(req.uri().path())
+    }
+    
+    /// Authenticate a request, failing with a challenge for every accepted scheme
+    pub async fn check(&self, req: &Request<Body>) -> Result<(), HttpError> {
+        let mut challenges = Vec::with_capacity(self.authenticators.len());
+        
+        for authenticator in &self.authenticators {
+            match authenticator.authenticate(req).await {
+                Ok(true) => return Ok(()),
+                Ok(false) => challenges.push(authenticator.challenge()),
+                Err(e) => challenges.push(authenticator.challenge_after(&e)),
+            }
+        }
+        
+        Err(HttpError::Unauthorized { challenges })
+    }
+}
+
+/// Compare two byte strings in time independent of where they differ
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn policy() -> AuthPolicy {
+        let auth = toml::from_str("paths = [\"/secret/*\"]\n[users]\nalice = \"wonderland\"\n").unwrap();
+        let config = Config { auth: Some(auth), ..Config::default() };
+        AuthPolicy::from_config(&config).unwrap().unwrap()
+    }
+    
+    #[test]
+    fn protects_encoded_and_dotted_variants() {
+        let policy = policy();
+        for path in ["/secret/a.txt", "/%73ecret/a.txt", "/./secret/a.txt", "//secret/a.txt", "/secret/./a.txt"] {
+            assert!(policy.protects(path), "{} is not protected", path);
+        }
+        assert!(!policy.protects("/public/a.txt"));
+        assert!(!policy.protects("/secretary"));
+    }
+    
+    #[test]
+    fn protects_paths_without_a_canonical_form() {
+        let policy = policy();
+        assert!(policy.protects("/public/%2e%2e/secret/a.txt"));
+        assert!(policy.protects("/public%2fa.txt"));
+    }
+    
+    #[test]
+    fn protected_paths_with_regex_metacharacters_match_literally() {
+        let auth = toml::from_str("paths = [\"/c++/*\", \"/v1.0/*\"]\n[users]\nalice = \"wonderland\"\n").unwrap();
+        let config = Config { auth: Some(auth), ..Config::default() };
+        let policy = AuthPolicy::from_config(&config).unwrap().unwrap();
+        
+        assert!(policy.protects("/c++/s.txt"));
+        assert!(policy.protects("/c%2B%2B/s.txt"));
+        assert!(policy.protects("/v1.0/users"));
+        assert!(!policy.protects("/v1X0/users"));
+    }
+}
@@ -0,0 +1,260 @@
+use hyper::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS,
+    ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE,
+    ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use regex::Regex;
+use std::error::Error;
+use std::fmt;
+use tracing::debug;
+
+use crate::core::config::{Config, CorsConfig};
+use crate::routing::router::wildcard_regex;
+
+/// Error types for CORS
+#[derive(Debug)]
+pub enum CorsError {
+    ConfigurationError(String),
+}
+
+impl fmt::Display for CorsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CorsError::ConfigurationError(detail) => write!(f, "CORS configuration error: {}", detail),
+        }
+    }
+}
+
+impl Error for CorsError {}
+
+/// Cross-origin resource sharing policy for the paths it covers.
+///
+/// Preflight requests are answered by the server itself; other requests
+/// from allowed origins get the response headers that let scripts read them.
+pub struct CorsPolicy {
+    /// Covered path patterns
+    paths: Vec<Regex>,
+    /// Allowed origin patterns, or `None` for any origin
+    origins: Option<Vec<Regex>>,
+    /// Allowed methods, or `None` for any method
+    methods: Option<Vec<Method>>,
+    /// Allowed request headers, or `None` for any header
+    headers: Option<Vec<HeaderName>>,
+    /// Value of `Access-Control-Expose-Headers`, if any headers are exposed
+    exposed_headers: Option<HeaderValue>,
+    /// Whether requests may carry credentials
+    allow_credentials: bool,
+    /// Seconds browsers may cache a preflight response
+    max_age: Option<u64>,
+}
+
+impl CorsPolicy {
+    /// Build the policy configured in `cors`, if any
+    pub fn from_config(config: &Config) -> Result<Option<Self>, CorsError> {
+        match &config.cors {
+            Some(cors_config) => Self::new(cors_config).map(Some),
+            None => Ok(None),
+        }
+    }
+    
+    /// Build a policy from its configuration
+    pub fn new(cors_config: &CorsConfig) -> Result<Self, CorsError> {
+        let invalid = |detail: String| CorsError::ConfigurationError(detail);
+        let allow_credentials = cors_config.allow_credentials.unwrap_or(false);
+        
+        let origins = if cors_config.allowed_origins.iter().any(|origin| origin == "*") {
+            // Reflecting any origin with credentials would let every site act as the user
+            if allow_credentials {
+                return Err(invalid("allow_credentials cannot be combined with allowed_origins \"*\"".to_string()));
+            }
+            None
+        } else {
+            let patterns = cors_config
+                .allowed_origins
+                .iter()
+                .map(|origin| {
+                    // A wildcard stands for part of the host or port, never a path
+                    let pattern = origin.split('*').map(regex::escape).collect::<Vec<_>>().join("[^/]*");
+                    Regex::new(&format!("(?i)^{}$", pattern))
+                        .map_err(|e| invalid(format!("invalid origin '{}': {}", origin, e)))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Some(patterns)
+        };
+        
+        let methods = match &cors_config.allowed_methods {
+            Some(methods) if methods.iter().any(|method| method == "*") => None,
+            Some(methods) => Some(
+                methods
+                    .iter()
+                    .map(|method| {
+                        Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                            .map_err(|_| invalid(format!("invalid method '{}'", method)))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            None => Some(vec![Method::GET, Method::HEAD, Method::POST]),
+        };
+        
+        let headers = match &cors_config.allowed_headers {
+            Some(headers) if headers.iter().any(|header| header == "*") => None,
+            headers => Some(
+                headers
+                    .iter()
+                    .flatten()
+                    .map(|header| {
+                        HeaderName::from_bytes(header.as_bytes())
+                            .map_err(|_| invalid(format!("invalid header name '{}'", header)))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+        };
+        
+        let exposed_headers = match cors_config.exposed_headers.as_deref() {
+            Some(exposed) if !exposed.is_empty() => Some(
+                HeaderValue::from_str(&exposed.join(", "))
+                    .map_err(|_| invalid(format!("invalid exposed headers '{}'", exposed.join(", "))))?,
+            ),
+            _ => None,
+        };
+        
+        let paths = cors_config
+            .paths
+            .clone()
+            .unwrap_or_else(|| vec!["/*".to_string()])
+            .iter()
+            .map(|path| wildcard_regex(path))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| invalid(e.to_string()))?;
+        
+        Ok(CorsPolicy {
+            paths,
+            origins,
+            methods,
+            headers,
+            exposed_headers,
+            allow_credentials,
+            max_age: cors_config.max_age,
+        })
+    }
+    
+    /// Check if the policy covers a request path
+    pub fn covers(&self, path: &str) -> bool {
+        self.paths.iter().any(|pattern| pattern.is_match(path))
+    }
+    
+    /// Check if a request is a CORS preflight rather than an `OPTIONS` request of its own
+    pub fn is_preflight(req: &Request<Body>) -> bool {
+        req.method() == Method::OPTIONS
+            && req.headers().contains_key(ORIGIN)
+            && req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+    }
+    
+    /// Get the `Access-Control-Allow-Origin` value for a request from an allowed origin
+    pub fn allowed_origin(&self, req: &Request<Body>) -> Option<HeaderValue> {
+        let origin = req.headers().get(ORIGIN)?;
+        match &self.origins {
+            None => Some(HeaderValue::from_static("*")),
+            Some(patterns) => {
+                let origin_str = origin.to_str().ok()?;
+                patterns.iter().any(|pattern| pattern.is_match(origin_str)).then(|| origin.clone())
+            }
+        }
+    }
+    
+    /// Answer a preflight request.
+    ///
+    /// A rejected preflight still succeeds but carries no CORS headers, so the
+    /// browser refuses the actual request without learning why.
+    pub fn preflight_response(&self, req: &Request<Body>) -> Response<Body> {
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::NO_CONTENT;
+        let headers = response.headers_mut();
+        headers.append(VARY, HeaderValue::from_static("Origin"));
+        headers.append(VARY, HeaderValue::from_static("Access-Control-Request-Method"));
+        headers.append(VARY, HeaderValue::from_static("Access-Control-Request-Headers"));
+        
+        let Some(origin) = self.allowed_origin(req) else {
+            debug!("CORS preflight from disallowed origin {:?}", req.headers().get(ORIGIN));
+            return response;
+        };
+        
+        let requested_method = req.headers().get(ACCESS_CONTROL_REQUEST_METHOD).cloned().unwrap_or_else(|| HeaderValue::from_static(""));
+        let method_allowed = match (&self.methods, Method::from_bytes(requested_method.as_bytes())) {
+            (None, Ok(_)) => true,
+            (Some(methods), Ok(method)) => methods.contains(&method),
+            (_, Err(_)) => false,
+        };
+        if !method_allowed {
+            debug!("CORS preflight for disallowed method {:?}", requested_method);
+            return response;
+        }
+        
+        let requested_headers = req.headers().get(ACCESS_CONTROL_REQUEST_HEADERS).cloned();
+        if let (Some(allowed), Some(requested)) = (&self.headers, &requested_headers) {
+            let requested_str = requested.to_str().unwrap_or_default();
+            let disallowed = requested_str
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .find(|name| !allowed.iter().any(|header| header.as_str().eq_ignore_ascii_case(name)));
+            if let Some(name) = disallowed {
+                debug!("CORS preflight for disallowed header {}", name);
+                return response;
+            }
+        }
+        
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        if self.allow_credentials {
+            headers.insert(ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+        }
+        
+        // Any method or header is granted by naming the requested ones, since "*" means nothing with credentials
+        let allow_methods = match &self.methods {
+            Some(methods) => {
+                let list = methods.iter().map(Method::as_str).collect::<Vec<_>>().join(", ");
+                HeaderValue::from_str(&list).ok()
+            }
+            None => Some(requested_method),
+        };
+        if let Some(allow_methods) = allow_methods {
+            headers.insert(ACCESS_CONTROL_ALLOW_METHODS, allow_methods);
+        }
+        let allow_headers = match &self.headers {
+            Some(allowed) if !allowed.is_empty() => {
+                let list = allowed.iter().map(HeaderName::as_str).collect::<Vec<_>>().join(", ");
+                HeaderValue::from_str(&list).ok()
+            }
+            Some(_) => None,
+            None => requested_headers,
+        };
+        if let Some(allow_headers) = allow_headers {
+            headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, allow_headers);
+        }
+        if let Some(max_age) = self.max_age {
+            headers.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from(max_age));
+        }
+        
+        response
+    }
+    
+    /// Add the CORS headers of an actual response, given the request's allowed origin if any
+    pub fn add_response_headers(&self, origin: Option<HeaderValue>, headers: &mut HeaderMap) {
+        // The headers depend on the Origin header whenever only some origins are allowed
+        if self.origins.is_some() {
+            headers.append(VARY, HeaderValue::from_static("Origin"));
+        }
+        
+        let Some(origin) = origin else {
+            return;
+        };
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        if self.allow_credentials {
+            headers.insert(ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+        }
+        if let Some(exposed) = &self.exposed_headers {
+            headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, exposed.clone());
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use async_trait::async_trait;
+use hyper::{Body, Request, Response, StatusCode};
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, error};
+
+use crate::security::auth::{constant_time_eq, AuthError, AuthMethod, Authenticator};
+
+/// Default time a nonce stays valid, in seconds
+pub const DEFAULT_NONCE_LIFETIME: u64 = 300;
+
+/// Number of tracked nonces above which expired ones are dropped
+const NONCE_SWEEP_THRESHOLD: usize = 1024;
+
+/// Hash algorithms of Digest authentication (RFC 7616)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestAlgorithm {
+    Md5,
+    Sha256,
+}
+
+impl DigestAlgorithm {
+    /// Name of the algorithm in challenges and credentials
+    pub fn name(&self) -> &'static str {
+        match self {
+            DigestAlgorithm::Md5 => "MD5",
+            DigestAlgorithm::Sha256 => "SHA-256",
+        }
+    }
+    
+    /// Hash data and return lowercase hex
+    fn hash(&self, data: &str) -> String {
+        match self {
+            DigestAlgorithm::Md5 => hex(&Md5::digest(data.as_bytes())),
+            DigestAlgorithm::Sha256 => hex(&Sha256::digest(data.as_bytes())),
+        }
+    }
+}
+
+/// Digest authenticator using username/password, for a single algorithm
+///
+/// Nonces are stateless: a timestamp signed with a per-process secret. Nonce
+/// counts are tracked while a nonce is valid to refuse replayed requests.
+pub struct DigestAuthenticator {
+    /// Realm for digest auth
+    realm: String,
+    /// Hash algorithm
+    algorithm: DigestAlgorithm,
+    /// Map of username to password
+    credentials: HashMap<String, String>,
+    /// Secret signing the nonces
+    secret: String,
+    /// Opaque value clients must echo back
+    opaque: String,
+    /// How long a nonce stays valid
+    nonce_lifetime: Duration,
+    /// Highest nonce count seen per nonce
+    nonce_counts: Mutex<HashMap<String, u32>>,
+}
+
+impl DigestAuthenticator {
+    /// Create a new digest authenticator
+    pub fn new(realm: &str, algorithm: DigestAlgorithm, nonce_lifetime: Duration) -> Result<Self, AuthError> {
+        Ok(DigestAuthenticator {
+            realm: realm.to_string(),
+            algorithm,
+            credentials: HashMap::new(),
+            secret: random_hex()?,
+            opaque: random_hex()?,
+            nonce_lifetime,
+            nonce_counts: Mutex::new(HashMap::new()),
+        })
+    }
+    
+    /// Add a user with password
+    pub fn add_user(&mut self, username: &str, password: &str) {
+        self.credentials.insert(username.to_string(), password.to_string());
+    }
+    
+    /// Create a nonce for the current time
+    fn new_nonce(&self) -> String {
+        self.sign_nonce(unix_time())
+    }
+    
+    /// Create the nonce of a timestamp
+    fn sign_nonce(&self, timestamp: u64) -> String {
+        let signature = self.algorithm.hash(&format!("{}:{}", timestamp, self.secret));
+        format!("{:x}.{}", timestamp, signature)
+    }
+    
+    /// Check a nonce's signature and return its timestamp
+    fn nonce_timestamp(&self, nonce: &str) -> Option<u64> {
+        let (timestamp, _) = nonce.split_once('.')?;
+        let timestamp = u64::from_str_radix(timestamp, 16).ok()?;
+        (self.sign_nonce(timestamp) == nonce).then_some(timestamp)
+    }
+    
+    /// Record a nonce count, refusing counts that do not increase
+    fn record_nonce_count(&self, nonce: &str, count: u32, now: u64) -> bool {
+        let mut counts = self.nonce_counts.lock().unwrap_or_else(|e| e.into_inner());
+        
+        if counts.len() >= NONCE_SWEEP_THRESHOLD {
+            let lifetime = self.nonce_lifetime.as_secs();
+            counts.retain(|nonce, _| {
+                self.nonce_timestamp(nonce).is_some_and(|timestamp| now.saturating_sub(timestamp) <= lifetime)
+            });
+        }
+        
+        match counts.get_mut(nonce) {
+            Some(last) if *last >= count => false,
+            Some(last) => {
+                *last = count;
+                true
+            }
+            None => {
+                counts.insert(nonce.to_string(), count);
+                true
+            }
+        }
+    }
+    
+    /// Build a challenge, flagging a stale nonce if the credentials were otherwise valid
+    fn build_challenge(&self, stale: bool) -> String {
+        format!(
+            "Digest realm=\"{}\", qop=\"auth\", algorithm={}, nonce=\"{}\", opaque=\"{}\"{}",
+            self.realm,
+            self.algorithm.name(),
+            self.new_nonce(),
+            self.opaque,
+            if stale { ", stale=true" } else { "" }
+        )
+    }
+}
+
+#[async_trait]
+impl Authenticator for DigestAuthenticator {
+    fn method(&self) -> AuthMethod {
+        AuthMethod::Digest
+    }
+    
+    async fn authenticate(&self, req: &Request<Body>) -> Result<bool, AuthError> {
+        let auth_header = match req.headers().get("authorization") {
+            Some(value) => value.to_str().map_err(|_| AuthError::InvalidCredentials)?,
+            None => return Err(AuthError::MissingCredentials),
+        };
+        
+        let params = match auth_header.strip_prefix("Digest ") {
+            Some(params) => parse_params(params),
+            None => return Err(AuthError::InvalidCredentials),
+        };
+        let param = |name: &str| params.get(name).map(String::as_str).ok_or(AuthError::InvalidCredentials);
+        
+        // Each authenticator answers for its own algorithm; MD5 is the default
+        let algorithm = params.get("algorithm").map(String::as_str).unwrap_or("MD5");
+        if !algorithm.eq_ignore_ascii_case(self.algorithm.name()) {
+            return Err(AuthError::InvalidCredentials);
+        }
+        
+        let username = param("username")?;
+        let nonce = param("nonce")?;
+        let uri = param("uri")?;
+        let cnonce = param("cnonce")?;
+        let nc = param("nc")?;
+        let response = param("response")?;
+        
+        if param("realm")? != self.realm || param("qop")? != "auth" || param("opaque")? != self.opaque {
+            return Err(AuthError::InvalidCredentials);
+        }
+        
+        // The credentials must be for this request's target
+        let target = req.uri().path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+        if uri != target {
+            debug!("Digest uri {} does not match request target {}", uri, target);
+            return Err(AuthError::InvalidCredentials);
+        }
+        
+        let Some(password) = self.credentials.get(username) else {
+            error!("Digest authentication failed for unknown user: {}", username);
+            return Err(AuthError::InvalidCredentials);
+        };
+        
+        let ha1 = self.algorithm.hash(&format!("{}:{}:{}", username, self.realm, password));
+        let ha2 = self.algorithm.hash(&format!("{}:{}", req.method(), uri));
+        let expected = self.algorithm.hash(&format!("{}:{}:{}:{}:auth:{}", ha1, nonce, nc, cnonce, ha2));
+        
+        if !constant_time_eq(expected.as_bytes(), response.as_bytes()) {
+            error!("Digest authentication failed for user: {}", username);
+            return Err(AuthError::InvalidCredentials);
+        }
+        
+        // Only a correct response can learn that its nonce has expired
+        let now = unix_time();
+        let timestamp = self.nonce_timestamp(nonce).ok_or(AuthError::InvalidCredentials)?;
+        if now.saturating_sub(timestamp) > self.nonce_lifetime.as_secs() {
+            debug!("Stale digest nonce for user: {}", username);
+            return Err(AuthError::StaleNonce);
+        }
+        
+        let count = u32::from_str_radix(nc, 16).map_err(|_| AuthError::InvalidCredentials)?;
+        if !self.record_nonce_count(nonce, count, now) {
+            error!("Replayed digest nonce count for user: {}", username);
+            return Err(AuthError::InvalidCredentials);
+        }
+        
+        debug!("Digest authentication successful for user: {}", username);
+        Ok(true)
+    }
+    
+    fn challenge(&self) -> String {
+        self.build_challenge(false)
+    }
+    
+    fn challenge_after(&self, error: &AuthError) -> String {
+        self.build_challenge(matches!(error, AuthError::StaleNonce))
+    }
+    
+    fn challenge_response(&self) -> Response<Body> {
+        Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .header("WWW-Authenticate", self.challenge())
+            .body(Body::from("401 Unauthorized: Authentication required"))
+            .unwrap()
+    }
+}
+
+/// Parse a comma-separated list of `name=value` or `name="quoted value"` parameters
+fn parse_params(input: &str) -> HashMap<String, String> {
+    let mut params = HashMap::new();
+    let mut rest = input.trim();
+    
+    while !rest.is_empty() {
+        let Some((name, after)) = rest.split_once('=') else {
+            break;
+        };
+        let name = name.trim().trim_start_matches(',').trim().to_ascii_lowercase();
+        let after = after.trim_start();
+        
+        let (value, remaining) = if let Some(quoted) = after.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            let mut end = quoted.len();
+            while let Some((index, c)) = chars.next() {
+                match c {
+                    '\\' => {
+                        if let Some((_, escaped)) = chars.next() {
+                            value.push(escaped);
+                        }
+                    }
+                    '"' => {
+                        end = index + 1;
+                        break;
+                    }
+                    _ => value.push(c),
+                }
+            }
+            (value, &quoted[end..])
+        } else {
+            let end = after.find(',').unwrap_or(after.len());
+            (after[..end].trim().to_string(), &after[end..])
+        };
+        
+        params.insert(name, value);
+        rest = remaining.trim_start().trim_start_matches(',').trim_start();
+    }
+    
+    params
+}
+
+/// Encode bytes as lowercase hex
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut out, byte| {
+        let _ = write!(out, "{:02x}", byte);
+        out
+    })
+}
+
+/// Generate 16 random bytes as hex
+fn random_hex() -> Result<String, AuthError> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| AuthError::ConfigurationError(format!("cannot generate random secret: {}", e)))?;
+    Ok(hex(&bytes))
+}
+
+/// Current time in seconds since the epoch
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
@@ -0,0 +1,98 @@
+use regex::Regex;
+use std::error::Error;
+use std::fmt;
+
+use crate::core::config::StaticFilesConfig;
+
+/// Error types for exclusion rules
+#[derive(Debug)]
+pub enum ExclusionError {
+    InvalidPattern(String, regex::Error),
+}
+
+impl fmt::Display for ExclusionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExclusionError::InvalidPattern(pattern, e) => write!(f, "invalid exclusion pattern '{}': {}", pattern, e),
+        }
+    }
+}
+
+impl Error for ExclusionError {}
+
+/// Rules hiding files from static serving and directory listings.
+///
+/// Patterns are globs: `*` and `?` stay within a path segment, `**` crosses
+/// segments. A pattern without a slash matches the name of any file or
+/// directory at any depth (`*.bak`), a trailing slash limits it to
+/// directories (`.git/`), and any other pattern is matched against the path
+/// from the root (`private/*.key`), excluding everything below a match.
+#[derive(Debug, Default)]
+pub struct ExclusionRules {
+    /// Whether names starting with a dot are hidden
+    hidden: bool,
+    /// Patterns matched against the name of every path segment
+    names: Vec<Regex>,
+    /// Patterns matched against the names of directories
+    directories: Vec<Regex>,
+    /// Patterns matched against the path from the root and its ancestors
+    paths: Vec<Regex>,
+}
+
+impl ExclusionRules {
+    /// Build the rules configured in `static_files`
+    pub fn from_config(config: &StaticFilesConfig) -> Result<Self, ExclusionError> {
+        let mut rules = ExclusionRules {
+            hidden: config.exclude_hidden.unwrap_or(false),
+            ..Default::default()
+        };
+        
+        for pattern in config.exclude.iter().flatten() {
+            let invalid = |e| ExclusionError::InvalidPattern(pattern.clone(), e);
+            let trimmed = pattern.trim_start_matches('/');
+            match trimmed.strip_suffix('/') {
+                Some(directory) if !directory.contains('/') => rules.directories.push(glob_regex(directory).map_err(invalid)?),
+                Some(directory) => rules.paths.push(glob_regex(directory).map_err(invalid)?),
+                None if !trimmed.contains('/') => rules.names.push(glob_regex(trimmed).map_err(invalid)?),
+                None => rules.paths.push(glob_regex(trimmed).map_err(invalid)?),
+            }
+        }
+        
+        Ok(rules)
+    }
+    
+    /// Check if a path relative to the root, such as `docs/a.txt`, is excluded.
+    ///
+    /// `is_dir` tells whether the last segment is a directory; every earlier one is.
+    pub fn excludes(&self, relative: &str, is_dir: bool) -> bool {
+        let segments = relative.split('/').filter(|segment| !segment.is_empty()).collect::<Vec<_>>();
+        
+        segments.iter().enumerate().any(|(i, segment)| {
+            let segment_is_dir = is_dir || i + 1 < segments.len();
+            // `.well-known` holds files meant for everyone, such as security.txt
+            (self.hidden && segment.starts_with('.') && *segment != ".well-known")
+                || self.names.iter().any(|pattern| pattern.is_match(segment))
+                || (segment_is_dir && self.directories.iter().any(|pattern| pattern.is_match(segment)))
+                || self.paths.iter().any(|pattern| pattern.is_match(&segments[..=i].join("/")))
+        })
+    }
+}
+
+/// Compile a glob into an anchored regular expression
+fn glob_regex(glob: &str) -> Result<Regex, regex::Error> {
+    let mut pattern = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                pattern.push_str(".*");
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern)
+}
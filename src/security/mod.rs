@@ -1,2 +1,9 @@
+pub mod acme;
 pub mod auth;
 pub mod acl;
+pub mod cors;
+pub mod digest;
+pub mod exclusion;
+pub mod ocsp;
+pub mod rate_limit;
+pub mod tls;
@@ -0,0 +1,129 @@
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Method, Request};
+use rustls::Certificate;
+use std::error::Error;
+use std::fmt;
+use x509_parser::certificate::X509Certificate;
+use x509_parser::extensions::{GeneralName, ParsedExtension};
+use x509_parser::oid_registry::OID_PKIX_ACCESS_DESCRIPTOR_OCSP;
+
+use crate::handlers::proxy::UpstreamClient;
+
+/// DER encoding of the SHA-1 object identifier (1.3.14.3.2.26)
+const SHA1_OID: [u8; 5] = [0x2B, 0x0E, 0x03, 0x02, 0x1A];
+
+/// Error types for fetching OCSP responses
+#[derive(Debug)]
+pub enum OcspError {
+    /// The certificate chain cannot be used to build a request
+    Certificate(String),
+    /// The certificate names no OCSP responder
+    NoResponder,
+    /// The responder could not be reached
+    Http(String),
+    /// The responder did not answer with a successful response
+    Unsuccessful(String),
+}
+
+impl fmt::Display for OcspError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OcspError::Certificate(message) => write!(f, "Cannot build OCSP request: {}", message),
+            OcspError::NoResponder => write!(f, "Certificate names no OCSP responder"),
+            OcspError::Http(message) => write!(f, "OCSP request failed: {}", message),
+            OcspError::Unsuccessful(message) => write!(f, "OCSP responder refused the request: {}", message),
+        }
+    }
+}
+
+impl Error for OcspError {}
+
+/// Fetch a response to staple for the leaf of a certificate chain.
+///
+/// The issuer must follow the leaf in the chain, as it does in the files
+/// CAs hand out. Only the response status is checked; clients verify the
+/// signed response themselves.
+pub async fn fetch_response(chain: &[Certificate], client: &UpstreamClient) -> Result<Vec<u8>, OcspError> {
+    let leaf = parse_certificate(chain.first().ok_or_else(|| OcspError::Certificate("empty chain".to_string()))?)?;
+    let issuer = parse_certificate(chain.get(1).ok_or_else(|| OcspError::Certificate("no issuer certificate in chain".to_string()))?)?;
+    let responder = responder_url(&leaf).ok_or(OcspError::NoResponder)?;
+    
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(responder.as_str())
+        .header(CONTENT_TYPE, "application/ocsp-request")
+        .body(Body::from(encode_request(&leaf, &issuer)))
+        .map_err(|e| OcspError::Http(e.to_string()))?;
+    let response = client.request(request).await.map_err(|e| OcspError::Http(format!("{}: {}", responder, e)))?;
+    if !response.status().is_success() {
+        return Err(OcspError::Http(format!("{} answered {}", responder, response.status())));
+    }
+    
+    let body = hyper::body::to_bytes(response.into_body()).await.map_err(|e| OcspError::Http(e.to_string()))?;
+    match response_status(&body) {
+        Some(0) => Ok(body.to_vec()),
+        Some(status) => Err(OcspError::Unsuccessful(format!("status {}", status))),
+        None => Err(OcspError::Unsuccessful("malformed response".to_string())),
+    }
+}
+
+/// Parse a DER certificate
+fn parse_certificate(cert: &Certificate) -> Result<X509Certificate<'_>, OcspError> {
+    x509_parser::parse_x509_certificate(&cert.0)
+        .map(|(_, parsed)| parsed)
+        .map_err(|e| OcspError::Certificate(e.to_string()))
+}
+
+/// URL of the OCSP responder named in a certificate's authority information access
+fn responder_url(cert: &X509Certificate) -> Option<String> {
+    cert.extensions().iter().find_map(|extension| match extension.parsed_extension() {
+        ParsedExtension::AuthorityInfoAccess(access) => access.accessdescs.iter().find_map(|description| {
+            match &description.access_location {
+                GeneralName::URI(uri) if description.access_method == OID_PKIX_ACCESS_DESCRIPTOR_OCSP => Some(uri.to_string()),
+                _ => None,
+            }
+        }),
+        _ => None,
+    })
+}
+
+/// Encode an OCSP request (RFC 6960) for one certificate, identified by SHA-1 hashes of its issuer
+fn encode_request(leaf: &X509Certificate, issuer: &X509Certificate) -> Vec<u8> {
+    let algorithm = der(0x30, &[der(0x06, &SHA1_OID), vec![0x05, 0x00]].concat());
+    let name_hash = sha1_smol::Sha1::from(leaf.issuer().as_raw()).digest().bytes();
+    let key_hash = sha1_smol::Sha1::from(&issuer.public_key().subject_public_key.data).digest().bytes();
+    let cert_id = der(
+        0x30,
+        &[algorithm, der(0x04, &name_hash), der(0x04, &key_hash), der(0x02, leaf.raw_serial())].concat(),
+    );
+    
+    // OCSPRequest { TBSRequest { requestList { Request { CertID } } } }
+    der(0x30, &der(0x30, &der(0x30, &der(0x30, &cert_id))))
+}
+
+/// Encode a DER element with a definite length
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut element = vec![tag];
+    match content.len() {
+        len if len < 0x80 => element.push(len as u8),
+        len if len <= 0xFF => element.extend_from_slice(&[0x81, len as u8]),
+        len => element.extend_from_slice(&[0x82, (len >> 8) as u8, len as u8]),
+    }
+    element.extend_from_slice(content);
+    element
+}
+
+/// Read the responseStatus of an OCSPResponse, the first element of its outer sequence
+fn response_status(response: &[u8]) -> Option<u8> {
+    if response.first() != Some(&0x30) {
+        return None;
+    }
+    
+    // Skip the sequence length, in short or long form
+    let length = *response.get(1)?;
+    let offset = if length & 0x80 == 0 { 2 } else { 2 + (length & 0x7F) as usize };
+    match response.get(offset..offset + 3)? {
+        [0x0A, 0x01, status] => Some(*status),
+        _ => None,
+    }
+}
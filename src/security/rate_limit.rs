@@ -0,0 +1,157 @@
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+use crate::core::config::Config;
+use crate::core::error::HttpError;
+use crate::network::http::path::canonical_path;
+use crate::routing::router::wildcard_regex;
+use crate::utils::metrics::Metrics;
+
+/// How often idle client buckets are dropped
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Token bucket of a single client
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    /// Tokens currently available
+    tokens: f64,
+    /// When the bucket was last refilled
+    updated: Instant,
+}
+
+/// Buckets of all clients of a rate limit
+#[derive(Debug)]
+struct ClientBuckets {
+    /// Buckets by client address
+    buckets: HashMap<IpAddr, TokenBucket>,
+    /// When idle buckets were last dropped
+    last_sweep: Instant,
+}
+
+/// Token-bucket limit applied to request paths matching a pattern
+#[derive(Debug)]
+struct RateLimit {
+    /// Path pattern as configured
+    pattern: String,
+    /// Compiled pattern
+    regex: Regex,
+    /// Maximum number of tokens a client can accumulate
+    burst: f64,
+    /// Tokens added per second
+    rate: f64,
+    /// Client buckets for this limit
+    clients: Mutex<ClientBuckets>,
+}
+
+impl RateLimit {
+    /// Take a token for a client, returning the seconds to wait when none is available
+    fn take(&self, client: IpAddr, now: Instant) -> Result<(), u64> {
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        
+        // Forget clients whose buckets have refilled completely; they are indistinguishable from new ones
+        if now.duration_since(clients.last_sweep) >= SWEEP_INTERVAL {
+            let (burst, rate) = (self.burst, self.rate);
+            clients.buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate < burst
+            });
+            clients.last_sweep = now;
+        }
+        
+        let bucket = clients.buckets.entry(client).or_insert(TokenBucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.updated = now;
+        
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - bucket.tokens) / self.rate).ceil().max(1.0) as u64)
+        }
+    }
+}
+
+/// Per-client request rate limits shared by all connections
+#[derive(Debug, Default)]
+pub struct RateLimits {
+    /// Limits in configuration order; the first matching pattern applies
+    limits: Vec<RateLimit>,
+}
+
+impl RateLimits {
+    /// Build the limits configured in `rate_limits`
+    pub fn from_config(config: &Config) -> Result<Self, regex::Error> {
+        let mut limits = Vec::new();
+        let now = Instant::now();
+        
+        for limit_config in config.rate_limits.iter().flatten() {
+            let path = limit_config.path.as_deref().unwrap_or("/*");
+            let regex = wildcard_regex(path)?;
+            let burst = limit_config.burst.max(1) as f64;
+            // Keep refilling, however slowly, so Retry-After stays finite
+            let rate = limit_config.rate.max(0.001);
+            
+            debug!("Limiting {} to {} requests/s per client (burst {})", path, rate, burst);
+            limits.push(RateLimit {
+                pattern: path.to_string(),
+                regex,
+                burst,
+                rate,
+                clients: Mutex::new(ClientBuckets {
+                    buckets: HashMap::new(),
+                    last_sweep: now,
+                }),
+            });
+        }
+        
+        Ok(RateLimits { limits })
+    }
+    
+    /// Check whether a client may make a request to a path, consuming a token if so.
+    ///
+    /// Limits are picked by the canonical path, so encoded or dotted variants of a
+    /// limited path count against the same limit.
+    pub fn check(&self, client: IpAddr, path: &str, metrics: &Metrics) -> Result<(), HttpError> {
+        let canonical = canonical_path(path).unwrap_or(Cow::Borrowed(path));
+        let Some(limit) = self.limits.iter().find(|limit| limit.regex.is_match(&canonical)) else {
+            return Ok(());
+        };
+        
+        limit.take(client, Instant::now()).map_err(|retry_after| {
+            warn!("Rate limit for {} exceeded by {}, rejecting {}", limit.pattern, client, path);
+            metrics.record_rate_limited();
+            HttpError::TooManyRequests { retry_after }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn encoded_variants_share_the_limit_of_their_path() {
+        let limit = toml::from_str("path = \"/api/*\"\nburst = 3\nrate = 0.001\n").unwrap();
+        let config = Config { rate_limits: Some(vec![limit]), ..Config::default() };
+        let limits = RateLimits::from_config(&config).unwrap();
+        let metrics = Metrics::new();
+        let client: IpAddr = "192.0.2.1".parse().unwrap();
+        
+        for path in ["/api/items", "/%61pi/items", "//api/./items"] {
+            assert!(limits.check(client, path, &metrics).is_ok(), "{} was limited early", path);
+        }
+        for path in ["/api/items", "/%61pi/items", "/./api/items"] {
+            assert!(limits.check(client, path, &metrics).is_err(), "{} was not limited", path);
+        }
+        assert!(limits.check(client, "/static/app.js", &metrics).is_ok());
+    }
+}
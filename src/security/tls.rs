@@ -0,0 +1,472 @@
+use hyper::header::HeaderValue;
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::{self, CertifiedKey};
+use rustls::{Certificate, PrivateKey, ServerConfig, SupportedCipherSuite, SupportedProtocolVersion};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+use crate::core::config::{TlsConfig, VirtualHostConfig};
+use crate::handlers::proxy::{build_client, UpstreamClient};
+use crate::routing::vhost::VirtualHost;
+use crate::security::acme::{Acme, AcmeChallenge, ACME_TLS_ALPN};
+use crate::security::ocsp;
+
+/// Seconds between checks of the certificate files for changes, unless configured
+const DEFAULT_RELOAD_INTERVAL: u64 = 60;
+
+/// Age certificate files must reach after a change before they are reloaded
+const RELOAD_SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// Interval between refreshes of stapled OCSP responses
+const OCSP_REFRESH: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Delay before retrying failed OCSP fetches
+const OCSP_RETRY: Duration = Duration::from_secs(5 * 60);
+
+/// Error types for building a TLS server configuration
+#[derive(Debug)]
+pub enum TlsError {
+    /// Certificate or key file is not configured
+    MissingFile(&'static str),
+    /// A certificate or key file could not be read
+    Io(String, std::io::Error),
+    /// No usable certificate was found
+    NoCertificate(String),
+    /// No usable private key was found
+    NoPrivateKey(String),
+    /// A configured cipher suite name is unknown
+    UnknownCipherSuite(String),
+    /// The cipher policy leaves no suite for the allowed protocol versions
+    NoCipherSuites,
+    /// rustls rejected the configuration
+    Rustls(rustls::Error),
+    /// The private key type is not supported
+    UnsupportedKey(String),
+    /// A virtual host pattern is invalid
+    InvalidHost(String),
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsError::MissingFile(name) => write!(f, "TLS {} is not configured", name),
+            TlsError::Io(path, e) => write!(f, "Failed to read {}: {}", path, e),
+            TlsError::NoCertificate(path) => write!(f, "No certificate found in {}", path),
+            TlsError::NoPrivateKey(path) => write!(f, "No private key found in {}", path),
+            TlsError::UnknownCipherSuite(name) => write!(f, "Unknown cipher suite: {}", name),
+            TlsError::NoCipherSuites => write!(f, "No cipher suites enabled for the allowed TLS versions"),
+            TlsError::Rustls(e) => write!(f, "Invalid TLS configuration: {}", e),
+            TlsError::UnsupportedKey(path) => write!(f, "Unsupported private key type in {}", path),
+            TlsError::InvalidHost(host) => write!(f, "Invalid virtual host pattern for TLS: {}", host),
+        }
+    }
+}
+
+impl Error for TlsError {}
+
+/// Minimum accepted TLS protocol version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub enum TlsVersion {
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+/// Cipher suite selection policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CipherPolicy {
+    /// The rustls default suites
+    SafeDefault,
+    /// TLS 1.3 suites only
+    Modern,
+    /// The suites listed in `cipher_suites`
+    Custom,
+}
+
+/// Protocol versions allowed by a minimum version
+fn protocol_versions(min_version: TlsVersion) -> Vec<&'static SupportedProtocolVersion> {
+    match min_version {
+        TlsVersion::Tls12 => vec![&rustls::version::TLS13, &rustls::version::TLS12],
+        TlsVersion::Tls13 => vec![&rustls::version::TLS13],
+    }
+}
+
+/// Find a supported cipher suite by its IANA name (e.g. `TLS13_AES_256_GCM_SHA384`)
+fn find_cipher_suite(name: &str) -> Option<SupportedCipherSuite> {
+    rustls::ALL_CIPHER_SUITES
+        .iter()
+        .find(|suite| format!("{:?}", suite.suite()).eq_ignore_ascii_case(name.trim()))
+        .copied()
+}
+
+/// Select the cipher suites for a policy, keeping only those usable with the allowed versions
+pub fn select_cipher_suites(
+    policy: CipherPolicy,
+    names: &[String],
+    min_version: TlsVersion,
+) -> Result<Vec<SupportedCipherSuite>, TlsError> {
+    let candidates: Vec<SupportedCipherSuite> = match policy {
+        CipherPolicy::SafeDefault => rustls::DEFAULT_CIPHER_SUITES.to_vec(),
+        CipherPolicy::Modern => rustls::ALL_CIPHER_SUITES
+            .iter()
+            .filter(|suite| suite.version() == &rustls::version::TLS13)
+            .copied()
+            .collect(),
+        CipherPolicy::Custom => names
+            .iter()
+            .map(|name| find_cipher_suite(name).ok_or_else(|| TlsError::UnknownCipherSuite(name.clone())))
+            .collect::<Result<_, _>>()?,
+    };
+    
+    let versions = protocol_versions(min_version);
+    let suites: Vec<SupportedCipherSuite> = candidates
+        .into_iter()
+        .filter(|suite| versions.contains(&suite.version()))
+        .collect();
+    
+    if suites.is_empty() {
+        return Err(TlsError::NoCipherSuites);
+    }
+    
+    Ok(suites)
+}
+
+/// Load all certificates from a PEM file
+pub(crate) fn load_certificates(path: &str) -> Result<Vec<Certificate>, TlsError> {
+    let file = File::open(path).map_err(|e| TlsError::Io(path.to_string(), e))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .map_err(|e| TlsError::Io(path.to_string(), e))?;
+    
+    if certs.is_empty() {
+        return Err(TlsError::NoCertificate(path.to_string()));
+    }
+    
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+/// Load the first private key from a PEM file
+pub(crate) fn load_private_key(path: &str) -> Result<PrivateKey, TlsError> {
+    let file = File::open(path).map_err(|e| TlsError::Io(path.to_string(), e))?;
+    let items = rustls_pemfile::read_all(&mut BufReader::new(file))
+        .map_err(|e| TlsError::Io(path.to_string(), e))?;
+    
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| TlsError::NoPrivateKey(path.to_string()))
+}
+
+/// Load a certificate chain and its private key as a signing key
+fn load_certified_key(tls: &TlsConfig) -> Result<CertifiedKey, TlsError> {
+    let cert_file = tls.cert_file.as_deref().ok_or(TlsError::MissingFile("cert_file"))?;
+    let key_file = tls.key_file.as_deref().ok_or(TlsError::MissingFile("key_file"))?;
+    let certs = load_certificates(cert_file)?;
+    let key = load_private_key(key_file)?;
+    let signing_key = sign::any_supported_type(&key).map_err(|_| TlsError::UnsupportedKey(key_file.to_string()))?;
+    
+    Ok(CertifiedKey::new(certs, signing_key))
+}
+
+/// Certificates loaded from the configured files
+struct LoadedCertificates {
+    /// Certificates for virtual hosts with their own TLS configuration
+    vhosts: Vec<(VirtualHost, Arc<CertifiedKey>)>,
+    /// Certificate used when no virtual host matches
+    default: Option<Arc<CertifiedKey>>,
+    /// Modification times of the certificate and key files when they were loaded
+    modified: Vec<Option<SystemTime>>,
+}
+
+/// Certificate resolver choosing a virtual host's certificate by SNI.
+///
+/// Certificates are reloaded when their files change and can carry stapled
+/// OCSP responses, both without rebuilding the TLS configuration.
+pub struct SniResolver {
+    /// Global TLS configuration
+    tls: TlsConfig,
+    /// Virtual hosts, some with their own TLS configuration
+    vhost_configs: Vec<VirtualHostConfig>,
+    /// Currently served certificates
+    certificates: RwLock<LoadedCertificates>,
+    /// Certificates provisioned over ACME, taking precedence for their domains
+    acme: Option<Arc<Acme>>,
+}
+
+impl SniResolver {
+    /// Load the global and virtual host certificates.
+    ///
+    /// The global certificate may be omitted when every virtual host brings its
+    /// own or ACME provisions one.
+    pub fn from_config(tls: &TlsConfig, vhosts: &[VirtualHostConfig], acme: Option<Arc<Acme>>) -> Result<Self, TlsError> {
+        let resolver = SniResolver {
+            tls: tls.clone(),
+            vhost_configs: vhosts.to_vec(),
+            certificates: RwLock::new(LoadedCertificates {
+                vhosts: Vec::new(),
+                default: None,
+                modified: Vec::new(),
+            }),
+            acme,
+        };
+        
+        let certificates = resolver.load()?;
+        *resolver.certificates.write().unwrap_or_else(|e| e.into_inner()) = certificates;
+        Ok(resolver)
+    }
+    
+    /// Read every configured certificate and key
+    fn load(&self) -> Result<LoadedCertificates, TlsError> {
+        let modified = self.modification_times();
+        
+        let mut vhosts = Vec::new();
+        for vhost_config in &self.vhost_configs {
+            if let Some(vhost_tls) = vhost_config.tls.as_ref().filter(|vhost_tls| vhost_tls.enabled) {
+                let vhost = VirtualHost::new(&vhost_config.host, &vhost_config.root_dir)
+                    .map_err(|_| TlsError::InvalidHost(vhost_config.host.clone()))?;
+                info!("Loaded TLS certificate for virtual host {}", vhost_config.host);
+                vhosts.push((vhost, Arc::new(load_certified_key(vhost_tls)?)));
+            }
+        }
+        
+        let default = if self.tls.cert_file.is_none() && self.tls.key_file.is_none() && (!vhosts.is_empty() || self.acme.is_some()) {
+            None
+        } else {
+            Some(Arc::new(load_certified_key(&self.tls)?))
+        };
+        
+        Ok(LoadedCertificates { vhosts, default, modified })
+    }
+    
+    /// Paths of every configured certificate and key file
+    fn source_files(&self) -> impl Iterator<Item = &str> {
+        let vhost_tls = self.vhost_configs.iter().filter_map(|vhost| vhost.tls.as_ref().filter(|tls| tls.enabled));
+        std::iter::once(&self.tls)
+            .chain(vhost_tls)
+            .flat_map(|tls| [tls.cert_file.as_deref(), tls.key_file.as_deref()])
+            .flatten()
+    }
+    
+    /// Current modification times of the certificate and key files
+    fn modification_times(&self) -> Vec<Option<SystemTime>> {
+        self.source_files()
+            .map(|path| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok())
+            .collect()
+    }
+    
+    /// Reload the certificates if their files changed, returning whether they were replaced.
+    ///
+    /// Files modified within the last moments are left alone, so a certificate
+    /// and key written one after the other are picked up together. A failed
+    /// reload keeps serving the previous certificates.
+    pub fn reload_if_changed(&self) -> bool {
+        let modified = self.modification_times();
+        if modified == self.certificates.read().unwrap_or_else(|e| e.into_inner()).modified {
+            return false;
+        }
+        
+        let settled = modified.iter().flatten().all(|time| time.elapsed().is_ok_and(|age| age >= RELOAD_SETTLE_TIME));
+        if !settled {
+            debug!("TLS certificate files changed; waiting for writes to settle");
+            return false;
+        }
+        
+        match self.load() {
+            Ok(certificates) => {
+                *self.certificates.write().unwrap_or_else(|e| e.into_inner()) = certificates;
+                info!("Reloaded TLS certificates");
+                true
+            }
+            Err(e) => {
+                error!("Failed to reload TLS certificates, keeping the current ones: {}", e);
+                // Retry only once the files change again
+                self.certificates.write().unwrap_or_else(|e| e.into_inner()).modified = modified;
+                false
+            }
+        }
+    }
+    
+    /// Fetch OCSP responses for every certificate and staple them, returning whether all succeeded
+    pub async fn staple_ocsp(&self, client: &UpstreamClient) -> bool {
+        let keys: Vec<Arc<CertifiedKey>> = {
+            let certificates = self.certificates.read().unwrap_or_else(|e| e.into_inner());
+            certificates.vhosts.iter().map(|(_, key)| key).chain(&certificates.default).cloned().collect()
+        };
+        
+        let mut stapled = Vec::new();
+        let mut complete = true;
+        for key in keys {
+            match ocsp::fetch_response(&key.cert, client).await {
+                Ok(response) => stapled.push((key, response)),
+                Err(e) => {
+                    warn!("Not stapling OCSP response: {}", e);
+                    complete = false;
+                }
+            }
+        }
+        
+        // Swap in stapled copies, unless a reload replaced the certificates meanwhile
+        let mut certificates = self.certificates.write().unwrap_or_else(|e| e.into_inner());
+        let LoadedCertificates { vhosts, default, .. } = &mut *certificates;
+        for (key, response) in stapled {
+            let with_ocsp = Arc::new(CertifiedKey {
+                ocsp: Some(response),
+                ..CertifiedKey::clone(&key)
+            });
+            let slots = vhosts.iter_mut().map(|(_, slot)| slot).chain(default.as_mut());
+            for slot in slots.filter(|slot| Arc::ptr_eq(slot, &key)) {
+                *slot = Arc::clone(&with_ocsp);
+            }
+        }
+        debug!("Stapled OCSP responses for TLS certificates");
+        complete
+    }
+    
+    /// Reload changed certificates and refresh stapled OCSP responses in the background
+    pub fn spawn_maintenance(self: &Arc<Self>) -> Option<JoinHandle<()>> {
+        let reload_interval = self.tls.reload_interval.unwrap_or(DEFAULT_RELOAD_INTERVAL);
+        let stapling = self.tls.ocsp_stapling.unwrap_or(false);
+        if reload_interval == 0 && !stapling {
+            return None;
+        }
+        
+        let resolver = Arc::clone(self);
+        let tick = Duration::from_secs(if reload_interval == 0 { DEFAULT_RELOAD_INTERVAL } else { reload_interval });
+        Some(tokio::spawn(async move {
+            let client = build_client();
+            let mut next_staple = Instant::now();
+            loop {
+                let reloaded = reload_interval > 0 && resolver.reload_if_changed();
+                if stapling && (reloaded || Instant::now() >= next_staple) {
+                    let refresh = if resolver.staple_ocsp(&client).await { OCSP_REFRESH } else { OCSP_RETRY };
+                    next_staple = Instant::now() + refresh;
+                }
+                tokio::time::sleep(tick).await;
+            }
+        }))
+    }
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let server_name = client_hello.server_name().map(str::to_ascii_lowercase);
+        if let (Some(acme), Some(server_name)) = (&self.acme, &server_name) {
+            // TLS-ALPN-01 validation handshakes only ever see the validation certificate
+            if client_hello.alpn().is_some_and(|mut protocols| protocols.any(|protocol| protocol == ACME_TLS_ALPN)) {
+                return acme.challenge_certificate(server_name);
+            }
+            if let Some(key) = acme.certificate_for(server_name) {
+                return Some(key);
+            }
+        }
+        
+        let certificates = self.certificates.read().unwrap_or_else(|e| e.into_inner());
+        if let Some(server_name) = &server_name {
+            if let Some((vhost, key)) = certificates.vhosts.iter().find(|(vhost, _)| vhost.matches(server_name)) {
+                debug!("Using certificate of virtual host {} for {}", vhost.hostname(), server_name);
+                return Some(Arc::clone(key));
+            }
+        }
+        
+        if certificates.default.is_none() {
+            // Without a global certificate the handshake fails; say why
+            warn!("No TLS certificate for server name {:?}", server_name);
+        }
+        certificates.default.clone()
+    }
+}
+
+/// Build a rustls server configuration honouring the version floor and cipher policy.
+///
+/// Virtual hosts with TLS enabled are served their own certificate when clients
+/// name them via SNI; the global certificate is used otherwise.
+/// HTTP/2 is offered through ALPN when `http2` is set.
+pub fn build_server_config(
+    tls: &TlsConfig,
+    resolver: Arc<SniResolver>,
+    http2: bool,
+) -> Result<Arc<ServerConfig>, TlsError> {
+    let min_version = tls.min_version.unwrap_or(TlsVersion::Tls12);
+    let policy = tls.cipher_policy.unwrap_or(CipherPolicy::SafeDefault);
+    let suites = select_cipher_suites(policy, tls.cipher_suites.as_deref().unwrap_or_default(), min_version)?;
+    
+    // Validation handshakes negotiate their own protocol and are never served HTTP
+    let acme_alpn = resolver.acme.as_ref().is_some_and(|acme| acme.challenge() == AcmeChallenge::TlsAlpn01);
+    
+    debug!("TLS minimum version {:?}, {} cipher suites enabled", min_version, suites.len());
+    
+    let mut config = ServerConfig::builder()
+        .with_cipher_suites(&suites)
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(&protocol_versions(min_version))
+        .map_err(TlsError::Rustls)?
+        .with_no_client_auth()
+        .with_cert_resolver(resolver);
+    config.alpn_protocols = if http2 {
+        vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+    } else {
+        vec![b"http/1.1".to_vec()]
+    };
+    if acme_alpn {
+        config.alpn_protocols.push(ACME_TLS_ALPN.to_vec());
+    }
+    
+    Ok(Arc::new(config))
+}
+
+/// Strict-Transport-Security value of a TLS configuration, if HSTS is configured
+pub fn hsts_header(tls: &TlsConfig) -> Option<HeaderValue> {
+    let max_age = tls.hsts_max_age?;
+    let value = if tls.hsts_include_subdomains.unwrap_or(false) {
+        format!("max-age={}; includeSubDomains", max_age)
+    } else {
+        format!("max-age={}", max_age)
+    };
+    HeaderValue::from_str(&value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn modern_policy_keeps_only_tls13_suites() {
+        let suites = select_cipher_suites(CipherPolicy::Modern, &[], TlsVersion::Tls12).unwrap();
+        assert!(!suites.is_empty());
+        assert!(suites.iter().all(|suite| suite.version() == &rustls::version::TLS13));
+    }
+    
+    #[test]
+    fn custom_policy_rejects_unknown_and_unusable_selections() {
+        let names = vec!["TLS13_AES_256_GCM_SHA384".to_string()];
+        assert_eq!(select_cipher_suites(CipherPolicy::Custom, &names, TlsVersion::Tls13).unwrap().len(), 1);
+        
+        let unknown = vec!["TLS_RSA_WITH_RC4_128_MD5".to_string()];
+        assert!(matches!(
+            select_cipher_suites(CipherPolicy::Custom, &unknown, TlsVersion::Tls12),
+            Err(TlsError::UnknownCipherSuite(_)),
+        ));
+        
+        assert!(matches!(select_cipher_suites(CipherPolicy::Custom, &[], TlsVersion::Tls12), Err(TlsError::NoCipherSuites)));
+        
+        // TLS 1.2 suites are useless once 1.2 is below the floor
+        let tls12_only = vec!["TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256".to_string()];
+        assert!(matches!(
+            select_cipher_suites(CipherPolicy::Custom, &tls12_only, TlsVersion::Tls13),
+            Err(TlsError::NoCipherSuites),
+        ));
+    }
+}
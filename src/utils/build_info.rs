@@ -0,0 +1,32 @@
+use serde::Serialize;
+
+/// Crate version
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Short git commit hash of the build, or "unknown"
+pub const GIT_HASH: &str = env!("KASERVE_GIT_HASH");
+
+/// Build time as seconds since the Unix epoch
+pub const BUILD_TIME: &str = env!("KASERVE_BUILD_TIME");
+
+/// Build metadata of the running binary
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_hash: &'static str,
+    pub build_time: &'static str,
+}
+
+/// Get the build metadata of the running binary
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        version: VERSION,
+        git_hash: GIT_HASH,
+        build_time: BUILD_TIME,
+    }
+}
+
+/// Human-readable version line, as printed by `--version`
+pub fn version_string() -> String {
+    format!("kaserve {} ({}, built {})", VERSION, GIT_HASH, BUILD_TIME)
+}
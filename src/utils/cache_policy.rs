@@ -0,0 +1,99 @@
+use regex::Regex;
+use std::path::Path;
+use tracing::debug;
+
+use crate::core::config::{CacheRuleConfig, StaticFilesConfig};
+use crate::routing::router::wildcard_regex;
+
+/// Cache-Control directive applied to files matching a path pattern and/or extension
+#[derive(Debug)]
+struct CacheRule {
+    /// Compiled request path pattern, if the rule is restricted by path
+    path: Option<Regex>,
+    /// Lowercase file extensions, if the rule is restricted by extension
+    extensions: Vec<String>,
+    /// Cache-Control value sent for matching files
+    directive: String,
+}
+
+impl CacheRule {
+    /// Check whether the rule applies to a request path served from a file
+    fn matches(&self, req_path: &str, file_path: &Path) -> bool {
+        let path_matches = self.path.as_ref().is_none_or(|regex| regex.is_match(req_path));
+        let extension_matches = self.extensions.is_empty()
+            || file_path
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+                .is_some_and(|ext| self.extensions.contains(&ext));
+        
+        path_matches && extension_matches
+    }
+}
+
+/// Cache-Control policy for static files: the first matching rule wins,
+/// falling back to `static_files.cache_control`
+#[derive(Debug, Default)]
+pub struct CachePolicy {
+    /// Rules in configuration order
+    rules: Vec<CacheRule>,
+    /// Directive for files no rule matches
+    default: Option<String>,
+}
+
+impl CachePolicy {
+    /// Build the policy from `static_files.cache_control` and `static_files.cache_rules`
+    pub fn from_config(config: &StaticFilesConfig) -> Result<Self, regex::Error> {
+        let mut rules = Vec::new();
+        
+        for rule_config in config.cache_rules.iter().flatten() {
+            let path = match &rule_config.path {
+                Some(path) => Some(wildcard_regex(path)?),
+                None => None,
+            };
+            let extensions = rule_config
+                .extensions
+                .iter()
+                .flatten()
+                .map(|ext| ext.trim_start_matches('.').to_ascii_lowercase())
+                .collect();
+            let directive = directive(rule_config);
+            
+            debug!("Cache rule {:?} {:?}: {}", rule_config.path, rule_config.extensions, directive);
+            rules.push(CacheRule { path, extensions, directive });
+        }
+        
+        Ok(CachePolicy {
+            rules,
+            default: config.cache_control.clone().filter(|value| !value.is_empty()),
+        })
+    }
+    
+    /// Get the Cache-Control value for a request path served from a file
+    pub fn directive_for(&self, req_path: &str, file_path: &Path) -> Option<&str> {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(req_path, file_path))
+            .map(|rule| rule.directive.as_str())
+            .or(self.default.as_deref())
+    }
+}
+
+/// Compose the Cache-Control value of a rule
+fn directive(config: &CacheRuleConfig) -> String {
+    if config.no_store.unwrap_or(false) {
+        return "no-store".to_string();
+    }
+    
+    let mut directives = vec![if config.private.unwrap_or(false) { "private" } else { "public" }.to_string()];
+    if config.no_cache.unwrap_or(false) {
+        directives.push("no-cache".to_string());
+    }
+    if let Some(max_age) = config.max_age {
+        directives.push(format!("max-age={}", max_age));
+    }
+    if config.immutable.unwrap_or(false) {
+        directives.push("immutable".to_string());
+    }
+    
+    directives.join(", ")
+}
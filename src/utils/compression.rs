@@ -1,6 +1,13 @@
-use std::io::{Read, Write};
+use async_compression::tokio::bufread::{BrotliEncoder, DeflateEncoder, GzipEncoder, ZstdEncoder};
+use async_compression::Level;
+use futures::TryStreamExt;
+use hyper::{header, Body, Request};
+use std::io::Write;
+use tokio_util::io::{ReaderStream, StreamReader};
 use tracing::{debug, warn};
 
+use crate::core::config::CompressionConfig;
+
 /// Determine if content should be compressed based on MIME type
 pub fn should_compress(mime: &str) -> bool {
     const COMPRESSIBLE_TYPES: [&str; 6] = [
@@ -11,57 +18,232 @@ pub fn should_compress(mime: &str) -> bool {
     COMPRESSIBLE_TYPES.iter().any(|t| mime.starts_with(t))
 }
 
-/// Compress data if the client accepts it and the MIME type is compressible
-pub fn compress_if_needed(data: &[u8], mime_type: &str, accept_encoding: &str) -> (Vec<u8>, Option<&'static str>) {
-    // Only compress if the data is large enough to benefit
-    if data.len() < 1024 || !should_compress(mime_type) {
-        return (data.to_vec(), None);
+/// Smallest body worth compressing under normal circumstances
+pub const MIN_COMPRESS_SIZE: usize = 1024;
+
+/// Gzip and deflate level used when none is configured
+pub const DEFAULT_GZIP_LEVEL: u32 = 6;
+
+/// Brotli quality used when none is configured
+pub const DEFAULT_BROTLI_LEVEL: u32 = 5;
+
+/// Zstandard level used when none is configured
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+/// Content codings supported for responses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Zstd,
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    /// All encodings, in order of preference when the client rates them equally
+    pub const ALL: [Encoding; 4] = [Encoding::Brotli, Encoding::Zstd, Encoding::Gzip, Encoding::Deflate];
+    
+    /// Name of the encoding in `Accept-Encoding` and `Content-Encoding`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zstd",
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
     }
+}
+
+/// Get the quality value an `Accept-Encoding` header gives a content coding (0 if not acceptable)
+pub fn encoding_quality(accept_encoding: &str, encoding: &str) -> f32 {
+    let mut wildcard = None;
     
-    // Check if client accepts gzip
-    if accept_encoding.contains("gzip") {
-        debug!("Compressing response with gzip ({})", mime_type);
-        return match compress_gzip(data) {
-            Ok(compressed) => (compressed, Some("gzip")),
-            Err(e) => {
-                warn!("Failed to compress with gzip: {}", e);
-                (data.to_vec(), None)
-            }
+    for item in accept_encoding.split(',') {
+        let mut params = item.split(';');
+        let name = params.next().unwrap_or("").trim();
+        let quality = params
+            .find_map(|param| param.trim().strip_prefix("q="))
+            .map(|q| q.trim().parse::<f32>().unwrap_or(0.0).clamp(0.0, 1.0))
+            .unwrap_or(1.0);
+        
+        if name.eq_ignore_ascii_case(encoding) {
+            return quality;
+        }
+        if name == "*" {
+            wildcard = Some(quality);
+        }
+    }
+    
+    wildcard.unwrap_or(0.0)
+}
+
+/// Pick the candidate the client rates highest, preferring earlier candidates on ties
+pub fn negotiate(accept_encoding: &str, candidates: &[Encoding]) -> Option<Encoding> {
+    let mut best: Option<(Encoding, f32)> = None;
+    
+    for &encoding in candidates {
+        let quality = encoding_quality(accept_encoding, encoding.name());
+        if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+            best = Some((encoding, quality));
+        }
+    }
+    
+    best.map(|(encoding, _)| encoding)
+}
+
+/// Compresses data with the configured level of each encoding
+#[derive(Debug, Clone, Copy)]
+pub struct Compressor {
+    /// Gzip and deflate level (0-9)
+    gzip_level: u32,
+    /// Brotli quality (0-11)
+    brotli_level: u32,
+    /// Zstandard level (1-22)
+    zstd_level: i32,
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        // Brotli and zstd defaults favour speed, as most responses are compressed on the fly
+        Compressor {
+            gzip_level: DEFAULT_GZIP_LEVEL,
+            brotli_level: DEFAULT_BROTLI_LEVEL,
+            zstd_level: DEFAULT_ZSTD_LEVEL,
+        }
+    }
+}
+
+impl Compressor {
+    /// Build the compressor from the `compression` section, clamping levels to each encoding's range
+    pub fn from_config(config: Option<&CompressionConfig>) -> Self {
+        let defaults = Compressor::default();
+        let Some(config) = config else {
+            return defaults;
         };
+        
+        Compressor {
+            gzip_level: config.gzip_level.map_or(defaults.gzip_level, |level| level.min(9)),
+            brotli_level: config.brotli_level.map_or(defaults.brotli_level, |level| level.min(11)),
+            zstd_level: config.zstd_level.map_or(defaults.zstd_level, |level| level.clamp(1, 22)),
+        }
     }
     
-    // Check if client accepts deflate
-    if accept_encoding.contains("deflate") {
-        debug!("Compressing response with deflate ({})", mime_type);
-        return match compress_deflate(data) {
-            Ok(compressed) => (compressed, Some("deflate")),
+    /// Compress data with an encoding
+    pub fn compress(&self, data: &[u8], encoding: Encoding) -> Result<Vec<u8>, std::io::Error> {
+        match encoding {
+            Encoding::Brotli => {
+                let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, self.brotli_level, 22);
+                encoder.write_all(data)?;
+                Ok(encoder.into_inner())
+            }
+            Encoding::Zstd => zstd::stream::encode_all(data, self.zstd_level),
+            Encoding::Gzip => compress_gzip(data, self.gzip_level),
+            Encoding::Deflate => {
+                let mut encoder = flate2::write::DeflateEncoder::new(
+                    Vec::new(),
+                    flate2::Compression::new(self.gzip_level),
+                );
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+    
+    /// Compress a body as it streams, holding only the encoder's window in memory
+    pub fn compress_stream(&self, body: Body, encoding: Encoding) -> Body {
+        let reader = StreamReader::new(body.map_err(std::io::Error::other));
+        
+        match encoding {
+            Encoding::Brotli => {
+                let encoder = BrotliEncoder::with_quality(reader, Level::Precise(self.brotli_level as i32));
+                Body::wrap_stream(ReaderStream::new(encoder))
+            }
+            Encoding::Zstd => {
+                let encoder = ZstdEncoder::with_quality(reader, Level::Precise(self.zstd_level));
+                Body::wrap_stream(ReaderStream::new(encoder))
+            }
+            Encoding::Gzip => {
+                let encoder = GzipEncoder::with_quality(reader, Level::Precise(self.gzip_level as i32));
+                Body::wrap_stream(ReaderStream::new(encoder))
+            }
+            Encoding::Deflate => {
+                let encoder = DeflateEncoder::with_quality(reader, Level::Precise(self.gzip_level as i32));
+                Body::wrap_stream(ReaderStream::new(encoder))
+            }
+        }
+    }
+    
+    /// Compress data if the client accepts it and the MIME type is compressible
+    pub fn compress_if_needed(&self, data: &[u8], mime_type: &str, accept_encoding: &str) -> (Vec<u8>, Option<Encoding>) {
+        self.compress_with_min_size(data, mime_type, accept_encoding, MIN_COMPRESS_SIZE)
+    }
+    
+    /// Compress data of at least `min_size` bytes if the client accepts it and the MIME type is compressible
+    pub fn compress_with_min_size(
+        &self,
+        data: &[u8],
+        mime_type: &str,
+        accept_encoding: &str,
+        min_size: usize,
+    ) -> (Vec<u8>, Option<Encoding>) {
+        // Only compress if the data is large enough to benefit
+        if data.is_empty() || data.len() < min_size || !should_compress(mime_type) {
+            return (data.to_vec(), None);
+        }
+        
+        let Some(encoding) = negotiate(accept_encoding, &Encoding::ALL) else {
+            return (data.to_vec(), None);
+        };
+        
+        debug!("Compressing response with {} ({})", encoding.name(), mime_type);
+        match self.compress(data, encoding) {
+            Ok(compressed) => (compressed, Some(encoding)),
             Err(e) => {
-                warn!("Failed to compress with deflate: {}", e);
+                warn!("Failed to compress with {}: {}", encoding.name(), e);
                 (data.to_vec(), None)
             }
-        };
+        }
     }
     
-    // No compression
-    (data.to_vec(), None)
+    /// Compress data ahead of time with every supported encoding, keeping only variants that are smaller
+    pub fn precompress(&self, data: &[u8]) -> Vec<(Encoding, Vec<u8>)> {
+        let mut variants = Vec::new();
+        
+        for encoding in Encoding::ALL {
+            match self.compress(data, encoding) {
+                Ok(compressed) if compressed.len() < data.len() => variants.push((encoding, compressed)),
+                Ok(_) => {}
+                Err(e) => warn!("Failed to precompress with {}: {}", encoding.name(), e),
+            }
+        }
+        
+        variants
+    }
 }
 
-/// Compress data using gzip
-fn compress_gzip(data: &[u8]) -> Result<Vec<u8>, std::io::Error> {
-    let mut encoder = flate2::write::GzEncoder::new(
-        Vec::new(),
-        flate2::Compression::default(),
-    );
+/// Gzip-compress a request body as it streams to an upstream that decodes it.
+///
+/// Requests that are already encoded or have an empty body are returned unchanged.
+/// The compressed length is unknown up front, so the body is sent chunked.
+pub fn compress_request_body(req: Request<Body>) -> Request<Body> {
+    if req.headers().contains_key(header::CONTENT_ENCODING) || hyper::body::HttpBody::is_end_stream(req.body()) {
+        return req;
+    }
     
-    encoder.write_all(data)?;
-    encoder.finish()
+    let (mut parts, body) = req.into_parts();
+    debug!("Compressing request body to {}", parts.uri.path());
+    
+    parts.headers.insert(header::CONTENT_ENCODING, header::HeaderValue::from_static("gzip"));
+    parts.headers.remove(header::CONTENT_LENGTH);
+    
+    Request::from_parts(parts, Compressor::default().compress_stream(body, Encoding::Gzip))
 }
 
-/// Compress data using deflate
-fn compress_deflate(data: &[u8]) -> Result<Vec<u8>, std::io::Error> {
-    let mut encoder = flate2::write::DeflateEncoder::new(
+/// Compress data using gzip
+fn compress_gzip(data: &[u8], level: u32) -> Result<Vec<u8>, std::io::Error> {
+    let mut encoder = flate2::write::GzEncoder::new(
         Vec::new(),
-        flate2::Compression::default(),
+        flate2::Compression::new(level),
     );
     
     encoder.write_all(data)?;
@@ -0,0 +1,196 @@
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+use tracing::debug;
+
+/// Default number of content digests kept in the cache
+pub const DEFAULT_CACHE_SIZE: usize = 4096;
+
+/// Strategy used to generate entity tags for static files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EtagStrategy {
+    /// Derive the tag from modification time and size
+    Mtime,
+    /// Derive the tag from a digest of the file content
+    ContentHash,
+}
+
+/// Key identifying one version of a file
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    size: u64,
+}
+
+/// Generator for static file entity tags, caching content digests
+#[derive(Clone)]
+pub struct EtagGenerator {
+    /// Tag generation strategy
+    strategy: EtagStrategy,
+    /// Content digests keyed by (path, mtime, size)
+    cache: Arc<DashMap<CacheKey, String>>,
+    /// Maximum number of cached digests
+    capacity: usize,
+}
+
+impl EtagGenerator {
+    /// Create a new ETag generator
+    pub fn new(strategy: EtagStrategy, capacity: Option<usize>) -> Self {
+        EtagGenerator {
+            strategy,
+            cache: Arc::new(DashMap::new()),
+            capacity: capacity.unwrap_or(DEFAULT_CACHE_SIZE),
+        }
+    }
+    
+    /// Get the tag generation strategy
+    pub fn strategy(&self) -> EtagStrategy {
+        self.strategy
+    }
+    
+    /// Generate an ETag for a file, using `content` when it is already in memory
+    pub async fn etag(
+        &self,
+        path: &Path,
+        modified: Option<SystemTime>,
+        size: u64,
+        content: Option<&[u8]>,
+    ) -> Option<String> {
+        match self.strategy {
+            EtagStrategy::Mtime => Some(mtime_etag(modified, size)),
+            EtagStrategy::ContentHash => self.content_etag(path, modified, size, content).await,
+        }
+    }
+    
+    /// Generate a content-hash ETag, consulting the digest cache first
+    async fn content_etag(
+        &self,
+        path: &Path,
+        modified: Option<SystemTime>,
+        size: u64,
+        content: Option<&[u8]>,
+    ) -> Option<String> {
+        let key = CacheKey {
+            path: path.to_path_buf(),
+            modified,
+            size,
+        };
+        
+        if let Some(etag) = self.cache.get(&key) {
+            return Some(etag.clone());
+        }
+        
+        let digest = match content {
+            Some(data) => blake3::hash(data),
+            None => hash_file(path).await.ok()?,
+        };
+        let etag = format!("\"{}\"", &digest.to_hex()[..32]);
+        
+        // Keep the cache bounded by evicting an arbitrary entry
+        if self.cache.len() >= self.capacity {
+            let victim = self.cache.iter().next().map(|entry| entry.key().clone());
+            if let Some(victim) = victim {
+                self.cache.remove(&victim);
+            }
+        }
+        
+        debug!("Computed content ETag {} for {}", etag, path.display());
+        self.cache.insert(key, etag.clone());
+        Some(etag)
+    }
+}
+
+/// Generate an ETag from modification time and size
+pub fn mtime_etag(modified: Option<SystemTime>, size: u64) -> String {
+    let mtime = modified
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    
+    format!("\"{:x}-{:x}\"", mtime, size)
+}
+
+/// Hash a file's content without loading it all into memory
+async fn hash_file(path: &Path) -> std::io::Result<blake3::Hash> {
+    let mut file = File::open(path).await?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    
+    Ok(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    
+    /// Write a file with the given content and modification time, returning its metadata
+    fn write_at(path: &Path, content: &[u8], modified: SystemTime) -> (Option<SystemTime>, u64) {
+        std::fs::write(path, content).unwrap();
+        std::fs::File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+        let metadata = std::fs::metadata(path).unwrap();
+        (metadata.modified().ok(), metadata.len())
+    }
+    
+    #[tokio::test]
+    async fn content_hash_etags_ignore_mtime_and_follow_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.js");
+        let generator = EtagGenerator::new(EtagStrategy::ContentHash, None);
+        let epoch = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        
+        let (modified, size) = write_at(&path, b"console.log(1)", epoch);
+        let first = generator.etag(&path, modified, size, None).await.unwrap();
+        
+        // A rebuild rewrites identical content with a new mtime
+        let (modified, size) = write_at(&path, b"console.log(1)", epoch + Duration::from_secs(60));
+        assert_eq!(generator.etag(&path, modified, size, None).await.unwrap(), first);
+        
+        // In-memory content hashes the same as the file
+        assert_eq!(generator.etag(&path, None, size, Some(b"console.log(1)")).await.unwrap(), first);
+        
+        let (modified, size) = write_at(&path, b"console.log(2)", epoch + Duration::from_secs(120));
+        assert_ne!(generator.etag(&path, modified, size, None).await.unwrap(), first);
+    }
+    
+    #[tokio::test]
+    async fn mtime_etags_change_with_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.js");
+        let generator = EtagGenerator::new(EtagStrategy::Mtime, None);
+        let epoch = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        
+        let (modified, size) = write_at(&path, b"console.log(1)", epoch);
+        let first = generator.etag(&path, modified, size, None).await.unwrap();
+        let (modified, size) = write_at(&path, b"console.log(1)", epoch + Duration::from_secs(60));
+        assert_ne!(generator.etag(&path, modified, size, None).await.unwrap(), first);
+    }
+    
+    #[tokio::test]
+    async fn digest_cache_is_bounded() {
+        let dir = tempfile::tempdir().unwrap();
+        let generator = EtagGenerator::new(EtagStrategy::ContentHash, Some(2));
+        
+        for i in 0..5 {
+            let path = dir.path().join(format!("{}.txt", i));
+            let (modified, size) = write_at(&path, i.to_string().as_bytes(), SystemTime::now());
+            generator.etag(&path, modified, size, None).await.unwrap();
+        }
+        
+        assert!(generator.cache.len() <= 2);
+    }
+}
@@ -0,0 +1,566 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::net::IpAddr;
+use tracing::info;
+
+use crate::core::config::Config;
+
+/// Marker preceding the metadata section at the end of a MaxMind DB file
+const METADATA_MARKER: &[u8] = b"\xAB\xCD\xEFMaxMind.com";
+
+/// Bytes at the end of a file searched for the metadata marker
+const METADATA_MAX_SIZE: usize = 128 * 1024;
+
+/// Size of the zero-filled separator between the search tree and the data section
+const DATA_SEPARATOR_SIZE: usize = 16;
+
+/// Deepest nesting of maps, arrays and pointers decoded, guarding against malformed files
+const MAX_DEPTH: usize = 32;
+
+/// Data section type of UTF-8 strings
+const TYPE_STRING: u8 = 2;
+
+/// Data section type of maps
+const TYPE_MAP: u8 = 7;
+
+/// Errors loading GeoIP databases
+#[derive(Debug)]
+pub enum GeoIpError {
+    /// A database file cannot be read
+    Io(String, std::io::Error),
+    /// A database file is not a valid MaxMind DB
+    InvalidDatabase(String, String),
+}
+
+impl fmt::Display for GeoIpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeoIpError::Io(path, e) => write!(f, "Cannot read GeoIP database {}: {}", path, e),
+            GeoIpError::InvalidDatabase(path, msg) => write!(f, "Invalid GeoIP database {}: {}", path, msg),
+        }
+    }
+}
+
+impl Error for GeoIpError {}
+
+/// Value decoded from the data section of a MaxMind DB
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Double(f64),
+    Bytes(Vec<u8>),
+    Uint(u128),
+    Int(i32),
+    Map(HashMap<String, Value>),
+    Array(Vec<Value>),
+    Bool(bool),
+    Float(f32),
+}
+
+impl Value {
+    /// Get the value if it is a string
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+    
+    /// Get the value if it is an unsigned integer fitting in 32 bits
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            Value::Uint(n) => u32::try_from(*n).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Read a big-endian unsigned integer of up to 8 bytes
+fn be(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |n, &b| (n << 8) | b as usize)
+}
+
+/// Decoder of the data section, or of the metadata section, which uses the same format
+struct Decoder<'a> {
+    /// The section; pointers are offsets into it
+    section: &'a [u8],
+}
+
+impl Decoder<'_> {
+    /// Read the type and size of the field at an offset, following a pointer.
+    ///
+    /// Returns the type, the size, where the payload starts, and where the
+    /// next field starts if the payload is elsewhere because of a pointer.
+    fn header(&self, offset: usize, depth: usize) -> Option<(u8, usize, usize, Option<usize>)> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let control = *self.section.get(offset)?;
+        let mut pos = offset + 1;
+        let mut kind = control >> 5;
+        
+        if kind == 1 {
+            let extra = ((control >> 3) & 0x3) as usize + 1;
+            let high = (control & 0x7) as usize;
+            let bytes = self.section.get(pos..pos + extra)?;
+            let pointer = match extra {
+                1 => (high << 8) | be(bytes),
+                2 => ((high << 16) | be(bytes)) + 2048,
+                3 => ((high << 24) | be(bytes)) + 526336,
+                _ => be(bytes),
+            };
+            let (kind, size, payload, _) = self.header(pointer, depth + 1)?;
+            return Some((kind, size, payload, Some(pos + extra)));
+        }
+        
+        // Types from 8 on are stored in an extra byte
+        if kind == 0 {
+            kind = self.section.get(pos)?.checked_add(7)?;
+            pos += 1;
+        }
+        
+        let mut size = (control & 0x1f) as usize;
+        if size >= 29 {
+            let extra = size - 28;
+            let bytes = self.section.get(pos..pos + extra)?;
+            pos += extra;
+            size = match extra {
+                1 => 29 + be(bytes),
+                2 => 285 + be(bytes),
+                _ => 65821 + be(bytes),
+            };
+        }
+        
+        Some((kind, size, pos, None))
+    }
+    
+    /// Get where the field after the one at an offset starts
+    fn skip(&self, offset: usize, depth: usize) -> Option<usize> {
+        let (kind, size, mut pos, next) = self.header(offset, depth)?;
+        if let Some(next) = next {
+            return Some(next);
+        }
+        match kind {
+            TYPE_MAP | 11 => {
+                let fields = if kind == TYPE_MAP { size.checked_mul(2)? } else { size };
+                for _ in 0..fields {
+                    pos = self.skip(pos, depth + 1)?;
+                }
+                Some(pos)
+            }
+            // Booleans keep their value in the size
+            14 => Some(pos),
+            _ => Some(pos + size),
+        }
+    }
+    
+    /// Decode the field at an offset
+    fn decode(&self, offset: usize, depth: usize) -> Option<Value> {
+        let (kind, size, pos, _) = self.header(offset, depth)?;
+        let payload = || self.section.get(pos..pos + size);
+        
+        Some(match kind {
+            TYPE_STRING => Value::String(std::str::from_utf8(payload()?).ok()?.to_string()),
+            3 => Value::Double(f64::from_be_bytes(payload()?.try_into().ok()?)),
+            4 => Value::Bytes(payload()?.to_vec()),
+            5 | 6 | 9 | 10 if size <= 16 => Value::Uint(payload()?.iter().fold(0, |n, &b| (n << 8) | b as u128)),
+            TYPE_MAP => {
+                let mut map = HashMap::with_capacity(size.min(64));
+                let mut field = pos;
+                for _ in 0..size {
+                    let key = self.decode(field, depth + 1)?;
+                    field = self.skip(field, depth + 1)?;
+                    map.insert(key.as_str()?.to_string(), self.decode(field, depth + 1)?);
+                    field = self.skip(field, depth + 1)?;
+                }
+                Value::Map(map)
+            }
+            8 if size <= 4 => Value::Int(be(payload()?) as u32 as i32),
+            11 => {
+                let mut array = Vec::with_capacity(size.min(64));
+                let mut field = pos;
+                for _ in 0..size {
+                    array.push(self.decode(field, depth + 1)?);
+                    field = self.skip(field, depth + 1)?;
+                }
+                Value::Array(array)
+            }
+            14 => Value::Bool(size != 0),
+            15 => Value::Float(f32::from_be_bytes(payload()?.try_into().ok()?)),
+            _ => return None,
+        })
+    }
+    
+    /// Decode the value at a path of map keys below the field at an offset, skipping everything else
+    fn find(&self, offset: usize, path: &[&str]) -> Option<Value> {
+        let Some((first, rest)) = path.split_first() else {
+            return self.decode(offset, 0);
+        };
+        
+        let (kind, size, mut field, _) = self.header(offset, 0)?;
+        if kind != TYPE_MAP {
+            return None;
+        }
+        for _ in 0..size {
+            let (key_kind, key_size, key_pos, _) = self.header(field, 0)?;
+            field = self.skip(field, 0)?;
+            if key_kind == TYPE_STRING && self.section.get(key_pos..key_pos + key_size)? == first.as_bytes() {
+                return self.find(field, rest);
+            }
+            field = self.skip(field, 0)?;
+        }
+        None
+    }
+}
+
+/// A MaxMind DB (`.mmdb`) file held in memory
+pub struct MaxMindDb {
+    /// Contents of the file
+    data: Vec<u8>,
+    /// Number of nodes in the search tree
+    node_count: usize,
+    /// Bits per record of a node: 24, 28 or 32
+    record_size: usize,
+    /// Start of the data section
+    data_start: usize,
+    /// Whether the tree holds IPv6 addresses, with IPv4 ones under `::/96`
+    ipv6: bool,
+    /// Node where IPv4 addresses start
+    ipv4_start: usize,
+    /// Type of the database, such as "GeoLite2-Country"
+    database_type: String,
+}
+
+impl MaxMindDb {
+    /// Load a database file, checking its metadata
+    pub fn open(path: &str) -> Result<Self, GeoIpError> {
+        let data = fs::read(path).map_err(|e| GeoIpError::Io(path.to_string(), e))?;
+        Self::from_bytes(data).map_err(|msg| GeoIpError::InvalidDatabase(path.to_string(), msg))
+    }
+    
+    /// Read a database from the contents of its file
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, String> {
+        let tail_start = data.len().saturating_sub(METADATA_MAX_SIZE);
+        let marker = data[tail_start..]
+            .windows(METADATA_MARKER.len())
+            .rposition(|window| window == METADATA_MARKER)
+            .map(|position| tail_start + position)
+            .ok_or("no metadata marker")?;
+        
+        let metadata = Decoder { section: &data[marker + METADATA_MARKER.len()..] };
+        let field = |key: &str| metadata.find(0, &[key]);
+        let number = |key: &str| {
+            field(key)
+                .and_then(|value| value.as_u32())
+                .map(|n| n as usize)
+                .ok_or(format!("missing metadata field {}", key))
+        };
+        let node_count = number("node_count")?;
+        let record_size = number("record_size")?;
+        let ip_version = number("ip_version")?;
+        let database_type = field("database_type").and_then(|value| value.as_str().map(str::to_string)).unwrap_or_default();
+        
+        if ![24, 28, 32].contains(&record_size) {
+            return Err(format!("unsupported record size {}", record_size));
+        }
+        if ip_version != 4 && ip_version != 6 {
+            return Err(format!("unsupported IP version {}", ip_version));
+        }
+        let data_start = node_count * record_size / 4 + DATA_SEPARATOR_SIZE;
+        if data_start > marker {
+            return Err("search tree exceeds the file".to_string());
+        }
+        
+        let mut db = MaxMindDb {
+            data,
+            node_count,
+            record_size,
+            data_start,
+            ipv6: ip_version == 6,
+            ipv4_start: 0,
+            database_type,
+        };
+        
+        // IPv4 addresses live under ::/96, after 96 zero bits
+        if db.ipv6 {
+            let mut node = 0;
+            for _ in 0..96 {
+                if node >= db.node_count {
+                    break;
+                }
+                node = db.read_node(node, 0).ok_or("truncated search tree")?;
+            }
+            db.ipv4_start = node;
+        }
+        
+        Ok(db)
+    }
+    
+    /// Get the type of the database, such as "GeoLite2-Country"
+    pub fn database_type(&self) -> &str {
+        &self.database_type
+    }
+    
+    /// Read the left (0) or right (1) record of a search tree node
+    fn read_node(&self, node: usize, bit: u8) -> Option<usize> {
+        let start = node * self.record_size / 4;
+        let bytes = self.data.get(start..start + self.record_size / 4)?;
+        Some(match (self.record_size, bit) {
+            (24, 0) => be(&bytes[0..3]),
+            (24, _) => be(&bytes[3..6]),
+            // The middle byte holds the high nibble of both records
+            (28, 0) => ((bytes[3] as usize & 0xF0) << 20) | be(&bytes[0..3]),
+            (28, _) => ((bytes[3] as usize & 0x0F) << 24) | be(&bytes[4..7]),
+            (_, 0) => be(&bytes[0..4]),
+            (_, _) => be(&bytes[4..8]),
+        })
+    }
+    
+    /// Find the offset of an address's record in the data section
+    fn record(&self, ip: IpAddr) -> Option<usize> {
+        let (address, bits, mut node) = match ip.to_canonical() {
+            IpAddr::V4(ip) => (u32::from(ip) as u128, 32, self.ipv4_start),
+            IpAddr::V6(ip) if self.ipv6 => (u128::from(ip), 128, 0),
+            IpAddr::V6(_) => return None,
+        };
+        
+        for i in (0..bits).rev() {
+            if node >= self.node_count {
+                break;
+            }
+            node = self.read_node(node, ((address >> i) & 1) as u8)?;
+        }
+        
+        // A record equal to the node count means the address is not in the database
+        if node <= self.node_count {
+            return None;
+        }
+        (node - self.node_count).checked_sub(DATA_SEPARATOR_SIZE)
+    }
+    
+    /// Look up the value at a path of map keys in an address's record
+    pub fn lookup(&self, ip: IpAddr, path: &[&str]) -> Option<Value> {
+        let offset = self.record(ip)?;
+        Decoder { section: self.data.get(self.data_start..)? }.find(offset, path)
+    }
+}
+
+/// Location and network of a client, as found in the GeoIP databases
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GeoInfo {
+    /// ISO 3166 country code, such as "CN"
+    pub country: Option<String>,
+    /// Autonomous system number
+    pub asn: Option<u32>,
+    /// Organization operating the autonomous system
+    pub as_org: Option<String>,
+}
+
+impl GeoInfo {
+    /// Get the attributes describing the client, keyed `geo.country`, `geo.asn` and `geo.as_org`
+    pub fn attributes(&self) -> HashMap<String, String> {
+        let mut attributes = HashMap::new();
+        if let Some(country) = &self.country {
+            attributes.insert("geo.country".to_string(), country.clone());
+        }
+        if let Some(asn) = self.asn {
+            attributes.insert("geo.asn".to_string(), asn.to_string());
+        }
+        if let Some(as_org) = &self.as_org {
+            attributes.insert("geo.as_org".to_string(), as_org.clone());
+        }
+        attributes
+    }
+}
+
+/// GeoIP lookups of client addresses in MaxMind databases
+pub struct GeoIp {
+    /// Databases in lookup order; the first one knowing a field answers it
+    databases: Vec<MaxMindDb>,
+}
+
+impl GeoIp {
+    /// Load the databases configured in `[geoip]`, if any
+    pub fn from_config(config: &Config) -> Result<Option<Self>, GeoIpError> {
+        let Some(geoip_config) = &config.geoip else {
+            return Ok(None);
+        };
+        
+        let mut databases = Vec::new();
+        for path in &geoip_config.databases {
+            let db = MaxMindDb::open(path)?;
+            info!("Loaded GeoIP database {} ({})", path, db.database_type());
+            databases.push(db);
+        }
+        
+        Ok(Some(GeoIp { databases }))
+    }
+    
+    /// Look up the country and autonomous system of an address
+    pub fn lookup(&self, ip: IpAddr) -> GeoInfo {
+        let mut geo = GeoInfo::default();
+        for db in &self.databases {
+            if geo.country.is_none() {
+                // Anonymous proxies and the like have only a registered country
+                geo.country = db.lookup(ip, &["country", "iso_code"])
+                    .or_else(|| db.lookup(ip, &["registered_country", "iso_code"]))
+                    .and_then(|value| value.as_str().map(str::to_string));
+            }
+            if geo.asn.is_none() {
+                geo.asn = db.lookup(ip, &["autonomous_system_number"]).and_then(|value| value.as_u32());
+            }
+            if geo.as_org.is_none() {
+                geo.as_org = db.lookup(ip, &["autonomous_system_organization"]).and_then(|value| value.as_str().map(str::to_string));
+            }
+        }
+        geo
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Encode the control bytes of a data section field
+    fn field(kind: u8, size: usize) -> Vec<u8> {
+        // Sizes from 29 to 284 take an extra byte
+        let (size, extra) = match size {
+            0..=28 => (size as u8, None),
+            _ => (29, Some(u8::try_from(size - 29).unwrap())),
+        };
+        let mut control = if kind < 8 { vec![(kind << 5) | size] } else { vec![size, kind - 7] };
+        control.extend(extra);
+        control
+    }
+    
+    fn string(s: &str) -> Vec<u8> {
+        [field(TYPE_STRING, s.len()), s.as_bytes().to_vec()].concat()
+    }
+    
+    fn uint(kind: u8, n: u32) -> Vec<u8> {
+        let bytes: Vec<u8> = n.to_be_bytes().into_iter().skip_while(|&b| b == 0).collect();
+        [field(kind, bytes.len()), bytes].concat()
+    }
+    
+    fn map(entries: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut encoded = field(TYPE_MAP, entries.len());
+        for (key, value) in entries {
+            encoded.extend(string(key));
+            encoded.extend(value);
+        }
+        encoded
+    }
+    
+    /// Build an IPv4 database with 24-bit records holding `data` for `prefix/len`
+    fn database(prefix: [u8; 4], len: usize, data: &[u8]) -> Vec<u8> {
+        let address = u32::from_be_bytes(prefix);
+        let mut db = Vec::new();
+        for i in 0..len {
+            // The last node points at the start of the data section
+            let next = if i + 1 == len { len + DATA_SEPARATOR_SIZE } else { i + 1 };
+            let (left, right) = if (address >> (31 - i)) & 1 == 0 { (next, len) } else { (len, next) };
+            db.extend(&(left as u32).to_be_bytes()[1..]);
+            db.extend(&(right as u32).to_be_bytes()[1..]);
+        }
+        db.extend([0; DATA_SEPARATOR_SIZE]);
+        db.extend(data);
+        db.extend(METADATA_MARKER);
+        db.extend(map(&[
+            ("node_count", uint(6, len as u32)),
+            ("record_size", uint(5, 24)),
+            ("ip_version", uint(5, 4)),
+            ("database_type", string("Test-Country")),
+        ]));
+        db
+    }
+    
+    fn record() -> Vec<u8> {
+        map(&[
+            ("country", map(&[("iso_code", string("CN"))])),
+            ("autonomous_system_number", uint(6, 4134)),
+            ("autonomous_system_organization", string("Chinanet")),
+        ])
+    }
+    
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+    
+    #[test]
+    fn records_are_found_below_their_network() {
+        let db = MaxMindDb::from_bytes(database([1, 2, 0, 0], 16, &record())).unwrap();
+        assert_eq!(db.database_type(), "Test-Country");
+        
+        assert_eq!(db.lookup(ip("1.2.3.4"), &["country", "iso_code"]), Some(Value::String("CN".to_string())));
+        assert_eq!(db.lookup(ip("::ffff:1.2.255.255"), &["autonomous_system_number"]).and_then(|v| v.as_u32()), Some(4134));
+        assert_eq!(db.lookup(ip("1.3.0.0"), &["country", "iso_code"]), None);
+        assert_eq!(db.lookup(ip("1.2.3.4"), &["city"]), None);
+        assert_eq!(db.lookup(ip("2001:db8::1"), &["country", "iso_code"]), None);
+        
+        let geo = GeoIp { databases: vec![db] }.lookup(ip("1.2.3.4"));
+        assert_eq!(geo, GeoInfo { country: Some("CN".to_string()), asn: Some(4134), as_org: Some("Chinanet".to_string()) });
+    }
+    
+    #[test]
+    fn pointers_are_followed() {
+        // {"registered_country": <pointer to {"iso_code": "DE"}>}
+        let mut data = [field(TYPE_MAP, 1), string("registered_country")].concat();
+        let target = data.len() + 2;
+        data.extend([0x20, target as u8]);
+        data.extend(map(&[("iso_code", string("DE"))]));
+        
+        let db = MaxMindDb::from_bytes(database([10, 0, 0, 0], 8, &data)).unwrap();
+        let geo = GeoIp { databases: vec![db] }.lookup(ip("10.1.2.3"));
+        assert_eq!(geo.country.as_deref(), Some("DE"));
+    }
+    
+    #[test]
+    fn invalid_metadata_is_rejected() {
+        assert!(MaxMindDb::from_bytes(Vec::new()).is_err());
+        assert!(MaxMindDb::from_bytes(b"not a database".to_vec()).is_err());
+        
+        // Metadata cut off before the record size
+        let db = database([1, 2, 0, 0], 16, &record());
+        let metadata = db.windows(METADATA_MARKER.len()).rposition(|w| w == METADATA_MARKER).unwrap() + METADATA_MARKER.len();
+        assert!(MaxMindDb::from_bytes(db[..metadata + 20].to_vec()).is_err());
+        
+        // A search tree larger than the file
+        let mut db = database([1, 2, 0, 0], 16, &record());
+        let count = db[metadata..].windows(10).position(|w| w == b"node_count").unwrap() + metadata + 10;
+        db.splice(count..count + 2, uint(6, 1_000_000));
+        assert_eq!(MaxMindDb::from_bytes(db).err().as_deref(), Some("search tree exceeds the file"));
+    }
+    
+    #[test]
+    fn extended_types_past_the_last_one_are_refused() {
+        // A control byte of type 0 reads the type from the next byte, which may overflow
+        let data = [vec![0x01, 0xff], string("x")].concat();
+        let db = MaxMindDb::from_bytes(database([1, 2, 0, 0], 16, &data)).unwrap();
+        assert_eq!(db.lookup(ip("1.2.3.4"), &[]), None);
+    }
+    
+    #[test]
+    fn truncated_and_corrupted_databases_never_panic() {
+        let db = database([1, 2, 0, 0], 16, &record());
+        let lookup = |bytes: Vec<u8>| {
+            if let Ok(db) = MaxMindDb::from_bytes(bytes) {
+                let geo = GeoIp { databases: vec![db] };
+                geo.lookup(ip("1.2.3.4"));
+                geo.lookup(ip("::1"));
+            }
+        };
+        
+        for len in 0..db.len() {
+            lookup(db[..len].to_vec());
+        }
+        for i in 0..db.len() {
+            for byte in [0x00, 0x01, 0x1d, 0x1f, 0x20, 0x3f, 0x7f, 0xe0, 0xff] {
+                let mut corrupted = db.clone();
+                corrupted[i] = byte;
+                lookup(corrupted);
+            }
+        }
+    }
+}
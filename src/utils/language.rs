@@ -0,0 +1,64 @@
+/// Check if a file extension looks like a language tag, such as `en`, `de` or `pt-br`
+pub fn is_language_tag(tag: &str) -> bool {
+    let mut subtags = tag.split('-');
+    let primary = subtags.next().unwrap_or_default();
+    (2..=3).contains(&primary.len())
+        && primary.bytes().all(|b| b.is_ascii_alphabetic())
+        && subtags.all(|subtag| (1..=8).contains(&subtag.len()) && subtag.bytes().all(|b| b.is_ascii_alphanumeric()))
+}
+
+/// Get the quality value an `Accept-Language` header gives a language tag (0 if not acceptable).
+///
+/// A range matches the tag itself and more specific tags (`en` matches `en-gb`).
+/// A tag only matching the primary language of a range (`en` for `en-gb`)
+/// is accepted at a slightly lower quality, as most clients mean either.
+pub fn language_quality(accept_language: &str, tag: &str) -> f32 {
+    // Quality of the most specific matching range, as for `en-gb;q=1, en;q=0`
+    let mut best: Option<(usize, f32)> = None;
+    let mut fallback: Option<f32> = None;
+    let mut wildcard = None;
+    
+    for item in accept_language.split(',') {
+        let mut params = item.split(';');
+        let range = params.next().unwrap_or("").trim();
+        let quality = params
+            .find_map(|param| param.trim().strip_prefix("q="))
+            .map(|q| q.trim().parse::<f32>().unwrap_or(0.0).clamp(0.0, 1.0))
+            .unwrap_or(1.0);
+        
+        if range == "*" {
+            wildcard = Some(quality);
+        } else if matches_range(range, tag) {
+            if best.is_none_or(|(length, _)| range.len() > length) {
+                best = Some((range.len(), quality));
+            }
+        } else if matches_range(tag, range) {
+            fallback = Some(fallback.map_or(quality, |fallback| fallback.max(quality)));
+        }
+    }
+    
+    best.map(|(_, quality)| quality)
+        .or(fallback.map(|quality| quality * 0.9))
+        .or(wildcard)
+        .unwrap_or(0.0)
+}
+
+/// Check if a language range matches a tag by prefix, as in RFC 4647 basic filtering
+fn matches_range(range: &str, tag: &str) -> bool {
+    tag.get(..range.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(range))
+        && (range.len() == tag.len() || tag.as_bytes()[range.len()] == b'-')
+}
+
+/// Pick the tag the client rates highest, preferring earlier tags on ties
+pub fn negotiate_language<'a>(accept_language: &str, tags: &'a [String]) -> Option<&'a str> {
+    let mut best: Option<(&str, f32)> = None;
+    
+    for tag in tags {
+        let quality = language_quality(accept_language, tag);
+        if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+            best = Some((tag, quality));
+        }
+    }
+    
+    best.map(|(tag, _)| tag)
+}
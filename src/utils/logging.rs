@@ -1,91 +1,385 @@
-use tracing::{info, debug, error, warn, Level};
-use tracing_subscriber::FmtSubscriber;
-use std::path::Path;
-use std::fs::OpenOptions;
+use serde::{Deserialize, Serialize};
+use tracing::{info, debug, error, Level};
+use tracing_subscriber::{EnvFilter, FmtSubscriber};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::io::Write;
 use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
 
-/// Initialize logging with tracing subscriber
-pub fn init_logging(log_level: Level) {
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(log_level)
-        .finish();
+use crate::core::config::{Config, LoggingConfig};
+use crate::utils::geoip::GeoInfo;
+use crate::utils::rotation::{RotatingFile, RotationPolicy};
+use crate::routing::router::parse_host;
+use crate::routing::vhost::VirtualHost;
+
+/// Where the server log is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogTarget {
+    /// Standard output
+    Stdout,
+    /// Standard error
+    Stderr,
+    /// The `error_log` file
+    File,
+}
+
+/// Initialize the server log from the logging configuration
+pub fn init_logging(logging: Option<&LoggingConfig>) -> Result<(), std::io::Error> {
+    let filter = log_filter(logging)?;
+    let directives = filter.to_string();
+    let target = log_target(logging)?;
+    let ansi = logging.and_then(|l| l.ansi).unwrap_or(target != LogTarget::File);
+    let builder = FmtSubscriber::builder().with_env_filter(filter).with_ansi(ansi);
+    
+    let result = match target {
+        LogTarget::Stdout => tracing::subscriber::set_global_default(builder.finish()),
+        LogTarget::Stderr => tracing::subscriber::set_global_default(builder.with_writer(std::io::stderr).finish()),
+        LogTarget::File => {
+            let path = logging.and_then(|l| l.error_log.as_ref()).expect("file target has an error log");
+            let rotation = RotationPolicy::from_config(logging.and_then(|l| l.rotation.as_ref()));
+            let file = RotatingFile::open(path, rotation)?;
+            tracing::subscriber::set_global_default(builder.with_writer(Mutex::new(file)).finish())
+        }
+    };
+    result.map_err(std::io::Error::other)?;
+    
+    info!("Logging initialized with filter: {}", directives);
+    Ok(())
+}
+
+/// Build the server log filter from the level and the per-module filters
+pub fn log_filter(logging: Option<&LoggingConfig>) -> Result<EnvFilter, std::io::Error> {
+    let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, msg);
+    
+    let log_level = match logging.and_then(|l| l.level.as_deref()) {
+        Some(level) => level.parse::<Level>().map_err(|_| invalid(format!("Invalid log level: {}", level)))?,
+        None => Level::INFO,
+    };
     
-    tracing::subscriber::set_global_default(subscriber)
-        .expect("Failed to set global default subscriber");
+    let mut filter = EnvFilter::new(log_level.to_string());
+    for (module, level) in logging.and_then(|l| l.filters.as_ref()).into_iter().flatten() {
+        // Module filters may also switch a module off entirely
+        if level != "off" && level.parse::<Level>().is_err() {
+            return Err(invalid(format!("Invalid log level for {}: {}", module, level)));
+        }
+        let directive = format!("{}={}", module, level)
+            .parse()
+            .map_err(|e| invalid(format!("Invalid log filter for {}: {}", module, e)))?;
+        filter = filter.add_directive(directive);
+    }
+    
+    Ok(filter)
+}
+
+/// Get where the server log goes, checking that the file target has a file
+pub fn log_target(logging: Option<&LoggingConfig>) -> Result<LogTarget, std::io::Error> {
+    let error_log = logging.and_then(|l| l.error_log.as_ref());
+    match logging.and_then(|l| l.target) {
+        Some(LogTarget::File) if error_log.is_none() => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Log target \"file\" needs an error_log file",
+        )),
+        Some(target) => Ok(target),
+        None if error_log.is_some() => Ok(LogTarget::File),
+        None => Ok(LogTarget::Stdout),
+    }
+}
+
+/// Access log line format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccessLogFormat {
+    /// Common Log Format
+    Common,
+    /// Combined Log Format, adding referer and user agent
+    #[default]
+    Combined,
+    /// Combined Log Format, adding the client country and autonomous system number
+    CombinedGeo,
+}
+
+/// Where access log lines are written
+enum AccessLogTarget {
+    /// The tracing subscriber, at info level
+    Tracing,
+    /// Standard output
+    Stdout,
+    /// Standard error
+    Stderr,
+    /// A file, appended to and rotated, opened through the shared writer pool
+    File(PathBuf, Arc<LogWriters>),
+}
+
+/// Default number of access log files kept open at once
+pub const DEFAULT_MAX_OPEN_LOGS: usize = 256;
+
+/// Open log files and when they were last used
+struct OpenLogs {
+    /// Writers by path, with the use count at their last use
+    files: HashMap<PathBuf, (Arc<Mutex<RotatingFile>>, u64)>,
+    /// Uses so far, ordering the open files
+    uses: u64,
+}
+
+/// Log files shared by every access logger writing to them.
+///
+/// Each path is opened once however many virtual hosts log to it. Beyond
+/// the limit, the least recently used file is closed and reopened on its
+/// next write, so many sites cannot exhaust file descriptors.
+pub struct LogWriters {
+    /// Files currently open
+    open: Mutex<OpenLogs>,
+    /// Most files kept open at once
+    max_open: usize,
+    /// Rotation policy of every file
+    rotation: Option<RotationPolicy>,
+}
+
+impl LogWriters {
+    /// Create an empty pool keeping at most `max_open` files open
+    pub fn new(max_open: usize, rotation: Option<RotationPolicy>) -> Self {
+        LogWriters {
+            open: Mutex::new(OpenLogs { files: HashMap::new(), uses: 0 }),
+            max_open: max_open.max(1),
+            rotation,
+        }
+    }
     
-    info!("Logging initialized at level: {:?}", log_level);
+    /// Get the writer of a log file, opening it if needed
+    pub fn get(&self, path: &Path) -> Result<Arc<Mutex<RotatingFile>>, std::io::Error> {
+        let mut open = self.open.lock().unwrap();
+        open.uses += 1;
+        let uses = open.uses;
+        if let Some((file, last_use)) = open.files.get_mut(path) {
+            *last_use = uses;
+            return Ok(Arc::clone(file));
+        }
+        
+        // Writes in progress keep a closed file alive until they finish
+        if open.files.len() >= self.max_open {
+            let oldest = open.files.iter().min_by_key(|(_, (_, last_use))| *last_use).map(|(path, _)| path.clone());
+            if let Some(oldest) = oldest {
+                debug!("Closing access log {} to stay within {} open files", oldest.display(), self.max_open);
+                open.files.remove(&oldest);
+            }
+        }
+        
+        let file = Arc::new(Mutex::new(RotatingFile::open(path, self.rotation.clone())?));
+        open.files.insert(path.to_path_buf(), (Arc::clone(&file), uses));
+        Ok(file)
+    }
+}
+
+/// A request to record in the access log
+pub struct AccessLogEntry<'a> {
+    /// Client address
+    pub client_ip: &'a str,
+    /// Request method
+    pub method: &'a str,
+    /// Request target (path and query)
+    pub target: &'a str,
+    /// Protocol version (e.g. "HTTP/1.1")
+    pub version: &'a str,
+    /// Response status code
+    pub status: u16,
+    /// Response body size
+    pub bytes: u64,
+    /// User-Agent header
+    pub user_agent: Option<&'a str>,
+    /// Referer header
+    pub referer: Option<&'a str>,
+    /// Client location and network, when GeoIP is configured
+    pub geo: Option<&'a GeoInfo>,
 }
 
 /// HTTP access logger
 pub struct AccessLogger {
-    /// Log file path
-    log_file: Option<Arc<Mutex<std::fs::File>>>,
+    /// Destination of log lines
+    target: AccessLogTarget,
+    /// Format of each log line
+    format: AccessLogFormat,
 }
 
 impl AccessLogger {
-    /// Create a new access logger
+    /// Create a new access logger writing to the tracing subscriber
     pub fn new() -> Self {
         AccessLogger {
-            log_file: None,
+            target: AccessLogTarget::Tracing,
+            format: AccessLogFormat::default(),
         }
     }
     
-    /// Set log file path
-    pub fn with_file<P: AsRef<Path>>(mut self, path: P) -> Result<Self, std::io::Error> {
-        let file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(path.as_ref())?;
-        
-        self.log_file = Some(Arc::new(Mutex::new(file)));
+    /// Set the log line format
+    pub fn with_format(mut self, format: AccessLogFormat) -> Self {
+        self.format = format;
+        self
+    }
+    
+    /// Set the destination: "stdout" (or "-"), "stderr", or a file path opened through `writers`
+    pub fn with_destination(self, destination: &str, writers: &Arc<LogWriters>) -> Result<Self, std::io::Error> {
+        match destination {
+            "stdout" | "-" => Ok(AccessLogger { target: AccessLogTarget::Stdout, ..self }),
+            "stderr" => Ok(AccessLogger { target: AccessLogTarget::Stderr, ..self }),
+            path => self.with_file(path, writers),
+        }
+    }
+    
+    /// Set log file path, creating its directory if needed
+    pub fn with_file<P: AsRef<Path>>(mut self, path: P, writers: &Arc<LogWriters>) -> Result<Self, std::io::Error> {
+        // Open the file now so an unwritable path fails at startup
+        writers.get(path.as_ref())?;
+        self.target = AccessLogTarget::File(path.as_ref().to_path_buf(), Arc::clone(writers));
         Ok(self)
     }
     
     /// Log HTTP access
-    pub fn log_access(
-        &self,
-        client_ip: &str,
-        method: &str,
-        path: &str,
-        status: u16,
-        bytes: usize,
-        user_agent: Option<&str>,
-        referer: Option<&str>,
-    ) {
-        // Get current time
-        let timestamp = match SystemTime::now().duration_since(UNIX_EPOCH) {
-            Ok(n) => n.as_secs(),
-            Err(_) => 0,
-        };
-        
+    pub fn log_access(&self, entry: &AccessLogEntry) {
         // Format time in common log format
         let time_str = chrono::Utc::now().format("%d/%b/%Y:%H:%M:%S %z").to_string();
         
-        // Create log entry in Common Log Format
-        let log_entry = format!(
-            "{} - - [{}] \"{} {} HTTP/1.1\" {} {} \"{}\" \"{}\"",
-            client_ip,
+        // Create log entry in Common Log Format; an empty body is written as "-"
+        let mut log_entry = format!(
+            "{} - - [{}] \"{} {} {}\" {} {}",
+            entry.client_ip,
             time_str,
-            method,
-            path,
-            status,
-            bytes,
-            referer.unwrap_or("-"),
-            user_agent.unwrap_or("-")
+            entry.method,
+            escape(entry.target),
+            entry.version,
+            entry.status,
+            if entry.bytes == 0 { "-".to_string() } else { entry.bytes.to_string() },
         );
         
-        // Log to tracing
-        info!("{}", log_entry);
+        // Extend it to the Combined Log Format if requested
+        if self.format != AccessLogFormat::Common {
+            log_entry.push_str(&format!(
+                " \"{}\" \"{}\"",
+                entry.referer.map(escape).unwrap_or_else(|| "-".to_string()),
+                entry.user_agent.map(escape).unwrap_or_else(|| "-".to_string())
+            ));
+        }
+        
+        // Then add the client country and AS number, "-" when unknown
+        if self.format == AccessLogFormat::CombinedGeo {
+            let geo = entry.geo.cloned().unwrap_or_default();
+            log_entry.push_str(&format!(
+                " {} {}",
+                geo.country.as_deref().map(escape).unwrap_or_else(|| "-".to_string()),
+                geo.asn.map(|asn| format!("AS{}", asn)).unwrap_or_else(|| "-".to_string())
+            ));
+        }
         
-        // Write to log file if configured
-        if let Some(file) = &self.log_file {
-            if let Ok(mut file) = file.lock() {
-                if let Err(e) = writeln!(file, "{}", log_entry) {
+        match &self.target {
+            AccessLogTarget::Tracing => info!("{}", log_entry),
+            AccessLogTarget::Stdout => println!("{}", log_entry),
+            AccessLogTarget::Stderr => eprintln!("{}", log_entry),
+            AccessLogTarget::File(path, writers) => {
+                let writer = match writers.get(path) {
+                    Ok(writer) => writer,
+                    Err(e) => {
+                        error!("Failed to open access log {}: {}", path.display(), e);
+                        return;
+                    }
+                };
+                let Ok(mut file) = writer.lock() else {
+                    return;
+                };
+                // Write the line at once so rotation cannot split it
+                log_entry.push('\n');
+                if let Err(e) = file.write_all(log_entry.as_bytes()) {
                     error!("Failed to write access log: {}", e);
                 }
             }
         }
     }
 }
+
+impl Default for AccessLogger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Escape quotes, backslashes and control characters so a value cannot forge log fields or lines
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Access loggers for the server and its virtual hosts
+#[derive(Clone)]
+pub struct AccessLogs {
+    /// Logger used when no virtual host has its own (`None` when turned off)
+    global: Option<Arc<AccessLogger>>,
+    /// Virtual hosts with their own loggers, in configuration order (`None` when turned off)
+    vhosts: Vec<(VirtualHost, Option<Arc<AccessLogger>>)>,
+}
+
+impl AccessLogs {
+    /// Open the access logs configured globally and per virtual host
+    pub fn from_config(config: &Config) -> Result<Self, std::io::Error> {
+        let logging = config.logging.as_ref();
+        let format = logging.and_then(|l| l.access_log_format).unwrap_or_default();
+        let rotation = RotationPolicy::from_config(logging.and_then(|l| l.rotation.as_ref()));
+        let max_open = logging.and_then(|l| l.max_open_logs).unwrap_or(DEFAULT_MAX_OPEN_LOGS);
+        let writers = Arc::new(LogWriters::new(max_open, rotation));
+        // Without a destination, access lines go to the server log
+        let global = match logging.and_then(|l| l.access_log.as_deref()) {
+            Some("off") => None,
+            Some(destination) => Some(Arc::new(
+                AccessLogger::new().with_format(format).with_destination(destination, &writers)?,
+            )),
+            None => Some(Arc::new(AccessLogger::new().with_format(format))),
+        };
+        
+        let mut vhosts = Vec::new();
+        for vhost_config in config.virtual_hosts.iter().flatten() {
+            let Some(destination) = vhost_config.access_log.as_deref() else {
+                continue;
+            };
+            
+            let vhost = VirtualHost::new(&vhost_config.host, &vhost_config.root_dir)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+            let logger = match destination {
+                "off" => None,
+                destination => {
+                    let format = vhost_config.access_log_format.unwrap_or_default();
+                    Some(Arc::new(AccessLogger::new().with_format(format).with_destination(destination, &writers)?))
+                }
+            };
+            
+            debug!("Access log for {} at {}", vhost_config.host, destination);
+            vhosts.push((vhost, logger));
+        }
+        
+        Ok(AccessLogs { global, vhosts })
+    }
+    
+    /// Select the logger for a request's Host header, falling back to the global logger
+    pub fn select(&self, host: Option<&str>) -> Option<Arc<AccessLogger>> {
+        if let Some(host) = host {
+            let hostname = parse_host(host).0;
+            if let Some((_, logger)) = self.vhosts.iter().find(|(vhost, _)| vhost.matches(&hostname)) {
+                return logger.clone();
+            }
+        }
+        
+        self.global.clone()
+    }
+    
+    /// Select the logger of the virtual host with the given pattern, falling back to the global logger
+    pub fn select_vhost(&self, pattern: &str) -> Option<Arc<AccessLogger>> {
+        match self.vhosts.iter().find(|(vhost, _)| vhost.hostname() == pattern) {
+            Some((_, logger)) => logger.clone(),
+            None => self.global.clone(),
+        }
+    }
+}
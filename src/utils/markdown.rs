@@ -0,0 +1,175 @@
+use pulldown_cmark::{html, CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::SyntaxSet;
+use tracing::{info, warn};
+
+use crate::core::config::StaticFilesConfig;
+use crate::core::error::escape_html;
+
+/// Built-in page template
+const DEFAULT_TEMPLATE: &str = "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{{title}}</title>\n<style>\n\
+body { font-family: -apple-system, \"Segoe UI\", Helvetica, Arial, sans-serif; line-height: 1.6; max-width: 860px; margin: 0 auto; padding: 2em 1em; color: #24292f; }\n\
+pre { padding: 1em; overflow: auto; border-radius: 6px; background: #f6f8fa; }\n\
+code { font-family: ui-monospace, Menlo, Consolas, monospace; font-size: 0.9em; }\n\
+table { border-collapse: collapse; }\n\
+th, td { border: 1px solid #d0d7de; padding: 6px 13px; }\n\
+blockquote { margin: 0; padding: 0 1em; color: #57606a; border-left: 4px solid #d0d7de; }\n\
+img { max-width: 100%; }\n\
+</style>\n</head>\n<body>\n{{content}}\n</body>\n</html>\n";
+
+/// Highlighting theme used when none is configured
+pub const DEFAULT_THEME: &str = "InspiredGitHub";
+
+/// Error types for Markdown rendering
+#[derive(Debug)]
+pub enum MarkdownError {
+    /// The template file could not be read
+    Template(std::io::Error),
+    /// The highlighting theme does not exist
+    UnknownTheme(String),
+}
+
+impl fmt::Display for MarkdownError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarkdownError::Template(e) => write!(f, "cannot read Markdown template: {}", e),
+            MarkdownError::UnknownTheme(name) => write!(
+                f,
+                "unknown highlighting theme '{}' (available: {})",
+                name,
+                ThemeSet::load_defaults().themes.keys().cloned().collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+}
+
+impl Error for MarkdownError {}
+
+/// Renderer turning Markdown files into HTML pages with highlighted code blocks
+pub struct MarkdownRenderer {
+    /// Page template with `{{title}}` and `{{content}}` placeholders
+    template: String,
+    /// Syntax definitions for fenced code blocks
+    syntaxes: SyntaxSet,
+    /// Highlighting theme
+    theme: Theme,
+}
+
+impl MarkdownRenderer {
+    /// Build the renderer if Markdown rendering is enabled
+    pub fn from_config(config: &StaticFilesConfig) -> Result<Option<Self>, MarkdownError> {
+        if !config.render_markdown.unwrap_or(false) {
+            return Ok(None);
+        }
+        
+        let template = match &config.markdown_template {
+            Some(path) => {
+                info!("Loading Markdown template from {}", path);
+                fs::read_to_string(path).map_err(MarkdownError::Template)?
+            }
+            None => DEFAULT_TEMPLATE.to_string(),
+        };
+        if !template.contains("{{content}}") {
+            warn!("Markdown template has no {{{{content}}}} placeholder");
+        }
+        
+        let theme_name = config.markdown_theme.as_deref().unwrap_or(DEFAULT_THEME);
+        let theme = ThemeSet::load_defaults()
+            .themes
+            .remove(theme_name)
+            .ok_or_else(|| MarkdownError::UnknownTheme(theme_name.to_string()))?;
+        
+        Ok(Some(MarkdownRenderer {
+            template,
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme,
+        }))
+    }
+    
+    /// Render a Markdown document as a page, titled by its first heading or else `fallback_title`.
+    ///
+    /// Raw HTML in the document is passed through, as in most Markdown renderers.
+    pub fn render(&self, source: &str, fallback_title: &str) -> String {
+        let mut title = None;
+        let mut in_title = false;
+        let mut code: Option<(String, String)> = None;
+        let mut events = Vec::new();
+        
+        let options = Options::ENABLE_TABLES
+            | Options::ENABLE_STRIKETHROUGH
+            | Options::ENABLE_TASKLISTS
+            | Options::ENABLE_FOOTNOTES
+            | Options::ENABLE_HEADING_ATTRIBUTES;
+        for event in Parser::new_ext(source, options) {
+            match event {
+                // Collect fenced code so it can be highlighted as a whole
+                Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref language))) => {
+                    let language = language.split([' ', ',']).next().unwrap_or_default().to_string();
+                    code = Some((language, String::new()));
+                }
+                Event::Text(ref text) if code.is_some() => {
+                    if let Some((_, buffer)) = code.as_mut() {
+                        buffer.push_str(text);
+                    }
+                }
+                Event::End(TagEnd::CodeBlock) if code.is_some() => {
+                    let (language, buffer) = code.take().unwrap_or_default();
+                    events.push(Event::Html(self.highlight(&language, &buffer).into()));
+                }
+                Event::Start(Tag::Heading { level: HeadingLevel::H1, .. }) if title.is_none() => {
+                    in_title = true;
+                    title = Some(String::new());
+                    events.push(event);
+                }
+                Event::End(TagEnd::Heading(_)) if in_title => {
+                    in_title = false;
+                    events.push(event);
+                }
+                Event::Text(ref text) | Event::Code(ref text) if in_title => {
+                    if let Some(title) = title.as_mut() {
+                        title.push_str(text);
+                    }
+                    events.push(event);
+                }
+                event => events.push(event),
+            }
+        }
+        
+        let mut content = String::with_capacity(source.len() * 3 / 2);
+        html::push_html(&mut content, events.into_iter());
+        
+        let title = title.filter(|title| !title.trim().is_empty()).unwrap_or_else(|| fallback_title.to_string());
+        // Substitute the title around the content, so neither can inject the other's placeholder
+        let (before, after) = self.template.split_once("{{content}}").unwrap_or((&self.template, ""));
+        let title = escape_html(title.trim());
+        format!("{}{}{}", before.replace("{{title}}", &title), content, after.replace("{{title}}", &title))
+    }
+    
+    /// Highlight a code block, or escape it as plain text if the language is unknown
+    fn highlight(&self, language: &str, code: &str) -> String {
+        let syntax = (!language.is_empty())
+            .then(|| self.syntaxes.find_syntax_by_token(language))
+            .flatten();
+        
+        match syntax.map(|syntax| highlighted_html_for_string(code, &self.syntaxes, syntax, &self.theme)) {
+            Some(Ok(highlighted)) => highlighted,
+            Some(Err(e)) => {
+                warn!("Failed to highlight {} code block: {}", language, e);
+                format!("<pre><code>{}</code></pre>\n", escape_html(code))
+            }
+            None => format!("<pre><code>{}</code></pre>\n", escape_html(code)),
+        }
+    }
+}
+
+/// Check if a file is Markdown by its extension
+pub fn is_markdown(path: &std::path::Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"))
+}
@@ -0,0 +1,238 @@
+use bytes::{Bytes, BytesMut};
+use hyper::body::HttpBody;
+use hyper::Body;
+use std::convert::Infallible;
+use std::error::Error;
+use std::io;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::debug;
+
+use crate::core::error::HttpError;
+
+/// Bytes read at a time when buffering a stream against the budget
+const READ_CHUNK_SIZE: usize = 16 * 1024;
+
+/// Global budget for in-flight buffered bytes (request bodies and buffered responses)
+#[derive(Clone)]
+pub struct MemoryBudget {
+    /// Semaphore holding one permit per available byte, or `None` when unlimited
+    semaphore: Option<Arc<Semaphore>>,
+    /// Total budget size in bytes
+    limit: usize,
+}
+
+/// Bytes reserved against a memory budget, released when dropped
+pub struct MemoryReservation {
+    /// Underlying semaphore permit
+    permit: Option<OwnedSemaphorePermit>,
+    /// Budget the reservation grows against
+    budget: MemoryBudget,
+}
+
+impl MemoryBudget {
+    /// Create a new memory budget with the given size in bytes
+    pub fn new(limit: usize) -> Self {
+        let limit = limit.min(Semaphore::MAX_PERMITS);
+        MemoryBudget {
+            semaphore: Some(Arc::new(Semaphore::new(limit))),
+            limit,
+        }
+    }
+    
+    /// Create a memory budget that never rejects reservations
+    pub fn unlimited() -> Self {
+        MemoryBudget {
+            semaphore: None,
+            limit: usize::MAX,
+        }
+    }
+    
+    /// Create a memory budget from an optional size in megabytes
+    pub fn from_megabytes(megabytes: Option<usize>) -> Self {
+        match megabytes {
+            Some(mb) => Self::new(mb.saturating_mul(1024 * 1024)),
+            None => Self::unlimited(),
+        }
+    }
+    
+    /// Try to reserve the given number of bytes without waiting
+    pub fn try_reserve(&self, bytes: usize) -> Option<MemoryReservation> {
+        let semaphore = match &self.semaphore {
+            Some(semaphore) => semaphore,
+            None => return Some(self.reservation()),
+        };
+        
+        let permits = match u32::try_from(bytes) {
+            Ok(permits) if bytes <= self.limit => permits,
+            _ => {
+                debug!("Reservation of {} bytes exceeds memory budget of {} bytes", bytes, self.limit);
+                return None;
+            }
+        };
+        
+        match Arc::clone(semaphore).try_acquire_many_owned(permits) {
+            Ok(permit) => Some(MemoryReservation { permit: Some(permit), budget: self.clone() }),
+            Err(_) => {
+                debug!("Memory budget exhausted, rejecting reservation of {} bytes", bytes);
+                None
+            }
+        }
+    }
+    
+    /// Start an empty reservation, for data whose size is only known once it has arrived
+    pub fn reservation(&self) -> MemoryReservation {
+        MemoryReservation { permit: None, budget: self.clone() }
+    }
+    
+    /// Get the number of bytes currently available
+    pub fn available(&self) -> usize {
+        match &self.semaphore {
+            Some(semaphore) => semaphore.available_permits(),
+            None => usize::MAX,
+        }
+    }
+    
+    /// Get the total budget size in bytes
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+impl MemoryReservation {
+    /// Try to reserve more bytes without waiting, keeping the reservation as is on failure
+    pub fn try_grow(&mut self, bytes: usize) -> bool {
+        let Some(more) = self.budget.try_reserve(bytes) else {
+            return false;
+        };
+        match (&mut self.permit, more.permit) {
+            (Some(permit), Some(more)) => permit.merge(more),
+            (permit, more) => *permit = permit.take().or(more),
+        }
+        true
+    }
+}
+
+/// Buffer a whole body, reserving its bytes as they arrive.
+///
+/// Fails with `HttpError::ServiceUnavailable` once the budget cannot hold the
+/// data read so far; errors reading the body are passed on.
+pub async fn buffer_body(mut body: Body, budget: &MemoryBudget) -> Result<(Bytes, MemoryReservation), Box<dyn Error + Send + Sync>> {
+    let mut reservation = budget.reservation();
+    let mut buffer = BytesMut::new();
+    
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if !reservation.try_grow(chunk.len()) {
+            return Err(Box::new(HttpError::ServiceUnavailable));
+        }
+        buffer.extend_from_slice(&chunk);
+    }
+    
+    Ok((buffer.freeze(), reservation))
+}
+
+/// Read a stream to its end, reserving its bytes as they arrive.
+///
+/// Fails with an `OutOfMemory` error once the budget cannot hold the data read so far.
+pub async fn read_to_end_reserved<R: AsyncRead + Unpin>(reader: &mut R, budget: &MemoryBudget) -> io::Result<(Vec<u8>, MemoryReservation)> {
+    let mut reservation = budget.reservation();
+    let mut output = Vec::new();
+    
+    loop {
+        if !reservation.try_grow(READ_CHUNK_SIZE) {
+            return Err(io::Error::new(io::ErrorKind::OutOfMemory, "memory budget exhausted"));
+        }
+        let read = reader.take(READ_CHUNK_SIZE as u64).read_to_end(&mut output).await?;
+        if read == 0 {
+            return Ok((output, reservation));
+        }
+    }
+}
+
+/// Create a body that keeps its memory reservation until the data has been sent
+pub fn reserved_body(data: impl Into<Bytes>, reservation: MemoryReservation) -> Body {
+    let data: Bytes = data.into();
+    let stream = futures::stream::once(async move {
+        let _reservation = reservation;
+        Ok::<_, Infallible>(data)
+    });
+    
+    Body::wrap_stream(stream)
+}
+
+/// Keep a memory reservation until a body has been sent, passing on its trailers
+pub fn body_holding(mut body: Body, reservation: MemoryReservation) -> Body {
+    let (mut sender, held) = Body::channel();
+    
+    tokio::spawn(async move {
+        let _reservation = reservation;
+        while let Some(chunk) = body.data().await {
+            match chunk {
+                Ok(data) => {
+                    if sender.send_data(data).await.is_err() {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    debug!("Error reading body holding a memory reservation: {}", e);
+                    sender.abort();
+                    return;
+                }
+            }
+        }
+        if let Ok(Some(trailers)) = body.trailers().await {
+            let _ = sender.send_trailers(trailers).await;
+        }
+    });
+    
+    held
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn reservations_grow_within_the_budget_and_release_on_drop() {
+        let budget = MemoryBudget::new(100);
+        let mut reservation = budget.reservation();
+        
+        assert!(reservation.try_grow(60));
+        assert!(reservation.try_grow(40));
+        assert!(!reservation.try_grow(1));
+        assert!(budget.try_reserve(1).is_none());
+        
+        drop(reservation);
+        assert_eq!(budget.available(), 100);
+    }
+    
+    #[tokio::test]
+    async fn buffering_beyond_the_budget_is_unavailable() {
+        let budget = MemoryBudget::new(10);
+        
+        let (data, reservation) = buffer_body(Body::from("0123456789"), &budget).await.unwrap();
+        assert_eq!(data, "0123456789");
+        assert_eq!(budget.available(), 0);
+        
+        let e = buffer_body(Body::from("x"), &budget).await.err().unwrap();
+        assert!(matches!(e.downcast_ref::<HttpError>(), Some(HttpError::ServiceUnavailable)));
+        
+        drop(reservation);
+        assert_eq!(budget.available(), 10);
+    }
+    
+    #[tokio::test]
+    async fn reading_beyond_the_budget_is_out_of_memory() {
+        let budget = MemoryBudget::new(READ_CHUNK_SIZE * 2);
+        
+        let data = vec![b'a'; READ_CHUNK_SIZE];
+        let (output, _reservation) = read_to_end_reserved(&mut &data[..], &budget).await.unwrap();
+        assert_eq!(output.len(), READ_CHUNK_SIZE);
+        
+        let data = vec![b'a'; READ_CHUNK_SIZE * 3];
+        let e = read_to_end_reserved(&mut &data[..], &budget).await.err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::OutOfMemory);
+    }
+}
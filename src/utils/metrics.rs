@@ -1,7 +1,28 @@
+use serde::Serialize;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+/// Serializable snapshot of server metrics
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsSnapshot {
+    pub uptime_seconds: u64,
+    pub requests: u64,
+    pub responses: u64,
+    pub status_2xx: u64,
+    pub status_3xx: u64,
+    pub status_4xx: u64,
+    pub status_5xx: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub active_connections: u64,
+    pub connections_queued: u64,
+    pub connections_rejected: u64,
+    pub concurrency_queued: u64,
+    pub concurrency_rejected: u64,
+    pub rate_limited: u64,
+}
+
 /// Server metrics collector
 #[derive(Clone)]
 pub struct Metrics {
@@ -21,6 +42,18 @@ pub struct Metrics {
     bytes_sent: Arc<AtomicU64>,
     /// Total bytes received
     bytes_received: Arc<AtomicU64>,
+    /// Number of currently open connections
+    active_connections: Arc<AtomicU64>,
+    /// Connections that waited for a free connection slot
+    connections_queued: Arc<AtomicU64>,
+    /// Connections closed by a connection limit
+    connections_rejected: Arc<AtomicU64>,
+    /// Requests that waited for a route concurrency slot
+    concurrency_queued: Arc<AtomicU64>,
+    /// Requests rejected by a route concurrency limit
+    concurrency_rejected: Arc<AtomicU64>,
+    /// Requests rejected by a client rate limit
+    rate_limited: Arc<AtomicU64>,
     /// Server start time
     start_time: Instant,
 }
@@ -37,6 +70,12 @@ impl Metrics {
             status_5xx: Arc::new(AtomicU64::new(0)),
             bytes_sent: Arc::new(AtomicU64::new(0)),
             bytes_received: Arc::new(AtomicU64::new(0)),
+            active_connections: Arc::new(AtomicU64::new(0)),
+            connections_queued: Arc::new(AtomicU64::new(0)),
+            connections_rejected: Arc::new(AtomicU64::new(0)),
+            concurrency_queued: Arc::new(AtomicU64::new(0)),
+            concurrency_rejected: Arc::new(AtomicU64::new(0)),
+            rate_limited: Arc::new(AtomicU64::new(0)),
             start_time: Instant::now(),
         }
     }
@@ -62,6 +101,41 @@ impl Metrics {
         };
     }
     
+    /// Record a newly opened connection
+    pub fn connection_opened(&self) {
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+    }
+    
+    /// Record a closed connection
+    pub fn connection_closed(&self) {
+        self.active_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+    
+    /// Record a connection that had to wait for a connection slot
+    pub fn record_connection_queued(&self) {
+        self.connections_queued.fetch_add(1, Ordering::Relaxed);
+    }
+    
+    /// Record a connection closed by a connection limit
+    pub fn record_connection_rejected(&self) {
+        self.connections_rejected.fetch_add(1, Ordering::Relaxed);
+    }
+    
+    /// Record a request that had to wait for a route concurrency slot
+    pub fn record_concurrency_queued(&self) {
+        self.concurrency_queued.fetch_add(1, Ordering::Relaxed);
+    }
+    
+    /// Record a request rejected by a route concurrency limit
+    pub fn record_concurrency_rejected(&self) {
+        self.concurrency_rejected.fetch_add(1, Ordering::Relaxed);
+    }
+    
+    /// Record a request rejected by a client rate limit
+    pub fn record_rate_limited(&self) {
+        self.rate_limited.fetch_add(1, Ordering::Relaxed);
+    }
+    
     /// Get total number of requests
     pub fn get_requests(&self) -> u64 {
         self.requests.load(Ordering::Relaxed)
@@ -102,11 +176,62 @@ impl Metrics {
         self.bytes_received.load(Ordering::Relaxed)
     }
     
+    /// Get number of currently open connections
+    pub fn get_active_connections(&self) -> u64 {
+        self.active_connections.load(Ordering::Relaxed)
+    }
+    
+    /// Get number of connections that waited for a connection slot
+    pub fn get_connections_queued(&self) -> u64 {
+        self.connections_queued.load(Ordering::Relaxed)
+    }
+    
+    /// Get number of connections closed by connection limits
+    pub fn get_connections_rejected(&self) -> u64 {
+        self.connections_rejected.load(Ordering::Relaxed)
+    }
+    
+    /// Get number of requests that waited for a route concurrency slot
+    pub fn get_concurrency_queued(&self) -> u64 {
+        self.concurrency_queued.load(Ordering::Relaxed)
+    }
+    
+    /// Get number of requests rejected by route concurrency limits
+    pub fn get_concurrency_rejected(&self) -> u64 {
+        self.concurrency_rejected.load(Ordering::Relaxed)
+    }
+    
+    /// Get number of requests rejected by client rate limits
+    pub fn get_rate_limited(&self) -> u64 {
+        self.rate_limited.load(Ordering::Relaxed)
+    }
+    
     /// Get server uptime
     pub fn get_uptime(&self) -> Duration {
         self.start_time.elapsed()
     }
     
+    /// Get a point-in-time snapshot of all metrics
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            uptime_seconds: self.get_uptime().as_secs(),
+            requests: self.get_requests(),
+            responses: self.get_responses(),
+            status_2xx: self.get_status_2xx(),
+            status_3xx: self.get_status_3xx(),
+            status_4xx: self.get_status_4xx(),
+            status_5xx: self.get_status_5xx(),
+            bytes_sent: self.get_bytes_sent(),
+            bytes_received: self.get_bytes_received(),
+            active_connections: self.get_active_connections(),
+            connections_queued: self.get_connections_queued(),
+            connections_rejected: self.get_connections_rejected(),
+            concurrency_queued: self.get_concurrency_queued(),
+            concurrency_rejected: self.get_concurrency_rejected(),
+            rate_limited: self.get_rate_limited(),
+        }
+    }
+    
     /// Get a formatted report of server metrics
     pub fn get_report(&self) -> String {
         let uptime = self.get_uptime();
@@ -142,3 +267,9 @@ impl Metrics {
         )
     }
 }
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use std::io::SeekFrom;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// Maximum number of leading bytes inspected for content sniffing
+pub const SNIFF_LEN: usize = 8192;
+
+/// When file content is inspected to determine the MIME type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MimeSniffing {
+    /// Rely on the file extension only
+    #[default]
+    Off,
+    /// Sniff only when the extension yields `application/octet-stream`
+    Fallback,
+    /// Prefer the sniffed type whenever the content is recognized
+    Always,
+}
+
+impl MimeSniffing {
+    /// Check whether content should be sniffed for an extension-based guess
+    pub fn applies_to(self, guessed: &str) -> bool {
+        match self {
+            MimeSniffing::Off => false,
+            MimeSniffing::Fallback => guessed == "application/octet-stream",
+            MimeSniffing::Always => true,
+        }
+    }
+}
+
+/// Detect a MIME type from the leading bytes of a file, leaving it rewound to the start
+pub async fn sniff_file(file: &mut File) -> std::io::Result<Option<&'static str>> {
+    let mut buffer = vec![0u8; SNIFF_LEN];
+    let mut filled = 0;
+    
+    while filled < buffer.len() {
+        let read = file.read(&mut buffer[filled..]).await?;
+        if read == 0 {
+            break;
+        }
+        filled += read;
+    }
+    
+    file.seek(SeekFrom::Start(0)).await?;
+    Ok(infer::get(&buffer[..filled]).map(|kind| kind.mime_type()))
+}
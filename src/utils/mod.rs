@@ -1,3 +1,13 @@
+pub mod cache_policy;
 pub mod compression;
 pub mod logging;
 pub mod metrics;
+pub mod memory;
+pub mod etag;
+pub mod upload;
+pub mod build_info;
+pub mod mime;
+pub mod rotation;
+pub mod markdown;
+pub mod language;
+pub mod geoip;
@@ -0,0 +1,245 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use chrono::{DateTime, Utc};
+use tracing::{debug, warn};
+
+use crate::core::config::RotationConfig;
+
+/// Default number of rotated files to keep
+const DEFAULT_KEEP: usize = 7;
+
+/// Format of the timestamp appended to rotated files
+const ROTATED_SUFFIX_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// Time-based rotation interval
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RotationInterval {
+    /// Start a new file every hour
+    Hourly,
+    /// Start a new file every day
+    Daily,
+}
+
+impl RotationInterval {
+    /// Key identifying the period a time falls in
+    fn period(&self, time: DateTime<Utc>) -> String {
+        match self {
+            RotationInterval::Hourly => time.format("%Y%m%d%H").to_string(),
+            RotationInterval::Daily => time.format("%Y%m%d").to_string(),
+        }
+    }
+}
+
+/// When and how a log file is rotated
+#[derive(Debug, Clone)]
+pub struct RotationPolicy {
+    /// Rotate once the file would exceed this many bytes
+    max_size: Option<u64>,
+    /// Rotate when this interval rolls over
+    interval: Option<RotationInterval>,
+    /// Number of rotated files to keep
+    keep: usize,
+    /// Gzip rotated files
+    compress: bool,
+}
+
+impl RotationPolicy {
+    /// Build the policy from `logging.rotation`, or `None` if rotation is not configured
+    pub fn from_config(config: Option<&RotationConfig>) -> Option<Self> {
+        let config = config?;
+        if config.max_size.is_none() && config.interval.is_none() {
+            return None;
+        }
+        
+        Some(RotationPolicy {
+            max_size: config.max_size.map(|mb| mb.max(1) * 1024 * 1024),
+            interval: config.interval,
+            keep: config.keep.unwrap_or(DEFAULT_KEEP),
+            compress: config.compress.unwrap_or(false),
+        })
+    }
+}
+
+/// Append-only log file that rotates itself according to a policy.
+///
+/// Rotated files are renamed with a timestamp suffix (e.g. `access.log.20240101-000000`),
+/// compressed in the background if configured, and pruned down to the retention count.
+pub struct RotatingFile {
+    /// Path of the live file
+    path: PathBuf,
+    /// Open handle of the live file
+    file: File,
+    /// Current size of the live file
+    size: u64,
+    /// Period the live file belongs to, for time-based rotation
+    period: Option<String>,
+    /// Rotation policy (never rotates if unset)
+    policy: Option<RotationPolicy>,
+}
+
+impl RotatingFile {
+    /// Open a log file for appending, creating its directory if needed
+    pub fn open<P: AsRef<Path>>(path: P, policy: Option<RotationPolicy>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let metadata = file.metadata()?;
+        
+        // An existing file belongs to the period it was last written in
+        let period = policy.as_ref().and_then(|policy| policy.interval).map(|interval| {
+            let modified = metadata.modified().unwrap_or_else(|_| SystemTime::now());
+            interval.period(DateTime::<Utc>::from(modified))
+        });
+        
+        Ok(RotatingFile {
+            path,
+            file,
+            size: metadata.len(),
+            period,
+            policy,
+        })
+    }
+    
+    /// Check whether writing `len` more bytes should start a new file
+    fn needs_rotation(&self, len: usize, now: DateTime<Utc>) -> bool {
+        let Some(policy) = &self.policy else {
+            return false;
+        };
+        
+        if self.size == 0 {
+            return false;
+        }
+        
+        let too_large = policy.max_size.is_some_and(|max_size| self.size + len as u64 > max_size);
+        let new_period = match (policy.interval, &self.period) {
+            (Some(interval), Some(period)) => interval.period(now) != *period,
+            _ => false,
+        };
+        
+        too_large || new_period
+    }
+    
+    /// Move the live file aside and start a new one
+    fn rotate(&mut self, now: DateTime<Utc>) -> io::Result<()> {
+        let Some(policy) = self.policy.clone() else {
+            return Ok(());
+        };
+        
+        self.file.flush()?;
+        
+        // Several size rotations can happen within one second
+        let base = format!("{}.{}", self.path.display(), now.format(ROTATED_SUFFIX_FORMAT));
+        let mut rotated = PathBuf::from(&base);
+        let mut counter = 1;
+        while rotated.exists() || Path::new(&format!("{}.gz", rotated.display())).exists() {
+            rotated = PathBuf::from(format!("{}-{}", base, counter));
+            counter += 1;
+        }
+        
+        fs::rename(&self.path, &rotated)?;
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+        self.period = policy.interval.map(|interval| interval.period(now));
+        
+        // Compression and pruning touch only rotated files, so they need not hold up logging
+        let path = self.path.clone();
+        std::thread::spawn(move || {
+            if policy.compress {
+                if let Err(e) = compress(&rotated) {
+                    warn!("Failed to compress {}: {}", rotated.display(), e);
+                }
+            }
+            if let Err(e) = prune(&path, policy.keep) {
+                warn!("Failed to remove old logs of {}: {}", path.display(), e);
+            }
+        });
+        
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let now = Utc::now();
+        if self.needs_rotation(buf.len(), now) {
+            // Keep logging to the current file if it cannot be moved aside. This may run
+            // inside the server log's own writer, so report without going through tracing.
+            if let Err(e) = self.rotate(now) {
+                eprintln!("Failed to rotate {}: {}", self.path.display(), e);
+            }
+        }
+        
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+    
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Gzip a rotated file, replacing it with `<name>.gz`
+fn compress(path: &Path) -> io::Result<()> {
+    let compressed = PathBuf::from(format!("{}.gz", path.display()));
+    let mut input = File::open(path)?;
+    let mut encoder = flate2::write::GzEncoder::new(File::create(&compressed)?, flate2::Compression::default());
+    
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    fs::remove_file(path)
+}
+
+/// Remove the oldest rotated files of a log beyond the retention count
+fn prune(path: &Path, keep: usize) -> io::Result<()> {
+    let directory = match path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        Some(parent) => parent.to_path_buf(),
+        None => PathBuf::from("."),
+    };
+    let prefix = match path.file_name() {
+        Some(name) => format!("{}.", name.to_string_lossy()),
+        None => return Ok(()),
+    };
+    
+    // Rotated names sort chronologically by their timestamp suffix and counter
+    let mut rotated: Vec<((String, u32), PathBuf)> = fs::read_dir(&directory)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let suffix = name.strip_prefix(&prefix)?;
+            let stamp = suffix.strip_suffix(".gz").unwrap_or(suffix);
+            rotation_stamp(stamp).map(|key| (key, entry.path()))
+        })
+        .collect();
+    
+    rotated.sort_by(|a, b| b.0.cmp(&a.0));
+    for (_, old) in rotated.into_iter().skip(keep) {
+        debug!("Removing old log {}", old.display());
+        fs::remove_file(old)?;
+    }
+    
+    Ok(())
+}
+
+/// Parse a `YYYYmmdd-HHMMSS` suffix with an optional `-N` counter into a sort key
+fn rotation_stamp(stamp: &str) -> Option<(String, u32)> {
+    let (time, counter) = match stamp.get(15..) {
+        Some("") => (stamp, 0),
+        Some(rest) => (&stamp[..15], rest.strip_prefix('-')?.parse().ok()?),
+        None => return None,
+    };
+    
+    let bytes = time.as_bytes();
+    let valid = bytes[..8].iter().all(u8::is_ascii_digit)
+        && bytes[8] == b'-'
+        && bytes[9..].iter().all(u8::is_ascii_digit);
+    
+    valid.then(|| (time.to_string(), counter))
+}
@@ -0,0 +1,113 @@
+use hyper::body::HttpBody;
+use hyper::Body;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, warn};
+
+/// Counter making temporary file names unique within the process
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Error types for streamed uploads
+#[derive(Debug)]
+pub enum UploadError {
+    /// The body exceeded the configured size limit
+    TooLarge,
+    /// The target path has no parent directory
+    InvalidTarget,
+    /// Reading the request body failed (e.g. client disconnect)
+    Body(hyper::Error),
+    /// Writing to disk failed
+    Io(std::io::Error),
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UploadError::TooLarge => write!(f, "Upload exceeds the maximum allowed size"),
+            UploadError::InvalidTarget => write!(f, "Invalid upload target path"),
+            UploadError::Body(e) => write!(f, "Failed to read upload body: {}", e),
+            UploadError::Io(e) => write!(f, "Failed to write upload: {}", e),
+        }
+    }
+}
+
+impl Error for UploadError {}
+
+impl From<std::io::Error> for UploadError {
+    fn from(e: std::io::Error) -> Self {
+        UploadError::Io(e)
+    }
+}
+
+/// Temporary file that is removed unless it has been committed
+struct TempFileGuard {
+    path: PathBuf,
+    committed: bool,
+}
+
+impl Drop for TempFileGuard {
+    fn drop(&mut self) {
+        if !self.committed {
+            debug!("Removing incomplete upload {}", self.path.display());
+            if let Err(e) = std::fs::remove_file(&self.path) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to remove temporary file {}: {}", self.path.display(), e);
+                }
+            }
+        }
+    }
+}
+
+/// Create a unique temporary path next to the target file
+fn temp_path_for(target: &Path) -> Option<PathBuf> {
+    let parent = target.parent()?;
+    let name = target.file_name()?.to_string_lossy();
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    
+    Some(parent.join(format!(".{}.{}-{}-{}.upload", name, std::process::id(), nanos, counter)))
+}
+
+/// Stream a request body to `target` through a temporary file in the same directory.
+///
+/// The temporary file is renamed into place only after the whole body has been
+/// written; on any error, size-limit violation or cancellation it is removed.
+/// Returns the number of bytes written.
+pub async fn stream_to_file(mut body: Body, target: &Path, max_size: Option<u64>) -> Result<u64, UploadError> {
+    let temp_path = temp_path_for(target).ok_or(UploadError::InvalidTarget)?;
+    let mut file = File::create(&temp_path).await?;
+    let mut guard = TempFileGuard {
+        path: temp_path,
+        committed: false,
+    };
+    
+    let mut written: u64 = 0;
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(UploadError::Body)?;
+        written += chunk.len() as u64;
+        
+        if matches!(max_size, Some(max) if written > max) {
+            return Err(UploadError::TooLarge);
+        }
+        
+        file.write_all(&chunk).await?;
+    }
+    
+    file.flush().await?;
+    file.sync_all().await?;
+    drop(file);
+    
+    fs::rename(&guard.path, target).await?;
+    guard.committed = true;
+    
+    debug!("Stored upload {} ({} bytes)", target.display(), written);
+    Ok(written)
+}
@@ -0,0 +1,46 @@
+//! The admin status endpoint reports metrics, configuration and upstreams to authenticated clients only.
+
+mod common;
+
+use common::{free_port, write_file, TestServer};
+
+#[tokio::test]
+async fn status_requires_credentials_and_reports_the_server() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "index.html", "home");
+    let rest = format!(
+        "[admin]\nenabled = true\nstatus_path = \"/ops/status\"\nusername = \"ops\"\npassword = \"s3cret\"\ntoken = \"t0ken\"\n\n\
+         [proxy]\n[[proxy.pools]]\nname = \"api\"\npath = \"/api/*\"\nservers = [\"http://127.0.0.1:{}\"]\n",
+        free_port(),
+    );
+    let server = TestServer::start(root.path(), "", "", &rest).await;
+    let client = reqwest::Client::new();
+    let url = server.url("/ops/status");
+    
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+    assert!(response.headers().contains_key("www-authenticate"));
+    
+    let response = client.get(&url).basic_auth("ops", Some("wrong")).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+    
+    let response = client.get(&url).basic_auth("ops", Some("s3cret")).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()["content-type"].to_str().unwrap().starts_with("application/json"));
+    let status: serde_json::Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    for key in ["build", "metrics", "active_connections", "upstreams", "config"] {
+        assert!(status.get(key).is_some(), "missing {}: {}", key, status);
+    }
+    assert_eq!(status["config"]["port"], server.addr.port());
+    assert!(status["upstreams"].get("api").is_some(), "{}", status);
+    
+    // The password itself is never part of the document
+    assert!(!status.to_string().contains("s3cret"));
+    
+    let response = client.get(&url).bearer_auth("t0ken").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    
+    // The default path is not served once another is configured
+    let response = client.get(server.url("/admin/status")).basic_auth("ops", Some("s3cret")).send().await.unwrap();
+    assert_ne!(response.status(), 200);
+}
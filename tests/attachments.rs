@@ -0,0 +1,38 @@
+//! Matching static files are served as downloads named after their basename.
+
+mod common;
+
+use common::{status_of, write_file, TestServer};
+
+fn disposition(response: &str) -> Option<&str> {
+    let (head, _) = response.split_once("\r\n\r\n")?;
+    head.lines().find_map(|line| line.strip_prefix("content-disposition: "))
+}
+
+#[tokio::test]
+async fn matching_paths_and_types_are_attachments() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "downloads/résumé final.html", "<h1>cv</h1>");
+    write_file(root.path(), "downloads/report.txt", "report");
+    write_file(root.path(), "pages/résumé.html", "<h1>cv</h1>");
+    write_file(root.path(), "data/table.csv", "a,b");
+    let static_files = "attachment_paths = [\"/downloads/*\"]\nattachment_types = [\"text/csv\"]";
+    let server = TestServer::start(root.path(), "", static_files, "").await;
+    
+    let response = server.get_raw("/downloads/r%C3%A9sum%C3%A9%20final.html", "").await;
+    assert_eq!(status_of(&response), 200, "{}", response);
+    assert_eq!(
+        disposition(&response),
+        Some("attachment; filename=\"r_sum_ final.html\"; filename*=UTF-8''r%C3%A9sum%C3%A9%20final.html"),
+    );
+    
+    let response = server.get_raw("/downloads/report.txt", "").await;
+    assert_eq!(disposition(&response), Some("attachment; filename=\"report.txt\""));
+    
+    let response = server.get_raw("/data/table.csv", "").await;
+    assert_eq!(disposition(&response), Some("attachment; filename=\"table.csv\""));
+    
+    let response = server.get_raw("/pages/r%C3%A9sum%C3%A9.html", "").await;
+    assert_eq!(status_of(&response), 200);
+    assert_eq!(disposition(&response), None);
+}
@@ -0,0 +1,37 @@
+//! Byte ranges of cached files are sliced from the cached, uncompressed content.
+
+mod common;
+
+use common::{status_of, write_file, TestServer};
+
+#[tokio::test]
+async fn ranges_are_served_from_the_cache() {
+    let root = tempfile::tempdir().unwrap();
+    let content = "0123456789".repeat(100);
+    let path = write_file(root.path(), "digits.txt", &content);
+    let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+    let server = TestServer::start(root.path(), "", "cache_size = 1", "").await;
+    
+    // The first request fills the cache
+    let response = server.get_raw("/digits.txt", "").await;
+    assert!(response.ends_with(&content));
+    
+    // Rewrite the file behind the cache's back, keeping its size and mtime
+    std::fs::write(&path, "x".repeat(content.len())).unwrap();
+    std::fs::File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+    
+    let response = server.get_raw("/digits.txt", "Range: bytes=12-17\r\nAccept-Encoding: gzip\r\n").await;
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let head = head.to_ascii_lowercase();
+    assert_eq!(status_of(&response), 206, "{}", response);
+    assert!(head.contains("content-range: bytes 12-17/1000"), "{}", head);
+    assert!(head.contains("content-length: 6"), "{}", head);
+    assert!(!head.contains("content-encoding"), "{}", head);
+    assert_eq!(body, "234567");
+    
+    let response = server.get_raw("/digits.txt", "Range: bytes=-4\r\n").await;
+    assert_eq!(status_of(&response), 206);
+    assert!(response.ends_with("6789"), "{}", response);
+    
+    assert_eq!(status_of(&server.get_raw("/digits.txt", "Range: bytes=5000-\r\n").await), 416);
+}
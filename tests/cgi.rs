@@ -0,0 +1,48 @@
+//! CGI scripts get the CGI/1.1 path variables, are found by their decoded
+//! names, and cannot write unbounded output.
+
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use common::{status_of, write_file, TestServer};
+
+fn write_script(root: &Path, path: &str, body: &str) {
+    let script = write_file(root, path, format!("#!/bin/sh\nprintf 'Content-Type: text/plain\\r\\n\\r\\n'\n{}", body));
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[tokio::test]
+async fn extra_path_after_the_script_is_path_info() {
+    let root = tempfile::tempdir().unwrap();
+    write_script(root.path(), "cgi-bin/env.sh", "echo \"$SCRIPT_NAME|$PATH_INFO|$PATH_TRANSLATED|$QUERY_STRING\"\n");
+    write_script(root.path(), "cgi-bin/my script.sh", "echo \"$SCRIPT_NAME\"\n");
+    let server = TestServer::start(root.path(), "", "", "[[cgi]]\npath = \"/cgi-bin/*\"\n").await;
+    
+    let response = server.get_raw("/cgi-bin/env.sh/extra/caf%C3%A9?q=1", "").await;
+    assert_eq!(status_of(&response), 200);
+    let translated = root.path().join("extra/café");
+    let expected = format!("/cgi-bin/env.sh|/extra/café|{}|q=1\n", translated.display());
+    assert!(response.ends_with(&expected), "{}", response);
+    
+    let response = server.get_raw("/cgi-bin/env.sh", "").await;
+    assert!(response.ends_with("/cgi-bin/env.sh|||\n"), "{}", response);
+    
+    let response = server.get_raw("/cgi-bin/my%20script.sh", "").await;
+    assert_eq!(status_of(&response), 200);
+    assert!(response.ends_with("/cgi-bin/my script.sh\n"), "{}", response);
+    
+    assert_eq!(status_of(&server.get_raw("/cgi-bin/missing.sh/extra", "").await), 404);
+}
+
+#[tokio::test]
+async fn output_beyond_the_limit_is_a_bad_gateway() {
+    let root = tempfile::tempdir().unwrap();
+    write_script(root.path(), "cgi-bin/small.sh", "echo ok\n");
+    write_script(root.path(), "cgi-bin/flood.sh", "while :; do echo aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa; done\n");
+    let server = TestServer::start(root.path(), "", "", "[[cgi]]\npath = \"/cgi-bin/*\"\nmax_output_size = 1024\n").await;
+    
+    assert_eq!(status_of(&server.get_raw("/cgi-bin/small.sh", "").await), 200);
+    assert_eq!(status_of(&server.get_raw("/cgi-bin/flood.sh", "").await), 502);
+}
@@ -0,0 +1,37 @@
+//! Extensionless URLs resolve to `.html` files and directory indexes without a redirect.
+
+mod common;
+
+use common::{status_of, write_file, TestServer};
+
+#[tokio::test]
+async fn clean_urls_try_extensions_then_directory_index() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "about.html", "about page");
+    write_file(root.path(), "team/index.html", "team index");
+    write_file(root.path(), "notes.txt", "plain notes");
+    let server = TestServer::start(root.path(), "", "clean_urls = true\nclean_url_extensions = [\"html\", \"txt\"]", "").await;
+    
+    let response = server.get_raw("/about", "").await;
+    assert_eq!(status_of(&response), 200, "{}", response);
+    assert!(response.ends_with("about page"));
+    assert!(!response.to_ascii_lowercase().contains("location:"));
+    
+    let response = server.get_raw("/team", "").await;
+    assert_eq!(status_of(&response), 200, "{}", response);
+    assert!(response.ends_with("team index"));
+    
+    let response = server.get_raw("/notes", "").await;
+    assert!(response.ends_with("plain notes"), "{}", response);
+    
+    assert_eq!(status_of(&server.get_raw("/missing", "").await), 404);
+}
+
+#[tokio::test]
+async fn clean_urls_are_off_by_default() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "about.html", "about page");
+    let server = TestServer::start(root.path(), "", "", "").await;
+    
+    assert_eq!(status_of(&server.get_raw("/about", "").await), 404);
+}
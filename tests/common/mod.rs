@@ -0,0 +1,117 @@
+//! Helpers shared by the integration tests: a server on a free port and raw HTTP/1 exchanges.
+
+#![allow(dead_code)]
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+
+use kaserve::{Config, Server};
+
+/// A server running in the background of a test, stopped when dropped
+pub struct TestServer {
+    /// Address the server listens on
+    pub addr: SocketAddr,
+    /// Static file root
+    pub root: PathBuf,
+    handle: JoinHandle<()>,
+}
+
+impl TestServer {
+    /// Start a server serving `root`.
+    ///
+    /// `server` and `static_files` are extra lines for those tables, `rest` any other
+    /// tables. `{root}` in `rest` is replaced with the root directory.
+    pub async fn start(root: &Path, server: &str, static_files: &str, rest: &str) -> TestServer {
+        let config = Config::from_toml(&config_toml(free_port(), root, server, static_files, rest)).unwrap();
+        Self::start_config(root, config).await
+    }
+    
+    /// Start a server with a complete configuration, waiting until it accepts connections
+    pub async fn start_config(root: &Path, config: Config) -> TestServer {
+        let addr: SocketAddr = format!("{}:{}", config.server.host, config.server.port).parse().unwrap();
+        let server = Server::new(config);
+        let handle = tokio::spawn(async move {
+            if let Err(e) = server.run().await {
+                panic!("test server failed: {}", e);
+            }
+        });
+        
+        for _ in 0..100 {
+            if TcpStream::connect(addr).await.is_ok() {
+                return TestServer { addr, root: root.to_path_buf(), handle };
+            }
+            assert!(!handle.is_finished(), "test server exited during startup");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("test server did not start listening on {}", addr);
+    }
+    
+    /// URL of a path on the server
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+    
+    /// Send a raw request and read the response until the server closes the connection
+    pub async fn raw(&self, request: &str) -> String {
+        raw_request(self.addr, request.as_bytes()).await
+    }
+    
+    /// GET a path exactly as written, without any client-side URL normalization
+    pub async fn get_raw(&self, path: &str, headers: &str) -> String {
+        self.raw(&format!("GET {} HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n", path, headers)).await
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// Configuration text for a test server
+pub fn config_toml(port: u16, root: &Path, server: &str, static_files: &str, rest: &str) -> String {
+    let root = root.display().to_string().replace('\\', "/");
+    format!(
+        "[server]\nhost = \"127.0.0.1\"\nport = {}\nworkers = 1\n{}\n\n[static_files]\nroot_dir = \"{}\"\n{}\n\n{}\n",
+        port,
+        server,
+        root,
+        static_files,
+        rest.replace("{root}", &root),
+    )
+}
+
+/// Find a port nothing listens on
+pub fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+/// Write a file below a directory, creating its parents
+pub fn write_file(root: &Path, path: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+    let path = root.join(path);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+/// Send raw bytes and read the response until the server closes the connection
+pub async fn raw_request(addr: SocketAddr, request: &[u8]) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(request).await.unwrap();
+    let mut response = Vec::new();
+    tokio::time::timeout(Duration::from_secs(10), stream.read_to_end(&mut response))
+        .await
+        .expect("server did not close the connection")
+        .unwrap();
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+/// Status code of a raw HTTP/1 response
+pub fn status_of(response: &str) -> u16 {
+    response.split(' ').nth(1).and_then(|status| status.parse().ok()).unwrap_or(0)
+}
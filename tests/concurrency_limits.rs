@@ -0,0 +1,65 @@
+//! Per-route concurrency limits reject or queue excess requests without affecting other routes.
+
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use common::{status_of, write_file, TestServer};
+
+fn write_script(root: &Path, path: &str) {
+    let script = write_file(root, path, "#!/bin/sh\nsleep 1\nprintf 'Content-Type: text/plain\\r\\n\\r\\ndone'\n");
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+async fn metrics(server: &TestServer) -> serde_json::Value {
+    let response = server.get_raw("/admin/status", "Authorization: Bearer t0ken\r\n").await;
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    serde_json::from_str::<serde_json::Value>(body).unwrap()["metrics"].clone()
+}
+
+#[tokio::test]
+async fn excess_requests_are_rejected_or_queued_per_route() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "index.html", "home");
+    write_script(root.path(), "cgi-bin/slow.sh");
+    write_script(root.path(), "queued/slow.sh");
+    let rest = "[admin]\nenabled = true\ntoken = \"t0ken\"\n\n\
+                [[cgi]]\npath = \"/cgi-bin/*\"\n\n[[cgi]]\npath = \"/queued/*\"\n\n\
+                [[concurrency_limits]]\npath = \"/cgi-bin/*\"\nmax_concurrent = 1\n\n\
+                [[concurrency_limits]]\npath = \"/queued/*\"\nmax_concurrent = 1\nqueue_timeout = 5000\n";
+    let server = TestServer::start(root.path(), "", "", rest).await;
+    
+    // A second request on a full route is turned away at once, while other routes answer normally
+    let (first, second, other) = tokio::join!(
+        server.get_raw("/cgi-bin/slow.sh", ""),
+        async {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            let started = Instant::now();
+            (server.get_raw("/cgi-bin/slow.sh", "").await, started.elapsed())
+        },
+        async {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            let started = Instant::now();
+            (server.get_raw("/index.html", "").await, started.elapsed())
+        },
+    );
+    assert_eq!(status_of(&first), 200);
+    assert_eq!(status_of(&second.0), 503, "{}", second.0);
+    assert!(second.1 < Duration::from_millis(500));
+    assert_eq!(status_of(&other.0), 200);
+    assert!(other.1 < Duration::from_millis(500));
+    
+    // With a queue, the second request waits for the first to finish
+    let (first, second) = tokio::join!(server.get_raw("/queued/slow.sh", ""), async {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        server.get_raw("/queued/slow.sh", "").await
+    });
+    assert_eq!(status_of(&first), 200);
+    assert_eq!(status_of(&second), 200, "{}", second);
+    
+    let metrics = metrics(&server).await;
+    assert_eq!(metrics["concurrency_rejected"], 1, "{}", metrics);
+    assert_eq!(metrics["concurrency_queued"], 1, "{}", metrics);
+}
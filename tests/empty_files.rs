@@ -0,0 +1,28 @@
+//! Zero-length files are served as empty 200 responses on every path.
+
+mod common;
+
+use common::{status_of, write_file, TestServer};
+
+fn assert_empty_ok(response: &str) {
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert_eq!(status_of(response), 200, "{}", response);
+    assert!(head.to_ascii_lowercase().contains("content-length: 0"), "{}", head);
+    assert!(body.is_empty(), "{:?}", body);
+}
+
+#[tokio::test]
+async fn empty_files_are_empty_200s() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "empty.txt", "");
+    write_file(root.path(), "empty.mp4", "");
+    let server = TestServer::start(root.path(), "", "stream_types = [\"video/\"]", "").await;
+    
+    assert_empty_ok(&server.get_raw("/empty.txt", "").await);
+    assert_empty_ok(&server.get_raw("/empty.txt", "Accept-Encoding: gzip\r\n").await);
+    assert_empty_ok(&server.get_raw("/empty.mp4", "").await);
+    
+    // There is no byte to range over, so the whole (empty) file is served
+    assert_empty_ok(&server.get_raw("/empty.txt", "Range: bytes=0-10\r\n").await);
+    assert_empty_ok(&server.get_raw("/empty.mp4", "Range: bytes=0-10\r\n").await);
+}
@@ -0,0 +1,81 @@
+//! Encoded responses carry their own entity tag and do not advertise ranges.
+
+mod common;
+
+use common::{status_of, write_file, TestServer};
+
+fn header(response: &str, name: &str) -> Option<String> {
+    let head = response.split_once("\r\n\r\n").unwrap().0;
+    head.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim().to_string())
+}
+
+#[tokio::test]
+async fn each_encoding_has_its_own_etag() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "page.html", "<p>compressible</p>\n".repeat(200));
+    let server = TestServer::start(root.path(), "", "", "").await;
+    
+    let identity = server.get_raw("/page.html", "").await;
+    let identity_etag = header(&identity, "etag").unwrap();
+    assert_eq!(header(&identity, "accept-ranges").as_deref(), Some("bytes"));
+    
+    let mut etags = vec![identity_etag.clone()];
+    for encoding in ["br", "gzip", "zstd"] {
+        let response = server.get_raw("/page.html", &format!("Accept-Encoding: {}\r\n", encoding)).await;
+        assert_eq!(header(&response, "content-encoding").as_deref(), Some(encoding), "{}", response);
+        assert_eq!(header(&response, "accept-ranges"), None, "{}", response);
+        let etag = header(&response, "etag").unwrap();
+        assert!(!etags.contains(&etag), "{} reuses {}", encoding, etag);
+        etags.push(etag.clone());
+        
+        // Each tag only validates its own representation
+        let revalidated = server.get_raw("/page.html", &format!("Accept-Encoding: {}\r\nIf-None-Match: {}\r\n", encoding, etag)).await;
+        assert_eq!(status_of(&revalidated), 304, "{}", revalidated);
+        let stale = server.get_raw("/page.html", &format!("Accept-Encoding: {}\r\nIf-None-Match: {}\r\n", encoding, identity_etag)).await;
+        assert_eq!(status_of(&stale), 200, "{}", stale);
+    }
+    
+    // Ranges are served over the original content, under its tag
+    let partial = server.get_raw("/page.html", "Accept-Encoding: br\r\nRange: bytes=0-9\r\n").await;
+    assert_eq!(status_of(&partial), 206, "{}", partial);
+    assert_eq!(header(&partial, "content-encoding"), None);
+    assert_eq!(header(&partial, "etag"), Some(identity_etag));
+}
+
+#[tokio::test]
+async fn compressing_on_the_fly_changes_the_etag() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "small.txt", "small but compressible ".repeat(10));
+    let server = TestServer::start(root.path(), "", "", "").await;
+    
+    let identity = server.get_raw("/small.txt", "Accept-Encoding: gzip\r\n").await;
+    assert_eq!(header(&identity, "content-encoding"), None);
+    let identity_etag = header(&identity, "etag").unwrap();
+    
+    let saved = server.get_raw("/small.txt", "Accept-Encoding: gzip\r\nSave-Data: on\r\n").await;
+    assert_eq!(header(&saved, "content-encoding").as_deref(), Some("gzip"), "{}", saved);
+    assert_eq!(header(&saved, "accept-ranges"), None, "{}", saved);
+    let etag = header(&saved, "etag").unwrap();
+    assert_ne!(etag, identity_etag);
+    
+    let revalidated = server.get_raw("/small.txt", &format!("Accept-Encoding: gzip\r\nSave-Data: on\r\nIf-None-Match: {}\r\n", etag)).await;
+    assert_eq!(status_of(&revalidated), 304, "{}", revalidated);
+}
+
+#[tokio::test]
+async fn streamed_encoded_responses_do_not_advertise_ranges() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "page.html", "<p>compressible</p>\n".repeat(200));
+    let server = TestServer::start(root.path(), "", "stream_types = [\"text/html\"]", "").await;
+    
+    let identity = server.get_raw("/page.html", "").await;
+    assert_eq!(header(&identity, "accept-ranges").as_deref(), Some("bytes"));
+    
+    let encoded = server.get_raw("/page.html", "Accept-Encoding: gzip\r\n").await;
+    assert_eq!(header(&encoded, "content-encoding").as_deref(), Some("gzip"), "{}", encoded);
+    assert_eq!(header(&encoded, "accept-ranges"), None, "{}", encoded);
+    assert_ne!(header(&encoded, "etag"), header(&identity, "etag"));
+}
@@ -0,0 +1,66 @@
+//! A failing or panicking request is answered on its own, and the connection keeps serving.
+
+mod common;
+
+use std::time::Duration;
+
+use hyper::{Body, Request, Response};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use common::{config_toml, free_port, write_file};
+use kaserve::{Config, Server, ServiceHandler};
+
+/// Status codes of the responses in a raw HTTP/1 exchange, in order
+fn statuses(exchange: &str) -> Vec<u16> {
+    exchange
+        .match_indices("HTTP/1.1 ")
+        .filter_map(|(at, _)| exchange[at + 9..at + 12].parse().ok())
+        .collect()
+}
+
+#[tokio::test]
+async fn errors_and_panics_do_not_end_the_connection() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "index.html", "home");
+    let port = free_port();
+    let config = Config::from_toml(&config_toml(port, root.path(), "", "", "")).unwrap();
+    let server = Server::builder(config)
+        .service("/boom", ServiceHandler::from_fn(|_req: Request<Body>| async {
+            if true {
+                panic!("handler bug");
+            }
+            Ok::<_, hyper::Error>(Response::new(Body::empty()))
+        }))
+        .service("/fail", ServiceHandler::from_fn(|_req: Request<Body>| async {
+            Err::<Response<Body>, _>(std::io::Error::other("backend unavailable"))
+        }))
+        .build()
+        .unwrap();
+    let handle = tokio::spawn(server.run());
+    
+    let mut stream = loop {
+        match TcpStream::connect(("127.0.0.1", port)).await {
+            Ok(stream) => break stream,
+            Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+        }
+    };
+    
+    // Each request is sent only after the previous response, on the same connection
+    let mut exchange = String::new();
+    for (path, last) in [("/boom", false), ("/fail", false), ("/missing.html", false), ("/index.html", true)] {
+        let connection = if last { "close" } else { "keep-alive" };
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: {}\r\n\r\n", path, connection);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        
+        let mut buffer = vec![0u8; 65536];
+        let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buffer)).await.unwrap().unwrap();
+        assert!(read > 0, "connection closed before answering {}", path);
+        exchange.push_str(&String::from_utf8_lossy(&buffer[..read]));
+    }
+    
+    assert_eq!(statuses(&exchange), [500, 500, 404, 200], "{}", exchange);
+    assert!(exchange.ends_with("home"));
+    assert!(!exchange.contains("handler bug") && !exchange.contains("backend unavailable"));
+    handle.abort();
+}
@@ -0,0 +1,34 @@
+//! Errors from every handler are rendered with the configured error page template.
+
+mod common;
+
+use common::{free_port, raw_request, status_of, write_file, TestServer};
+
+#[tokio::test]
+async fn handler_errors_use_the_configured_template() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "index.html", "home");
+    let template = write_file(root.path(), "errors/template.html", "<p class=\"error\">{{status}} {{reason}}: {{message}}</p>");
+    let rest = format!(
+        "[error_pages]\ntemplate = \"{}\"\n\n\
+         [admin]\nenabled = true\ntoken = \"t0ken\"\n\n\
+         [proxy]\n[[proxy.pools]]\nname = \"api\"\npath = \"/api/*\"\nservers = [\"http://127.0.0.1:{}\"]\n",
+        template.display(),
+        free_port(),
+    );
+    let server = TestServer::start(root.path(), "", "", &rest).await;
+    
+    let delete = "DELETE /index.html HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+    let responses = [
+        (server.get_raw("/missing.html", "").await, 404, "Not Found"),
+        (server.get_raw("/admin/status", "").await, 401, "Unauthorized"),
+        (server.get_raw("/api/users", "").await, 502, "Bad Gateway"),
+        (raw_request(server.addr, delete.as_bytes()).await, 405, "Method Not Allowed"),
+    ];
+    
+    for (response, status, reason) in responses {
+        assert_eq!(status_of(&response), status, "{}", response);
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(body.starts_with(&format!("<p class=\"error\">{} {}: ", status, reason)), "{}", body);
+    }
+}
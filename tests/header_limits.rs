@@ -0,0 +1,28 @@
+//! Requests with more headers than `server.max_headers` are answered with 431.
+
+mod common;
+
+use common::{status_of, write_file, TestServer};
+
+/// `count` distinct headers, one per line
+fn headers(count: usize) -> String {
+    (0..count).map(|i| format!("X-Flood-{}: {}\r\n", i, i)).collect()
+}
+
+#[tokio::test]
+async fn header_floods_are_refused() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "index.html", "home");
+    let server = TestServer::start(root.path(), "max_headers = 10", "", "").await;
+    
+    // Host and Connection count towards the limit
+    assert_eq!(status_of(&server.get_raw("/", &headers(8)).await), 200);
+    assert_eq!(status_of(&server.get_raw("/", &headers(9)).await), 431);
+    
+    // Repeated names count once per line
+    let repeated = "X-Same: 1\r\n".repeat(20);
+    assert_eq!(status_of(&server.get_raw("/", &repeated).await), 431);
+    
+    // Beyond what the parser accepts at all
+    assert_eq!(status_of(&server.get_raw("/", &headers(200)).await), 431);
+}
@@ -0,0 +1,30 @@
+//! `page.html.<language>` variants are served in the language the client prefers.
+
+mod common;
+
+use common::{status_of, write_file, TestServer};
+
+#[tokio::test]
+async fn variants_follow_accept_language() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "page.html.en", "hello");
+    write_file(root.path(), "page.html.fr", "bonjour");
+    write_file(root.path(), "docs/index.html.de", "hallo");
+    let server = TestServer::start(root.path(), "", "negotiate_language = true\ndefault_language = \"en\"", "").await;
+    
+    let response = server.get_raw("/page.html", "Accept-Language: fr-CH, fr;q=0.9, en;q=0.5\r\n").await;
+    assert_eq!(status_of(&response), 200);
+    assert!(response.to_ascii_lowercase().contains("content-language: fr"), "{}", response);
+    assert!(response.ends_with("bonjour"));
+    
+    // Without an acceptable variant the default language is served
+    let response = server.get_raw("/page.html", "Accept-Language: ja\r\n").await;
+    assert!(response.ends_with("hello"), "{}", response);
+    
+    // A directory's default file may exist only as variants
+    let response = server.get_raw("/docs/", "").await;
+    assert_eq!(status_of(&response), 200);
+    assert!(response.ends_with("hallo"), "{}", response);
+    
+    assert_eq!(status_of(&server.get_raw("/missing.html", "Accept-Language: fr\r\n").await), 404);
+}
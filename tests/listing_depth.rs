@@ -0,0 +1,27 @@
+//! Directory listings are refused below `static_files.max_listing_depth`.
+
+mod common;
+
+use common::{status_of, write_file, TestServer};
+
+#[tokio::test]
+async fn listings_deeper_than_the_limit_are_forbidden() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "a/top.txt", "top");
+    write_file(root.path(), "a/b/deep.txt", "deep");
+    let server = TestServer::start(root.path(), "", "directory_listing = true\nmax_listing_depth = 1", "").await;
+    
+    let response = server.get_raw("/", "").await;
+    assert_eq!(status_of(&response), 200, "{}", response);
+    
+    let response = server.get_raw("/a/", "").await;
+    assert_eq!(status_of(&response), 200, "{}", response);
+    assert!(response.contains("top.txt"));
+    
+    assert_eq!(status_of(&server.get_raw("/a/b/", "").await), 403);
+    
+    // Files stay reachable at any depth
+    let response = server.get_raw("/a/b/deep.txt", "").await;
+    assert_eq!(status_of(&response), 200);
+    assert!(response.ends_with("deep"));
+}
@@ -0,0 +1,70 @@
+//! Memory-mapped files changed in place end their responses instead of crashing the server.
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use rustls::{Certificate, ClientConfig, RootCertStore, ServerName};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+
+use common::{write_file, TestServer};
+
+#[tokio::test]
+async fn truncating_a_mapped_file_mid_stream_cuts_the_response_short() {
+    let root = tempfile::tempdir().unwrap();
+    let len = 32 * 1024 * 1024;
+    let path = write_file(root.path(), "video.bin", vec![b'v'; len]);
+    
+    // TLS encrypts the body in the server process, reading every mapped page itself
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let cert_file = write_file(root.path(), "tls/cert.pem", cert.serialize_pem().unwrap());
+    let key_file = write_file(root.path(), "tls/key.pem", cert.serialize_private_key_pem());
+    let tls = format!("[tls]\nenabled = true\ncert_file = \"{}\"\nkey_file = \"{}\"\n", cert_file.display(), key_file.display());
+    let server = TestServer::start(root.path(), "", "mmap_threshold = 1024", &tls).await;
+    
+    let mut roots = RootCertStore::empty();
+    roots.add(&Certificate(cert.serialize_der().unwrap())).unwrap();
+    let config = ClientConfig::builder().with_safe_defaults().with_root_certificates(roots).with_no_client_auth();
+    let tcp = TcpStream::connect(server.addr).await.unwrap();
+    let mut stream = TlsConnector::from(Arc::new(config))
+        .connect(ServerName::try_from("localhost").unwrap(), tcp)
+        .await
+        .unwrap();
+    stream.write_all(b"GET /video.bin HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await.unwrap();
+    let mut received = vec![0; 64 * 1024];
+    stream.read_exact(&mut received).await.unwrap();
+    assert!(String::from_utf8_lossy(&received).starts_with("HTTP/1.1 200"));
+    
+    // Let the server fill the socket buffers and wait for the client, then truncate
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    std::fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(0).unwrap();
+    
+    let mut rest = Vec::new();
+    let read = tokio::time::timeout(Duration::from_secs(10), stream.read_to_end(&mut rest)).await.unwrap();
+    assert!(read.is_err() || received.len() + rest.len() < len, "the whole file was sent");
+    
+    // The server is still up and serves the file at its new size
+    let client = reqwest::Client::builder().danger_accept_invalid_certs(true).build().unwrap();
+    let response = client.get(format!("https://localhost:{}/video.bin", server.addr.port())).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.bytes().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn unchanged_mapped_files_are_served_whole() {
+    let root = tempfile::tempdir().unwrap();
+    let content: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+    write_file(root.path(), "archive.bin", &content);
+    let server = TestServer::start(root.path(), "", "mmap_threshold = 1024", "").await;
+    let client = reqwest::Client::new();
+    
+    // The second request is served from the kept mapping
+    for _ in 0..2 {
+        let response = client.get(server.url("/archive.bin")).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.bytes().await.unwrap().as_ref(), content.as_slice());
+    }
+}
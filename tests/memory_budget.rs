@@ -0,0 +1,41 @@
+//! Buffered bodies are accounted against `server.memory_budget`; requests that
+//! would exceed it are answered with 503 until memory is released.
+
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+use std::time::Duration;
+
+use common::{status_of, write_file, TestServer};
+
+/// A script printing about 700 KB, then holding its output buffered for a while
+const LARGE_OUTPUT_SCRIPT: &str = "#!/bin/sh\n\
+    printf 'Content-Type: text/plain\\r\\n\\r\\n'\n\
+    head -c 700000 /dev/zero | tr '\\000' a\n\
+    sleep 1\n";
+
+#[tokio::test]
+async fn concurrent_buffered_responses_beyond_the_budget_get_503() {
+    let root = tempfile::tempdir().unwrap();
+    let script = write_file(root.path(), "cgi-bin/large.sh", LARGE_OUTPUT_SCRIPT);
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let server = TestServer::start(root.path(), "memory_budget = 1", "", "[[cgi]]\npath = \"/cgi-bin/*\"\n").await;
+    
+    // The first script's output holds most of the 1 MB budget until it exits
+    let first = tokio::spawn({
+        let addr = server.addr;
+        async move { common::raw_request(addr, b"GET /cgi-bin/large.sh HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await }
+    });
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    
+    let second = server.get_raw("/cgi-bin/large.sh", "").await;
+    assert_eq!(status_of(&second), 503);
+    assert!(second.contains("retry-after: 1"));
+    
+    let first = first.await.unwrap();
+    assert_eq!(status_of(&first), 200);
+    assert!(first.ends_with(&"a".repeat(1000)));
+    
+    // The reservation is released once the first response has been sent
+    assert_eq!(status_of(&server.get_raw("/cgi-bin/large.sh", "").await), 200);
+}
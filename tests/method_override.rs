@@ -0,0 +1,52 @@
+//! POST requests can stand in for PUT through the override header, when enabled.
+
+mod common;
+
+use common::{raw_request, status_of, TestServer};
+
+async fn start(uploads: &std::path::Path, method_override: &str) -> TestServer {
+    let root = tempfile::tempdir().unwrap();
+    let rest = format!(
+        "{}\n\n[auth]\npaths = [\"/upload/*\"]\ntokens = [\"t0ken\"]\n\n\
+         [[upload]]\npath = \"/upload/*\"\ndirectory = \"{}\"\nmethods = [\"PUT\"]\n",
+        method_override,
+        uploads.display(),
+    );
+    TestServer::start(root.path(), "", "", &rest).await
+}
+
+async fn post(server: &TestServer, path: &str, override_header: &str) -> String {
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer t0ken\r\n{}Content-Length: 5\r\nConnection: close\r\n\r\nhello",
+        path,
+        override_header,
+    );
+    raw_request(server.addr, request.as_bytes()).await
+}
+
+#[tokio::test]
+async fn override_header_reaches_put_only_when_enabled() {
+    let uploads = tempfile::tempdir().unwrap();
+    let server = start(uploads.path(), "[method_override]\nenabled = true\nallowed_methods = [\"PUT\"]").await;
+    
+    let response = post(&server, "/upload/a.txt", "X-HTTP-Method-Override: put\r\n").await;
+    assert_eq!(status_of(&response), 201, "{}", response);
+    assert_eq!(std::fs::read_to_string(uploads.path().join("a.txt")).unwrap(), "hello");
+    
+    // Methods outside the allowed list are not honoured
+    let response = post(&server, "/upload/b.txt", "X-HTTP-Method-Override: DELETE\r\n").await;
+    assert_eq!(status_of(&response), 405, "{}", response);
+    
+    let response = post(&server, "/upload/c.txt", "").await;
+    assert_eq!(status_of(&response), 405, "{}", response);
+}
+
+#[tokio::test]
+async fn override_header_is_ignored_when_disabled() {
+    let uploads = tempfile::tempdir().unwrap();
+    let server = start(uploads.path(), "").await;
+    
+    let response = post(&server, "/upload/a.txt", "X-HTTP-Method-Override: PUT\r\n").await;
+    assert_eq!(status_of(&response), 405, "{}", response);
+    assert!(!uploads.path().join("a.txt").exists());
+}
@@ -0,0 +1,35 @@
+//! Content sniffing identifies files whose extension says nothing about them.
+
+mod common;
+
+use common::{write_file, TestServer};
+
+/// PNG signature followed by the start of an IHDR chunk
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR\x00\x00\x00\x01\x00\x00\x00\x01\x08\x02\x00\x00\x00";
+
+async fn content_type(sniffing: &str, path: &str) -> String {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "image.dat", PNG);
+    write_file(root.path(), "image.txt", PNG);
+    let server = TestServer::start(root.path(), "", &format!("mime_sniffing = \"{}\"", sniffing), "").await;
+    
+    let response = server.get_raw(path, "").await;
+    let (head, _) = response.split_once("\r\n\r\n").unwrap();
+    head.lines()
+        .find_map(|line| line.strip_prefix("content-type: "))
+        .unwrap_or_else(|| panic!("no content type: {}", head))
+        .to_string()
+}
+
+#[tokio::test]
+async fn png_content_is_detected_only_when_sniffing() {
+    assert_eq!(content_type("off", "/image.dat").await, "application/octet-stream");
+    assert_eq!(content_type("fallback", "/image.dat").await, "image/png");
+    assert_eq!(content_type("always", "/image.dat").await, "image/png");
+}
+
+#[tokio::test]
+async fn fallback_sniffing_keeps_known_extensions() {
+    assert!(content_type("fallback", "/image.txt").await.starts_with("text/plain"));
+    assert_eq!(content_type("always", "/image.txt").await, "image/png");
+}
@@ -0,0 +1,64 @@
+//! Traversal out of the document root is refused however the path is spelled.
+
+mod common;
+
+use common::{status_of, write_file, TestServer};
+
+#[tokio::test]
+async fn encoded_and_backslash_traversal_gets_403() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "index.html", "home");
+    let server = TestServer::start(root.path(), "", "", "").await;
+    
+    for path in [
+        "/../etc/passwd",
+        "/%2e%2e/etc/passwd",
+        "/%2E%2E/%2E%2E/etc/passwd",
+        "/..\\..\\etc\\passwd",
+        "/..%5c..%5cetc%5cpasswd",
+        "/a/%2e%2e%2f%2e%2e%2fetc/passwd",
+    ] {
+        let response = server.get_raw(path, "").await;
+        assert_eq!(status_of(&response), 403, "{}: {}", path, response);
+        assert!(!response.contains("root:"), "{}", response);
+    }
+    
+    // A smuggled separator without traversal is merely malformed
+    assert_eq!(status_of(&server.get_raw("/a%2fb", "").await), 400);
+}
+
+#[tokio::test]
+async fn symlink_escape_gets_403() {
+    let outside = tempfile::tempdir().unwrap();
+    write_file(outside.path(), "secret.txt", "top secret");
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "inside.txt", "inside");
+    std::os::unix::fs::symlink(outside.path().join("secret.txt"), root.path().join("escape.txt")).unwrap();
+    std::os::unix::fs::symlink(outside.path(), root.path().join("elsewhere")).unwrap();
+    std::os::unix::fs::symlink(root.path().join("inside.txt"), root.path().join("alias.txt")).unwrap();
+    let server = TestServer::start(root.path(), "", "", "").await;
+    
+    for path in ["/escape.txt", "/elsewhere/secret.txt"] {
+        let response = server.get_raw(path, "").await;
+        assert_eq!(status_of(&response), 403, "{}: {}", path, response);
+        assert!(!response.contains("top secret"), "{}", response);
+    }
+    
+    // Links that stay inside the root are served
+    let response = server.get_raw("/alias.txt", "").await;
+    assert_eq!(status_of(&response), 200, "{}", response);
+    assert!(response.ends_with("inside"), "{}", response);
+}
+
+#[tokio::test]
+async fn followed_symlinks_may_leave_the_root() {
+    let outside = tempfile::tempdir().unwrap();
+    write_file(outside.path(), "shared.txt", "shared");
+    let root = tempfile::tempdir().unwrap();
+    std::os::unix::fs::symlink(outside.path().join("shared.txt"), root.path().join("shared.txt")).unwrap();
+    let server = TestServer::start(root.path(), "", "follow_symlinks = true", "").await;
+    
+    let response = server.get_raw("/shared.txt", "").await;
+    assert_eq!(status_of(&response), 200, "{}", response);
+    assert!(response.ends_with("shared"), "{}", response);
+}
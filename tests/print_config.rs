@@ -0,0 +1,55 @@
+//! `kaserve print-config` prints the effective configuration, defaults included.
+
+use std::process::Command;
+
+use kaserve::core::config::Config;
+use kaserve::utils::etag::EtagStrategy;
+
+fn print_config(config: &str, args: &[&str]) -> String {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, config).unwrap();
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_kaserve"))
+        .arg("print-config")
+        .arg("--config")
+        .arg(&path)
+        .args(args)
+        .env_clear()
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn unset_settings_are_printed_with_their_defaults() {
+    let printed = print_config("[server]\nhost = \"127.0.0.1\"\nport = 8080\n\n[static_files]\nroot_dir = \"./site\"\n", &[]);
+    let config = Config::from_toml(&printed).unwrap();
+    
+    assert_eq!(config.server.port, 8080);
+    assert_eq!(config.static_files.root_dir, "./site");
+    assert_eq!(config.server.connection_timeout, Some(60));
+    assert_eq!(config.static_files.default_file.as_deref(), Some("index.html"));
+    assert_eq!(config.static_files.trailing_slash_redirect, Some(true));
+    assert_eq!(config.static_files.precompressed, Some(true));
+    assert_eq!(config.static_files.etag, Some(EtagStrategy::Mtime));
+    assert_eq!(config.compression.and_then(|compression| compression.gzip_level), Some(6));
+    
+    // Limits that are off when unset have no value to print
+    assert_eq!(config.server.request_timeout, None);
+    assert_eq!(config.static_files.cache_size, None);
+}
+
+#[test]
+fn file_and_command_line_settings_win_over_defaults() {
+    let printed = print_config(
+        "[server]\nhost = \"127.0.0.1\"\nport = 8080\nconnection_timeout = 5\n\n[static_files]\nroot_dir = \"./site\"\n",
+        &["-s", "server.workers=7", "-s", "static_files.precompressed=false"],
+    );
+    let config = Config::from_toml(&printed).unwrap();
+    
+    assert_eq!(config.server.connection_timeout, Some(5));
+    assert_eq!(config.server.workers, Some(7));
+    assert_eq!(config.static_files.precompressed, Some(false));
+}
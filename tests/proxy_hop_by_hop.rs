@@ -0,0 +1,65 @@
+//! Hop-by-hop headers stop at the proxy in both directions.
+
+mod common;
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response};
+
+use common::{raw_request, status_of, write_file, TestServer};
+
+/// Start an upstream listing the request headers it received and answering with hop-by-hop headers
+async fn start_upstream() -> SocketAddr {
+    let make_service = make_service_fn(|_| async {
+        Ok::<_, Infallible>(service_fn(|req: Request<Body>| async move {
+            let mut names: Vec<&str> = req.headers().keys().map(|name| name.as_str()).collect();
+            names.sort();
+            let response = Response::builder()
+                .header("keep-alive", "timeout=5")
+                .header("proxy-authenticate", "Basic")
+                .header("connection", "x-upstream-private")
+                .header("x-upstream-private", "secret")
+                .header("x-upstream-kept", "yes")
+                .body(Body::from(names.join(",")))
+                .unwrap();
+            Ok::<_, Infallible>(response)
+        }))
+    });
+    let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    addr
+}
+
+#[tokio::test]
+async fn hop_by_hop_headers_are_stripped_both_ways() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "index.html", "home");
+    let upstream = start_upstream().await;
+    let rest = format!("[proxy]\n[[proxy.pools]]\nname = \"api\"\npath = \"/api/*\"\nservers = [\"http://{}\"]\n", upstream);
+    let server = TestServer::start(root.path(), "", "", &rest).await;
+    
+    let request = "GET /api/headers HTTP/1.1\r\nHost: localhost\r\n\
+                   Keep-Alive: timeout=5\r\nProxy-Authorization: Basic Zm9vOmJhcg==\r\nTE: gzip\r\n\
+                   Proxy-Connection: keep-alive\r\nX-Client-Private: secret\r\nX-Client-Kept: yes\r\n\
+                   Connection: close, X-Client-Private\r\n\r\n";
+    let response = raw_request(server.addr, request.as_bytes()).await;
+    assert_eq!(status_of(&response), 200, "{}", response);
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let head = head.to_ascii_lowercase();
+    
+    // Upstream side: only end-to-end headers were forwarded
+    let forwarded: Vec<&str> = body.split(',').collect();
+    assert!(forwarded.contains(&"x-client-kept"), "{}", body);
+    for name in ["keep-alive", "proxy-authorization", "te", "proxy-connection", "x-client-private"] {
+        assert!(!forwarded.contains(&name), "{} was forwarded: {}", name, body);
+    }
+    
+    // Client side: the upstream's hop-by-hop headers were not relayed
+    assert!(head.contains("x-upstream-kept: yes"), "{}", head);
+    for name in ["keep-alive:", "proxy-authenticate:", "x-upstream-private:"] {
+        assert!(!head.contains(name), "{} was relayed: {}", name, head);
+    }
+}
@@ -0,0 +1,73 @@
+//! Pools with `compress_requests` gzip request bodies as they stream to the upstream.
+
+mod common;
+
+use std::convert::Infallible;
+use std::io::Read;
+use std::net::SocketAddr;
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response};
+
+use common::{raw_request, status_of, write_file, TestServer};
+
+/// Start an upstream describing the encoding, length and decoded content of each request body
+async fn start_upstream() -> SocketAddr {
+    let make_service = make_service_fn(|_| async {
+        Ok::<_, Infallible>(service_fn(|req: Request<Body>| async move {
+            let header = |name: &str| req.headers().get(name).map_or("none".to_string(), |v| v.to_str().unwrap().to_string());
+            let (encoding, length) = (header("content-encoding"), header("content-length"));
+            let data = hyper::body::to_bytes(req.into_body()).await.unwrap();
+            let content = match encoding.as_str() {
+                "gzip" => {
+                    let mut decoded = String::new();
+                    flate2::read::GzDecoder::new(&data[..]).read_to_string(&mut decoded).unwrap();
+                    decoded
+                }
+                _ => String::from_utf8(data.to_vec()).unwrap(),
+            };
+            let description = format!("encoding={} length={} content={}", encoding, length, content);
+            Ok::<_, Infallible>(Response::new(Body::from(description)))
+        }))
+    });
+    let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    addr
+}
+
+async fn post(server: &TestServer, path: &str, body: &str) -> String {
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        body.len(),
+        body,
+    );
+    raw_request(server.addr, request.as_bytes()).await
+}
+
+#[tokio::test]
+async fn request_bodies_are_gzipped_only_when_enabled() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "index.html", "home");
+    let upstream = start_upstream().await;
+    let rest = format!(
+        "[proxy]\n\
+         [[proxy.pools]]\nname = \"gzip\"\npath = \"/gzip/*\"\nservers = [\"http://{upstream}\"]\ncompress_requests = true\n\
+         [[proxy.pools]]\nname = \"raw\"\npath = \"/raw/*\"\nservers = [\"http://{upstream}\"]\n",
+    );
+    let server = TestServer::start(root.path(), "", "", &rest).await;
+    let body = "hello upstream ".repeat(100);
+    
+    let response = post(&server, "/gzip/echo", &body).await;
+    assert_eq!(status_of(&response), 200);
+    assert!(response.ends_with(&format!("encoding=gzip length=none content={}", body)), "{}", response);
+    
+    let response = post(&server, "/raw/echo", &body).await;
+    assert_eq!(status_of(&response), 200);
+    assert!(response.ends_with(&format!("encoding=none length={} content={}", body.len(), body)), "{}", response);
+    
+    // An empty body has nothing to compress
+    let response = post(&server, "/gzip/echo", "").await;
+    assert!(response.ends_with("encoding=none length=0 content="), "{}", response);
+}
@@ -0,0 +1,64 @@
+//! Trailers from an HTTP/2 upstream, such as gRPC's status, reach HTTP/2 clients.
+
+mod common;
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use hyper::body::HttpBody;
+use hyper::header::HeaderValue;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Client, Request, Response};
+
+use common::{write_file, TestServer};
+
+/// Start an h2c upstream answering every request with a body and trailers.
+///
+/// The trailers echo the request's `TE` header, so tests can see it was forwarded.
+async fn start_upstream() -> SocketAddr {
+    let make_service = make_service_fn(|_| async {
+        Ok::<_, Infallible>(service_fn(|req: Request<Body>| async move {
+            let te = req.headers().get("te").cloned().unwrap_or(HeaderValue::from_static("none"));
+            let (mut sender, body) = Body::channel();
+            tokio::spawn(async move {
+                sender.send_data("payload".into()).await.unwrap();
+                let mut trailers = hyper::HeaderMap::new();
+                trailers.insert("grpc-status", HeaderValue::from_static("0"));
+                trailers.insert("x-upstream-te", te);
+                sender.send_trailers(trailers).await.unwrap();
+            });
+            Ok::<_, Infallible>(Response::new(body))
+        }))
+    });
+    let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).http2_only(true).serve(make_service);
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    addr
+}
+
+#[tokio::test]
+async fn upstream_trailers_reach_the_client() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "index.html", "home");
+    let upstream = start_upstream().await;
+    let rest = format!(
+        "[proxy]\n[[proxy.pools]]\nname = \"grpc\"\npath = \"/grpc/*\"\nservers = [\"http://{}\"]\nhttp2 = true\n",
+        upstream,
+    );
+    let server = TestServer::start(root.path(), "http2 = true", "", &rest).await;
+    
+    let client: Client<_, Body> = Client::builder().http2_only(true).build_http();
+    let request = Request::get(server.url("/grpc/Echo")).header("te", "trailers").body(Body::empty()).unwrap();
+    let mut response = client.request(request).await.unwrap();
+    
+    assert_eq!(response.status(), 200);
+    let body = response.body_mut();
+    let mut data = Vec::new();
+    while let Some(chunk) = body.data().await {
+        data.extend_from_slice(&chunk.unwrap());
+    }
+    assert_eq!(data, b"payload");
+    let trailers = body.trailers().await.unwrap().expect("trailers were dropped");
+    assert_eq!(trailers["grpc-status"], "0");
+    assert_eq!(trailers["x-upstream-te"], "trailers");
+}
@@ -0,0 +1,62 @@
+//! The response timeout answers 504 before headers and cleanly truncates bodies after them.
+
+mod common;
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response};
+
+use common::{status_of, write_file, TestServer};
+
+/// Start an upstream that is slow to answer `/late` and slow to finish `/stream`
+async fn start_upstream() -> SocketAddr {
+    let make_service = make_service_fn(|_| async {
+        Ok::<_, Infallible>(service_fn(|req: Request<Body>| async move {
+            if req.uri().path() == "/late" {
+                tokio::time::sleep(Duration::from_secs(3)).await;
+                return Ok::<_, Infallible>(Response::new(Body::from("late")));
+            }
+            
+            let (mut sender, body) = Body::channel();
+            tokio::spawn(async move {
+                sender.send_data("first part;".into()).await.unwrap();
+                tokio::time::sleep(Duration::from_secs(3)).await;
+                let _ = sender.send_data("second part".into()).await;
+            });
+            Ok::<_, Infallible>(Response::new(body))
+        }))
+    });
+    let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    addr
+}
+
+#[tokio::test]
+async fn slow_responses_are_cut_off() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "index.html", "home");
+    let upstream = start_upstream().await;
+    let rest = format!("[proxy]\n[[proxy.pools]]\nname = \"api\"\npath = \"/*\"\npriority = 1\nservers = [\"http://{}\"]\n", upstream);
+    let server = TestServer::start(root.path(), "response_timeout = 1", "", &rest).await;
+    
+    // Nothing was sent yet, so the client still learns about the timeout
+    let started = Instant::now();
+    let response = server.get_raw("/late", "").await;
+    assert_eq!(status_of(&response), 504, "{}", response);
+    assert!(started.elapsed() < Duration::from_secs(2));
+    
+    // Headers are out: the data produced so far arrives and the chunked body ends normally
+    let started = Instant::now();
+    let response = server.get_raw("/stream", "").await;
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_eq!(status_of(&response), 200, "{}", response);
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.to_ascii_lowercase().contains("transfer-encoding: chunked"), "{}", head);
+    assert!(body.contains("first part;"), "{:?}", body);
+    assert!(!body.contains("second part"), "{:?}", body);
+    assert!(body.ends_with("0\r\n\r\n"), "{:?}", body);
+}
@@ -0,0 +1,37 @@
+//! Each route's timeout is enforced on its own and takes precedence over the global one.
+
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use common::{status_of, write_file, TestServer};
+
+fn write_script(root: &Path, path: &str, body: &str) {
+    let script = write_file(root, path, format!("#!/bin/sh\n{}printf 'Content-Type: text/plain\\r\\n\\r\\ndone'\n", body));
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[tokio::test]
+async fn routes_enforce_their_own_timeouts() {
+    let root = tempfile::tempdir().unwrap();
+    let marker = root.path().join("finished");
+    let slow = format!("sleep 2\ntouch '{}'\n", marker.display());
+    write_script(root.path(), "quick/slow.sh", &slow);
+    write_script(root.path(), "patient/slow.sh", "sleep 2\n");
+    let rest = "[[cgi]]\npath = \"/quick/*\"\ntimeout = 1\n\n[[cgi]]\npath = \"/patient/*\"\ntimeout = 5\n";
+    let server = TestServer::start(root.path(), "request_timeout = 1", "", rest).await;
+    
+    let started = Instant::now();
+    assert_eq!(status_of(&server.get_raw("/quick/slow.sh", "").await), 504);
+    assert!(started.elapsed() < Duration::from_secs(2));
+    
+    // The longer route timeout wins over the shorter global one
+    let response = server.get_raw("/patient/slow.sh", "").await;
+    assert_eq!(status_of(&response), 200, "{}", response);
+    assert!(response.ends_with("done"));
+    
+    // The timed-out handler was cancelled along with its script
+    assert!(!marker.exists());
+}
@@ -0,0 +1,44 @@
+//! `Save-Data: on` compresses small responses and selects `.min` image variants.
+
+mod common;
+
+use common::{status_of, write_file, TestServer};
+
+fn head_of(response: &str) -> String {
+    response.split_once("\r\n\r\n").unwrap().0.to_ascii_lowercase()
+}
+
+#[tokio::test]
+async fn save_data_compresses_small_responses() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "small.txt", "small but compressible ".repeat(10));
+    let server = TestServer::start(root.path(), "", "", "").await;
+    
+    let head = head_of(&server.get_raw("/small.txt", "Accept-Encoding: gzip\r\n").await);
+    assert!(!head.contains("content-encoding"), "{}", head);
+    assert!(head.contains("vary: save-data"), "{}", head);
+    
+    let head = head_of(&server.get_raw("/small.txt", "Accept-Encoding: gzip\r\nSave-Data: on\r\n").await);
+    assert!(head.contains("content-encoding: gzip"), "{}", head);
+    assert!(head.contains("save-data"), "{}", head);
+    
+    // Any other value is no request to save data
+    let head = head_of(&server.get_raw("/small.txt", "Accept-Encoding: gzip\r\nSave-Data: off\r\n").await);
+    assert!(!head.contains("content-encoding"), "{}", head);
+}
+
+#[tokio::test]
+async fn save_data_selects_min_image_variants() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "photo.jpg", "full quality");
+    write_file(root.path(), "photo.min.jpg", "low quality");
+    let server = TestServer::start(root.path(), "", "save_data_variants = true", "").await;
+    
+    let response = server.get_raw("/photo.jpg", "").await;
+    assert!(response.ends_with("full quality"));
+    assert!(head_of(&response).contains("save-data"));
+    
+    let response = server.get_raw("/photo.jpg", "Save-Data: on\r\n").await;
+    assert_eq!(status_of(&response), 200);
+    assert!(response.ends_with("low quality"), "{}", response);
+}
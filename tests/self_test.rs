@@ -0,0 +1,45 @@
+//! The startup self-test reports unreachable backends and fails startup in strict mode.
+
+mod common;
+
+use std::net::TcpListener;
+
+use common::{config_toml, free_port};
+use kaserve::core::selftest;
+use kaserve::{Config, Server};
+
+fn proxy_to(upstream: u16) -> String {
+    format!("[proxy]\n[[proxy.pools]]\nname = \"api\"\npath = \"/api/*\"\nservers = [\"http://127.0.0.1:{}\"]\n", upstream)
+}
+
+#[tokio::test]
+async fn self_test_reports_a_dead_proxy_upstream() {
+    let root = tempfile::tempdir().unwrap();
+    let config = Config::from_toml(&config_toml(free_port(), root.path(), "", "", &proxy_to(free_port()))).unwrap();
+    
+    let report = selftest::run(&config).await;
+    assert!(!report.is_ok());
+    assert_eq!(report.failures.len(), 1, "{:?}", report.failures);
+    assert!(report.failures[0].starts_with("proxy.pools[api]: cannot connect"), "{:?}", report.failures);
+}
+
+#[tokio::test]
+async fn self_test_passes_with_a_live_upstream() {
+    let root = tempfile::tempdir().unwrap();
+    let upstream = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = upstream.local_addr().unwrap().port();
+    let config = Config::from_toml(&config_toml(free_port(), root.path(), "", "", &proxy_to(port))).unwrap();
+    
+    let report = selftest::run(&config).await;
+    assert!(report.is_ok(), "{:?}", report.failures);
+}
+
+#[tokio::test]
+async fn strict_self_test_stops_startup() {
+    let root = tempfile::tempdir().unwrap();
+    let server = "self_test = true\nself_test_strict = true";
+    let config = Config::from_toml(&config_toml(free_port(), root.path(), server, "", &proxy_to(free_port()))).unwrap();
+    
+    let error = Server::new(config).run().await.unwrap_err();
+    assert!(error.to_string().contains("proxy.pools[api]"), "{}", error);
+}
@@ -0,0 +1,84 @@
+//! A server instance runs at most once, and its plugins are initialized once.
+
+mod common;
+
+use std::error::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common::{config_toml, free_port};
+use kaserve::plugins::api::{Plugin, PluginContext};
+use kaserve::{Config, Server, ServerError, ServerState};
+use tokio::net::TcpStream;
+
+/// Plugin counting how often it is initialized
+struct CountingPlugin {
+    inits: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl Plugin for CountingPlugin {
+    fn name(&self) -> &str {
+        "counting"
+    }
+    
+    fn version(&self) -> &str {
+        "1.0.0"
+    }
+    
+    async fn init(&mut self, _context: PluginContext) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.inits.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+    
+    async fn shutdown(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        Ok(())
+    }
+}
+
+fn server_error(error: Box<dyn Error + Send + Sync>) -> ServerError {
+    *error.downcast::<ServerError>().unwrap_or_else(|error| panic!("not a ServerError: {}", error))
+}
+
+#[tokio::test]
+async fn a_second_server_on_the_same_address_is_refused_and_plugins_init_once() {
+    let root = tempfile::tempdir().unwrap();
+    let port = free_port();
+    let config = Config::from_toml(&config_toml(port, root.path(), "", "", "")).unwrap();
+    let inits = Arc::new(AtomicUsize::new(0));
+    
+    let mut server = Server::new(config.clone());
+    server.register_plugin(CountingPlugin { inits: Arc::clone(&inits) }).unwrap();
+    server.init().unwrap();
+    assert!(matches!(server_error(server.init().unwrap_err()), ServerError::AlreadyInitialized));
+    assert_eq!(server.state(), ServerState::Initialized);
+    
+    let handle = tokio::spawn(server.run());
+    for _ in 0..100 {
+        if TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(inits.load(Ordering::SeqCst), 1);
+    
+    let error = server_error(Server::new(config).run().await.unwrap_err());
+    assert!(matches!(error, ServerError::AddressInUse(_)), "{}", error);
+    assert_eq!(inits.load(Ordering::SeqCst), 1);
+    
+    handle.abort();
+}
+
+#[tokio::test]
+async fn a_shut_down_server_cannot_run_or_shut_down_again() {
+    let root = tempfile::tempdir().unwrap();
+    let config = Config::from_toml(&config_toml(free_port(), root.path(), "", "", "")).unwrap();
+    
+    let server = Server::new(config);
+    server.shutdown().await.unwrap();
+    assert_eq!(server.state(), ServerState::ShutDown);
+    assert!(matches!(server_error(server.shutdown().await.unwrap_err()), ServerError::AlreadyShutDown));
+    assert!(matches!(server_error(server.run().await.unwrap_err()), ServerError::AlreadyShutDown));
+}
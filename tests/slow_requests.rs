@@ -0,0 +1,56 @@
+//! Requests slower than `logging.slow_request_threshold` are logged as warnings.
+
+mod common;
+
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::sync::{Arc, Mutex};
+
+use common::{status_of, write_file, TestServer};
+
+/// Log output shared with the subscriber
+#[derive(Clone, Default)]
+struct CapturedLog(Arc<Mutex<Vec<u8>>>);
+
+impl Write for CapturedLog {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl CapturedLog {
+    fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
+
+#[tokio::test]
+async fn only_slow_requests_are_reported() {
+    let log = CapturedLog::default();
+    let writer = log.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::WARN)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    tracing::subscriber::set_global_default(subscriber).unwrap();
+    
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "fast.txt", "fast");
+    let script = write_file(root.path(), "cgi-bin/slow.sh", "#!/bin/sh\nsleep 0.5\nprintf 'Content-Type: text/plain\\r\\n\\r\\nslow'\n");
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let rest = "[logging]\nslow_request_threshold = 300\n\n[[cgi]]\npath = \"/cgi-bin/*\"\n";
+    let server = TestServer::start(root.path(), "", "", rest).await;
+    
+    assert_eq!(status_of(&server.get_raw("/fast.txt", "").await), 200);
+    assert_eq!(status_of(&server.get_raw("/cgi-bin/slow.sh", "").await), 200);
+    
+    let output = log.contents();
+    assert!(output.contains("Slow request: GET /cgi-bin/slow.sh -> 200 in "), "{}", output);
+    assert!(!output.contains("/fast.txt"), "{}", output);
+}
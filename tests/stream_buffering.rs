@@ -0,0 +1,26 @@
+//! Text is buffered and compressed while media is streamed as stored.
+
+mod common;
+
+use common::{status_of, write_file, TestServer};
+
+#[tokio::test]
+async fn text_is_compressed_and_video_is_streamed_as_stored() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "notes.txt", "compressible text ".repeat(200));
+    write_file(root.path(), "clip.mp4", vec![0x5a; 256 * 1024]);
+    let static_files = "stream_threshold = 1048576\nstream_types = [\"video/\"]";
+    let server = TestServer::start(root.path(), "", static_files, "").await;
+    
+    let response = server.get_raw("/notes.txt", "Accept-Encoding: gzip\r\n").await.to_ascii_lowercase();
+    assert_eq!(status_of(&response), 200);
+    assert!(response.contains("content-encoding: gzip"), "{}", response);
+    
+    let response = server.get_raw("/clip.mp4", "Accept-Encoding: gzip\r\n").await;
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let head = head.to_ascii_lowercase();
+    assert_eq!(status_of(&response), 200);
+    assert!(!head.contains("content-encoding"), "{}", head);
+    assert!(head.contains("content-length: 262144"), "{}", head);
+    assert_eq!(body.len(), 256 * 1024);
+}
@@ -0,0 +1,95 @@
+//! The TLS version floor turns away older clients while compliant ones are served.
+
+mod common;
+
+use std::sync::Arc;
+
+use rustls::{Certificate, ClientConfig, RootCertStore, ServerName, SupportedProtocolVersion};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+
+use common::{write_file, TestServer};
+
+/// Start a TLS server with a self-signed certificate for localhost, returning it with the certificate
+async fn start_tls(root: &std::path::Path, tls: &str) -> (TestServer, Certificate) {
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let cert_file = write_file(root, "tls/cert.pem", cert.serialize_pem().unwrap());
+    let key_file = write_file(root, "tls/key.pem", cert.serialize_private_key_pem());
+    let rest = format!(
+        "[tls]\nenabled = true\ncert_file = \"{}\"\nkey_file = \"{}\"\n{}\n",
+        cert_file.display(),
+        key_file.display(),
+        tls,
+    );
+    let server = TestServer::start(root, "", "", &rest).await;
+    (server, Certificate(cert.serialize_der().unwrap()))
+}
+
+/// GET / over TLS with a client limited to the given versions
+async fn get_with(server: &TestServer, cert: &Certificate, versions: &[&'static SupportedProtocolVersion]) -> std::io::Result<String> {
+    let mut roots = RootCertStore::empty();
+    roots.add(cert).unwrap();
+    let config = ClientConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(versions)
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let connector = TlsConnector::from(Arc::new(config));
+    
+    let tcp = TcpStream::connect(server.addr).await?;
+    let mut stream = connector.connect(ServerName::try_from("localhost").unwrap(), tcp).await?;
+    stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await?;
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response).await;
+    Ok(String::from_utf8_lossy(&response).into_owned())
+}
+
+/// Send a TLS 1.1 ClientHello and return the record type of the server's answer
+async fn tls11_hello(server: &TestServer) -> Option<u8> {
+    let mut body = vec![0x03, 0x02];
+    body.extend_from_slice(&[0x42; 32]);
+    body.push(0x00);
+    body.extend_from_slice(&[0x00, 0x04, 0xc0, 0x13, 0x00, 0x2f]);
+    body.extend_from_slice(&[0x01, 0x00]);
+    let mut handshake = vec![0x01, 0x00, (body.len() >> 8) as u8, body.len() as u8];
+    handshake.extend_from_slice(&body);
+    let mut record = vec![0x16, 0x03, 0x01, (handshake.len() >> 8) as u8, handshake.len() as u8];
+    record.extend_from_slice(&handshake);
+    
+    let mut stream = TcpStream::connect(server.addr).await.unwrap();
+    stream.write_all(&record).await.unwrap();
+    let mut first = [0u8; 1];
+    match stream.read(&mut first).await {
+        Ok(1) => Some(first[0]),
+        _ => None,
+    }
+}
+
+#[tokio::test]
+async fn tls_1_1_is_refused_below_a_1_2_floor() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "index.html", "secure");
+    let (server, cert) = start_tls(root.path(), "min_version = \"1.2\"").await;
+    
+    // The server answers with an alert record (0x15) rather than a ServerHello
+    assert_eq!(tls11_hello(&server).await, Some(0x15));
+    
+    let response = get_with(&server, &cert, &[&rustls::version::TLS12]).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.ends_with("secure"));
+}
+
+#[tokio::test]
+async fn tls_1_2_is_refused_below_a_1_3_floor() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "index.html", "secure");
+    let (server, cert) = start_tls(root.path(), "min_version = \"1.3\"\ncipher_policy = \"modern\"").await;
+    
+    assert!(get_with(&server, &cert, &[&rustls::version::TLS12]).await.is_err());
+    
+    let response = get_with(&server, &cert, &[&rustls::version::TLS13]).await.unwrap();
+    assert!(response.ends_with("secure"), "{}", response);
+}
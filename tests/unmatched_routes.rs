@@ -0,0 +1,31 @@
+//! Requests matching no route either fall through to static files or get a 404.
+
+mod common;
+
+use common::{status_of, write_file, TestServer};
+
+const ROUTES: &str = "[[routes]]\npath = \"/docs/*\"\nhandler = \"static\"\n";
+
+#[tokio::test]
+async fn unmatched_requests_fall_through_to_static_files_by_default() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "docs/guide.html", "guide");
+    write_file(root.path(), "stray.html", "stray");
+    let server = TestServer::start(root.path(), "", "", ROUTES).await;
+    
+    assert!(server.get_raw("/docs/guide.html", "").await.ends_with("guide"));
+    assert!(server.get_raw("/stray.html", "").await.ends_with("stray"));
+}
+
+#[tokio::test]
+async fn unmatched_requests_get_404_in_not_found_mode() {
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "docs/guide.html", "guide");
+    write_file(root.path(), "stray.html", "stray");
+    let server = TestServer::start(root.path(), "unmatched_routes = \"not-found\"", "", ROUTES).await;
+    
+    assert!(server.get_raw("/docs/guide.html", "").await.ends_with("guide"));
+    let response = server.get_raw("/stray.html", "").await;
+    assert_eq!(status_of(&response), 404, "{}", response);
+    assert!(!response.ends_with("stray"));
+}
@@ -0,0 +1,62 @@
+//! Uploads stream through a temporary file that only becomes visible once complete.
+
+mod common;
+
+use std::path::Path;
+use std::time::Duration;
+
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+use common::TestServer;
+
+/// Names of the files in a directory, sorted
+fn files_in(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn large_uploads_land_and_failed_ones_leave_nothing() {
+    let root = tempfile::tempdir().unwrap();
+    let uploads = tempfile::tempdir().unwrap();
+    let rest = format!(
+        "[auth]\npaths = [\"/upload/*\"]\ntokens = [\"t0ken\"]\n\n\
+         [[upload]]\npath = \"/upload/*\"\ndirectory = \"{}\"\nmax_size = 16777216\n",
+        uploads.path().display(),
+    );
+    let server = TestServer::start(root.path(), "", "", &rest).await;
+    let client = reqwest::Client::new();
+    
+    let body: Vec<u8> = (0..12 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let response = client.put(server.url("/upload/big.bin")).bearer_auth("t0ken").body(body.clone()).send().await.unwrap();
+    assert_eq!(response.status(), 201);
+    assert!(std::fs::read(uploads.path().join("big.bin")).unwrap() == body);
+    
+    // Over the limit: refused without leaving a partial file
+    let response = client.put(server.url("/upload/huge.bin")).bearer_auth("t0ken").body(vec![0u8; 17 * 1024 * 1024]).send().await.unwrap();
+    assert_eq!(response.status(), 413);
+    assert_eq!(files_in(uploads.path()), ["big.bin"]);
+    
+    // The client disconnects halfway through
+    let mut stream = TcpStream::connect(server.addr).await.unwrap();
+    let head = "PUT /upload/aborted.bin HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer t0ken\r\nContent-Length: 4194304\r\n\r\n";
+    stream.write_all(head.as_bytes()).await.unwrap();
+    stream.write_all(&vec![1u8; 1024 * 1024]).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let partial = files_in(uploads.path());
+    assert!(partial.iter().any(|name| name.starts_with(".aborted.bin.")), "{:?}", partial);
+    drop(stream);
+    
+    for _ in 0..50 {
+        if files_in(uploads.path()) == ["big.bin"] {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("aborted upload left files behind: {:?}", files_in(uploads.path()));
+}
@@ -0,0 +1,34 @@
+//! `kaserve --version` and the admin version endpoint report the same build.
+
+mod common;
+
+use std::process::Command;
+
+use common::{write_file, TestServer};
+
+#[tokio::test]
+async fn version_flag_matches_the_admin_endpoint() {
+    let output = Command::new(env!("CARGO_BIN_EXE_kaserve")).arg("--version").output().unwrap();
+    assert!(output.status.success());
+    let line = String::from_utf8(output.stdout).unwrap();
+    let line = line.trim();
+    assert!(line.starts_with(&format!("kaserve {} (", env!("CARGO_PKG_VERSION"))), "{}", line);
+    
+    let root = tempfile::tempdir().unwrap();
+    write_file(root.path(), "index.html", "home");
+    let server = TestServer::start(root.path(), "", "", "[admin]\nenabled = true\ntoken = \"t0ken\"\n").await;
+    let client = reqwest::Client::new();
+    
+    assert_eq!(client.get(server.url("/admin/version")).send().await.unwrap().status(), 401);
+    
+    let response = client.get(server.url("/admin/version")).bearer_auth("t0ken").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let info: serde_json::Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    let expected = format!(
+        "kaserve {} ({}, built {})",
+        info["version"].as_str().unwrap(),
+        info["git_hash"].as_str().unwrap(),
+        info["build_time"].as_str().unwrap(),
+    );
+    assert_eq!(line, expected);
+}
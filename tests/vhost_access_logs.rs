@@ -0,0 +1,55 @@
+//! Each virtual host's requests are written to its own access log.
+
+mod common;
+
+use std::path::Path;
+use std::time::Duration;
+
+use common::{raw_request, write_file, TestServer};
+
+async fn get(server: &TestServer, host: &str, path: &str) {
+    let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", path, host);
+    raw_request(server.addr, request.as_bytes()).await;
+}
+
+/// Read a log once it mentions `needle`, waiting for the write to land
+async fn log_with(path: &Path, needle: &str) -> String {
+    for _ in 0..100 {
+        let log = std::fs::read_to_string(path).unwrap_or_default();
+        if log.contains(needle) {
+            return log;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("{} never mentioned {}", path.display(), needle);
+}
+
+#[tokio::test]
+async fn requests_are_logged_to_their_virtual_host_log() {
+    let root = tempfile::tempdir().unwrap();
+    let logs = tempfile::tempdir().unwrap();
+    write_file(root.path(), "index.html", "default");
+    let rest = format!(
+        "[logging]\naccess_log = \"{logs}/global.log\"\n\n\
+         [[virtual_hosts]]\nhost = \"a.test\"\nroot_dir = \"{root}\"\naccess_log = \"{logs}/a.log\"\n\n\
+         [[virtual_hosts]]\nhost = \"b.test\"\nroot_dir = \"{root}\"\naccess_log = \"{logs}/b.log\"\naccess_log_format = \"combined\"\n",
+        logs = logs.path().display(),
+        root = root.path().display(),
+    );
+    let server = TestServer::start(root.path(), "", "", &rest).await;
+    
+    get(&server, "a.test", "/from-a").await;
+    get(&server, "b.test:8080", "/from-b").await;
+    get(&server, "other.test", "/from-other").await;
+    
+    let a = log_with(&logs.path().join("a.log"), "/from-a").await;
+    let b = log_with(&logs.path().join("b.log"), "/from-b").await;
+    let global = log_with(&logs.path().join("global.log"), "/from-other").await;
+    
+    assert!(!a.contains("/from-b") && !a.contains("/from-other"), "{}", a);
+    assert!(!b.contains("/from-a") && !b.contains("/from-other"), "{}", b);
+    assert!(!global.contains("/from-a") && !global.contains("/from-b"), "{}", global);
+    
+    // The combined format adds the referer and user agent fields
+    assert!(b.trim_end().ends_with("\"-\" \"-\""), "{}", b);
+}
@@ -0,0 +1,58 @@
+//! Virtual hosts share the global route table, so a request naming a virtual
+//! host reaches the same proxy and route-table handlers as one without a Host.
+
+mod common;
+
+use common::{free_port, raw_request, status_of, write_file, TestServer};
+
+#[tokio::test]
+async fn global_routes_apply_to_virtual_hosts() {
+    let root = tempfile::tempdir().unwrap();
+    let site = tempfile::tempdir().unwrap();
+    write_file(root.path(), "index.html", "default");
+    write_file(site.path(), "index.html", "site");
+    write_file(site.path(), "local/page.html", "scoped");
+    let dead = free_port();
+    let rest = format!(
+        "[[virtual_hosts]]\nhost = \"site.test\"\nroot_dir = \"{site}\"\n\
+         [[routes]]\npath = \"/legacy/*\"\nhandler = \"proxy\"\nparams = \"api\"\n\
+         [[routes]]\npath = \"/local/*\"\nhandler = \"static\"\nhost = \"site.test\"\n\
+         [proxy]\n[[proxy.pools]]\nname = \"api\"\npath = \"/api/*\"\nservers = [\"http://127.0.0.1:{dead}\"]\n",
+        site = site.path().display(),
+    );
+    let server = TestServer::start(root.path(), "", "", &rest).await;
+    
+    // The unreachable upstream answers 502 whichever host is named
+    for path in ["/api/users", "/legacy/page"] {
+        let request = format!("GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path);
+        assert_eq!(status_of(&raw_request(server.addr, request.as_bytes()).await), 502, "{} without Host", path);
+        let request = format!("GET {} HTTP/1.1\r\nHost: site.test\r\nConnection: close\r\n\r\n", path);
+        assert_eq!(status_of(&raw_request(server.addr, request.as_bytes()).await), 502, "{} on site.test", path);
+    }
+    
+    // Static files still come from the virtual host's own root
+    let request = b"GET / HTTP/1.1\r\nHost: site.test\r\nConnection: close\r\n\r\n";
+    assert!(raw_request(server.addr, request).await.ends_with("site"));
+    let request = b"GET /local/page.html HTTP/1.1\r\nHost: site.test\r\nConnection: close\r\n\r\n";
+    assert!(raw_request(server.addr, request).await.ends_with("scoped"));
+    assert!(server.get_raw("/", "").await.ends_with("default"));
+}
+
+#[tokio::test]
+async fn host_ports_and_case_do_not_affect_virtual_host_matching() {
+    let root = tempfile::tempdir().unwrap();
+    let site = tempfile::tempdir().unwrap();
+    write_file(root.path(), "index.html", "default");
+    write_file(site.path(), "index.html", "site");
+    let rest = format!("[[virtual_hosts]]\nhost = \"site.test\"\nroot_dir = \"{}\"\n", site.path().display());
+    let server = TestServer::start(root.path(), "", "", &rest).await;
+    
+    for host in ["site.test", "site.test:8080", "SITE.test."] {
+        let request = format!("GET / HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", host);
+        assert!(raw_request(server.addr, request.as_bytes()).await.ends_with("site"), "Host: {}", host);
+    }
+    
+    // An IPv6 literal is not cut at its first colon into something else
+    let request = b"GET / HTTP/1.1\r\nHost: [::1]:8080\r\nConnection: close\r\n\r\n";
+    assert!(raw_request(server.addr, request).await.ends_with("default"));
+}